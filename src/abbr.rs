@@ -0,0 +1,127 @@
+//! Fish-style abbreviations: a short word in command position is replaced
+//! on the command line itself when Space or Enter is pressed, so what runs
+//! and what lands in history is the full text.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+use crate::config::Config;
+use crate::messages::msg;
+
+/// Shared with the line editor's key handler.
+static ABBREVIATIONS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+/// Expansion found by the Space handler, applied by the completer: a
+/// completion replaces the word and leaves the cursor after it, which a
+/// plain edit command can't do.
+static PENDING: Mutex<Option<(usize, String)>> = Mutex::new(None);
+
+/// Loads the `[abbr]` table from the config.
+pub fn init(config: &Config) {
+    *ABBREVIATIONS.lock().unwrap() = Some(config.get_str_map("abbr").into_iter().collect());
+}
+
+/// Finds an abbreviation ending at byte `pos` of `line`: its start offset
+/// and expansion. Only words in command position (the start of the line or
+/// after `|`, `;`, `&`) are expanded, so `git gco` stays as typed.
+fn expansion_at(line: &str, pos: usize) -> Option<(usize, String)> {
+    if line[pos..].chars().next().is_some_and(|c| !c.is_whitespace()) {
+        return None;
+    }
+    let before = &line[..pos];
+    let start = before.rfind(|c: char| c.is_whitespace() || "|;&".contains(c)).map_or(0, |i| i + 1);
+    let word = &before[start..];
+    let head = before[..start].trim_end();
+    if word.is_empty() || !(head.is_empty() || head.ends_with(['|', ';', '&'])) {
+        return None;
+    }
+    let abbreviations = ABBREVIATIONS.lock().unwrap();
+    let expansion = abbreviations.as_ref()?.get(word)?;
+    Some((start, expansion.clone()))
+}
+
+/// Expands an abbreviation typed just before the end of `line`, for lines
+/// accepted with Enter straight after the abbreviation.
+pub fn expand_line(line: &str) -> String {
+    match expansion_at(line, line.len()) {
+        Some((start, expansion)) => format!("{}{}", &line[..start], expansion),
+        None => line.to_string(),
+    }
+}
+
+/// The expansion requested by the Space handler, as the start of the word
+/// to replace and its replacement text.
+pub fn take_pending() -> Option<(usize, String)> {
+    PENDING.lock().unwrap().take()
+}
+
+/// Space: replaces the abbreviation before the cursor with its expansion,
+/// otherwise inserts the space as usual.
+pub struct ExpandKey;
+
+impl ConditionalEventHandler for ExpandKey {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        let (start, expansion) = expansion_at(ctx.line(), ctx.pos())?;
+        *PENDING.lock().unwrap() = Some((start, format!("{} ", expansion)));
+        Some(Cmd::Complete)
+    }
+}
+
+fn print_abbr(name: &str, expansion: &str) {
+    println!("abbr -a {} {}", name, expansion);
+}
+
+/// `abbr [-a [-s] <word> <expansion...>] [-e [-s] <word>]`
+///
+/// Lists, adds (`-a`) or erases (`-e`) abbreviations for this session; `-s`
+/// also saves the change to the `[abbr]` table of the config file.
+pub fn run(args: &[&str], config: &mut Config) -> i32 {
+    let mut guard = ABBREVIATIONS.lock().unwrap();
+    let abbreviations = guard.get_or_insert_with(HashMap::new);
+    let (action, rest) = match args.split_first() {
+        Some((action, rest)) => (*action, rest),
+        None => {
+            let mut names: Vec<&String> = abbreviations.keys().collect();
+            names.sort();
+            for name in names {
+                print_abbr(name, &abbreviations[name]);
+            }
+            return 0;
+        }
+    };
+    let (save, rest) = match rest.first() {
+        Some(&"-s") | Some(&"--save") => (true, &rest[1..]),
+        _ => (false, rest),
+    };
+    match (action, rest) {
+        ("-a" | "--add", [name, expansion @ ..]) if !expansion.is_empty() => {
+            let expansion = expansion.join(" ");
+            if save && let Err(e) = config.persist("abbr", name, Some(&expansion)) {
+                println!("{}", msg!("error.generic", e));
+                return 1;
+            }
+            abbreviations.insert(name.to_string(), expansion);
+            0
+        }
+        ("-e" | "--erase", names) if !names.is_empty() => {
+            let mut status = 0;
+            for name in names {
+                if abbreviations.remove(*name).is_none() {
+                    println!("abbr: {}", msg!("abbr.not_found", name));
+                    status = 1;
+                    continue;
+                }
+                if save && let Err(e) = config.persist("abbr", name, None) {
+                    println!("{}", msg!("error.generic", e));
+                    status = 1;
+                }
+            }
+            status
+        }
+        _ => {
+            println!("{}", msg!("usage.abbr"));
+            2
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Connects the shell to ssh-agent and gpg-agent at startup.
+//!
+//! With `[agent]` configured, the shell reuses a running agent (the one in
+//! `SSH_AUTH_SOCK`, or the one it started in an earlier session), starts one
+//! otherwise, loads `agent.keys` (asking for passphrases at a hidden prompt),
+//! and exports the agents' variables to every command it runs.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::{self, Config};
+use crate::messages::msg;
+use crate::term;
+use crate::vars;
+
+/// Set on `ssh-add` so that this binary, run as its `SSH_ASKPASS` helper,
+/// answers with the passphrase typed at the shell's hidden prompt.
+const ASKPASS_VAR: &str = "OCTANE_ASKPASS";
+const PASSPHRASE_ATTEMPTS: usize = 3;
+
+/// When started as `ssh-add`'s askpass helper, prints the passphrase and
+/// returns true; the caller should exit straight away. A retry after a bad
+/// passphrase gets an empty answer, which makes `ssh-add` give up so the
+/// shell can ask again.
+pub fn answer_askpass() -> bool {
+    let Ok(passphrase) = env::var(ASKPASS_VAR) else {
+        return false;
+    };
+    let args: Vec<String> = env::args().collect();
+    let [_, prompt] = args.as_slice() else {
+        return false;
+    };
+    if prompt.starts_with("Bad passphrase") {
+        println!();
+    } else {
+        println!("{}", passphrase);
+    }
+    true
+}
+
+fn quiet(cmd: &mut Command) -> &mut Command {
+    cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+}
+
+/// `ssh-add -l` exits with 2 when it can't reach an agent.
+fn ssh_agent_reachable() -> bool {
+    env::var_os("SSH_AUTH_SOCK").is_some()
+        && quiet(Command::new("ssh-add").arg("-l"))
+            .status()
+            .is_ok_and(|status| status.code() != Some(2))
+}
+
+/// Where the variables of an agent started by the shell are kept, so later
+/// sessions can reuse it.
+fn ssh_env_file() -> PathBuf {
+    config::data_dir().join("ssh-agent.env")
+}
+
+/// Picks `SSH_AUTH_SOCK` and `SSH_AGENT_PID` out of `ssh-agent -s` output.
+fn parse_agent_env(text: &str) -> Vec<(String, String)> {
+    text.split(';')
+        .filter_map(|part| {
+            let (name, value) = part.trim().split_once('=')?;
+            matches!(name, "SSH_AUTH_SOCK" | "SSH_AGENT_PID").then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn start_ssh_agent() {
+    if ssh_agent_reachable() {
+        return;
+    }
+    // Windows' OpenSSH agent is a service on a fixed named pipe.
+    if cfg!(windows) {
+        println!("agent: {}", msg!("agent.windows_service"));
+        return;
+    }
+    if let Ok(saved) = fs::read_to_string(ssh_env_file()) {
+        for (name, value) in parse_agent_env(&saved) {
+            vars::set_env(&name, &value);
+        }
+        if ssh_agent_reachable() {
+            return;
+        }
+    }
+    let output = match Command::new("ssh-agent").arg("-s").stderr(Stdio::null()).output() {
+        Ok(output) if output.status.success() => output,
+        Ok(_) => {
+            println!("agent: {}", msg!("agent.failed", "ssh-agent"));
+            return;
+        }
+        Err(e) => {
+            println!("agent: ssh-agent: {}", e);
+            return;
+        }
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let vars = parse_agent_env(&text);
+    for (name, value) in &vars {
+        vars::set_env(name, value);
+    }
+    let file = ssh_env_file();
+    if let Some(dir) = file.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let saved: String = vars.iter().map(|(name, value)| format!("{}={};\n", name, value)).collect();
+    if fs::write(&file, saved).is_ok() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&file, fs::Permissions::from_mode(0o600));
+        }
+    }
+}
+
+/// Fingerprints of the keys already in the agent.
+fn loaded_fingerprints() -> Vec<String> {
+    let Ok(output) = Command::new("ssh-add").arg("-l").stderr(Stdio::null()).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1).map(str::to_string))
+        .collect()
+}
+
+/// The fingerprint of `key`, read from its public half.
+fn fingerprint(key: &str) -> Option<String> {
+    let output = Command::new("ssh-keygen")
+        .args(["-l", "-f", &format!("{}.pub", key)])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.split_whitespace().nth(1).map(str::to_string)
+}
+
+/// Whether `key` can be read without a passphrase.
+fn unencrypted(key: &str) -> bool {
+    quiet(Command::new("ssh-keygen").args(["-y", "-P", "", "-f", key]))
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn add_key(key: &str) {
+    if unencrypted(key) {
+        if !quiet(Command::new("ssh-add").arg(key)).status().is_ok_and(|s| s.success()) {
+            println!("agent: {}", msg!("agent.not_added", key));
+        }
+        return;
+    }
+    let Ok(askpass) = env::current_exe() else {
+        return;
+    };
+    for _ in 0..PASSPHRASE_ATTEMPTS {
+        let Some(passphrase) = term::read_hidden(&msg!("agent.passphrase", key)) else {
+            return;
+        };
+        let added = quiet(Command::new("ssh-add").arg(key))
+            .env("SSH_ASKPASS", &askpass)
+            .env("SSH_ASKPASS_REQUIRE", "force")
+            .env(ASKPASS_VAR, passphrase)
+            .status()
+            .is_ok_and(|status| status.success());
+        if added {
+            return;
+        }
+        println!("{}", msg!("agent.bad_passphrase"));
+    }
+}
+
+fn load_ssh_keys(keys: &[String]) {
+    let loaded = loaded_fingerprints();
+    for key in keys {
+        let key = config::expand_home(key);
+        if !fs::metadata(&key).is_ok_and(|m| m.is_file()) {
+            println!("agent: {}", msg!("agent.no_such_key", key));
+            continue;
+        }
+        if fingerprint(&key).is_some_and(|f| loaded.contains(&f)) {
+            continue;
+        }
+        add_key(&key);
+    }
+}
+
+#[cfg(unix)]
+fn tty_name() -> Option<String> {
+    // SAFETY: ttyname returns a pointer to a static buffer or NULL.
+    let name = unsafe { libc::ttyname(0) };
+    if name.is_null() {
+        return None;
+    }
+    // SAFETY: a non-NULL result is a NUL-terminated string.
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(windows)]
+fn tty_name() -> Option<String> {
+    None
+}
+
+/// Launches gpg-agent and points it at this terminal for pinentry. With
+/// `ssh`, gpg-agent also serves as the ssh agent.
+fn start_gpg_agent(ssh: bool) {
+    if let Some(tty) = tty_name() {
+        vars::set_env("GPG_TTY", &tty);
+    }
+    if !quiet(Command::new("gpgconf").args(["--launch", "gpg-agent"]))
+        .status()
+        .is_ok_and(|s| s.success())
+    {
+        println!("agent: {}", msg!("agent.failed", "gpg-agent"));
+        return;
+    }
+    let _ = quiet(Command::new("gpg-connect-agent").args(["updatestartuptty", "/bye"])).status();
+    if ssh
+        && let Ok(output) = Command::new("gpgconf").args(["--list-dirs", "agent-ssh-socket"]).output()
+    {
+        let socket = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !socket.is_empty() {
+            vars::set_env("SSH_AUTH_SOCK", &socket);
+        }
+    }
+}
+
+/// Sets up the agents configured under `[agent]`:
+///
+/// * `ssh = true` starts or reuses ssh-agent;
+/// * `gpg = true` launches gpg-agent, and `gpg_ssh = true` uses it as the
+///   ssh agent instead;
+/// * `keys` lists private keys to add to the ssh agent if missing.
+pub fn init(config: &Config) {
+    let gpg_ssh = config.get_bool("agent.gpg_ssh").unwrap_or(false);
+    if config.get_bool("agent.gpg").unwrap_or(false) || gpg_ssh {
+        start_gpg_agent(gpg_ssh);
+    }
+    if config.get_bool("agent.ssh").unwrap_or(false) && !gpg_ssh {
+        start_ssh_agent();
+    }
+    let keys = config.get_str_list("agent.keys");
+    if !keys.is_empty() {
+        load_ssh_keys(&keys);
+    }
+}
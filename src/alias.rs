@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::messages::msg;
+use crate::syntax;
+use crate::vars;
+
+const DEFAULT_ALIASES: [(&str, &str); 3] = [("ll", "ls -la"), ("..", "cd .."), ("h", "cd ~")];
+
+/// The built-in aliases, overridden and extended by `[aliases]` in the config.
+pub fn load(config: &Config) -> HashMap<String, String> {
+    let mut aliases: HashMap<String, String> = DEFAULT_ALIASES
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    aliases.extend(config.get_str_map("aliases"));
+    aliases
+}
+
+/// Replaces the first word of `command` with its alias, repeatedly, so
+/// `ll -h /tmp` becomes `ls -la -h /tmp`. An alias is expanded at most once,
+/// which lets `ls='ls --color'` refer to the command it shadows and stops
+/// aliases that refer to each other from looping.
+fn expand_command(aliases: &HashMap<String, String>, command: &str) -> String {
+    let mut line = command.to_string();
+    let mut seen: Vec<String> = Vec::new();
+    loop {
+        let end = line.find(char::is_whitespace).unwrap_or(line.len());
+        let word = &line[..end];
+        let Some(value) = aliases.get(word) else {
+            break;
+        };
+        if seen.iter().any(|s| s == word) {
+            break;
+        }
+        seen.push(word.to_string());
+        line = format!("{}{}", value.trim_start(), &line[end..]);
+    }
+    line
+}
+
+/// Where the word starting at `start` ends: at whitespace, `;`, `&` or `|`
+/// outside quotes.
+fn word_end(line: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (i, c) in line[start..].char_indices() {
+        match (c, quote) {
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('\'' | '"', None) => quote = Some(c),
+            (c, None) if c.is_whitespace() || ";&|".contains(c) => return start + i,
+            _ => {}
+        }
+    }
+    line.len()
+}
+
+/// Expands the aliases of a line before it is split into commands, so an
+/// alias can hold `|`, `&&` or `;`. Each command's first word is expanded
+/// (see `expand_command`): at the start of the line, after `;`, `&&`, `||`,
+/// `|` or `&`, and after `!` or `NAME=value` assignments. What an alias's
+/// value holds isn't looked at again, and commands inside quotes or blocks
+/// such as `{ ...; }` are expanded when they run.
+pub fn expand(aliases: &HashMap<String, String>, line: &str) -> String {
+    let line = line.trim_start();
+    let mut out = String::with_capacity(line.len());
+    let mut depth = 0;
+    let mut changes = syntax::depth_changes(line).into_iter().peekable();
+    let mut command_position = true;
+    let mut i = 0;
+    while let Some(c) = line[i..].chars().next() {
+        while let Some((_, change)) = changes.next_if(|(at, _)| *at <= i) {
+            depth += change;
+        }
+        if c.is_whitespace() || ";&|".contains(c) {
+            command_position |= !c.is_whitespace();
+            out.push(c);
+            i += c.len_utf8();
+            continue;
+        }
+        let end = word_end(line, i);
+        let word = &line[i..end];
+        let assignment = word.split_once('=').is_some_and(|(name, _)| vars::valid_name(name));
+        if command_position && depth == 0 && word != "!" && !assignment {
+            out.push_str(&expand_command(aliases, word));
+        } else {
+            out.push_str(word);
+        }
+        command_position &= word == "!" || assignment;
+        i = end;
+    }
+    out
+}
+
+/// Single-quotes `value` so the shell reads it back unchanged.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn print_alias(name: &str, value: &str) {
+    println!("alias {}={}", name, quote(value));
+}
+
+/// `alias [-s] [name[=value]]`
+///
+/// Without arguments lists every alias; with `name` prints it; with
+/// `name=value` defines it for this session, or also saves it to the
+/// `[aliases]` table of the config file with `-s`.
+pub fn alias(args: &[&str], aliases: &mut HashMap<String, String>, config: &mut Config) -> i32 {
+    let (save, args) = match args.first() {
+        Some(&"-s") | Some(&"--save") => (true, &args[1..]),
+        _ => (false, args),
+    };
+    if args.is_empty() {
+        let mut names: Vec<&String> = aliases.keys().collect();
+        names.sort();
+        for name in names {
+            print_alias(name, &aliases[name]);
+        }
+        return 0;
+    }
+
+    // An unquoted value arrives in pieces: `ll=ls` `-la`.
+    let definition = args.join(" ");
+    let Some((name, value)) = definition.split_once('=') else {
+        let mut status = 0;
+        for name in args {
+            match aliases.get(*name) {
+                Some(value) => print_alias(name, value),
+                None => {
+                    println!("alias: {}", msg!("alias.not_found", name));
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    };
+    let value = value.trim().to_string();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        println!("alias: {}", msg!("alias.invalid_name", name));
+        return 2;
+    }
+    if save && let Err(e) = config.persist("aliases", name, Some(&value)) {
+        println!("{}", msg!("error.generic", e));
+        return 1;
+    }
+    aliases.insert(name.to_string(), value);
+    0
+}
+
+/// `unalias [-s] <name...>` / `unalias -a`
+///
+/// Removes aliases for this session; `-s` also deletes them from the config
+/// file. `-a` removes every alias.
+pub fn unalias(args: &[&str], aliases: &mut HashMap<String, String>, config: &mut Config) -> i32 {
+    let (save, args) = match args.first() {
+        Some(&"-s") | Some(&"--save") => (true, &args[1..]),
+        _ => (false, args),
+    };
+    if args == ["-a"] {
+        aliases.clear();
+        return 0;
+    }
+    if args.is_empty() {
+        println!("{}", msg!("usage.unalias"));
+        return 2;
+    }
+    let mut status = 0;
+    for name in args {
+        if aliases.remove(*name).is_none() {
+            println!("unalias: {}", msg!("alias.not_found", name));
+            status = 1;
+            continue;
+        }
+        if save && let Err(e) = config.persist("aliases", name, None) {
+            println!("{}", msg!("error.generic", e));
+            status = 1;
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn expands_the_first_word() {
+        let aliases = aliases(&[("ll", "ls -la")]);
+        assert_eq!(expand(&aliases, "ll -h /tmp"), "ls -la -h /tmp");
+        assert_eq!(expand(&aliases, "echo ll"), "echo ll");
+    }
+
+    #[test]
+    fn expands_every_command_of_a_line() {
+        let aliases = aliases(&[("g", "git"), ("e", "echo")]);
+        assert_eq!(expand(&aliases, "g status && e done; e x | g log"), "git status && echo done; echo x | git log");
+        assert_eq!(expand(&aliases, "! g diff"), "! git diff");
+        assert_eq!(expand(&aliases, "A=1 B='x y' g log"), "A=1 B='x y' git log");
+    }
+
+    #[test]
+    fn values_may_hold_pipelines_and_lists() {
+        let aliases = aliases(&[("g", "echo hi | tr a-z A-Z"), ("b", "echo one && echo two")]);
+        assert_eq!(expand(&aliases, "g"), "echo hi | tr a-z A-Z");
+        assert_eq!(expand(&aliases, "b; g | wc"), "echo one && echo two; echo hi | tr a-z A-Z | wc");
+    }
+
+    #[test]
+    fn expands_each_alias_once() {
+        let aliases = aliases(&[("ls", "ls --color"), ("a", "b x"), ("b", "a y")]);
+        assert_eq!(expand(&aliases, "ls /tmp"), "ls --color /tmp");
+        assert_eq!(expand(&aliases, "a"), "a y x");
+        // The value's other words aren't commands.
+        assert_eq!(expand(&aliases, "echo; ls"), "echo; ls --color");
+    }
+
+    #[test]
+    fn leaves_quotes_and_blocks_alone() {
+        let aliases = aliases(&[("e", "echo")]);
+        assert_eq!(expand(&aliases, "echo 'a; e' \"b | e\""), "echo 'a; e' \"b | e\"");
+        assert_eq!(expand(&aliases, "{ e a; } && e b"), "{ e a; } && echo b");
+        assert_eq!(expand(&aliases, "if e; then e; fi"), "if e; then e; fi");
+    }
+}
@@ -0,0 +1,229 @@
+//! The registry of builtin commands.
+//!
+//! Each builtin is registered under its name with a usage line, a help
+//! text, the function that runs it, and optionally a completer for its
+//! arguments. The shell looks commands up here before functions and
+//! `PATH`, `help` lists what is registered, and completion offers the
+//! names and asks the completers.
+//!
+//! Builtins can be added and removed while the shell runs: octane registers
+//! its own this way, and code built into it can add more. A handler is
+//! given the shell as a [`Context`]:
+//!
+//! ```
+//! use rustshell::builtins::{self, Builtin};
+//!
+//! builtins::register(Builtin::new("greet", "greet [name]", "Says hello.", |shell, _, args| {
+//!     let name = args.first().map(|name| name.to_string()).or_else(|| shell.var("USER"));
+//!     println!("hello, {}", name.as_deref().unwrap_or("you"));
+//!     shell.set_var("GREETED", "1");
+//!     0
+//! }));
+//! assert!(builtins::get("greet").is_some());
+//! builtins::unregister("greet");
+//! assert!(builtins::get("greet").is_none());
+//! ```
+//!
+//! `disable` hides a builtin so that a function or a program of the same
+//! name runs instead.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// The shell a builtin runs in.
+pub trait Context {
+    /// The value of `$name`: a shell variable, else the environment's.
+    fn var(&self, name: &str) -> Option<String>;
+
+    /// Assigns `name` as `name=value` would.
+    fn set_var(&mut self, name: &str, value: &str);
+
+    /// Runs `commands` as if they were typed, returning the status.
+    fn run(&mut self, commands: &str) -> i32;
+
+    /// The shell itself, for octane's own builtins.
+    fn as_any(&mut self) -> &mut dyn Any;
+}
+
+/// Runs a builtin with the name it was called by and its arguments,
+/// returning its exit status.
+pub type Handler = Arc<dyn Fn(&mut dyn Context, &str, &[&str]) -> i32 + Send + Sync>;
+/// Completes the word being typed as the builtin's `n`th argument (from 1).
+pub type Completer = Arc<dyn Fn(&str, usize) -> Vec<String> + Send + Sync>;
+
+pub struct Builtin {
+    pub name: String,
+    /// The synopsis, e.g. `cd [dir]`.
+    pub usage: String,
+    /// One or two sentences for `help`.
+    pub help: String,
+    pub handler: Handler,
+    pub completer: Option<Completer>,
+}
+
+impl Builtin {
+    pub fn new(
+        name: &str,
+        usage: &str,
+        help: &str,
+        handler: impl Fn(&mut dyn Context, &str, &[&str]) -> i32 + Send + Sync + 'static,
+    ) -> Builtin {
+        Builtin {
+            name: name.to_string(),
+            usage: usage.to_string(),
+            help: help.to_string(),
+            handler: Arc::new(handler),
+            completer: None,
+        }
+    }
+
+    pub fn with_completer(mut self, completer: impl Fn(&str, usize) -> Vec<String> + Send + Sync + 'static) -> Builtin {
+        self.completer = Some(Arc::new(completer));
+        self
+    }
+}
+
+struct Registry {
+    builtins: BTreeMap<String, Arc<Builtin>>,
+    /// Registered but switched off with `disable`.
+    disabled: BTreeMap<String, Arc<Builtin>>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry { builtins: BTreeMap::new(), disabled: BTreeMap::new() });
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Adds `builtin`, replacing (and returning) one of the same name.
+pub fn register(builtin: Builtin) -> Option<Arc<Builtin>> {
+    let mut registry = registry();
+    registry.disabled.remove(&builtin.name);
+    registry.builtins.insert(builtin.name.clone(), Arc::new(builtin))
+}
+
+/// Removes a builtin, enabled or not, returning it.
+pub fn unregister(name: &str) -> Option<Arc<Builtin>> {
+    let mut registry = registry();
+    registry.builtins.remove(name).or_else(|| registry.disabled.remove(name))
+}
+
+/// The enabled builtin called `name`.
+pub fn get(name: &str) -> Option<Arc<Builtin>> {
+    registry().builtins.get(name).cloned()
+}
+
+/// The enabled builtins, by name.
+pub fn all() -> Vec<Arc<Builtin>> {
+    registry().builtins.values().cloned().collect()
+}
+
+/// Names of the builtins switched off with `disable`.
+pub fn disabled() -> Vec<String> {
+    registry().disabled.keys().cloned().collect()
+}
+
+/// Switches a builtin on or off. Returns false if there is no such builtin.
+pub fn set_enabled(name: &str, enabled: bool) -> bool {
+    let mut registry = registry();
+    let Registry { builtins, disabled } = &mut *registry;
+    let (from, to) = if enabled { (disabled, builtins) } else { (builtins, disabled) };
+    match from.remove(name) {
+        Some(builtin) => {
+            to.insert(name.to_string(), builtin);
+            true
+        }
+        None => to.contains_key(name),
+    }
+}
+
+/// Completion candidates for the word at `arg_index` (0 for the command
+/// itself) of a line starting with `command`.
+pub fn complete(command: Option<&str>, word: &str, arg_index: usize) -> Vec<String> {
+    match command {
+        Some(command) if arg_index > 0 => {
+            let completer = get(command).and_then(|b| b.completer.clone());
+            completer.map(|complete| complete(word, arg_index)).unwrap_or_default()
+        }
+        _ => registry().builtins.keys().filter(|name| name.starts_with(word)).cloned().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Shell {
+        variables: BTreeMap<String, String>,
+    }
+
+    impl Context for Shell {
+        fn var(&self, name: &str) -> Option<String> {
+            self.variables.get(name).cloned()
+        }
+
+        fn set_var(&mut self, name: &str, value: &str) {
+            self.variables.insert(name.to_string(), value.to_string());
+        }
+
+        fn run(&mut self, _: &str) -> i32 {
+            0
+        }
+
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    // The registry is shared, so each test uses names of its own.
+
+    #[test]
+    fn registered_builtins_run_with_the_shell() {
+        register(Builtin::new("t-set", "t-set <value>", "Sets X.", |shell, name, args| {
+            shell.set_var("X", &format!("{} {}", name, args.join(" ")));
+            3
+        }));
+        let mut shell = Shell::default();
+        let builtin = get("t-set").unwrap();
+        assert_eq!((builtin.handler)(&mut shell, "t-set", &["a", "b"]), 3);
+        assert_eq!(shell.var("X").as_deref(), Some("t-set a b"));
+        assert!(all().iter().any(|b| b.name == "t-set"));
+    }
+
+    #[test]
+    fn register_replaces_and_unregister_removes() {
+        let first = Builtin::new("t-swap", "t-swap", "First.", |_, _, _| 1);
+        assert!(register(first).is_none());
+        let replaced = register(Builtin::new("t-swap", "t-swap", "Second.", |_, _, _| 2));
+        assert_eq!(replaced.map(|b| b.help.clone()).as_deref(), Some("First."));
+        assert_eq!(unregister("t-swap").map(|b| b.help.clone()).as_deref(), Some("Second."));
+        assert!(get("t-swap").is_none());
+        assert!(unregister("t-swap").is_none());
+    }
+
+    #[test]
+    fn disabled_builtins_are_hidden_until_enabled() {
+        register(Builtin::new("t-off", "t-off", "Off.", |_, _, _| 0));
+        assert!(set_enabled("t-off", false));
+        assert!(get("t-off").is_none());
+        assert!(disabled().contains(&"t-off".to_string()));
+        assert!(complete(None, "t-of", 0).is_empty());
+        assert!(set_enabled("t-off", true));
+        assert_eq!(complete(None, "t-of", 0), ["t-off"]);
+        // Unregistering takes a disabled builtin too.
+        set_enabled("t-off", false);
+        assert!(unregister("t-off").is_some());
+        assert!(!set_enabled("t-off", true));
+    }
+
+    #[test]
+    fn completers_get_the_argument() {
+        let builtin = Builtin::new("t-comp", "t-comp <arg>", "Completes.", |_, _, _| 0)
+            .with_completer(|word, n| vec![format!("{}{}", word, n)]);
+        register(builtin);
+        assert_eq!(complete(Some("t-comp"), "x", 2), ["x2"]);
+        assert!(complete(Some("t-none"), "x", 1).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+//! The `bulkrename` builtin.
+//!
+//! `bulkrename <glob>...` writes the matching file names to a temporary
+//! file, one per line, and opens it in `$VISUAL` or `$EDITOR`. Once the
+//! editor exits, each changed line is a rename: they are listed, checked for
+//! collisions, and applied together after a `y` — or not at all.
+//!
+//! Renames go through temporary names first, so swaps and cycles (`a` to
+//! `b` and `b` to `a`) work, and if one of them fails the ones already made
+//! are undone.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::collate;
+use crate::messages::msg;
+use crate::term;
+
+/// Matches `text` against a glob with `*` (any run) and `?` (any one char).
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        Some((p, rest)) => text
+            .split_first()
+            .is_some_and(|(t, text)| (*p == '?' || p == t) && glob(rest, text)),
+    }
+}
+
+fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob(&pattern, &text)
+}
+
+/// The files matching `pattern`, which may have `*` and `?` in its last
+/// component (`src/*.rs`). Hidden files match only a pattern starting
+/// with `.`.
+pub fn expand(pattern: &str) -> Vec<String> {
+    if !pattern.contains(['*', '?']) {
+        return if Path::new(pattern).exists() { vec![pattern.to_string()] } else { Vec::new() };
+    }
+    let (dir, name) = match pattern.rfind(std::path::is_separator) {
+        Some(i) => (&pattern[..=i], &pattern[i + 1..]),
+        None => ("", pattern),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    let mut found: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|file| (name.starts_with('.') || !file.starts_with('.')) && matches(name, file))
+        .map(|file| format!("{}{}", dir, file))
+        .collect();
+    collate::sort(&mut found);
+    found
+}
+
+/// Has the names edited, returning the edited lines.
+fn edit(names: &[String]) -> Result<Vec<String>, String> {
+    let edited = term::edit(&term::editor(), "bulkrename.txt", &(names.join("\n") + "\n"))
+        .map_err(|e| msg!("bulkrename.not_renamed", e))?;
+    Ok(edited.lines().map(|line| line.trim_end_matches('\r').to_string()).collect())
+}
+
+/// Checks the renames before any is made: every new name must be unique,
+/// free (or being renamed away itself), and in a directory that exists.
+fn check(renames: &[(String, String)]) -> Result<(), String> {
+    let leaving: HashSet<&str> = renames.iter().map(|(from, _)| from.as_str()).collect();
+    let mut taken = HashSet::new();
+    for (_, to) in renames {
+        if to.is_empty() {
+            return Err(msg!("bulkrename.empty_name"));
+        }
+        if !taken.insert(to) || (!leaving.contains(to.as_str()) && Path::new(to).exists()) {
+            return Err(msg!("bulkrename.collision", to));
+        }
+        let parent = Path::new(to).parent().filter(|p| !p.as_os_str().is_empty());
+        if parent.is_some_and(|p| !p.is_dir()) {
+            return Err(msg!("bulkrename.no_directory", to));
+        }
+    }
+    Ok(())
+}
+
+/// A name next to `path` that nothing else is using.
+fn temporary_name(path: &str, n: usize) -> PathBuf {
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    dir.join(format!(".octane-rename-{}-{}", std::process::id(), n))
+}
+
+/// Makes the renames, or none of them.
+fn apply(renames: &[(String, String)]) -> io::Result<()> {
+    // Each step is (from, to); on failure the completed ones run backwards.
+    let mut done: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let steps = renames
+        .iter()
+        .enumerate()
+        .map(|(n, (from, _))| (PathBuf::from(from), temporary_name(from, n)))
+        .chain(renames.iter().enumerate().map(|(n, (from, to))| (temporary_name(from, n), PathBuf::from(to))));
+    for (from, to) in steps.collect::<Vec<_>>() {
+        if let Err(e) = fs::rename(&from, &to) {
+            for (from, to) in done.iter().rev() {
+                let _ = fs::rename(to, from);
+            }
+            return Err(io::Error::new(e.kind(), format!("{}: {}", from.display(), e)));
+        }
+        done.push((from, to));
+    }
+    Ok(())
+}
+
+/// `bulkrename <glob>...`
+pub fn run(args: &[&str]) -> i32 {
+    if args.is_empty() {
+        println!("{}", msg!("usage.bulkrename"));
+        return 2;
+    }
+    let mut names: Vec<String> = Vec::new();
+    for pattern in args {
+        for name in expand(pattern) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    if names.is_empty() {
+        println!("bulkrename: {}", msg!("bulkrename.no_match", args.join(" ")));
+        return 1;
+    }
+
+    let edited = match edit(&names) {
+        Ok(edited) => edited,
+        Err(e) => {
+            println!("bulkrename: {}", e);
+            return 1;
+        }
+    };
+    if edited.len() != names.len() {
+        println!("bulkrename: {}", msg!("bulkrename.line_count", names.len(), edited.len()));
+        return 1;
+    }
+    let renames: Vec<(String, String)> =
+        names.iter().cloned().zip(edited).filter(|(from, to)| from != to).collect();
+    if renames.is_empty() {
+        println!("{}", msg!("bulkrename.nothing"));
+        return 0;
+    }
+    if let Err(e) = check(&renames) {
+        println!("bulkrename: {}", e);
+        return 1;
+    }
+
+    let width = renames.iter().map(|(from, _)| from.chars().count()).max().unwrap_or(0);
+    for (from, to) in &renames {
+        println!("  {:<width$}  ->  {}", from, to, width = width);
+    }
+    if !term::confirm(&msg!("bulkrename.confirm", renames.len())) {
+        return 1;
+    }
+    match apply(&renames) {
+        Ok(()) => 0,
+        Err(e) => {
+            println!("bulkrename: {}", msg!("bulkrename.rolled_back", e));
+            1
+        }
+    }
+}
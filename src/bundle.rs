@@ -0,0 +1,189 @@
+//! Configuration bundles: `config export` and `config import`.
+//!
+//! A bundle is a tar archive of the configuration directory: `config.toml`,
+//! themes, message catalogs, and anything else kept there, for moving a
+//! setup to another machine. `config import --dry-run` lists what importing
+//! would change, with a line diff of each changed file; a real import keeps
+//! the file it replaces as `<name>.bak`. Files the bundle doesn't have are
+//! left alone.
+//!
+//! `octane config ...` on the command line runs the builtin and exits.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::capture::{self, Change};
+use crate::config;
+use crate::messages::msg;
+use crate::theme::Theme;
+
+const BLOCK: usize = 512;
+
+/// Every file under `dir`, as paths relative to it with `/` separators.
+fn collect(dir: &Path, prefix: &str, out: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let path = entry.path();
+        if path.is_dir() {
+            collect(&path, &format!("{}/", name), out)?;
+        } else if path.is_file() && !name.ends_with(".bak") {
+            out.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+/// A ustar header for a regular file.
+fn header(name: &str, size: usize) -> io::Result<[u8; BLOCK]> {
+    if name.len() >= 100 {
+        return Err(io::Error::other(msg!("bundle.name_too_long", name)));
+    }
+    let mut header = [0u8; BLOCK];
+    let mut put = |at: usize, text: &str| header[at..at + text.len()].copy_from_slice(text.as_bytes());
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    put(0, name);
+    put(100, "0000644\0");
+    put(108, "0000000\0");
+    put(116, "0000000\0");
+    put(124, &format!("{:011o}\0", size));
+    put(136, &format!("{:011o}\0", mtime));
+    put(148, "        ");
+    put(156, "0");
+    put(257, "ustar\0");
+    put(263, "00");
+    let sum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    Ok(header)
+}
+
+fn octal(field: &[u8]) -> Option<usize> {
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() { Some(0) } else { usize::from_str_radix(text, 8).ok() }
+}
+
+/// The regular files in a tar archive, by name. Names that would land
+/// outside the configuration directory are refused.
+fn read_tar(data: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let corrupt = || io::Error::new(io::ErrorKind::InvalidData, msg!("bundle.corrupt"));
+    let mut files = Vec::new();
+    let mut at = 0;
+    while at + BLOCK <= data.len() {
+        let header = &data[at..at + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let (name, prefix) = (field(0..100), field(345..500));
+        let name = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        let size = octal(&header[124..136]).ok_or_else(corrupt)?;
+        let start = at + BLOCK;
+        let content = data.get(start..start + size).ok_or_else(corrupt)?;
+        at = start + size.div_ceil(BLOCK) * BLOCK;
+        if !matches!(header[156], b'0' | 0) {
+            continue;
+        }
+        let safe = Path::new(&name).components().all(|c| matches!(c, Component::Normal(_)));
+        if !safe {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg!("bundle.unsafe_path", name)));
+        }
+        files.push((name, content.to_vec()));
+    }
+    Ok(files)
+}
+
+fn export(file: &str) -> io::Result<usize> {
+    let mut files = Vec::new();
+    collect(&config::config_dir(), "", &mut files)?;
+    let mut tar = Vec::new();
+    for (name, path) in &files {
+        let content = fs::read(path)?;
+        tar.extend_from_slice(&header(name, content.len())?);
+        tar.extend_from_slice(&content);
+        tar.resize(tar.len().div_ceil(BLOCK) * BLOCK, 0);
+    }
+    tar.resize(tar.len() + 2 * BLOCK, 0);
+    fs::write(config::expand_home(file), tar)?;
+    Ok(files.len())
+}
+
+fn show_diff(theme: &Theme, old: &str, new: &str) {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    for change in capture::diff_lines(&old, &new).unwrap_or_default() {
+        match change {
+            Change::Same => {}
+            Change::Added(line) => println!("    {}", theme.paint("diff_added", &format!("+ {}", line))),
+            Change::Removed(line) => println!("    {}", theme.paint("diff_removed", &format!("- {}", line))),
+        }
+    }
+}
+
+fn import(theme: &Theme, file: &str, dry_run: bool) -> io::Result<()> {
+    let files = read_tar(&fs::read(config::expand_home(file))?)?;
+    let dir = config::config_dir();
+    let (mut unchanged, mut imported) = (0, 0);
+    for (name, content) in files {
+        let path = dir.join(&name);
+        let old = fs::read(&path).ok();
+        if old.as_deref() == Some(&content[..]) {
+            unchanged += 1;
+            continue;
+        }
+        match &old {
+            Some(old) => {
+                println!("{}", msg!("bundle.changed", name));
+                if dry_run {
+                    show_diff(theme, &String::from_utf8_lossy(old), &String::from_utf8_lossy(&content));
+                }
+            }
+            None => println!("{}", msg!("bundle.new", name)),
+        }
+        if dry_run {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if old.is_some() {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            fs::copy(&path, backup)?;
+        }
+        fs::write(&path, content)?;
+        imported += 1;
+    }
+    println!("{}", msg!("bundle.unchanged", unchanged));
+    if !dry_run && imported > 0 {
+        println!("{}", msg!("bundle.restart"));
+    }
+    Ok(())
+}
+
+/// `config export <file.tar>` / `config import [--dry-run] <file.tar>`
+pub fn run(theme: &Theme, args: &[&str]) -> i32 {
+    let result = match args {
+        ["export", file] => export(file).map(|count| println!("{}", msg!("bundle.exported", count, file))),
+        ["import", "--dry-run", file] | ["import", file, "--dry-run"] => import(theme, file, true),
+        ["import", file] => import(theme, file, false),
+        _ => {
+            println!("{}", msg!("usage.config"));
+            return 2;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            println!("config: {}", e);
+            1
+        }
+    }
+}
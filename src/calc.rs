@@ -0,0 +1,214 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use meval::{Context, Expr};
+
+use crate::config;
+use crate::messages::msg;
+
+const HISTORY_LIMIT: usize = 500;
+
+/// Evaluates `expression` with `ans` bound to the previous result.
+pub fn eval(expression: &str, ans: f64) -> Result<f64, meval::Error> {
+    let expr: Expr = expression.parse()?;
+    let mut ctx = Context::new();
+    ctx.var("ans", ans);
+    expr.eval_with_context(ctx)
+}
+
+/// Formats a result without a trailing `.0` for whole numbers so it can be
+/// spliced into command lines (`head -n =(2*10)` → `head -n 20`).
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// Replaces every `=(expr)` in `input` with the evaluated result. The form
+/// must start a word (or follow punctuation), so `name=(x)` is left alone, and
+/// a surrounding `$(...)` is absorbed: `$(=(4*1024))` also becomes `4096`.
+pub fn expand_inline(input: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = input;
+    while let Some(found) = rest.find("=(") {
+        let before = &rest[..found];
+        if before.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+            result.push_str(&rest[..found + 2]);
+            rest = &rest[found + 2..];
+            continue;
+        }
+        let body = &rest[found + 2..];
+        let mut depth = 1;
+        let end = body.char_indices().find_map(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        });
+        let end = end.ok_or_else(|| msg!("calc.unterminated", input))?;
+        let value = eval(&body[..end], 0.0).map_err(|e| format!("=({}): {}", &body[..end], e))?;
+        let mut after = &body[end + 1..];
+        match before.strip_suffix("$(") {
+            Some(outer) if after.starts_with(')') => {
+                result.push_str(outer);
+                after = &after[1..];
+            }
+            _ => result.push_str(before),
+        }
+        result.push_str(&format_number(value));
+        rest = after;
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+pub fn run(args: &[&str]) -> i32 {
+    if args.is_empty() {
+        repl();
+        return 0;
+    }
+    let expression = args.join(" ");
+    match eval(&expression, 0.0) {
+        Ok(result) => {
+            println!("{}", result);
+            0
+        }
+        Err(e) => {
+            println!("{}", msg!("error.evaluating", e));
+            1
+        }
+    }
+}
+
+fn history_path() -> PathBuf {
+    config::cache_dir().join("calc_history")
+}
+
+fn load_history() -> Vec<String> {
+    let text = fs::read_to_string(history_path()).unwrap_or_default();
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let skip = lines.len().saturating_sub(HISTORY_LIMIT);
+    lines.into_iter().skip(skip).collect()
+}
+
+fn append_history(entry: &str) {
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(history_path()) {
+        let _ = writeln!(file, "{}", entry);
+    }
+}
+
+/// An expression continues onto the next line while parentheses are open,
+/// or when the line ends in a binary operator or a backslash.
+fn is_incomplete(expression: &str) -> bool {
+    let depth = expression.chars().fold(0i32, |depth, c| match c {
+        '(' => depth + 1,
+        ')' => depth - 1,
+        _ => depth,
+    });
+    let trimmed = expression.trim_end();
+    depth > 0 || trimmed.ends_with(['+', '-', '*', '/', '^', '%', ',', '\\'])
+}
+
+/// Interactive calculator entered by `calc` with no arguments. Keeps its own
+/// history file and exposes the previous result as `ans`; Ctrl+D returns to
+/// the shell.
+fn repl() {
+    println!("{}", msg!("calc.welcome"));
+    let mut history = load_history();
+    let mut ans = 0.0;
+
+    loop {
+        let mut expression = String::new();
+        let mut prompt = "calc> ";
+        loop {
+            print!("{prompt}");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    println!();
+                    return;
+                }
+                Ok(_) => {}
+            }
+            let line = line.trim();
+            expression.push_str(line.strip_suffix('\\').unwrap_or(line));
+            expression.push(' ');
+            if !is_incomplete(line) {
+                break;
+            }
+            prompt = "  ... ";
+        }
+
+        let expression = expression.trim().to_string();
+        match expression.as_str() {
+            "" => continue,
+            "exit" | "quit" => return,
+            "history" => {
+                for (i, entry) in history.iter().enumerate() {
+                    println!("{:>5}  {}", i + 1, entry);
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        if history.last() != Some(&expression) {
+            append_history(&expression);
+            history.push(expression.clone());
+        }
+        match eval(&expression, ans) {
+            Ok(result) => {
+                ans = result;
+                println!("= {}", result);
+            }
+            Err(e) => println!("{}", msg!("error.evaluating", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_numbers_have_no_fraction() {
+        assert_eq!(format_number(20.0), "20");
+        assert_eq!(format_number(-3.0), "-3");
+        assert_eq!(format_number(2.5), "2.5");
+        assert_eq!(format_number(1e20), "100000000000000000000");
+    }
+
+    #[test]
+    fn inline_calculations_are_replaced() {
+        assert_eq!(expand_inline("head -n =(2*10) log"), Ok("head -n 20 log".to_string()));
+        assert_eq!(expand_inline("echo =((1+2)*3) =(7/2)"), Ok("echo 9 3.5".to_string()));
+        assert_eq!(expand_inline("dd bs=$(=(4*1024))"), Ok("dd bs=4096".to_string()));
+        assert_eq!(expand_inline("i==(4+1)"), Ok("i=5".to_string()));
+    }
+
+    #[test]
+    fn only_a_word_can_start_a_calculation() {
+        assert_eq!(expand_inline("arr=(a b)"), Ok("arr=(a b)".to_string()));
+        assert_eq!(expand_inline("echo a=b"), Ok("echo a=b".to_string()));
+    }
+
+    #[test]
+    fn bad_calculations_are_errors() {
+        assert_eq!(expand_inline("echo =(1+"), Err(msg!("calc.unterminated", "echo =(1+")));
+        assert!(expand_inline("echo =(1+)").is_err());
+    }
+
+    #[test]
+    fn open_parentheses_and_operators_continue_the_line() {
+        assert!(is_incomplete("(1 + 2"));
+        assert!(is_incomplete("2 *"));
+        assert!(is_incomplete("max(1, \\"));
+        assert!(!is_incomplete("(1 + 2) * 3"));
+    }
+}
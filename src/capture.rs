@@ -0,0 +1,342 @@
+//! Recent commands and their captured output, for `marks`.
+//!
+//! Every command line run at the prompt is recorded with its exit status.
+//! With `capture.enabled`, the stdout and stderr of foreground commands are
+//! also teed through the shell into a bounded buffer so they can be shown
+//! again later. Capturing means the command writes to a pipe rather than the
+//! terminal, so full-screen and interactive programs (`capture.exclude`) are
+//! never captured.
+//!
+//! Ending a command with `--diff-last` captures it regardless, and shows what
+//! changed in its output since the last time the same command ran.
+//!
+//! With `capture.lastout`, the stdout of the last foreground program is
+//! kept as well, in `$__` and for the `lastout` builtin, so it can be used
+//! again without running the program twice.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Local};
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+use crate::config::Config;
+use crate::messages::msg;
+use crate::term;
+use crate::theme::Theme;
+
+/// Commands remembered for `marks`.
+const MAX_RECORDS: usize = 100;
+/// Output kept per command; anything beyond is dropped from the capture
+/// (but still shown on the terminal).
+const MAX_OUTPUT: usize = 1024 * 1024;
+/// Largest old × new line count compared line by line; bigger outputs are
+/// only reported as changed.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+const DIFF_FLAG: &str = "--diff-last";
+
+const DEFAULT_EXCLUDE: [&str; 22] = [
+    "vi", "vim", "nvim", "nano", "emacs", "less", "more", "man", "top", "htop", "btop", "tmux",
+    "screen", "ssh", "mosh", "sshm", "fzf", "python", "python3", "node", "irb", "psql",
+];
+
+pub struct Record {
+    pub id: usize,
+    pub command: String,
+    pub status: i32,
+    pub finished: DateTime<Local>,
+    pub output: Option<String>,
+    /// Bookmarked with Alt-M at the prompt.
+    pub marked: bool,
+}
+
+static RECORDS: Mutex<VecDeque<Record>> = Mutex::new(VecDeque::new());
+/// Output captured for the command currently being run, collected by
+/// `record` once the whole command finishes.
+static PENDING: Mutex<Option<String>> = Mutex::new(None);
+/// Capture the current command even if capturing is off or it is excluded.
+static FORCED: AtomicBool = AtomicBool::new(false);
+/// The stdout of the last foreground program, for `$__` and `lastout`.
+static LAST: Mutex<Option<String>> = Mutex::new(None);
+
+/// Whether `program`'s output should be captured.
+pub fn wanted(config: &Config, program: &str) -> bool {
+    if FORCED.load(Ordering::Relaxed) {
+        return true;
+    }
+    config.get_bool("capture.enabled").unwrap_or(false) && !excluded(config, program)
+}
+
+/// Whether `program`'s stdout should be kept for `$__`.
+pub fn keeps_last(config: &Config, program: &str) -> bool {
+    config.get_bool("capture.lastout").unwrap_or(false) && !excluded(config, program)
+}
+
+/// Whether `program` is full-screen or interactive (`capture.exclude`), so
+/// its output must go straight to the terminal.
+pub fn excluded(config: &Config, program: &str) -> bool {
+    let name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    let exclude = if config.has("capture.exclude") {
+        config.get_str_list("capture.exclude")
+    } else {
+        DEFAULT_EXCLUDE.iter().map(|s| s.to_string()).collect()
+    };
+    exclude.iter().any(|e| e == name)
+}
+
+/// Adds relayed output to `buffer`, up to the most kept per command.
+pub fn keep(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    let room = MAX_OUTPUT.saturating_sub(buffer.len());
+    buffer.extend_from_slice(&bytes[..bytes.len().min(room)]);
+}
+
+/// Stores the output captured for the command being recorded.
+pub fn store(output: &[u8]) {
+    *PENDING.lock().unwrap() = Some(String::from_utf8_lossy(output).into_owned());
+}
+
+/// Keeps `output` as the last program's stdout, without the final newline.
+pub fn store_last(output: &[u8]) {
+    let output = String::from_utf8_lossy(output);
+    *LAST.lock().unwrap() = Some(output.trim_end_matches(['\n', '\r']).to_string());
+}
+
+pub fn last_output() -> Option<String> {
+    LAST.lock().unwrap().clone()
+}
+
+/// Records a finished command line, together with any output captured
+/// while it ran.
+pub fn record(command: &str, status: i32) {
+    FORCED.store(false, Ordering::Relaxed);
+    let output = PENDING.lock().unwrap().take();
+    let mut records = RECORDS.lock().unwrap();
+    let id = records.back().map_or(1, |r| r.id + 1);
+    records.push_back(Record {
+        id,
+        command: command.to_string(),
+        status,
+        finished: Local::now(),
+        output,
+        marked: false,
+    });
+    if records.len() > MAX_RECORDS {
+        records.pop_front();
+    }
+}
+
+/// Strips a trailing `--diff-last` from a command line, returning the
+/// command and whether the flag was present. The flag forces the command's
+/// output to be captured.
+pub fn take_diff_flag(command: &str) -> (String, bool) {
+    let trimmed = command.trim_end();
+    match trimmed.strip_suffix(DIFF_FLAG) {
+        Some(rest) if rest.is_empty() || rest.ends_with(char::is_whitespace) => {
+            FORCED.store(true, Ordering::Relaxed);
+            (rest.trim_end().to_string(), true)
+        }
+        _ => (command.to_string(), false),
+    }
+}
+
+pub enum Change<'a> {
+    Same,
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// Line diff of `old` and `new` by longest common subsequence, or None when
+/// the outputs are too large to compare.
+pub fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Option<Vec<Change<'a>>> {
+    let (n, m) = (old.len(), new.len());
+    if n.saturating_mul(m) > MAX_DIFF_CELLS {
+        return None;
+    }
+    // common[i][j]: length of the LCS of old[i..] and new[j..].
+    let mut common = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[at(i, j)] = if old[i] == new[j] {
+                common[at(i + 1, j + 1)] + 1
+            } else {
+                common[at(i + 1, j)].max(common[at(i, j + 1)])
+            };
+        }
+    }
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            changes.push(Change::Same);
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || common[at(i + 1, j)] >= common[at(i, j + 1)]) {
+            changes.push(Change::Removed(old[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(new[j]));
+            j += 1;
+        }
+    }
+    Some(changes)
+}
+
+/// Shows how the output of the command just recorded differs from the
+/// previous run of the same command line. With `explicit` (the user asked
+/// with `--diff-last`), also says so when there is nothing to compare.
+pub fn show_diff(theme: &Theme, explicit: bool) {
+    let records = RECORDS.lock().unwrap();
+    let Some(latest) = records.back() else {
+        return;
+    };
+    let previous = records
+        .iter()
+        .rev()
+        .skip(1)
+        .find(|r| r.command == latest.command && r.output.is_some());
+    let (Some(new), Some(previous)) = (&latest.output, previous) else {
+        if explicit {
+            println!("diff-last: {}", msg!("capture.no_earlier", latest.command));
+        }
+        return;
+    };
+    let old = previous.output.as_deref().unwrap_or_default();
+    let since = msg!("capture.since", previous.id, previous.finished.format("%H:%M:%S"));
+    if old == new {
+        println!("{}", theme.paint("segment", &msg!("capture.no_changes", since)));
+        return;
+    }
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let Some(changes) = diff_lines(&old_lines, &new_lines) else {
+        println!("{}", theme.paint("segment", &msg!("capture.output_changed", since)));
+        return;
+    };
+    println!("{}", theme.paint("segment", &msg!("capture.changes", since)));
+    for change in changes {
+        match change {
+            Change::Same => {}
+            Change::Added(line) => println!("{}", theme.paint("diff_added", &format!("+ {}", line))),
+            Change::Removed(line) => println!("{}", theme.paint("diff_removed", &format!("- {}", line))),
+        }
+    }
+}
+
+/// Bookmarks the current scrollback position: flags the last command and
+/// asks the terminal to set a mark there (iTerm2's `SetMark`; terminals with
+/// OSC 133 support already navigate by prompt).
+pub fn bookmark() {
+    if let Some(last) = RECORDS.lock().unwrap().back_mut() {
+        last.marked = true;
+    }
+    term::set_mark();
+}
+
+/// Alt-M at the prompt: `bookmark()` without disturbing the line being edited.
+pub struct BookmarkKey;
+
+impl ConditionalEventHandler for BookmarkKey {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        bookmark();
+        Some(Cmd::Noop)
+    }
+}
+
+fn line_count(output: &str) -> String {
+    match output.lines().count() {
+        1 => msg!("capture.one_line"),
+        n => msg!("capture.lines", n),
+    }
+}
+
+/// `marks [n]` lists the last `n` commands (10 by default), bookmarked ones
+/// flagged with `*`; `marks show <id>` prints a command's captured output.
+pub fn run(args: &[&str]) -> i32 {
+    let records = RECORDS.lock().unwrap();
+    match args {
+        ["show", id] => {
+            let Some(record) = id.parse().ok().and_then(|id: usize| records.iter().find(|r| r.id == id)) else {
+                println!("marks: {}", msg!("capture.no_such_command", id));
+                return 1;
+            };
+            match &record.output {
+                Some(output) => {
+                    print!("{}", output);
+                    if !output.is_empty() && !output.ends_with('\n') {
+                        println!();
+                    }
+                    0
+                }
+                None => {
+                    println!("marks: {}", msg!("capture.not_captured", record.command));
+                    1
+                }
+            }
+        }
+        [] | [_] => {
+            let count = match args.first().map(|n| n.parse::<usize>()) {
+                None => 10,
+                Some(Ok(n)) => n,
+                Some(Err(_)) => {
+                    println!("{}", msg!("usage.marks"));
+                    return 2;
+                }
+            };
+            let skip = records.len().saturating_sub(count);
+            for record in records.iter().skip(skip) {
+                let captured = match &record.output {
+                    Some(output) => format!("  ({})", line_count(output)),
+                    None => String::new(),
+                };
+                println!(
+                    "{:>4}{} {} [{}] {}{}",
+                    record.id,
+                    if record.marked { "*" } else { " " },
+                    record.finished.format("%H:%M:%S"),
+                    record.status,
+                    record.command,
+                    captured
+                );
+            }
+            0
+        }
+        _ => {
+            println!("{}", msg!("usage.marks"));
+            2
+        }
+    }
+}
+
+/// `lastout` prints the stdout of the last foreground program; `lastout n`
+/// only its `n`th line, counting from the end when negative.
+pub fn lastout(args: &[&str]) -> i32 {
+    let Some(output) = last_output() else {
+        println!("lastout: {}", msg!("capture.no_lastout"));
+        return 1;
+    };
+    match args {
+        [] => println!("{}", output),
+        [n] => {
+            let lines: Vec<&str> = output.lines().collect();
+            let index = match n.parse::<i64>() {
+                Ok(n) if n > 0 => Some(n as usize - 1),
+                Ok(n) if n < 0 => lines.len().checked_sub(n.unsigned_abs() as usize),
+                _ => {
+                    println!("{}", msg!("usage.lastout"));
+                    return 2;
+                }
+            };
+            match index.and_then(|i| lines.get(i)) {
+                Some(line) => println!("{}", line),
+                None => return 1,
+            }
+        }
+        _ => {
+            println!("{}", msg!("usage.lastout"));
+            return 2;
+        }
+    }
+    0
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Local, NaiveTime, TimeZone, Utc};
+use chrono_tz::{TZ_VARIANTS, Tz};
+
+use crate::config::Config;
+use crate::messages::msg;
+
+/// Resolves an IANA zone (`Asia/Tokyo`) or a bare city (`tokyo`, `new york`)
+/// case-insensitively.
+pub fn resolve_zone(name: &str) -> Option<Tz> {
+    let wanted = name.trim().replace(' ', "_").to_lowercase();
+    if wanted == "utc" {
+        return Some(Tz::UTC);
+    }
+    TZ_VARIANTS.iter().copied().find(|tz| {
+        let full = tz.name().to_lowercase();
+        full == wanted || full.rsplit('/').next() == Some(wanted.as_str())
+    })
+}
+
+fn city(tz: &Tz) -> String {
+    tz.name().rsplit('/').next().unwrap_or("").replace('_', " ")
+}
+
+fn print_time(label: &str, time: DateTime<Tz>) {
+    println!("{:<16} {}", label, time.format("%a %H:%M (UTC%:z)"));
+}
+
+/// `clock [HH:MM] [zone...]` prints the current (or given local) time in each zone.
+pub fn run(args: &[&str], config: &Config) -> i32 {
+    let mut args = args;
+    let mut base: DateTime<Utc> = Utc::now();
+    if let Some(first) = args.first()
+        && let Ok(time) = NaiveTime::parse_from_str(first, "%H:%M")
+    {
+        let today = Local::now().date_naive().and_time(time);
+        match Local.from_local_datetime(&today).earliest() {
+            Some(local) => base = local.with_timezone(&Utc),
+            None => {
+                println!("clock: {}", msg!("clock.no_such_time", first));
+                return 1;
+            }
+        }
+        args = &args[1..];
+    }
+
+    println!("{:<16} {}", msg!("clock.local"), base.with_timezone(&Local).format("%a %H:%M (UTC%:z)"));
+
+    let zones: Vec<String> = if args.is_empty() {
+        config.get_str_list("clock.zones")
+    } else {
+        args.iter().map(|s| s.to_string()).collect()
+    };
+    let mut status = 0;
+    for name in zones {
+        match resolve_zone(&name) {
+            Some(tz) => print_time(&city(&tz), base.with_timezone(&tz)),
+            None => {
+                println!("clock: {}", msg!("clock.unknown_zone", name));
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// Prompt segment showing `clock.zones` as compact `City HH:MM` pairs.
+pub fn segment(config: &Config) -> Option<String> {
+    let now = Utc::now();
+    let parts: Vec<String> = config
+        .get_str_list("clock.zones")
+        .iter()
+        .filter_map(|name| resolve_zone(name))
+        .map(|tz| format!("{} {}", city(&tz), now.with_timezone(&tz).format("%H:%M")))
+        .collect();
+    if parts.is_empty() {
+        Some(Local::now().format("%H:%M").to_string())
+    } else {
+        Some(parts.join(" | "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zones_by_name_or_city() {
+        assert_eq!(resolve_zone("Asia/Tokyo"), Some(chrono_tz::Asia::Tokyo));
+        assert_eq!(resolve_zone("new york"), Some(chrono_tz::America::New_York));
+        assert_eq!(resolve_zone(" UTC "), Some(Tz::UTC));
+        assert_eq!(resolve_zone("atlantis"), None);
+        assert_eq!(city(&chrono_tz::America::Los_Angeles), "Los Angeles");
+    }
+}
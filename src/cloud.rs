@@ -0,0 +1,183 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::config;
+
+/// A segment value remembered together with the inputs it was derived from,
+/// so the files are only re-read when their mtime or the relevant
+/// environment variables change.
+struct Cached {
+    key: Vec<(PathBuf, Option<SystemTime>)>,
+    env: Vec<Option<String>>,
+    value: Option<String>,
+}
+
+static KUBE: Mutex<Option<Cached>> = Mutex::new(None);
+static AWS: Mutex<Option<Cached>> = Mutex::new(None);
+
+fn file_key(paths: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    paths
+        .iter()
+        .map(|p| (p.clone(), fs::metadata(p).and_then(|m| m.modified()).ok()))
+        .collect()
+}
+
+fn cached(
+    slot: &Mutex<Option<Cached>>,
+    paths: Vec<PathBuf>,
+    vars: &[&str],
+    compute: impl FnOnce(&[PathBuf]) -> Option<String>,
+) -> Option<String> {
+    let key = file_key(&paths);
+    let env: Vec<Option<String>> = vars.iter().map(|v| env::var(v).ok()).collect();
+    let mut slot = slot.lock().unwrap();
+    if let Some(c) = slot.as_ref()
+        && c.key == key
+        && c.env == env
+    {
+        return c.value.clone();
+    }
+    let value = compute(&paths);
+    *slot = Some(Cached { key, env, value: value.clone() });
+    value
+}
+
+fn kubeconfig_paths() -> Vec<PathBuf> {
+    match env::var_os("KUBECONFIG") {
+        Some(list) => env::split_paths(&list).collect(),
+        None => config::home_dir()
+            .map(|h| vec![h.join(".kube").join("config")])
+            .unwrap_or_default(),
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+/// Extracts `current-context` and that context's namespace from a kubeconfig
+/// file. Only the handful of keys needed here are understood, which keeps the
+/// prompt free of a YAML dependency.
+fn read_kubeconfig(path: &Path) -> Option<(String, Option<String>)> {
+    let text = fs::read_to_string(path).ok()?;
+    let current = text
+        .lines()
+        .find_map(|l| l.strip_prefix("current-context:"))
+        .map(unquote)
+        .filter(|c| !c.is_empty())?
+        .to_string();
+
+    // Each entry under `contexts:` starts with `- context:` or `- name:`; the
+    // entry whose name matches holds the namespace.
+    let mut in_contexts = false;
+    let mut entry_name: Option<String> = None;
+    let mut entry_ns: Option<String> = None;
+    let mut namespace = None;
+    for line in text.lines() {
+        if !line.starts_with([' ', '-']) {
+            if entry_name.as_deref() == Some(current.as_str()) {
+                namespace = entry_ns.take();
+            }
+            in_contexts = line.starts_with("contexts:");
+            entry_name = None;
+            entry_ns = None;
+            continue;
+        }
+        if !in_contexts {
+            continue;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('-') {
+            if entry_name.as_deref() == Some(current.as_str()) {
+                namespace = entry_ns.take();
+            }
+            entry_name = None;
+            entry_ns = None;
+        }
+        let field = trimmed.trim_start_matches(['-', ' ']);
+        if let Some(v) = field.strip_prefix("name:") {
+            entry_name = Some(unquote(v).to_string());
+        } else if let Some(v) = field.strip_prefix("namespace:") {
+            entry_ns = Some(unquote(v).to_string());
+        }
+    }
+    if entry_name.as_deref() == Some(current.as_str()) {
+        namespace = entry_ns.or(namespace);
+    }
+    Some((current, namespace))
+}
+
+/// `context/namespace` from the active kubeconfig.
+pub fn kube_segment() -> Option<String> {
+    cached(&KUBE, kubeconfig_paths(), &["KUBECONFIG"], |paths| {
+        let (context, namespace) = paths.iter().find_map(|p| read_kubeconfig(p))?;
+        Some(match namespace {
+            Some(ns) => format!("{}/{}", context, ns),
+            None => context,
+        })
+    })
+}
+
+fn aws_config_path() -> Option<PathBuf> {
+    match env::var_os("AWS_CONFIG_FILE") {
+        Some(p) => Some(PathBuf::from(p)),
+        None => config::home_dir().map(|h| h.join(".aws").join("config")),
+    }
+}
+
+fn read_aws_region(path: &Path, profile: &str) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    let wanted = if profile == "default" {
+        "[default]".to_string()
+    } else {
+        format!("[profile {}]", profile)
+    };
+    let mut in_section = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line == wanted;
+        } else if in_section
+            && let Some((key, value)) = line.split_once('=')
+            && key.trim() == "region"
+        {
+            return Some(value.trim().to_string());
+        }
+    }
+    None
+}
+
+const AWS_VARS: [&str; 5] = [
+    "AWS_PROFILE",
+    "AWS_DEFAULT_PROFILE",
+    "AWS_REGION",
+    "AWS_DEFAULT_REGION",
+    "AWS_CONFIG_FILE",
+];
+
+/// `profile (region)` from the AWS environment variables, falling back to the
+/// region configured for that profile in `~/.aws/config`. Hidden when no
+/// profile is selected and no config file exists.
+pub fn aws_segment() -> Option<String> {
+    let paths: Vec<PathBuf> = aws_config_path().into_iter().collect();
+    cached(&AWS, paths, &AWS_VARS, |paths| {
+        let explicit = env::var("AWS_PROFILE")
+            .or_else(|_| env::var("AWS_DEFAULT_PROFILE"))
+            .ok();
+        let config_path = paths.first().filter(|p| p.exists());
+        if explicit.is_none() && config_path.is_none() {
+            return None;
+        }
+        let profile = explicit.unwrap_or_else(|| "default".to_string());
+        let region = env::var("AWS_REGION")
+            .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+            .ok()
+            .or_else(|| config_path.and_then(|p| read_aws_region(p, &profile)));
+        Some(match region {
+            Some(region) => format!("{} ({})", profile, region),
+            None => profile,
+        })
+    })
+}
@@ -0,0 +1,121 @@
+//! Hooks run around each command line at the prompt.
+//!
+//! `preexec` hooks run after a line is entered and before it runs, with the
+//! line as `$1`; `precmd` hooks run before each prompt is drawn, with the
+//! status of the last command as `$1`. As with directory hooks, a hook that
+//! is just the name of a function calls it. Functions named `preexec` and
+//! `precmd` run too, after the listed hooks, as in zsh.
+//!
+//! Hooks come from `hooks.preexec` and `hooks.precmd` in the config and from
+//! the `add-hook` builtin. They don't change `$?`, and only run in an
+//! interactive shell.
+
+use crate::Shell;
+use crate::config::Config;
+use crate::messages::msg;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Preexec,
+    Precmd,
+}
+
+impl Event {
+    const ALL: [Event; 2] = [Event::Preexec, Event::Precmd];
+
+    fn name(self) -> &'static str {
+        match self {
+            Event::Preexec => "preexec",
+            Event::Precmd => "precmd",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Event> {
+        Event::ALL.into_iter().find(|event| event.name() == name)
+    }
+}
+
+/// The hooks for each event, in the order they run.
+pub struct Hooks {
+    preexec: Vec<String>,
+    precmd: Vec<String>,
+}
+
+impl Hooks {
+    /// `hooks.preexec` and `hooks.precmd`, each a list of commands.
+    pub fn from_config(config: &Config) -> Hooks {
+        Hooks { preexec: config.get_str_list("hooks.preexec"), precmd: config.get_str_list("hooks.precmd") }
+    }
+
+    fn list_mut(&mut self, event: Event) -> &mut Vec<String> {
+        match event {
+            Event::Preexec => &mut self.preexec,
+            Event::Precmd => &mut self.precmd,
+        }
+    }
+
+    fn list(&self, event: Event) -> &[String] {
+        match event {
+            Event::Preexec => &self.preexec,
+            Event::Precmd => &self.precmd,
+        }
+    }
+}
+
+/// Runs the hooks for `event` with `args` as the positional parameters.
+pub fn fire(shell: &mut Shell, event: Event, args: &[&str]) {
+    let mut hooks = shell.cmd_hooks.list(event).to_vec();
+    if shell.functions.contains_key(event.name()) {
+        hooks.push(event.name().to_string());
+    }
+    if hooks.is_empty() {
+        return;
+    }
+    let status = shell.last_status;
+    for hook in hooks {
+        match shell.functions.get(hook.trim()).cloned() {
+            Some(body) => crate::call_function(shell, hook.trim(), &body, args),
+            None => crate::call_function(shell, event.name(), &hook, args),
+        };
+    }
+    shell.last_status = status;
+}
+
+/// `add-hook <preexec|precmd> <command...>`, `add-hook -d <preexec|precmd> n`,
+/// or without arguments, the list of hooks.
+pub fn run(hooks: &mut Hooks, args: &[&str]) -> i32 {
+    let usage = || {
+        println!("{}", msg!("usage.add_hook"));
+        2
+    };
+    match args {
+        [] => {
+            for event in Event::ALL {
+                for (n, hook) in hooks.list(event).iter().enumerate() {
+                    println!("{:>3}  {} {}", n + 1, event.name(), hook);
+                }
+            }
+        }
+        ["-d", event, n] => {
+            let Some(event) = Event::parse(event) else {
+                return usage();
+            };
+            let list = hooks.list_mut(event);
+            match n.parse::<usize>() {
+                Ok(n) if (1..=list.len()).contains(&n) => {
+                    list.remove(n - 1);
+                }
+                _ => {
+                    println!("add-hook: {}", msg!("dirhooks.no_such_hook", n));
+                    return 1;
+                }
+            }
+        }
+        [event, command @ ..] if !command.is_empty() => match Event::parse(event) {
+            Some(event) => hooks.list_mut(event).push(command.join(" ")),
+            None => return usage(),
+        },
+        _ => return usage(),
+    }
+    0
+}
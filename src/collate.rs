@@ -0,0 +1,88 @@
+//! Locale-aware ordering for the lists octane shows: completion candidates
+//! and the files a glob matches in `bulkrename` and `parallel`.
+//!
+//! Names are compared with the collation rules of the user's locale
+//! (`LC_ALL`, `LC_COLLATE`, or `LANG` on Unix, the user's default locale on
+//! Windows), so `Éclair` sorts next to `eclair` rather than after `zebra`.
+//! `shell.collation = "c"` restores plain byte order.
+
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+use crate::config::Config;
+use crate::messages::msg;
+
+static LOCALE: AtomicBool = AtomicBool::new(false);
+
+/// Reads `shell.collation` ("locale", the default, or "c"). Call before
+/// starting other threads.
+pub fn init(config: &Config) {
+    let locale = match config.get_str("shell.collation") {
+        None | Some("locale") => true,
+        Some("c" | "C") => false,
+        Some(other) => {
+            println!("{}", msg!("shell.unknown_collation", other));
+            false
+        }
+    };
+    #[cfg(unix)]
+    if locale {
+        // SAFETY: called from main before other threads start; only the
+        // collation category changes.
+        unsafe { libc::setlocale(libc::LC_COLLATE, c"".as_ptr()) };
+    }
+    LOCALE.store(locale, AtomicOrdering::Relaxed);
+}
+
+#[cfg(unix)]
+fn locale_compare(a: &str, b: &str) -> Option<Ordering> {
+    let a = std::ffi::CString::new(a).ok()?;
+    let b = std::ffi::CString::new(b).ok()?;
+    // SAFETY: both are NUL-terminated strings.
+    let order = unsafe { libc::strcoll(a.as_ptr(), b.as_ptr()) };
+    Some(order.cmp(&0))
+}
+
+#[cfg(windows)]
+fn locale_compare(a: &str, b: &str) -> Option<Ordering> {
+    use windows_sys::Win32::Globalization::{
+        CSTR_EQUAL, CSTR_GREATER_THAN, CSTR_LESS_THAN, CompareStringEx, NORM_LINGUISTIC_CASING,
+        SORT_DIGITSASNUMBERS,
+    };
+    let a: Vec<u16> = a.encode_utf16().chain(Some(0)).collect();
+    let b: Vec<u16> = b.encode_utf16().chain(Some(0)).collect();
+    // SAFETY: both are NUL-terminated UTF-16 strings; a null locale name
+    // means the user's default locale.
+    let result = unsafe {
+        CompareStringEx(
+            std::ptr::null(),
+            NORM_LINGUISTIC_CASING | SORT_DIGITSASNUMBERS,
+            a.as_ptr(),
+            -1,
+            b.as_ptr(),
+            -1,
+            std::ptr::null(),
+            std::ptr::null(),
+            0,
+        )
+    };
+    match result as u32 {
+        CSTR_LESS_THAN => Some(Ordering::Less),
+        CSTR_EQUAL => Some(Ordering::Equal),
+        CSTR_GREATER_THAN => Some(Ordering::Greater),
+        _ => None,
+    }
+}
+
+/// Orders `a` and `b` by the configured collation. Names the locale
+/// considers equal fall back to byte order, so sorting is deterministic.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    if !LOCALE.load(AtomicOrdering::Relaxed) {
+        return a.cmp(b);
+    }
+    locale_compare(a, b).unwrap_or(Ordering::Equal).then_with(|| a.cmp(b))
+}
+
+pub fn sort(names: &mut [String]) {
+    names.sort_by(|a, b| compare(a, b));
+}
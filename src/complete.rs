@@ -0,0 +1,363 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+use rustshell::builtins;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+
+use crate::abbr;
+use crate::collate;
+use crate::config::{self, Config};
+use crate::hint;
+use crate::redact::Redactor;
+use crate::ssh;
+use crate::syntax;
+
+/// How forgiving path completion is when nothing matches the typed prefix.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Correction {
+    /// Only exact prefix matches.
+    Off,
+    /// Also match ignoring case.
+    Case,
+    /// Also match with a small number of typos or transposed letters.
+    Typo,
+}
+
+impl Correction {
+    pub fn from_config(config: &Config) -> Correction {
+        match config.get_str("completion.correction") {
+            Some("off") => Correction::Off,
+            Some("case") => Correction::Case,
+            _ => Correction::Typo,
+        }
+    }
+}
+
+/// Optimal string alignment distance: edits plus adjacent transpositions.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Typos tolerated for a fragment: one per four characters, at most two.
+fn typo_budget(fragment: &str) -> usize {
+    (fragment.chars().count() / 4).clamp(1, 2)
+}
+
+/// Distance between a typed fragment and a directory entry, comparing
+/// against both the whole name and a same-length prefix so partially typed
+/// names still match.
+fn typo_distance(fragment: &str, name: &str) -> usize {
+    let fragment = fragment.to_lowercase();
+    let name = name.to_lowercase();
+    let len = fragment.chars().count();
+    let prefix: String = name.chars().take(len).collect();
+    let longer: String = name.chars().take(len + 1).collect();
+    edit_distance(&fragment, &name)
+        .min(edit_distance(&fragment, &prefix))
+        .min(edit_distance(&fragment, &longer))
+}
+
+fn read_entries(dir: &Path) -> Vec<(String, bool)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<(String, bool)> = entries
+        .flatten()
+        .map(|e| {
+            let is_dir = e.path().is_dir();
+            (e.file_name().to_string_lossy().to_string(), is_dir)
+        })
+        .collect();
+    names.sort_by(|a, b| collate::compare(&a.0, &b.0));
+    names
+}
+
+/// Entries of `dir` matching `fragment`, trying exact prefixes first and then
+/// progressively looser matches up to `level`.
+fn matching_entries(dir: &Path, fragment: &str, level: Correction) -> Vec<(String, bool)> {
+    let entries: Vec<(String, bool)> = read_entries(dir)
+        .into_iter()
+        .filter(|(name, _)| !name.starts_with('.') || fragment.starts_with('.'))
+        .collect();
+
+    let exact: Vec<_> = entries.iter().filter(|(n, _)| n.starts_with(fragment)).cloned().collect();
+    if !exact.is_empty() || level == Correction::Off || fragment.is_empty() {
+        return exact;
+    }
+
+    let lower = fragment.to_lowercase();
+    let caseless: Vec<_> = entries
+        .iter()
+        .filter(|(n, _)| n.to_lowercase().starts_with(&lower))
+        .cloned()
+        .collect();
+    if !caseless.is_empty() || level == Correction::Case {
+        return caseless;
+    }
+
+    let budget = typo_budget(fragment);
+    let mut scored: Vec<(usize, (String, bool))> = entries
+        .into_iter()
+        .map(|e| (typo_distance(fragment, &e.0), e))
+        .filter(|(d, _)| *d <= budget)
+        .collect();
+    let best = scored.iter().map(|(d, _)| *d).min().unwrap_or(0);
+    scored.retain(|(d, _)| *d == best);
+    scored.into_iter().map(|(_, e)| e).collect()
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || c == MAIN_SEPARATOR
+}
+
+/// Completes a path-like word. Directory components that don't exist are
+/// corrected when exactly one entry matches at the configured level, so
+/// `Desk/dowloads` completes to `Desktop/Downloads/`.
+pub fn complete_path(word: &str, level: Correction) -> Vec<Pair> {
+    let (dir_part, fragment) = match word.rfind(is_separator) {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+
+    let home = config::home_dir();
+    let mut shown_dir = String::new();
+    let mut fs_dir = PathBuf::from(".");
+    let components: Vec<&str> = dir_part.split(is_separator).collect();
+    for (i, component) in components.iter().enumerate() {
+        let last = i == components.len() - 1;
+        if last {
+            break;
+        }
+        match *component {
+            "" if i == 0 => {
+                shown_dir.push('/');
+                fs_dir = PathBuf::from("/");
+                continue;
+            }
+            "" | "." => {
+                if *component == "." {
+                    shown_dir.push_str("./");
+                }
+                continue;
+            }
+            "~" if i == 0 => {
+                let Some(home) = &home else { return Vec::new() };
+                shown_dir.push_str("~/");
+                fs_dir = home.clone();
+                continue;
+            }
+            ".." => {
+                shown_dir.push_str("../");
+                fs_dir.push("..");
+                continue;
+            }
+            _ => {}
+        }
+        let candidate = fs_dir.join(component);
+        let resolved = if candidate.is_dir() {
+            component.to_string()
+        } else {
+            let dirs: Vec<_> = matching_entries(&fs_dir, component, level)
+                .into_iter()
+                .filter(|(_, is_dir)| *is_dir)
+                .collect();
+            let exact_len: Vec<_> = dirs
+                .iter()
+                .filter(|(n, _)| n.to_lowercase() == component.to_lowercase())
+                .collect();
+            match (dirs.len(), exact_len.first()) {
+                (_, Some((name, _))) => name.clone(),
+                (1, None) => dirs[0].0.clone(),
+                _ => return Vec::new(),
+            }
+        };
+        shown_dir.push_str(&resolved);
+        shown_dir.push('/');
+        fs_dir.push(&resolved);
+    }
+
+    matching_entries(&fs_dir, fragment, level)
+        .into_iter()
+        .map(|(name, is_dir)| {
+            let suffix = if is_dir { "/" } else { "" };
+            Pair {
+                display: format!("{}{}", name, suffix),
+                replacement: format!("{}{}{}", shown_dir, name, suffix),
+            }
+        })
+        .collect()
+}
+
+/// Commands whose arguments may name ssh hosts or `host:path` remotes.
+const SSH_COMMANDS: [&str; 4] = ["ssh", "scp", "rsync", "sftp"];
+
+/// Completes `[user@]host` from the ssh config and known_hosts, appending
+/// `suffix` (`:` for copy commands) to each host.
+fn complete_host(word: &str, suffix: &str) -> Vec<Pair> {
+    let (user, fragment) = match word.split_once('@') {
+        Some((user, rest)) => (format!("{}@", user), rest),
+        None => (String::new(), word),
+    };
+    ssh::all_hosts()
+        .into_iter()
+        .filter(|h| h.starts_with(fragment))
+        .map(|h| Pair {
+            replacement: format!("{}{}{}", user, h, suffix),
+            display: h,
+        })
+        .collect()
+}
+
+/// Completes the path part of `host:path` by listing the remote directory.
+/// Returns `None` when `word` isn't a remote spec.
+fn complete_remote(word: &str) -> Option<Vec<Pair>> {
+    let (host, path) = word.split_once(':')?;
+    if host.is_empty() || host.contains(is_separator) {
+        return None;
+    }
+    let (dir, fragment) = match path.rfind('/') {
+        Some(i) => (&path[..=i], &path[i + 1..]),
+        None => ("", path),
+    };
+    let mut entries: Vec<String> = ssh::list_remote(host, dir)
+        .into_iter()
+        .filter(|entry| entry.starts_with(fragment))
+        .filter(|entry| !entry.starts_with('.') || fragment.starts_with('.'))
+        .collect();
+    // Remote `ls` sorts by the remote locale.
+    collate::sort(&mut entries);
+    let pairs = entries
+        .into_iter()
+        .map(|entry| Pair {
+            replacement: format!("{}:{}{}", host, dir, entry),
+            display: entry,
+        })
+        .collect();
+    Some(pairs)
+}
+
+/// Line-editor integration: completion and the hooks rustyline requires.
+pub struct ShellHelper {
+    pub correction: Correction,
+    /// Set with `redact.mask_input`: secrets are shown as `•` while typing.
+    pub mask: Option<Redactor>,
+    /// Where options of commands without a completer come from.
+    pub from_help: hint::Source,
+}
+
+impl ShellHelper {
+    pub fn from_config(config: &Config) -> ShellHelper {
+        ShellHelper {
+            correction: Correction::from_config(config),
+            mask: config.get_bool("redact.mask_input").unwrap_or(false).then(|| Redactor::from_config(config)),
+            from_help: hint::Source::from_config(config),
+        }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if let Some((start, expansion)) = abbr::take_pending() {
+            return Ok((start, vec![Pair { display: expansion.clone(), replacement: expansion }]));
+        }
+        let start = line[..pos].rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let word = &line[start..pos];
+        let command = line[..start].split_whitespace().next();
+
+        let arg_index = line[..start].split_whitespace().count();
+        let pair = |name: String| Pair { display: name.clone(), replacement: name };
+        if arg_index == 0 && !word.contains(is_separator) {
+            let mut pairs: Vec<Pair> = builtins::complete(None, word, 0).into_iter().map(pair).collect();
+            pairs.extend(complete_path(word, self.correction));
+            return Ok((start, pairs));
+        }
+        let names = builtins::complete(command, word, arg_index);
+        if !names.is_empty() {
+            return Ok((start, names.into_iter().map(pair).collect()));
+        }
+
+        if let Some(command) = command
+            && word.starts_with('-')
+            && builtins::get(command).is_none()
+        {
+            let flags = hint::complete(command, word, self.from_help);
+            if !flags.is_empty() {
+                return Ok((start, flags.into_iter().map(pair).collect()));
+            }
+        }
+
+        if let Some(command) = command
+            && SSH_COMMANDS.contains(&command)
+            && !word.starts_with('-')
+        {
+            if let Some(pairs) = complete_remote(word) {
+                return Ok((start, pairs));
+            }
+            if command == "ssh" || command == "sftp" {
+                return Ok((start, complete_host(word, "")));
+            }
+            let mut pairs = complete_path(word, Correction::Off);
+            if !word.contains(is_separator) {
+                pairs.extend(complete_host(word, ":"));
+            }
+            return Ok((start, pairs));
+        }
+
+        Ok((start, complete_path(word, self.correction)))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        match self.mask.as_ref().and_then(|m| m.mask(line)) {
+            Some(masked) => Cow::Owned(masked),
+            None => Cow::Borrowed(line),
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        self.mask.is_some()
+    }
+}
+
+/// Asks for more lines while a `{` group, such as a function body, is open.
+impl Validator for ShellHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if syntax::open_blocks(ctx.input()) > 0 {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for ShellHelper {}
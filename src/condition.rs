@@ -0,0 +1,292 @@
+//! The `test` and `[` builtins.
+//!
+//! Expressions follow POSIX `test`: file checks (`-e`, `-f`, `-d`, `-x`,
+//! ...), string and integer comparisons, combined with `!`, `-a`, `-o` and
+//! parentheses. They are evaluated by the shell itself, so conditionals work
+//! the same on Windows, which has no `/usr/bin/test`.
+
+use std::fs;
+use std::path::Path;
+
+use crate::messages::msg;
+
+const UNARY: [&str; 12] = ["-e", "-f", "-d", "-x", "-r", "-w", "-s", "-L", "-h", "-n", "-z", "-t"];
+const BINARY: [&str; 14] = [
+    "=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+#[cfg(unix)]
+fn access(path: &str, mode: libc::c_int) -> bool {
+    let Ok(path) = std::ffi::CString::new(path) else {
+        return false;
+    };
+    // SAFETY: `path` is a NUL-terminated string.
+    unsafe { libc::access(path.as_ptr(), mode) == 0 }
+}
+
+#[cfg(unix)]
+pub fn executable(path: &str) -> bool {
+    access(path, libc::X_OK)
+}
+
+/// Directories, and files with an extension from `PATHEXT`.
+#[cfg(windows)]
+pub fn executable(path: &str) -> bool {
+    let path = Path::new(path);
+    if path.is_dir() {
+        return true;
+    }
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    path.is_file()
+        && path.extension().is_some_and(|ext| {
+            let ext = format!(".{}", ext.to_string_lossy());
+            extensions.split(';').any(|e| e.eq_ignore_ascii_case(&ext))
+        })
+}
+
+#[cfg(unix)]
+fn readable(path: &str) -> bool {
+    access(path, libc::R_OK)
+}
+
+#[cfg(windows)]
+fn readable(path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+#[cfg(unix)]
+fn writable(path: &str) -> bool {
+    access(path, libc::W_OK)
+}
+
+#[cfg(windows)]
+fn writable(path: &str) -> bool {
+    fs::metadata(path).is_ok_and(|m| !m.permissions().readonly())
+}
+
+fn is_terminal(fd: &str) -> Result<bool, String> {
+    use std::io::IsTerminal;
+    Ok(match integer(fd)? {
+        0 => std::io::stdin().is_terminal(),
+        1 => std::io::stdout().is_terminal(),
+        2 => std::io::stderr().is_terminal(),
+        _ => false,
+    })
+}
+
+fn integer(text: &str) -> Result<i64, String> {
+    text.trim().parse().map_err(|_| msg!("test.integer_expected", text))
+}
+
+fn unary(op: &str, operand: &str) -> Result<bool, String> {
+    let path = Path::new(operand);
+    Ok(match op {
+        "-e" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-x" => path.exists() && executable(operand),
+        "-r" => path.exists() && readable(operand),
+        "-w" => path.exists() && writable(operand),
+        "-s" => fs::metadata(path).is_ok_and(|m| m.len() > 0),
+        "-L" | "-h" => fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()),
+        "-n" => !operand.is_empty(),
+        "-z" => operand.is_empty(),
+        "-t" => is_terminal(operand)?,
+        _ => unreachable!("not a unary operator: {}", op),
+    })
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn binary(left: &str, op: &str, right: &str) -> Result<bool, String> {
+    let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+    Ok(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        ">" => left > right,
+        "-eq" => integer(left)? == integer(right)?,
+        "-ne" => integer(left)? != integer(right)?,
+        "-lt" => integer(left)? < integer(right)?,
+        "-le" => integer(left)? <= integer(right)?,
+        "-gt" => integer(left)? > integer(right)?,
+        "-ge" => integer(left)? >= integer(right)?,
+        // A missing file is older than any existing one.
+        "-nt" => modified(left) > modified(right),
+        "-ot" => modified(left) < modified(right),
+        "-ef" => same_file(left, right),
+        _ => unreachable!("not a binary operator: {}", op),
+    })
+}
+
+/// A recursive-descent parser over the arguments:
+/// `or := and (-o and)*`, `and := not (-a not)*`, `not := ! not | primary`.
+struct Parser<'a> {
+    args: &'a [&'a str],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self, offset: usize) -> Option<&'a str> {
+        self.args.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let arg = self.peek(0).ok_or_else(|| msg!("test.argument_expected"))?;
+        self.pos += 1;
+        Ok(arg)
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut value = self.and()?;
+        while self.peek(0) == Some("-o") {
+            self.pos += 1;
+            value |= self.and()?;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut value = self.not()?;
+        while self.peek(0) == Some("-a") {
+            self.pos += 1;
+            value &= self.not()?;
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<bool, String> {
+        // A lone `!` is just a non-empty string.
+        if self.peek(0) == Some("!") && self.peek(1).is_some() {
+            self.pos += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        let first = self.next()?;
+        if let (Some(op), Some(right)) = (self.peek(0), self.peek(1))
+            && BINARY.contains(&op)
+        {
+            self.pos += 2;
+            return binary(first, op, right);
+        }
+        if first == "(" && self.peek(0).is_some() {
+            let value = self.or()?;
+            if self.next()? != ")" {
+                return Err(msg!("test.missing_paren"));
+            }
+            return Ok(value);
+        }
+        if UNARY.contains(&first)
+            && let Some(operand) = self.peek(0)
+        {
+            self.pos += 1;
+            return unary(first, operand);
+        }
+        Ok(!first.is_empty())
+    }
+}
+
+fn evaluate(args: &[&str]) -> Result<bool, String> {
+    if args.is_empty() {
+        return Ok(false);
+    }
+    let mut parser = Parser { args, pos: 0 };
+    let value = parser.or()?;
+    match parser.peek(0) {
+        Some(extra) => Err(msg!("test.unexpected", extra)),
+        None => Ok(value),
+    }
+}
+
+/// `test expr` / `[ expr ]`: 0 when `expr` is true, 1 when false, and 2
+/// when it can't be evaluated.
+pub fn run(name: &str, args: &[&str]) -> i32 {
+    let args = if name == "[" {
+        match args.split_last() {
+            Some((&"]", rest)) => rest,
+            _ => {
+                println!("[: {}", msg!("test.missing_bracket"));
+                return 2;
+            }
+        }
+    } else {
+        args
+    };
+    match evaluate(args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            println!("{}: {}", name, e);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::words;
+
+    /// The status of `line` as `[` or `test` sees it, once its words are
+    /// split and unquoted.
+    fn status(line: &str) -> i32 {
+        let words = words::split(line);
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        run(words[0], &words[1..])
+    }
+
+    #[test]
+    fn empty_quoted_strings() {
+        assert_eq!(status("test -n \"\""), 1);
+        assert_eq!(status("test -z ''"), 0);
+        assert_eq!(status("[ -z \"\" ]"), 0);
+        assert_eq!(status("[ \"\" ]"), 1);
+    }
+
+    #[test]
+    fn quoted_strings_with_spaces() {
+        assert_eq!(status("[ \"a b\" = \"a b\" ]"), 0);
+        assert_eq!(status("[ 'a b' != \"a b\" ]"), 1);
+        assert_eq!(status("test -n 'a b'"), 0);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(evaluate(&["2", "-lt", "10"]), Ok(true));
+        assert_eq!(evaluate(&["b", "<", "a"]), Ok(false));
+        assert_eq!(evaluate(&["!", "a", "=", "b"]), Ok(true));
+        assert_eq!(evaluate(&["(", "a", "-o", "", ")", "-a", "-n", "x"]), Ok(true));
+        assert!(evaluate(&["x", "-eq", "1"]).is_err());
+    }
+
+    #[test]
+    fn extra_arguments_are_errors() {
+        assert_eq!(evaluate(&["a", "b"]), Err(msg!("test.unexpected", "b")));
+        assert_eq!(status("[ a = a"), 2);
+    }
+
+    #[test]
+    fn files() {
+        assert_eq!(evaluate(&["-d", "src"]), Ok(true));
+        assert_eq!(evaluate(&["-f", "src"]), Ok(false));
+        assert_eq!(evaluate(&["-f", "Cargo.toml", "-a", "-s", "Cargo.toml"]), Ok(true));
+        assert_eq!(evaluate(&["-e", "no/such/file"]), Ok(false));
+        assert_eq!(evaluate(&["Cargo.toml", "-ef", "./Cargo.toml"]), Ok(true));
+        assert_eq!(evaluate(&["no/such/file", "-ot", "Cargo.toml"]), Ok(true));
+    }
+
+    #[test]
+    fn a_lone_word_is_true_unless_empty() {
+        assert_eq!(evaluate(&["-n"]), Ok(true));
+        assert_eq!(evaluate(&[""]), Ok(false));
+        assert_eq!(evaluate(&[]), Ok(false));
+    }
+}
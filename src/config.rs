@@ -0,0 +1,234 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use toml::{Table, Value};
+use toml_edit::DocumentMut;
+
+/// User configuration loaded from `config.toml` in the octane config directory.
+///
+/// Values are looked up by dotted path (`"weather.location"`), and every
+/// accessor falls back to `None` so callers can supply their own defaults.
+#[derive(Default)]
+pub struct Config {
+    table: Table,
+}
+
+fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let path = config_path();
+        let table = match fs::read_to_string(&path) {
+            Ok(text) => match text.parse::<Table>() {
+                Ok(table) => table,
+                Err(e) => {
+                    println!("Error parsing {}: {}", path.display(), e);
+                    Table::new()
+                }
+            },
+            Err(_) => Table::new(),
+        };
+        Config { table }
+    }
+
+    /// A configuration read from `text`, for tests.
+    #[cfg(test)]
+    pub fn parse(text: &str) -> Config {
+        Config { table: text.parse().unwrap() }
+    }
+
+    fn get(&self, path: &str) -> Option<&Value> {
+        let mut keys = path.split('.');
+        let mut value = self.table.get(keys.next()?)?;
+        for key in keys {
+            value = value.as_table()?.get(key)?;
+        }
+        Some(value)
+    }
+
+    pub fn has(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    pub fn get_str(&self, path: &str) -> Option<&str> {
+        self.get(path)?.as_str()
+    }
+
+    pub fn get_bool(&self, path: &str) -> Option<bool> {
+        self.get(path)?.as_bool()
+    }
+
+    pub fn get_int(&self, path: &str) -> Option<i64> {
+        self.get(path)?.as_integer()
+    }
+
+    /// String entries of the table at `path`, e.g. `[aliases]`.
+    pub fn get_str_map(&self, path: &str) -> Vec<(String, String)> {
+        match self.get(path).and_then(Value::as_table) {
+            Some(table) => table
+                .iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Sets `key` in the table at `table_path` to `value` (or removes it when
+    /// `value` is None), both here and in `config.toml`. The file is edited in
+    /// place, so comments and formatting elsewhere are kept.
+    pub fn persist(&mut self, table_path: &str, key: &str, value: Option<&str>) -> Result<(), String> {
+        let path = config_path();
+        let text = fs::read_to_string(&path).unwrap_or_default();
+        let mut doc: DocumentMut = text
+            .parse()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut table = doc.as_table_mut();
+        for name in table_path.split('.') {
+            let entry = table
+                .entry(name)
+                .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()));
+            table = entry
+                .as_table_mut()
+                .ok_or_else(|| format!("{} in {} is not a table", name, path.display()))?;
+        }
+        match value {
+            Some(value) => {
+                table.insert(key, toml_edit::value(value));
+            }
+            None => {
+                table.remove(key);
+            }
+        }
+        let _ = fs::create_dir_all(config_dir());
+        fs::write(&path, doc.to_string()).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut table = &mut self.table;
+        for name in table_path.split('.') {
+            let entry = table.entry(name).or_insert_with(|| Value::Table(Table::new()));
+            let Some(next) = entry.as_table_mut() else {
+                return Ok(());
+            };
+            table = next;
+        }
+        match value {
+            Some(value) => {
+                table.insert(key.to_string(), Value::String(value.to_string()));
+            }
+            None => {
+                table.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Keys of the table at `path`, e.g. the names of `[prompt.profiles.*]`.
+    pub fn keys(&self, path: &str) -> Vec<String> {
+        match self.get(path).and_then(Value::as_table) {
+            Some(table) => table.keys().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Every setting that differs from `other`, by dotted path, with its
+    /// value here and there (`None` where it isn't set). Tables are compared
+    /// key by key; other values, arrays included, as a whole.
+    pub fn changes(&self, other: &Config) -> Vec<(String, Option<String>, Option<String>)> {
+        let mut found = Vec::new();
+        diff_tables("", &self.table, &other.table, &mut found);
+        found
+    }
+
+    pub fn get_str_list(&self, path: &str) -> Vec<String> {
+        match self.get(path).and_then(Value::as_array) {
+            Some(items) => items
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn diff_tables(prefix: &str, old: &Table, new: &Table, found: &mut Vec<(String, Option<String>, Option<String>)>) {
+    let mut keys: Vec<&String> = old.keys().chain(new.keys().filter(|k| !old.contains_key(*k))).collect();
+    keys.sort();
+    for key in keys {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (old.get(key), new.get(key)) {
+            (Some(Value::Table(old)), Some(Value::Table(new))) => diff_tables(&path, old, new, found),
+            (Some(Value::Table(old)), None) => diff_tables(&path, old, &Table::new(), found),
+            (None, Some(Value::Table(new))) => diff_tables(&path, &Table::new(), new, found),
+            (old, new) if old != new => found.push((path, old.map(Value::to_string), new.map(Value::to_string))),
+            _ => {}
+        }
+    }
+}
+
+/// When `config.toml` was last written, if it exists.
+pub fn modified() -> Option<SystemTime> {
+    fs::metadata(config_path()).and_then(|meta| meta.modified()).ok()
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// Expands a leading `~` or `~/` in a path.
+pub fn expand_home(path: &str) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => rest,
+        _ => return path.to_string(),
+    };
+    match home_dir() {
+        Some(home) => format!("{}{}", home.display(), rest),
+        None => path.to_string(),
+    }
+}
+
+/// `$OCTANE_CONFIG_DIR`, else `$XDG_CONFIG_HOME/octane`, else `~/.config/octane`.
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("OCTANE_CONFIG_DIR") {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        return PathBuf::from(dir).join("octane");
+    }
+    home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config")
+        .join("octane")
+}
+
+/// `$XDG_CACHE_HOME/octane`, else `~/.cache/octane`. Created on demand.
+pub fn cache_dir() -> PathBuf {
+    let dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir).join("octane"),
+        None => home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".cache")
+            .join("octane"),
+    };
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+/// `$XDG_DATA_HOME/octane`, else `~/.local/share/octane`. Holds state that
+/// should survive cache cleanups. Created on demand.
+pub fn data_dir() -> PathBuf {
+    let dir = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir).join("octane"),
+        None => home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".local")
+            .join("share")
+            .join("octane"),
+    };
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
@@ -0,0 +1,136 @@
+use std::env;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::messages::msg;
+use crate::picker;
+use crate::ssh;
+
+const LIST_TIMEOUT: Duration = Duration::from_secs(3);
+/// Container lists change often, so completion only reuses them briefly.
+const LIST_TTL: Duration = Duration::from_secs(10);
+
+/// Starts the best shell available in the container.
+const SHELL_PROBE: &str = "command -v bash >/dev/null 2>&1 && exec bash || exec sh";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Docker or Podman containers (`indocker`).
+    Container,
+    /// Kubernetes pods in the current context (`inpod`).
+    Pod,
+}
+
+static CACHE: Mutex<Vec<(Kind, Instant, Vec<String>)>> = Mutex::new(Vec::new());
+
+fn in_path(program: &str) -> bool {
+    let Some(path) = env::var_os("PATH") else {
+        return false;
+    };
+    env::split_paths(&path).any(|dir| {
+        dir.join(program).is_file() || dir.join(format!("{}.exe", program)).is_file()
+    })
+}
+
+/// `containers.runtime` from the config, else docker if installed, else podman.
+fn runtime(configured: Option<&str>) -> String {
+    if let Some(runtime) = configured {
+        return runtime.to_string();
+    }
+    if in_path("docker") || !in_path("podman") {
+        "docker".to_string()
+    } else {
+        "podman".to_string()
+    }
+}
+
+fn fetch(kind: Kind, configured_runtime: Option<&str>) -> Vec<String> {
+    let cmd = match kind {
+        Kind::Container => {
+            let mut cmd = Command::new(runtime(configured_runtime));
+            cmd.args(["ps", "--format", "{{.Names}}"]);
+            cmd
+        }
+        Kind::Pod => {
+            let mut cmd = Command::new("kubectl");
+            cmd.args(["get", "pods", "--field-selector=status.phase=Running", "-o", "name"]);
+            cmd
+        }
+    };
+    let Some(out) = ssh::output_with_timeout(cmd, LIST_TIMEOUT) else {
+        return Vec::new();
+    };
+    out.lines()
+        .map(|l| l.trim().trim_start_matches("pod/").to_string())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Running containers or pods, reusing a listing younger than `LIST_TTL`.
+pub fn list(kind: Kind, configured_runtime: Option<&str>) -> Vec<String> {
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((_, _, names)) = cache.iter().find(|(k, at, _)| *k == kind && at.elapsed() < LIST_TTL) {
+        return names.clone();
+    }
+    let names = fetch(kind, configured_runtime);
+    cache.retain(|(k, _, _)| *k != kind);
+    cache.push((kind, Instant::now(), names.clone()));
+    names
+}
+
+/// `indocker [name] [-- cmd...]` / `inpod [name] [-- cmd...]`
+///
+/// Without a name, offers a picker over the running containers or pods.
+/// Without a command, opens an interactive shell inside.
+pub fn run(kind: Kind, args: &[&str], config: &Config) -> i32 {
+    let configured_runtime = config.get_str("containers.runtime");
+    let split = args.iter().position(|a| *a == "--");
+    let (selector, command) = match split {
+        Some(i) => (&args[..i], &args[i + 1..]),
+        None => (args, &args[args.len()..]),
+    };
+    let what = if kind == Kind::Pod { msg!("containers.pods") } else { msg!("containers.containers") };
+
+    let name = match selector.first() {
+        Some(name) => name.to_string(),
+        None => {
+            let names = list(kind, configured_runtime);
+            if names.is_empty() {
+                println!("{}", msg!("containers.none_running", what));
+                return 1;
+            }
+            match picker::pick(&what, &names) {
+                Some(i) => names[i].clone(),
+                None => return 1,
+            }
+        }
+    };
+
+    let mut cmd = match kind {
+        Kind::Container => {
+            let mut cmd = Command::new(runtime(configured_runtime));
+            cmd.args(["exec", "-it", &name]);
+            cmd
+        }
+        Kind::Pod => {
+            let mut cmd = Command::new("kubectl");
+            cmd.args(["exec", "-it", &name, "--"]);
+            cmd
+        }
+    };
+    if command.is_empty() {
+        cmd.args(["sh", "-c", SHELL_PROBE]);
+    } else {
+        cmd.args(command);
+    }
+
+    match cmd.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            println!("{}", msg!("containers.failed", cmd.get_program().to_string_lossy(), e));
+            127
+        }
+    }
+}
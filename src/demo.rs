@@ -0,0 +1,90 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::messages::msg;
+use crate::profile::Profile;
+use crate::theme::Theme;
+
+/// Settings for an active demo session.
+pub struct Demo {
+    /// Pause between showing a command's banner and running it.
+    pub delay: Duration,
+    /// Profile to restore on `demo off`.
+    saved_profile: Profile,
+}
+
+impl Demo {
+    /// Prints `command` as a highlighted banner, then waits for the
+    /// configured delay so the audience can read it.
+    pub fn announce(&self, theme: &Theme, command: &str) {
+        println!("{}", theme.paint("banner", &format!("  {}  ", command)));
+        if !self.delay.is_zero() {
+            thread::sleep(self.delay);
+        }
+    }
+}
+
+fn parse_delay(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Some(ms) = value.strip_suffix("ms") {
+        return ms.parse().ok().map(Duration::from_millis);
+    }
+    let secs: f64 = value.trim_end_matches('s').parse().ok()?;
+    (secs >= 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+/// `demo [on [delay]|off]`
+///
+/// `demo on` switches to the `demo.profile` prompt profile (`presentation`
+/// by default) and shows every command in a banner before it runs, pausing
+/// for `delay` (`500ms`, `1.5s`; default `demo.delay`). `demo off` restores
+/// the previous prompt profile.
+pub fn run(args: &[&str], config: &Config, demo: &mut Option<Demo>, profile: &mut Profile) -> i32 {
+    match args {
+        [] => {
+            match demo {
+                Some(d) => println!("{}", msg!("demo.on", d.delay.as_millis())),
+                None => println!("{}", msg!("demo.off")),
+            }
+            0
+        }
+        ["on", rest @ ..] if rest.len() <= 1 => {
+            let spec = rest.first().copied().or(config.get_str("demo.delay"));
+            let delay = match spec.map(parse_delay) {
+                None => Duration::ZERO,
+                Some(Some(delay)) => delay,
+                Some(None) => {
+                    println!("demo: {}", msg!("demo.invalid_delay", spec.unwrap_or_default()));
+                    return 2;
+                }
+            };
+            let name = config.get_str("demo.profile").unwrap_or("presentation");
+            let new_profile = match Profile::load(name, config) {
+                Ok(p) => p,
+                Err(e) => {
+                    println!("{}", msg!("error.generic", e));
+                    return 1;
+                }
+            };
+            match demo {
+                Some(d) => d.delay = delay,
+                None => {
+                    let saved_profile = std::mem::replace(profile, new_profile);
+                    *demo = Some(Demo { delay, saved_profile });
+                }
+            }
+            0
+        }
+        ["off"] => {
+            if let Some(d) = demo.take() {
+                *profile = d.saved_profile;
+            }
+            0
+        }
+        _ => {
+            println!("{}", msg!("usage.demo"));
+            2
+        }
+    }
+}
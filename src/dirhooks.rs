@@ -0,0 +1,147 @@
+//! Hooks run when the working directory changes.
+//!
+//! A hook is a command line run with the old directory as `$1` and the new
+//! one as `$2`; a hook that is just the name of a function calls it with
+//! the two as arguments. It runs on every change, or only on entering or
+//! leaving a directory tree: an `enter` hook for `~/work/api` runs when `cd`
+//! moves from outside the tree to anywhere inside it, and a `leave` hook
+//! when it moves back out, so that a virtualenv can be activated and
+//! deactivated around a project.
+//!
+//! Hooks come from `[hooks]` in the config and from the `on-dir-change`
+//! builtin. The shell notices a change after each command, whatever made
+//! it — `cd`, a function, a sourced file — but not `@dir command`, which
+//! returns to where it started.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::messages::msg;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Change,
+    Enter,
+    Leave,
+}
+
+struct Hook {
+    event: Event,
+    /// The tree an `Enter` or `Leave` hook watches, as typed and resolved.
+    dir: Option<(String, PathBuf)>,
+    command: String,
+}
+
+/// The hooks, and the directory the shell was last seen in.
+pub struct Hooks {
+    hooks: Vec<Hook>,
+    current: Option<PathBuf>,
+    /// Set while hooks run, so a hook that changes directory doesn't start
+    /// them all again.
+    running: bool,
+}
+
+/// Resolves `dir` the way the working directory is reported, so that
+/// prefixes compare: `~` expanded and symlinks followed.
+fn resolve(dir: &str) -> PathBuf {
+    let dir = config::expand_home(dir);
+    fs::canonicalize(&dir).unwrap_or_else(|_| PathBuf::from(dir))
+}
+
+impl Hooks {
+    /// `hooks.on_dir_change` (a list of commands), and the tables
+    /// `hooks.enter` and `hooks.leave` from directory to command.
+    pub fn from_config(config: &Config) -> Hooks {
+        let mut hooks = Hooks { hooks: Vec::new(), current: std::env::current_dir().ok(), running: false };
+        for command in config.get_str_list("hooks.on_dir_change") {
+            hooks.add(Event::Change, None, &command);
+        }
+        for (event, table) in [(Event::Enter, "hooks.enter"), (Event::Leave, "hooks.leave")] {
+            for (dir, command) in config.get_str_map(table) {
+                hooks.add(event, Some(&dir), &command);
+            }
+        }
+        hooks
+    }
+
+    fn add(&mut self, event: Event, dir: Option<&str>, command: &str) {
+        let dir = dir.map(|dir| (dir.to_string(), resolve(dir)));
+        self.hooks.push(Hook { event, dir, command: command.to_string() });
+    }
+
+    /// Records the working directory. If it changed since last time, returns
+    /// the old and new directories and the hooks to run (perhaps none), in
+    /// order: `leave` hooks, then `enter` hooks, then those for every change.
+    pub fn changed(&mut self) -> Option<(PathBuf, PathBuf, Vec<String>)> {
+        let new = std::env::current_dir().ok()?;
+        if self.current.as_ref() == Some(&new) {
+            return None;
+        }
+        let old = self.current.replace(new.clone())?;
+        if self.running {
+            return None;
+        }
+        let inside =
+            |path: &Path, dir: &Option<(String, PathBuf)>| dir.as_ref().is_some_and(|(_, d)| path.starts_with(d));
+        let mut due = Vec::new();
+        for event in [Event::Leave, Event::Enter, Event::Change] {
+            for hook in self.hooks.iter().filter(|h| h.event == event) {
+                let runs = match event {
+                    Event::Change => true,
+                    Event::Enter => inside(&new, &hook.dir) && !inside(&old, &hook.dir),
+                    Event::Leave => inside(&old, &hook.dir) && !inside(&new, &hook.dir),
+                };
+                if runs {
+                    due.push(hook.command.clone());
+                }
+            }
+        }
+        Some((old, new, due))
+    }
+
+    /// Marks hooks as running or finished.
+    pub fn set_running(&mut self, running: bool) {
+        self.running = running;
+    }
+
+    fn list(&self) {
+        for (n, hook) in self.hooks.iter().enumerate() {
+            let when = match (&hook.event, &hook.dir) {
+                (Event::Enter, Some((dir, _))) => format!("--enter {}", dir),
+                (Event::Leave, Some((dir, _))) => format!("--leave {}", dir),
+                _ => String::new(),
+            };
+            println!("{:>3}  {}{}{}", n + 1, when, if when.is_empty() { "" } else { " " }, hook.command);
+        }
+    }
+}
+
+/// `on-dir-change [--enter|--leave dir] <command...>`, `on-dir-change -d n`,
+/// or without arguments, the list of hooks.
+pub fn run(hooks: &mut Hooks, args: &[&str]) -> i32 {
+    let usage = || {
+        println!("{}", msg!("usage.on_dir_change"));
+        2
+    };
+    match args {
+        [] => hooks.list(),
+        ["-d", n] => match n.parse::<usize>() {
+            Ok(n) if (1..=hooks.hooks.len()).contains(&n) => {
+                hooks.hooks.remove(n - 1);
+            }
+            _ => {
+                println!("on-dir-change: {}", msg!("dirhooks.no_such_hook", n));
+                return 1;
+            }
+        },
+        ["--enter" | "--leave", _] | ["-d", ..] => return usage(),
+        [flag @ ("--enter" | "--leave"), dir, command @ ..] => {
+            let event = if *flag == "--enter" { Event::Enter } else { Event::Leave };
+            hooks.add(event, Some(dir), &command.join(" "));
+        }
+        [flag, ..] if flag.starts_with('-') => return usage(),
+        command => hooks.add(Event::Change, None, &command.join(" ")),
+    }
+    0
+}
@@ -0,0 +1,169 @@
+//! The directory stack: `pushd`, `popd` and `dirs`.
+//!
+//! The stack lists directories to come back to. Its first entry is always
+//! the current directory, so `dirs` shows where the shell is and then the
+//! directories pushed before, most recent first. Entries are numbered from
+//! 0 on the left (`+N`) or from the right (`-N`). The stack belongs to the
+//! session and is not shared with other shells.
+
+use std::env;
+use std::path::PathBuf;
+
+use crate::messages::msg;
+use crate::term;
+use crate::{Shell, change_dir};
+
+/// The whole stack, the current directory first.
+fn entries(shell: &Shell) -> Vec<PathBuf> {
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    std::iter::once(cwd).chain(shell.dir_stack.iter().cloned()).collect()
+}
+
+/// The entry `+N` or `-N` names in a stack of `len` entries.
+fn position(arg: &str, len: usize) -> Option<Result<usize, String>> {
+    let (from_right, n) = match arg.as_bytes().first() {
+        Some(b'+') => (false, &arg[1..]),
+        Some(b'-') if arg.len() > 1 && arg[1..].bytes().all(|b| b.is_ascii_digit()) => (true, &arg[1..]),
+        _ => return None,
+    };
+    let found = n.parse::<usize>().ok().filter(|&n| n < len);
+    Some(match found {
+        Some(n) if from_right => Ok(len - 1 - n),
+        Some(n) => Ok(n),
+        None => Err(msg!("dirstack.out_of_range", arg)),
+    })
+}
+
+/// Changes to `dir`, the new top of the stack.
+fn enter(shell: &mut Shell, dir: &str) -> bool {
+    change_dir(shell, &[dir]) == 0
+}
+
+fn print(shell: &Shell, long: bool, numbered: bool, per_line: bool) {
+    let names: Vec<String> = entries(shell)
+        .iter()
+        .map(|dir| if long { dir.display().to_string() } else { term::display_path(dir) })
+        .collect();
+    if numbered {
+        for (n, name) in names.iter().enumerate() {
+            println!("{:>2}  {}", n, name);
+        }
+    } else if per_line {
+        for name in &names {
+            println!("{}", name);
+        }
+    } else {
+        println!("{}", names.join(" "));
+    }
+}
+
+/// `dirs [-c] [-l] [-p] [-v] [+N|-N]`
+pub fn dirs(shell: &mut Shell, args: &[&str]) -> i32 {
+    let (mut long, mut numbered, mut per_line) = (false, false, false);
+    for arg in args {
+        if let Some(found) = position(arg, shell.dir_stack.len() + 1) {
+            return match found {
+                Ok(n) => {
+                    println!("{}", term::display_path(&entries(shell)[n]));
+                    0
+                }
+                Err(e) => {
+                    println!("dirs: {}", e);
+                    1
+                }
+            };
+        }
+        match *arg {
+            "-c" => shell.dir_stack.clear(),
+            "-l" => long = true,
+            "-p" => per_line = true,
+            "-v" => numbered = true,
+            _ => {
+                println!("{}", msg!("usage.dirs"));
+                return 2;
+            }
+        }
+    }
+    if !args.contains(&"-c") || args.len() > 1 {
+        print(shell, long, numbered, per_line);
+    }
+    0
+}
+
+/// `pushd [dir | +N | -N]`: pushes the current directory and changes to
+/// `dir`; without arguments, swaps the top two entries; with `+N` or `-N`,
+/// rotates the stack so that entry is on top.
+pub fn pushd(shell: &mut Shell, args: &[&str]) -> i32 {
+    let mut stack = entries(shell);
+    match args {
+        [] => {
+            if stack.len() < 2 {
+                println!("pushd: {}", msg!("dirstack.no_other_directory"));
+                return 1;
+            }
+            stack.swap(0, 1);
+        }
+        [arg] => match position(arg, stack.len()) {
+            Some(Ok(n)) => stack.rotate_left(n),
+            Some(Err(e)) => {
+                println!("pushd: {}", e);
+                return 1;
+            }
+            None => {
+                if !enter(shell, arg) {
+                    return 1;
+                }
+                shell.dir_stack = stack;
+                print(shell, false, false, false);
+                return 0;
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.pushd"));
+            return 2;
+        }
+    }
+    let top = stack.remove(0);
+    if !enter(shell, &top.display().to_string()) {
+        return 1;
+    }
+    shell.dir_stack = stack;
+    print(shell, false, false, false);
+    0
+}
+
+/// `popd [+N | -N]`: removes the top entry and changes to the next one, or
+/// removes entry `N`, changing directory only if it was the top.
+pub fn popd(shell: &mut Shell, args: &[&str]) -> i32 {
+    let mut stack = entries(shell);
+    if stack.len() < 2 {
+        println!("popd: {}", msg!("dirstack.empty"));
+        return 1;
+    }
+    let n = match args {
+        [] => 0,
+        [arg] => match position(arg, stack.len()) {
+            Some(Ok(n)) => n,
+            Some(Err(e)) => {
+                println!("popd: {}", e);
+                return 1;
+            }
+            None => {
+                println!("{}", msg!("usage.popd"));
+                return 2;
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.popd"));
+            return 2;
+        }
+    };
+    stack.remove(n);
+    let top = stack.remove(0);
+    if n == 0 && !enter(shell, &top.display().to_string()) {
+        return 1;
+    }
+    shell.dir_stack = stack;
+    print(shell, false, false, false);
+    0
+}
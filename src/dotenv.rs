@@ -0,0 +1,181 @@
+//! The `dotenv` builtin: loads `.env` files into the environment.
+//!
+//! A `.env` file has one `KEY=value` per line, optionally after `export`.
+//! Lines starting with `#` are comments, as is the rest of a line after
+//! ` #` when the value isn't quoted. Single quotes keep a value as it is;
+//! double quotes may span lines and understand `\n`, `\t`, `\"`, `\$` and `\\`.
+//! Unquoted and double-quoted values expand `$VAR` and `${VAR}`, including
+//! variables set earlier in the same file.
+//!
+//! `dotenv --unload` puts back what loading a file changed; a file that
+//! wasn't loaded in this session has its variables removed.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config;
+use crate::messages::msg;
+use crate::vars;
+
+/// The files loaded in this session, with the values they replaced.
+#[derive(Default)]
+pub struct Loaded {
+    files: HashMap<PathBuf, Vec<(String, Option<OsString>)>>,
+}
+
+/// Expands `$VAR` and `${VAR}` in `text`, and with `escapes` the
+/// backslash escapes of double quotes; `assigned` holds the values set
+/// earlier in the file.
+fn expand(text: &str, assigned: &[(String, String)], escapes: bool) -> String {
+    let lookup = |name: &str| {
+        assigned
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .or_else(|| env::var(name).ok())
+            .unwrap_or_default()
+    };
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if escapes => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(c @ ('"' | '\\' | '$')) => out.push(c),
+                Some(c) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                None => out.push('\\'),
+            },
+            '$' => {
+                let mut name = String::new();
+                if chars.next_if_eq(&'{').is_some() {
+                    name.extend(chars.by_ref().take_while(|&c| c != '}'));
+                } else {
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                        name.push(c);
+                    }
+                    if name.is_empty() {
+                        out.push('$');
+                        continue;
+                    }
+                }
+                out.push_str(&lookup(&name));
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The assignments in `text`, in order, or the number of the first line
+/// that isn't one.
+fn parse(text: &str) -> Result<Vec<(String, String)>, usize> {
+    let mut assigned: Vec<(String, String)> = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let Some((key, value)) = line.split_once('=').filter(|(key, _)| vars::valid_name(key.trim())) else {
+            return Err(number + 1);
+        };
+        let value = value.trim_start();
+        let value = if let Some(quoted) = value.strip_prefix('\'') {
+            quoted.split_once('\'').ok_or(number + 1)?.0.to_string()
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            // Up to the closing quote, which may be on a later line.
+            let mut raw = quoted.to_string();
+            let body = loop {
+                if let Some(end) = closing_quote(&raw) {
+                    break raw[..end].to_string();
+                }
+                let (_, next) = lines.next().ok_or(number + 1)?;
+                raw.push('\n');
+                raw.push_str(next);
+            };
+            expand(&body, &assigned, true)
+        } else {
+            let value = value.find(" #").map_or(value, |end| &value[..end]).trim_end();
+            expand(value, &assigned, false)
+        };
+        assigned.push((key.trim().to_string(), value));
+    }
+    Ok(assigned)
+}
+
+/// The byte offset of the first `"` not escaped with a backslash.
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// `dotenv [--unload] [file]`
+pub fn run(loaded: &mut Loaded, args: &[&str]) -> i32 {
+    let (unload, file) = match args {
+        [] => (false, ".env"),
+        ["--unload"] => (true, ".env"),
+        ["--unload", file] => (true, *file),
+        [file] if !file.starts_with('-') => (false, *file),
+        _ => {
+            println!("{}", msg!("usage.dotenv"));
+            return 2;
+        }
+    };
+    let path = PathBuf::from(config::expand_home(file));
+    let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+    if unload && let Some(saved) = loaded.files.remove(&key) {
+        let count = saved.len();
+        vars::restore(saved);
+        println!("{}", msg!("dotenv.unloaded", count, file));
+        return 0;
+    }
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("dotenv: {}: {}", file, e);
+            return 1;
+        }
+    };
+    let assignments = match parse(&text) {
+        Ok(assignments) => assignments,
+        Err(line) => {
+            println!("dotenv: {}", msg!("dotenv.bad_line", file, line));
+            return 1;
+        }
+    };
+    if unload {
+        for (name, _) in &assignments {
+            vars::remove_env(name);
+        }
+        println!("{}", msg!("dotenv.unloaded", assignments.len(), file));
+        return 0;
+    }
+    // Loading again keeps the values from before the first load.
+    let mut saved = loaded.files.remove(&key).unwrap_or_default();
+    for (name, value) in &assignments {
+        if !saved.iter().any(|(saved, _)| saved == name) {
+            saved.push((name.clone(), env::var_os(name)));
+        }
+        vars::set_env(name, value);
+    }
+    let names: Vec<&str> = assignments.iter().map(|(name, _)| name.as_str()).collect();
+    println!("{}", msg!("dotenv.loaded", file, names.join(" ")));
+    loaded.files.insert(key, saved);
+    0
+}
@@ -0,0 +1,341 @@
+//! Aliases, functions and themes from a git repository of dotfiles.
+//!
+//! `dotfiles.repo` names the repository, by URL or path. `dotfiles update`
+//! fetches it into `dotfiles` in the data directory, shows what changed
+//! since the last update, checks that every fragment parses, and once
+//! confirmed checks the new commit out and reloads the fragments in the
+//! running shell. At startup the shell loads what was checked out last,
+//! without going to the network. The fragments are:
+//!
+//! * `aliases.toml`: `name = "value"` pairs,
+//! * `functions/*.osh`: function definitions, and nothing else,
+//! * `themes/*.toml`: themes, which `theme` finds after the user's own.
+//!
+//! Aliases set in the config's `[aliases]` win over the repository's.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use git2::build::CheckoutBuilder;
+use git2::{Cred, DiffFormat, FetchOptions, ObjectType, Oid, RemoteCallbacks, Repository, Tree};
+use toml::{Table, Value};
+
+use crate::Shell;
+use crate::alias;
+use crate::config::{self, Config};
+use crate::functions;
+use crate::messages::msg;
+use crate::syntax;
+use crate::term;
+use crate::theme::Theme;
+
+/// Where the repository is checked out.
+pub fn dir() -> PathBuf {
+    config::data_dir().join("dotfiles")
+}
+
+pub fn themes_dir() -> PathBuf {
+    dir().join("themes")
+}
+
+/// What the repository's fragments define.
+#[derive(Default)]
+struct Fragments {
+    aliases: Vec<(String, String)>,
+    functions: Vec<(String, String)>,
+    themes: Vec<String>,
+}
+
+/// What the shell loaded from the repository, so an update can take it
+/// back.
+#[derive(Default)]
+pub struct Loaded {
+    commit: Option<Oid>,
+    aliases: Vec<(String, String)>,
+    functions: Vec<(String, String)>,
+    themes: Vec<String>,
+}
+
+/// The text of blob `id`, the file at `path`.
+fn text(repo: &Repository, id: Oid, path: &str) -> Result<String, String> {
+    let blob = repo.find_blob(id).map_err(|e| e.message().to_string())?;
+    String::from_utf8(blob.content().to_vec()).map_err(|_| msg!("dotfiles.not_text", path))
+}
+
+/// The files directly in `dir` of `tree` ending in `extension`, by path,
+/// with their text.
+fn files(repo: &Repository, tree: &Tree, dir: &str, extension: &str) -> Result<Vec<(String, String)>, String> {
+    let tree = match tree.get_name(dir) {
+        Some(entry) if entry.kind() == Some(ObjectType::Tree) => {
+            repo.find_tree(entry.id()).map_err(|e| e.message().to_string())?
+        }
+        _ => return Ok(Vec::new()),
+    };
+    let mut found = Vec::new();
+    for entry in tree.iter().filter(|entry| entry.kind() == Some(ObjectType::Blob)) {
+        if let Some(name) = entry.name().filter(|name| name.ends_with(extension)) {
+            let path = format!("{}/{}", dir, name);
+            found.push((path.clone(), text(repo, entry.id(), &path)?));
+        }
+    }
+    Ok(found)
+}
+
+/// Splits `text` into function definitions, refusing anything else.
+fn definitions(path: &str, text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut found = Vec::new();
+    let mut pending = String::new();
+    for line in text.lines().map(str::trim) {
+        if pending.is_empty() && (line.is_empty() || line.starts_with('#')) {
+            continue;
+        }
+        if !pending.is_empty() {
+            pending.push('\n');
+        }
+        pending.push_str(line);
+        if syntax::open_blocks(&pending) > 0 {
+            continue;
+        }
+        let statement = std::mem::take(&mut pending);
+        match functions::parse_definition(&statement) {
+            Some((name, body)) => found.push((name.to_string(), body.to_string())),
+            None => return Err(msg!("dotfiles.not_a_definition", path, statement.lines().next().unwrap_or(""))),
+        }
+    }
+    if !pending.is_empty() {
+        return Err(msg!("dotfiles.not_a_definition", path, pending.lines().next().unwrap_or("")));
+    }
+    Ok(found)
+}
+
+/// Reads and checks the fragments of `tree`; the first one that doesn't
+/// parse is the error.
+fn read(repo: &Repository, tree: &Tree) -> Result<Fragments, String> {
+    let mut fragments = Fragments::default();
+    if let Some(entry) = tree.get_name("aliases.toml") {
+        let path = "aliases.toml";
+        let table: Table = text(repo, entry.id(), path)?.parse().map_err(|e| format!("{}: {}", path, e))?;
+        for (name, value) in table {
+            match value {
+                Value::String(value) if !name.contains(char::is_whitespace) => fragments.aliases.push((name, value)),
+                _ => return Err(msg!("dotfiles.bad_alias", path, name)),
+            }
+        }
+    }
+    for (path, text) in files(repo, tree, "functions", ".osh")? {
+        fragments.functions.extend(definitions(&path, &text)?);
+    }
+    for (path, text) in files(repo, tree, "themes", ".toml")? {
+        let name = path.trim_start_matches("themes/").trim_end_matches(".toml");
+        Theme::parse(name, &text).map_err(|e| format!("{}: {}", path, e))?;
+        fragments.themes.push(name.to_string());
+    }
+    Ok(fragments)
+}
+
+/// Replaces what `shell` loaded from the repository before with
+/// `fragments`. Aliases and functions changed since in the session stay.
+fn apply(shell: &mut Shell, commit: Oid, fragments: Fragments) {
+    let loaded = std::mem::take(&mut shell.dotfiles);
+    let base = alias::load(&shell.config);
+    for (name, value) in loaded.aliases {
+        if shell.aliases.get(&name) == Some(&value) {
+            match base.get(&name) {
+                Some(value) => shell.aliases.insert(name, value.clone()),
+                None => shell.aliases.remove(&name),
+            };
+        }
+    }
+    for (name, body) in loaded.functions {
+        if shell.functions.get(&name) == Some(&body) {
+            shell.functions.remove(&name);
+        }
+    }
+    let configured: HashMap<String, String> = shell.config.get_str_map("aliases").into_iter().collect();
+    for (name, value) in &fragments.aliases {
+        if !configured.contains_key(name) {
+            shell.aliases.insert(name.clone(), value.clone());
+        }
+    }
+    for (name, body) in &fragments.functions {
+        shell.functions.insert(name.clone(), body.clone());
+    }
+    // The theme in use is drawn from the new file.
+    if fragments.themes.contains(&shell.theme.name)
+        && let Ok(theme) = Theme::load(&shell.theme.name)
+    {
+        shell.theme = theme;
+    }
+    shell.dotfiles = Loaded {
+        commit: Some(commit),
+        aliases: fragments.aliases,
+        functions: fragments.functions,
+        themes: fragments.themes,
+    };
+}
+
+/// Loads the fragments checked out last, if `dotfiles.repo` is set.
+pub fn load(shell: &mut Shell) {
+    if !shell.config.has("dotfiles.repo") {
+        return;
+    }
+    let Ok(repo) = Repository::open(dir()) else {
+        return;
+    };
+    let Ok(commit) = repo.head().and_then(|head| head.peel_to_commit()) else {
+        return;
+    };
+    let fragments = commit.tree().map_err(|e| e.message().to_string()).and_then(|tree| read(&repo, &tree));
+    match fragments {
+        Ok(fragments) => apply(shell, commit.id(), fragments),
+        Err(e) => println!("{}", msg!("dotfiles.invalid", e)),
+    }
+}
+
+/// Fetches every branch of `url` into the remote `origin`.
+fn fetch(repo: &Repository, url: &str) -> Result<(), git2::Error> {
+    let mut remote = match repo.find_remote("origin") {
+        Ok(remote) if remote.url() == Some(url) => remote,
+        _ => {
+            let _ = repo.remote_delete("origin");
+            repo.remote("origin", url)?
+        }
+    };
+    let mut callbacks = RemoteCallbacks::new();
+    let mut tried = false;
+    // Keys from the SSH agent, asked once: libgit2 asks again on failure.
+    callbacks.credentials(move |_, username, _| {
+        if std::mem::replace(&mut tried, true) {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+        Cred::ssh_key_from_agent(username.unwrap_or("git"))
+    });
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    remote.fetch(&["+refs/heads/*:refs/remotes/origin/*"], Some(&mut options), None)
+}
+
+/// The branch to follow: `dotfiles.branch`, the one checked out, or
+/// `main` or `master`, whichever the remote has.
+fn branch(repo: &Repository, config: &Config) -> Option<String> {
+    if let Some(branch) = config.get_str("dotfiles.branch") {
+        return Some(branch.to_string());
+    }
+    if let Ok(head) = repo.head()
+        && let Some(name) = head.shorthand()
+    {
+        return Some(name.to_string());
+    }
+    ["main", "master"]
+        .into_iter()
+        .find(|name| repo.find_reference(&format!("refs/remotes/origin/{}", name)).is_ok())
+        .map(str::to_string)
+}
+
+/// Prints the diff between two trees, `None` being an empty one.
+fn show_diff(repo: &Repository, theme: &Theme, old: Option<&Tree>, new: &Tree) -> Result<(), git2::Error> {
+    let diff = repo.diff_tree_to_tree(old, Some(new), None)?;
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        let text = String::from_utf8_lossy(line.content());
+        let text = text.trim_end_matches('\n');
+        match line.origin() {
+            '+' => println!("{}", theme.paint("diff_added", &format!("+{}", text))),
+            '-' => println!("{}", theme.paint("diff_removed", &format!("-{}", text))),
+            ' ' => println!(" {}", text),
+            _ => println!("{}", text),
+        }
+        true
+    })
+}
+
+/// `dotfiles update [-y]`
+fn update(shell: &mut Shell, assume_yes: bool) -> Result<i32, String> {
+    let git = |e: git2::Error| e.message().to_string();
+    let Some(url) = shell.config.get_str("dotfiles.repo").map(config::expand_home) else {
+        println!("dotfiles: {}", msg!("dotfiles.not_configured"));
+        return Ok(1);
+    };
+    let repo = match Repository::open(dir()) {
+        Ok(repo) => repo,
+        Err(_) => Repository::init(dir()).map_err(git)?,
+    };
+    println!("{}", msg!("dotfiles.fetching", url));
+    fetch(&repo, &url).map_err(git)?;
+    let Some(branch) = branch(&repo, &shell.config) else {
+        return Err(msg!("dotfiles.no_branch"));
+    };
+    let remote = repo
+        .find_reference(&format!("refs/remotes/origin/{}", branch))
+        .map_err(|_| msg!("dotfiles.no_such_branch", branch))?;
+    let new = remote.peel_to_commit().map_err(git)?;
+    let old = repo.head().and_then(|head| head.peel_to_commit()).ok();
+    if old.as_ref().is_some_and(|old| old.id() == new.id()) {
+        println!("{}", msg!("dotfiles.up_to_date"));
+        return Ok(0);
+    }
+    let old_tree = old.as_ref().map(|commit| commit.tree()).transpose().map_err(git)?;
+    let new_tree = new.tree().map_err(git)?;
+    show_diff(&repo, &shell.theme, old_tree.as_ref(), &new_tree).map_err(git)?;
+    let fragments = match read(&repo, &new_tree) {
+        Ok(fragments) => fragments,
+        Err(e) => {
+            println!("dotfiles: {}", msg!("dotfiles.invalid", e));
+            return Ok(1);
+        }
+    };
+    if !assume_yes && !term::confirm(&msg!("dotfiles.confirm")) {
+        return Ok(1);
+    }
+    repo.checkout_tree(new.as_object(), Some(CheckoutBuilder::new().force().remove_untracked(true)))
+        .map_err(git)?;
+    let local = format!("refs/heads/{}", branch);
+    repo.reference(&local, new.id(), true, "dotfiles update").map_err(git)?;
+    repo.set_head(&local).map_err(git)?;
+    let summary = new.summary().unwrap_or("").to_string();
+    apply(shell, new.id(), fragments);
+    println!("{}", msg!("dotfiles.updated", short(new.id()), summary));
+    Ok(0)
+}
+
+fn short(oid: Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+/// `dotfiles [status]` / `dotfiles update [-y]`
+pub fn run(shell: &mut Shell, args: &[&str]) -> i32 {
+    match args {
+        [] | ["status"] => {
+            let Some(url) = shell.config.get_str("dotfiles.repo") else {
+                println!("dotfiles: {}", msg!("dotfiles.not_configured"));
+                return 1;
+            };
+            println!("{}", msg!("dotfiles.repo", url));
+            let loaded = &shell.dotfiles;
+            match loaded.commit {
+                Some(commit) => println!(
+                    "{}",
+                    msg!(
+                        "dotfiles.loaded",
+                        short(commit),
+                        loaded.aliases.len(),
+                        loaded.functions.len(),
+                        loaded.themes.len()
+                    )
+                ),
+                None => println!("{}", msg!("dotfiles.never_updated")),
+            }
+            0
+        }
+        ["update"] | ["update", "-y"] => match update(shell, args.len() == 2) {
+            Ok(status) => status,
+            Err(e) => {
+                println!("dotfiles: {}", e);
+                1
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.dotfiles"));
+            2
+        }
+    }
+}
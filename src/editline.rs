@@ -0,0 +1,40 @@
+//! Ctrl-X Ctrl-E: edit the command line in `$VISUAL` or `$EDITOR`.
+//!
+//! The key handler can't run an editor while the line editor owns the
+//! terminal, so it stashes the line and ends `readline`; the REPL then opens
+//! the editor and starts the next prompt with the saved text, ready to run
+//! with Enter.
+
+use std::sync::Mutex;
+
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+use crate::term;
+
+/// The line to edit, set by the key handler.
+static PENDING: Mutex<Option<String>> = Mutex::new(None);
+
+/// Ctrl-X Ctrl-E: hands the line over to [`edit_pending`].
+pub struct EditKey;
+
+impl ConditionalEventHandler for EditKey {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        *PENDING.lock().unwrap() = Some(ctx.line().to_string());
+        Some(Cmd::Interrupt)
+    }
+}
+
+/// Edits the line stashed by the key handler, if any, and returns the text
+/// to start the next prompt with: the saved file, or the line as it was when
+/// the editor failed.
+pub fn edit_pending() -> Option<String> {
+    let line = PENDING.lock().unwrap().take()?;
+    println!();
+    match term::edit(&term::editor(), "line.osh", &format!("{}\n", line)) {
+        Ok(edited) => Some(edited.trim_end_matches(['\n', '\r']).to_string()),
+        Err(e) => {
+            println!("{}", e);
+            Some(line)
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Per-directory environments: `.octane-env` and `.envrc`.
+//!
+//! When the interactive shell changes directory, the nearest `.octane-env`
+//! or `.envrc` in the new directory or above it is sourced, and the
+//! environment variables it set are put back as they were when the shell
+//! moves out of that tree again. Since sourcing a file runs whatever is in
+//! it, only files allowed with `envfile allow` are loaded: the allowed
+//! content is kept under the data directory, and a file that changed since
+//! has to be allowed again. Other files are only reported.
+//!
+//! `envfile.names` picks the file names to look for, nearest first where
+//! several are in one directory; `envfile.enabled = false` turns loading
+//! off.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Shell;
+use crate::config::{self, Config};
+use crate::messages::msg;
+use crate::vars;
+
+const NAMES: [&str; 2] = [".octane-env", ".envrc"];
+
+/// The file loaded, and what it changed.
+struct Loaded {
+    file: PathBuf,
+    /// The variables it set or removed, and their values before.
+    saved: Vec<(String, Option<OsString>)>,
+}
+
+#[derive(Default)]
+pub struct State {
+    loaded: Option<Loaded>,
+    /// The last file found but not allowed, so it's reported once.
+    blocked: Option<PathBuf>,
+}
+
+fn names(config: &Config) -> Vec<String> {
+    if config.has("envfile.names") {
+        config.get_str_list("envfile.names")
+    } else {
+        NAMES.iter().map(|name| name.to_string()).collect()
+    }
+}
+
+/// The nearest env file at or above `dir`.
+fn find(config: &Config, dir: &Path) -> Option<PathBuf> {
+    let names = names(config);
+    dir.ancestors()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Where the allowed content of `file` is kept: its path on the first line,
+/// then the content. Named by an FNV-1a hash of the path, which unlike std's
+/// hasher stays the same from one build to the next.
+fn allowed_copy(file: &Path) -> PathBuf {
+    let hash = file
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3));
+    config::data_dir().join("envfile").join(format!("{:016x}", hash))
+}
+
+fn record(file: &Path) -> Option<Vec<u8>> {
+    let mut record = format!("{}\n", file.display()).into_bytes();
+    record.extend(fs::read(file).ok()?);
+    Some(record)
+}
+
+fn allowed(file: &Path) -> bool {
+    record(file).is_some_and(|record| fs::read(allowed_copy(file)).is_ok_and(|copy| copy == record))
+}
+
+fn snapshot() -> HashMap<OsString, OsString> {
+    env::vars_os().collect()
+}
+
+/// A summary of the changes, as direnv shows them: `+NEW ~CHANGED -REMOVED`.
+fn summary(saved: &[(String, Option<OsString>)]) -> String {
+    let mut changes: Vec<String> = saved
+        .iter()
+        .map(|(name, previous)| {
+            let sign = match (previous, env::var_os(name)) {
+                (None, _) => '+',
+                (Some(_), None) => '-',
+                (Some(_), Some(_)) => '~',
+            };
+            format!("{}{}", sign, name)
+        })
+        .collect();
+    changes.sort_by(|a, b| a[1..].cmp(&b[1..]));
+    changes.join(" ")
+}
+
+fn load(shell: &mut Shell, file: &Path) {
+    let before = snapshot();
+    crate::source(shell, &[&file.display().to_string()]);
+    let after = snapshot();
+    let mut saved = Vec::new();
+    for (name, value) in &after {
+        if before.get(name) != Some(value) {
+            saved.push((name.to_string_lossy().into_owned(), before.get(name).cloned()));
+        }
+    }
+    for (name, value) in &before {
+        if !after.contains_key(name) {
+            saved.push((name.to_string_lossy().into_owned(), Some(value.clone())));
+        }
+    }
+    println!("{}", msg!("envfile.loaded", file.display(), summary(&saved)));
+    shell.env_file.loaded = Some(Loaded { file: file.to_path_buf(), saved });
+}
+
+fn unload(shell: &mut Shell) {
+    if let Some(loaded) = shell.env_file.loaded.take() {
+        vars::restore(loaded.saved);
+        println!("{}", msg!("envfile.unloaded", loaded.file.display()));
+    }
+}
+
+/// Loads the env file for the working directory, after unloading the one
+/// for the last, when they differ. Called at startup and by the directory
+/// hooks.
+pub fn update(shell: &mut Shell) {
+    if !shell.interactive || !shell.config.get_bool("envfile.enabled").unwrap_or(true) {
+        return;
+    }
+    let Ok(cwd) = env::current_dir() else {
+        return;
+    };
+    let found = find(&shell.config, &cwd);
+    if found.is_some() && shell.env_file.loaded.as_ref().map(|loaded| &loaded.file) == found.as_ref() {
+        return;
+    }
+    unload(shell);
+    let Some(file) = found else {
+        shell.env_file.blocked = None;
+        return;
+    };
+    if allowed(&file) {
+        shell.env_file.blocked = None;
+        load(shell, &file);
+    } else if shell.env_file.blocked.as_ref() != Some(&file) {
+        println!("{}", msg!("envfile.blocked", file.display()));
+        shell.env_file.blocked = Some(file);
+    }
+}
+
+/// The file named on the command line, or else the nearest one.
+fn target(shell: &Shell, args: &[&str]) -> Option<PathBuf> {
+    match args {
+        [] => find(&shell.config, &env::current_dir().ok()?),
+        [path] => {
+            let path = PathBuf::from(config::expand_home(path));
+            let path = if path.is_dir() { NAMES.iter().map(|name| path.join(name)).find(|p| p.is_file())? } else { path };
+            fs::canonicalize(path).ok()
+        }
+        _ => None,
+    }
+}
+
+/// `envfile [status]`, `envfile allow [file]`, `envfile deny [file]` or
+/// `envfile reload`
+pub fn run(shell: &mut Shell, args: &[&str]) -> i32 {
+    match args {
+        [] | ["status"] => {
+            match &shell.env_file.loaded {
+                Some(loaded) => println!("{}", msg!("envfile.loaded", loaded.file.display(), summary(&loaded.saved))),
+                None => println!("{}", msg!("envfile.none")),
+            }
+            if let Some(file) = &shell.env_file.blocked {
+                println!("{}", msg!("envfile.blocked", file.display()));
+            }
+            0
+        }
+        ["reload"] => {
+            unload(shell);
+            shell.env_file.blocked = None;
+            update(shell);
+            0
+        }
+        [action @ ("allow" | "deny"), rest @ ..] => {
+            let Some(file) = target(shell, rest) else {
+                println!("envfile: {}", msg!("envfile.not_found"));
+                return 1;
+            };
+            let copy = allowed_copy(&file);
+            let result = if *action == "allow" {
+                let record = record(&file).unwrap_or_default();
+                copy.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&copy, record))
+            } else {
+                fs::remove_file(&copy).or_else(|e| if e.kind() == std::io::ErrorKind::NotFound { Ok(()) } else { Err(e) })
+            };
+            if let Err(e) = result {
+                println!("envfile: {}", msg!("error.path", copy.display(), e));
+                return 1;
+            }
+            let key = if *action == "allow" { "envfile.allowed" } else { "envfile.denied" };
+            println!("{}", msg!(key, file.display()));
+            // Loaded again from the content just allowed, or not at all.
+            if shell.env_file.loaded.as_ref().is_some_and(|loaded| loaded.file == file) {
+                unload(shell);
+            }
+            if *action == "allow" {
+                shell.env_file.blocked = None;
+                update(shell);
+            } else if find(&shell.config, &env::current_dir().unwrap_or_default()).as_ref() == Some(&file) {
+                shell.env_file.blocked = Some(file);
+            }
+            0
+        }
+        _ => {
+            println!("{}", msg!("usage.envfile"));
+            2
+        }
+    }
+}
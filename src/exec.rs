@@ -0,0 +1,297 @@
+//! The `exec` builtin.
+//!
+//! `exec command [args...]` replaces the shell with `command`. Without a
+//! command, `exec` applies its redirections to the shell itself, so they
+//! stay in effect for everything run afterwards: `exec >build.log 2>&1`
+//! sends the rest of a script's output to a file.
+//!
+//! Redirections are `[n]>file`, `[n]>>file`, `[n]<file` and `[n]>&m`,
+//! where `n` is 0, 1 or 2; the file may also be the next word
+//! (`exec > out.txt`).
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::process::Command;
+
+use crate::Shell;
+use crate::config;
+use crate::lookup;
+use crate::messages::msg;
+use crate::shutdown;
+
+/// Where a redirection sends a standard stream.
+enum Target {
+    /// Opened for reading.
+    Read(String),
+    /// Truncated, or appended to when `true`.
+    Write(String, bool),
+    /// Another standard stream (`2>&1`).
+    Duplicate(i32),
+}
+
+struct Redirection {
+    fd: i32,
+    target: Target,
+}
+
+/// Parses `word` as a redirection, taking the file from `next` when `word`
+/// is only the operator. Returns the redirection and whether `next` was used.
+fn parse(word: &str, next: Option<&str>) -> Option<Result<(Redirection, bool), String>> {
+    let digits = word.chars().take_while(char::is_ascii_digit).count();
+    let (number, rest) = word.split_at(digits);
+    let (default_fd, operator) = [(">>", 1), (">&", 1), ("<&", 0), (">", 1), ("<", 0)]
+        .into_iter()
+        .find(|(op, _)| rest.starts_with(op))
+        .map(|(op, fd)| (fd, op))?;
+    let fd = if number.is_empty() { default_fd } else { number.parse().ok()? };
+    let word_target = &rest[operator.len()..];
+    let (target, used_next) = match (word_target, next) {
+        ("", Some(next)) => (next, true),
+        ("", None) => return Some(Err(msg!("exec.missing_target", word))),
+        (target, _) => (target, false),
+    };
+    if !(0..=2).contains(&fd) {
+        return Some(Err(msg!("exec.bad_descriptor", fd)));
+    }
+    let target = match operator {
+        ">&" | "<&" => match target.parse() {
+            Ok(other) if (0..=2).contains(&other) => Target::Duplicate(other),
+            _ => return Some(Err(msg!("exec.bad_descriptor", target))),
+        },
+        "<" => Target::Read(config::expand_home(target)),
+        _ => Target::Write(config::expand_home(target), operator == ">>"),
+    };
+    Some(Ok((Redirection { fd, target }, used_next)))
+}
+
+fn open(target: &Target) -> io::Result<Option<File>> {
+    match target {
+        Target::Read(path) => File::open(path).map(Some),
+        Target::Write(path, append) => OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(*append)
+            .truncate(!*append)
+            .open(path)
+            .map(Some),
+        Target::Duplicate(_) => Ok(None),
+    }
+}
+
+/// Points the shell's standard stream `fd` at `file`, or at the stream
+/// `other` when there is no file.
+#[cfg(unix)]
+fn redirect(fd: i32, file: Option<File>, other: i32) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let source = file.as_ref().map_or(other, |f| f.as_raw_fd());
+    // SAFETY: both descriptors are valid; `file` is closed afterwards,
+    // leaving `fd` as its only reference.
+    if unsafe { libc::dup2(source, fd) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn redirect(fd: i32, file: Option<File>, other: i32) -> io::Result<()> {
+    use std::os::windows::io::IntoRawHandle;
+    use windows_sys::Win32::System::Console::{
+        GetStdHandle, STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE, SetStdHandle,
+    };
+    let standard = |fd: i32| match fd {
+        0 => STD_INPUT_HANDLE,
+        1 => STD_OUTPUT_HANDLE,
+        _ => STD_ERROR_HANDLE,
+    };
+    unsafe {
+        // The handle stays open for as long as the shell runs.
+        let handle = match file {
+            Some(file) => file.into_raw_handle() as isize,
+            None => GetStdHandle(standard(other)),
+        };
+        if SetStdHandle(standard(fd), handle) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Standard streams of the shell, saved while they point at a file.
+#[cfg(unix)]
+pub struct Saved(Vec<(libc::c_int, libc::c_int)>);
+
+/// Points the shell's stdout and stderr at `file`, until `restore_output`.
+pub fn redirect_output(file: &File) -> io::Result<Saved> {
+    redirect_streams(file, &[1, 2])
+}
+
+/// Points the shell's stdout alone at `file`, until `restore_output`.
+pub fn redirect_stdout(file: &File) -> io::Result<Saved> {
+    redirect_streams(file, &[1])
+}
+
+/// Points the shell's stdin at `input` and its stdout at `output`, where
+/// given, until `restore_output`.
+pub fn redirect_stdio(input: Option<&File>, output: Option<&File>) -> io::Result<Saved> {
+    let mut saved = match input {
+        Some(input) => redirect_streams(input, &[0])?,
+        None => Saved(Vec::new()),
+    };
+    if let Some(output) = output {
+        match redirect_streams(output, &[1]) {
+            Ok(more) => saved.0.extend(more.0),
+            Err(e) => {
+                restore_output(saved);
+                return Err(e);
+            }
+        }
+    }
+    Ok(saved)
+}
+
+#[cfg(unix)]
+fn redirect_streams(file: &File, fds: &[libc::c_int]) -> io::Result<Saved> {
+    use std::os::unix::io::AsRawFd;
+    let mut saved = Saved(Vec::new());
+    for &fd in fds {
+        // SAFETY: the standard streams are open for as long as the shell
+        // runs; the copies are closed again in `restore_output`.
+        unsafe {
+            let copy = libc::dup(fd);
+            if copy < 0 || libc::dup2(file.as_raw_fd(), fd) < 0 {
+                let error = io::Error::last_os_error();
+                saved.0.push((fd, copy));
+                restore_output(saved);
+                return Err(error);
+            }
+            saved.0.push((fd, copy));
+        }
+    }
+    Ok(saved)
+}
+
+#[cfg(unix)]
+pub fn restore_output(saved: Saved) {
+    for (fd, copy) in saved.0 {
+        if copy >= 0 {
+            // SAFETY: `copy` came from `dup` and isn't used afterwards.
+            unsafe {
+                libc::dup2(copy, fd);
+                libc::close(copy);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+pub struct Saved(Vec<(windows_sys::Win32::System::Console::STD_HANDLE, windows_sys::Win32::Foundation::HANDLE)>);
+
+#[cfg(windows)]
+fn redirect_streams(file: &File, fds: &[i32]) -> io::Result<Saved> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Console::{
+        GetStdHandle, STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE, SetStdHandle,
+    };
+    let mut saved = Saved(Vec::new());
+    for &fd in fds {
+        let stream = match fd {
+            0 => STD_INPUT_HANDLE,
+            1 => STD_OUTPUT_HANDLE,
+            _ => STD_ERROR_HANDLE,
+        };
+        // SAFETY: the file outlives the redirection, which `restore_output` undoes.
+        unsafe {
+            saved.0.push((stream, GetStdHandle(stream)));
+            if SetStdHandle(stream, file.as_raw_handle() as isize) == 0 {
+                let error = io::Error::last_os_error();
+                restore_output(saved);
+                return Err(error);
+            }
+        }
+    }
+    Ok(saved)
+}
+
+#[cfg(windows)]
+pub fn restore_output(saved: Saved) {
+    use windows_sys::Win32::System::Console::SetStdHandle;
+    for (stream, handle) in saved.0 {
+        // SAFETY: `handle` is the standard handle from before.
+        unsafe { SetStdHandle(stream, handle) };
+    }
+}
+
+/// Replaces the shell with `program`. Only returns if it can't be started.
+#[cfg(unix)]
+fn replace(program: &str, args: &[&str]) -> io::Error {
+    use std::os::unix::process::CommandExt;
+    Command::new(program).args(args).exec()
+}
+
+/// Windows can't replace a running process, so the command runs as a child
+/// and the shell exits with its status.
+#[cfg(windows)]
+fn replace(program: &str, args: &[&str]) -> io::Error {
+    match Command::new(program).args(args).status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => e,
+    }
+}
+
+/// `exec [redirection...] [command [args...]]`.
+pub fn run(shell: &mut Shell, args: &[&str]) -> i32 {
+    let mut redirections = Vec::new();
+    let mut command = Vec::new();
+    let mut words = args.iter().copied().peekable();
+    while let Some(word) = words.next() {
+        if !command.is_empty() {
+            command.push(word);
+            continue;
+        }
+        match parse(word, words.peek().copied()) {
+            Some(Ok((redirection, used_next))) => {
+                if used_next {
+                    words.next();
+                }
+                redirections.push(redirection);
+            }
+            Some(Err(e)) => {
+                println!("exec: {}", e);
+                return 2;
+            }
+            None => command.push(word),
+        }
+    }
+
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    for Redirection { fd, target } in &redirections {
+        let result = open(target).and_then(|file| {
+            let other = if let Target::Duplicate(other) = target { *other } else { *fd };
+            redirect(*fd, file, other)
+        });
+        if let Err(e) = result {
+            let name = match target {
+                Target::Read(path) | Target::Write(path, _) => path.clone(),
+                Target::Duplicate(other) => other.to_string(),
+            };
+            println!("exec: {}: {}", name, e);
+            return 1;
+        }
+    }
+
+    let Some((program, program_args)) = command.split_first() else {
+        return 0;
+    };
+    // A command that isn't there leaves the shell running; replacing it
+    // ends the shell, so the EXIT trap runs and history is saved first.
+    if lookup::find_program(program).is_none() {
+        println!("exec: {}", msg!("suggest.not_found", program));
+        return 127;
+    }
+    crate::before_exit(shell);
+    shutdown::restore_terminal();
+    let e = replace(program, program_args);
+    println!("exec: {}: {}", program, e);
+    if e.kind() == io::ErrorKind::NotFound { 127 } else { 126 }
+}
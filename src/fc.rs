@@ -0,0 +1,172 @@
+//! The `fc` builtin: fix a command and run it again.
+//!
+//! `fc` opens the previous command in an editor (`-e editor`, or else
+//! `$FCEDIT`, `$VISUAL` or `$EDITOR`) and runs what is saved when the
+//! editor exits; `fc first last` opens a range of commands, one per line.
+//! Commands are picked as in bash: by number (as `history` lists them),
+//! counting back with a negative number, or as the most recent command
+//! starting with a word. `fc -l` lists the commands instead, and
+//! `fc -s [old=new] [command]` runs one again with `old` replaced by `new`,
+//! without an editor.
+//!
+//! What runs goes into the history in place of the `fc` line.
+
+use std::env;
+
+use crate::Shell;
+use crate::histcmd;
+use crate::histpolicy;
+use crate::messages::msg;
+use crate::term;
+
+/// How many commands `fc -l` lists by default.
+const LISTED: usize = 16;
+
+/// The index in `entries` of the command `spec` picks.
+fn find(entries: &[String], spec: &str) -> Option<usize> {
+    match spec.parse::<isize>() {
+        Ok(n) if n > 0 => Some(n as usize - 1).filter(|&i| i < entries.len()),
+        Ok(n) if n < 0 => entries.len().checked_sub(n.unsigned_abs()),
+        Ok(_) => None,
+        Err(_) => entries.iter().rposition(|entry| entry.starts_with(spec)),
+    }
+}
+
+/// The range of `entries` from `first` to `last`, either way round.
+fn range(entries: &[String], first: Option<&str>, last: Option<&str>, default: usize) -> Result<(usize, usize), String> {
+    let pick = |spec: &str| find(entries, spec).ok_or_else(|| msg!("fc.not_found", spec));
+    let end = entries.len().checked_sub(1).ok_or_else(|| msg!("fc.empty"))?;
+    let (first, last) = match (first, last) {
+        (None, _) => (end + 1 - default.min(end + 1), end),
+        (Some(first), None) if default > 1 => (pick(first)?, end),
+        (Some(first), None) => (pick(first)?, pick(first)?),
+        (Some(first), Some(last)) => (pick(first)?, pick(last)?),
+    };
+    Ok((first.min(last), first.max(last)))
+}
+
+/// Replaces the `fc` line at the end of the history with `command`.
+fn record(shell: &mut Shell, current: bool, command: &str) {
+    let dedup = histpolicy::Policy::from_config(&shell.config).dedup;
+    if current {
+        let index = shell.history_entries.len() - 1;
+        if let Some(store) = &mut shell.history
+            && let Err(e) = histcmd::delete_stored(store.as_mut(), &shell.history_entries, index, dedup)
+        {
+            println!("{}", msg!("history.error", e));
+        }
+        shell.history_entries.pop();
+    }
+    shell.history_edited = true;
+    if dedup != histpolicy::Dedup::None && shell.history_entries.last().is_some_and(|last| last == command) {
+        return;
+    }
+    shell.history_entries.push(command.to_string());
+    if let Some(store) = &mut shell.history
+        && let Err(e) = store.append(command)
+    {
+        println!("{}", msg!("history.error", e));
+    }
+}
+
+/// `fc [-e editor] [first [last]]`, `fc -l [-n] [-r] [first [last]]` or
+/// `fc -s [old=new] [command]`
+pub fn run(shell: &mut Shell, args: &[&str]) -> i32 {
+    let mut args = args.to_vec();
+    let (mut list, mut numbers, mut reverse, mut again) = (false, true, false, false);
+    let mut editor = None;
+    while let Some(&option) = args.first().filter(|arg| arg.starts_with('-') && arg.parse::<isize>().is_err()) {
+        args.remove(0);
+        match option {
+            "-e" if !args.is_empty() => editor = Some(args.remove(0).to_string()),
+            "-l" => list = true,
+            "-n" => numbers = false,
+            "-r" => reverse = true,
+            "-s" => again = true,
+            _ => {
+                println!("{}", msg!("usage.fc"));
+                return 2;
+            }
+        }
+    }
+    // The `fc` line itself, when it was recorded, isn't one to pick.
+    let current = shell.history_entries.last().is_some_and(|entry| entry.split_whitespace().next() == Some("fc"));
+    let entries = &shell.history_entries[..shell.history_entries.len() - current as usize];
+
+    if again || editor.as_deref() == Some("-") {
+        let (substitution, spec) = match args.as_slice() {
+            [pair, spec] if pair.contains('=') => (Some(*pair), Some(*spec)),
+            [pair] if pair.contains('=') => (Some(*pair), None),
+            [spec] => (None, Some(*spec)),
+            [] => (None, None),
+            _ => {
+                println!("{}", msg!("usage.fc"));
+                return 2;
+            }
+        };
+        let command = match range(entries, spec, None, 1) {
+            Ok((index, _)) => entries[index].clone(),
+            Err(e) => {
+                println!("fc: {}", e);
+                return 1;
+            }
+        };
+        let command = match substitution.and_then(|pair| pair.split_once('=')) {
+            Some((old, new)) => command.replace(old, new),
+            None => command,
+        };
+        println!("{}", command);
+        record(shell, current, &command);
+        return crate::run_lines(shell, &command);
+    }
+
+    if args.len() > 2 {
+        println!("{}", msg!("usage.fc"));
+        return 2;
+    }
+    let (first, last) = match range(entries, args.first().copied(), args.get(1).copied(), if list { LISTED } else { 1 }) {
+        Ok(range) => range,
+        Err(e) => {
+            println!("fc: {}", e);
+            return 1;
+        }
+    };
+    if list {
+        let mut picked: Vec<usize> = (first..=last).collect();
+        if reverse {
+            picked.reverse();
+        }
+        for index in picked {
+            if numbers {
+                histcmd::print(index + 1, &entries[index]);
+            } else {
+                println!("       {}", entries[index].replace('\n', "\n       "));
+            }
+        }
+        return 0;
+    }
+
+    let mut picked: Vec<&String> = entries[first..=last].iter().collect();
+    if reverse {
+        picked.reverse();
+    }
+    let text: String = picked.iter().map(|entry| format!("{}\n", entry)).collect();
+    let editor = match editor.or_else(|| env::var("FCEDIT").ok()) {
+        Some(editor) => editor.split_whitespace().map(str::to_string).collect(),
+        None => term::editor(),
+    };
+    let edited = match term::edit(&editor, "fc.osh", &text) {
+        Ok(edited) => edited,
+        Err(e) => {
+            println!("fc: {}", e);
+            return 1;
+        }
+    };
+    let command = edited.trim();
+    if command.is_empty() {
+        return 0;
+    }
+    println!("{}", command);
+    record(shell, current, command);
+    crate::run_lines(shell, command)
+}
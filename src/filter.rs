@@ -0,0 +1,236 @@
+//! Output filters: highlighting, redacting and collapsing lines of command
+//! output as the shell relays it to the terminal.
+//!
+//! A filter pairs a pattern with an action. Patterns are regular
+//! expressions (see `regex`), found anywhere in a line and regardless of
+//! case unless they have an uppercase letter. `highlight` colors what
+//! matches, `redact` replaces it with `•••`, and `collapse` folds a run of
+//! consecutive lines the same pattern matches into the first one and a
+//! count; anchor it with `^` to match only at the start of lines.
+//!
+//! When filters are set, foreground commands write to a pipe, like captured
+//! ones, except the full-screen and interactive programs in
+//! `capture.exclude`.
+
+use std::ops::Range;
+
+use colored::*;
+
+use crate::config::Config;
+use crate::messages::msg;
+use crate::redact;
+use crate::regex::Regex;
+use crate::term;
+use crate::theme;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Highlight(Color),
+    Redact,
+    Collapse,
+}
+
+#[derive(Clone)]
+struct Filter {
+    action: Action,
+    pattern: String,
+    regex: Regex,
+}
+
+impl Filter {
+    /// Compiles `pattern`, folding case unless it has an uppercase letter
+    /// that isn't part of an escape such as `\W`.
+    fn new(action: Action, pattern: String) -> Result<Filter, String> {
+        let mut escaped = false;
+        let fold = !pattern.chars().any(|c| {
+            let upper = !escaped && c.is_uppercase();
+            escaped = !escaped && c == '\\';
+            upper
+        });
+        let regex = Regex::new(&pattern, fold).map_err(|e| format!("{}: {}", pattern, e))?;
+        Ok(Filter { action, pattern, regex })
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Filters {
+    filters: Vec<Filter>,
+}
+
+impl Filters {
+    /// `filters.highlight` maps patterns to colors; `filters.redact` and
+    /// `filters.collapse` list patterns. Invalid patterns are reported and
+    /// left out.
+    pub fn from_config(config: &Config) -> Filters {
+        let mut filters = Vec::new();
+        for (pattern, color) in config.get_str_map("filters.highlight") {
+            let color = theme::parse_color(&color).unwrap_or(Color::Red);
+            filters.push(Filter::new(Action::Highlight(color), pattern));
+        }
+        for pattern in config.get_str_list("filters.redact") {
+            filters.push(Filter::new(Action::Redact, pattern));
+        }
+        for pattern in config.get_str_list("filters.collapse") {
+            filters.push(Filter::new(Action::Collapse, pattern));
+        }
+        let filters =
+            filters.into_iter().filter_map(|filter| filter.map_err(|e| println!("filter: {}", e)).ok()).collect();
+        Filters { filters }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// `line` with secrets redacted.
+    fn redact(&self, line: &str) -> String {
+        let mut line = line.to_string();
+        for filter in self.filters.iter().filter(|f| f.action == Action::Redact) {
+            line = replace(&line, filter.regex.find_all(&line), |_| redact::MASK.to_string());
+        }
+        line
+    }
+
+    /// `line` with secrets redacted and matches highlighted.
+    fn apply(&self, line: &str) -> String {
+        let line = self.redact(line);
+        let mut marks: Vec<(Range<usize>, Color)> = Vec::new();
+        for filter in &self.filters {
+            if let Action::Highlight(color) = filter.action {
+                marks.extend(filter.regex.find_all(&line).into_iter().map(|span| (span, color)));
+            }
+        }
+        marks.sort_by_key(|(span, _)| span.start);
+        let mut last = 0;
+        marks.retain(|(span, _)| {
+            let keep = span.start >= last;
+            if keep {
+                last = span.end;
+            }
+            keep
+        });
+        let colors: Vec<Color> = marks.iter().map(|(_, color)| *color).collect();
+        let mut colors = colors.into_iter();
+        let spans = marks.into_iter().map(|(span, _)| span).collect();
+        replace(&line, spans, |text| {
+            let color = colors.next().unwrap_or(Color::Red);
+            text.color(term::adapt(color)).bold().to_string()
+        })
+    }
+}
+
+/// `line` with each of `spans` (sorted, not overlapping) replaced.
+fn replace(line: &str, spans: Vec<Range<usize>>, mut with: impl FnMut(&str) -> String) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut last = 0;
+    for span in spans {
+        if span.start < last {
+            continue;
+        }
+        out.push_str(&line[last..span.start]);
+        out.push_str(&with(&line[span.clone()]));
+        last = span.end;
+    }
+    out.push_str(&line[last..]);
+    out
+}
+
+/// Filters one stream of output line by line, remembering runs of
+/// collapsed lines.
+pub struct Lines {
+    filters: Filters,
+    /// The collapse filter of the current run, and how many lines it hid.
+    run: Option<(usize, usize)>,
+}
+
+impl Lines {
+    pub fn new(filters: Filters) -> Lines {
+        Lines { filters, run: None }
+    }
+
+    /// Filters `text`, a whole line when `whole` is set or else part of one
+    /// (such as a prompt). Returns the summary of a run of collapsed lines
+    /// that just ended, if any, and the text to show, unless it is hidden.
+    pub fn filter(&mut self, text: &str, whole: bool) -> (Option<String>, Option<String>) {
+        let collapse = whole
+            .then(|| self.filters.filters.iter().position(|f| f.action == Action::Collapse && f.regex.is_match(text)))
+            .flatten();
+        if let (Some(index), Some((run, hidden))) = (collapse, self.run.as_mut())
+            && index == *run
+        {
+            *hidden += 1;
+            return (None, None);
+        }
+        let summary = self.finish();
+        self.run = collapse.map(|index| (index, 0));
+        (summary, Some(self.filters.apply(text)))
+    }
+
+    /// `text` with secrets redacted, but nothing highlighted or collapsed,
+    /// for keeping.
+    pub fn redact(&self, text: &str) -> String {
+        self.filters.redact(text)
+    }
+
+    /// Ends the current run, returning its summary if it hid any lines.
+    pub fn finish(&mut self) -> Option<String> {
+        match self.run.take() {
+            Some((_, hidden)) if hidden > 0 => Some(msg!("filter.collapsed", hidden).dimmed().to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// `filter [highlight [-c color] | redact | collapse] <pattern...>`, or
+/// `filter -d n` to remove the `n`th. The rest of the line is the pattern.
+pub fn run(filters: &mut Filters, args: &[&str]) -> i32 {
+    let (action, pattern) = match args {
+        [] => {
+            for (n, filter) in filters.filters.iter().enumerate() {
+                let action = match filter.action {
+                    Action::Highlight(_) => "highlight",
+                    Action::Redact => "redact",
+                    Action::Collapse => "collapse",
+                };
+                println!("{:>3}  {:<9}  {}", n + 1, action, filter.pattern);
+            }
+            return 0;
+        }
+        ["-d", n] => {
+            return match n.parse::<usize>().ok().filter(|&n| n >= 1 && n <= filters.filters.len()) {
+                Some(n) => {
+                    filters.filters.remove(n - 1);
+                    0
+                }
+                None => {
+                    println!("filter: {}", msg!("filter.no_such_filter", n));
+                    1
+                }
+            };
+        }
+        ["highlight", "-c", color, pattern @ ..] if !pattern.is_empty() => match theme::parse_color(color) {
+            Some(color) => (Action::Highlight(color), pattern),
+            None => {
+                println!("filter: {}", msg!("filter.bad_color", color));
+                return 1;
+            }
+        },
+        ["highlight", pattern @ ..] if !pattern.is_empty() => (Action::Highlight(Color::Red), pattern),
+        ["redact", pattern @ ..] if !pattern.is_empty() => (Action::Redact, pattern),
+        ["collapse", pattern @ ..] if !pattern.is_empty() => (Action::Collapse, pattern),
+        _ => {
+            println!("{}", msg!("usage.filter"));
+            return 2;
+        }
+    };
+    match Filter::new(action, pattern.join(" ")) {
+        Ok(filter) => {
+            filters.filters.push(filter);
+            0
+        }
+        Err(e) => {
+            println!("filter: {}", e);
+            1
+        }
+    }
+}
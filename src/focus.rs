@@ -0,0 +1,103 @@
+//! Do-not-disturb mode.
+//!
+//! While a focus session is active, anything that would interrupt the user
+//! unprompted (job completion notices, notifications, periodic hooks) checks
+//! `active()` and holds back until the session ends.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// End of the current focus session, if one is running.
+static UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Whether a focus session is in progress.
+pub fn active() -> bool {
+    remaining().is_some()
+}
+
+fn remaining() -> Option<Duration> {
+    let mut until = UNTIL.lock().unwrap();
+    match *until {
+        Some(end) if end > Instant::now() => Some(end - Instant::now()),
+        Some(_) => {
+            *until = None;
+            None
+        }
+        None => None,
+    }
+}
+
+/// Parses durations like `25m`, `1h30m`, `90s`, or a bare number of minutes.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    if let Ok(minutes) = text.parse::<u64>() {
+        return Some(Duration::from_secs(minutes * 60));
+    }
+    let mut total = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().ok()?;
+        number.clear();
+        total += value
+            * match c {
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => return None,
+            };
+    }
+    (number.is_empty() && total > 0).then(|| Duration::from_secs(total))
+}
+
+/// `1h05m`, `25m`, `40s`, rounded up so a fresh `25m` session shows `25m`.
+fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs_f64().ceil() as u64;
+    if secs < 60 {
+        return format!("{}s", secs);
+    }
+    let minutes = secs.div_ceil(60);
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, m) => format!("{}h{:02}m", h, m),
+    }
+}
+
+/// Prompt segment with the time left in the focus session.
+pub fn segment() -> Option<String> {
+    remaining().map(format_remaining)
+}
+
+/// `focus [on <duration>|off]`
+pub fn run(args: &[&str]) -> i32 {
+    match args {
+        [] => {
+            match remaining() {
+                Some(left) => println!("focus on ({} left)", format_remaining(left)),
+                None => println!("focus off"),
+            }
+            0
+        }
+        ["on", duration] => match parse_duration(duration) {
+            Some(duration) => {
+                *UNTIL.lock().unwrap() = Some(Instant::now() + duration);
+                println!("Focus on for {}; notifications are held until then", format_remaining(duration));
+                0
+            }
+            None => {
+                println!("focus: invalid duration: {}", duration);
+                2
+            }
+        },
+        ["off"] => {
+            *UNTIL.lock().unwrap() = None;
+            0
+        }
+        _ => {
+            println!("Usage: focus [on <duration>|off]");
+            2
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Parsing for shell function definitions.
+//!
+//! `greet() { echo "hi $1"; }` (or `function greet { ... }`) stores the body
+//! under the function's name; the shell runs it when `greet` is used as a
+//! command, before looking in `PATH`. Definitions can span several lines.
+
+use crate::syntax;
+
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+}
+
+/// Splits `name() { body }`, `name () { body }` or `function name { body }`
+/// into the name and body.
+pub fn parse_definition(command: &str) -> Option<(&str, &str)> {
+    let command = command.trim();
+    let (name, rest) = match command.strip_prefix("function ") {
+        Some(rest) => {
+            let rest = rest.trim_start();
+            let end = rest.find(|c: char| c.is_whitespace() || c == '(' || c == '{').unwrap_or(rest.len());
+            let (name, rest) = rest.split_at(end);
+            (name, rest.trim_start().strip_prefix("()").unwrap_or(rest))
+        }
+        None => {
+            let (name, rest) = command.split_once('(')?;
+            (name.trim_end(), rest.strip_prefix(')')?)
+        }
+    };
+    if !valid_name(name) {
+        return None;
+    }
+    let body = syntax::group_body(rest)?;
+    Some((name, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definitions() {
+        assert_eq!(parse_definition("greet() { echo \"hi $1\"; }"), Some(("greet", " echo \"hi $1\"; ")));
+        assert_eq!(parse_definition("function greet { echo hi; }"), Some(("greet", " echo hi; ")));
+        assert_eq!(parse_definition("greet () {\necho hi\n}"), Some(("greet", "\necho hi\n")));
+    }
+
+    #[test]
+    fn quoted_braces_stay_in_the_body() {
+        assert_eq!(parse_definition("f() { echo '}'; }"), Some(("f", " echo '}'; ")));
+    }
+
+    #[test]
+    fn not_definitions() {
+        assert_eq!(parse_definition("echo (hi)"), None);
+        assert_eq!(parse_definition("f() echo"), None);
+        assert_eq!(parse_definition("1f() { echo; }"), None);
+    }
+}
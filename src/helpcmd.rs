@@ -0,0 +1,63 @@
+//! The `help`, `enable` and `disable` builtins, which look at the
+//! registry in `builtins` as it is when they run.
+
+use rustshell::builtins;
+
+use crate::messages::msg;
+
+/// `help [builtin...]`: lists the builtins with a line about each, or shows
+/// the usage and help of the named ones.
+pub fn help(args: &[&str]) -> i32 {
+    if args.is_empty() {
+        let registered = builtins::all();
+        let width = registered.iter().map(|b| b.name.chars().count()).max().unwrap_or(0);
+        for builtin in registered {
+            let summary = builtin.help.split(". ").next().unwrap_or("").trim_end_matches('.');
+            println!("  {:<width$}  {}", builtin.name, summary, width = width);
+        }
+        let disabled = builtins::disabled();
+        if !disabled.is_empty() {
+            println!("{}", msg!("builtins.disabled", disabled.join(" ")));
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for name in args {
+        match builtins::get(name) {
+            Some(builtin) => {
+                println!("{}", msg!("builtins.usage", builtin.usage));
+                println!("{}", builtin.help);
+            }
+            None => {
+                println!("{}", msg!("builtins.no_such_builtin", "help", name));
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// `enable [name...]` and `disable [-b] <name...>`. Without names, `enable`
+/// lists the disabled builtins.
+pub fn enable(args: &[&str], on: bool) -> i32 {
+    let command = if on { "enable" } else { "disable" };
+    let names: Vec<&str> = args.iter().copied().filter(|a| *a != "-b").collect();
+    if names.is_empty() {
+        if !on {
+            println!("{}", msg!("usage.disable"));
+            return 2;
+        }
+        for name in builtins::disabled() {
+            println!("{}", name);
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for name in names {
+        if !builtins::set_enabled(name, on) {
+            println!("{}", msg!("builtins.no_such_builtin", command, name));
+            status = 1;
+        }
+    }
+    status
+}
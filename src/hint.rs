@@ -0,0 +1,219 @@
+//! The `hint` builtin: a condensed summary of a command's `--help`.
+//!
+//! `hint cmd` runs `cmd --help` once and caches the output in the `help`
+//! directory under the cache directory, so later hints are instant; the
+//! cache is refreshed when the program changes, or with `hint -r`. The
+//! summary is the usage line and one line per option.
+//!
+//! With `completion.from_help`, Tab after `-` completes the options of
+//! commands that have no completion of their own from the same parsed help:
+//! `"cached"` uses only help already cached, `"run"` also runs `--help`
+//! for commands seen for the first time.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use colored::*;
+
+use crate::config::{self, Config};
+use crate::lookup;
+use crate::messages::msg;
+use crate::ssh;
+use crate::term;
+
+/// How long `--help` may take before it is given up on.
+const HELP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Where completion may take options from (`completion.from_help`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Off,
+    /// Help cached by `hint`.
+    Cached,
+    /// Help cached, or else got by running `--help`.
+    Run,
+}
+
+impl Source {
+    pub fn from_config(config: &Config) -> Source {
+        match config.get_str("completion.from_help") {
+            Some("cached") => Source::Cached,
+            Some("run") => Source::Run,
+            _ => Source::Off,
+        }
+    }
+}
+
+/// An option as `--help` describes it.
+struct Flag {
+    /// Its spellings, e.g. `-a` and `--all`.
+    names: Vec<String>,
+    /// The argument it takes, as written: `WHEN`, `=FILE`, `[=WHEN]`.
+    arg: Option<String>,
+    description: String,
+}
+
+fn cache_path(program: &str) -> PathBuf {
+    let name = std::path::Path::new(program).file_name().map_or_else(|| program.into(), |n| n.to_string_lossy());
+    config::cache_dir().join("help").join(format!("{}.txt", name))
+}
+
+/// The `--help` output of `cmd`: from the cache while it is newer than the
+/// program, else by running it when `run` is set.
+fn help_text(cmd: &str, run: bool, refresh: bool) -> Option<String> {
+    let program = if cmd.contains(std::path::is_separator) { Some(PathBuf::from(cmd)) } else { lookup::find_program(cmd) }?;
+    let cache = cache_path(cmd);
+    let modified = |path: &PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let fresh = match (modified(&cache), modified(&program)) {
+        (Some(cached), Some(built)) => cached >= built,
+        (cached, _) => cached.is_some(),
+    };
+    if fresh
+        && !refresh
+        && let Ok(text) = fs::read_to_string(&cache)
+    {
+        return Some(text);
+    }
+    if !run {
+        return None;
+    }
+    let mut command = if cmd.contains(std::path::is_separator) { Command::new(&program) } else { lookup::command_for(cmd) };
+    command.arg("--help").env("COLUMNS", "200").env("NO_COLOR", "1");
+    let text = ssh::output_with_timeout(command, HELP_TIMEOUT).filter(|text| !text.trim().is_empty())?;
+    if let Some(dir) = cache.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(&cache, &text);
+    Some(text)
+}
+
+/// Splits an option's spelling from its argument: `--color[=WHEN]`.
+fn split_arg(word: &str) -> (&str, Option<&str>) {
+    match word.find(['=', '[', '<']) {
+        Some(i) if i > 1 => (&word[..i], Some(&word[i..])),
+        _ => (word, None),
+    }
+}
+
+/// Reads the options out of `--help` output: lines that start with `-`,
+/// with the description after a run of spaces or on the next line.
+fn parse(text: &str) -> Vec<Flag> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut flags: Vec<Flag> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let mut chars = trimmed.chars();
+        if chars.next() != Some('-') || !chars.next().is_some_and(|c| c.is_alphanumeric() || c == '-') {
+            continue;
+        }
+        let (spec, description) = match trimmed.find("  ").or_else(|| trimmed.find('\t')) {
+            Some(gap) => (&trimmed[..gap], trimmed[gap..].trim()),
+            None => (trimmed, ""),
+        };
+        let mut flag = Flag { names: Vec::new(), arg: None, description: description.to_string() };
+        for word in spec.split([',', ' ', '|']).filter(|w| !w.is_empty()) {
+            if word.starts_with('-') {
+                let (name, arg) = split_arg(word);
+                flag.names.push(name.to_string());
+                if flag.arg.is_none() {
+                    flag.arg = arg.map(str::to_string);
+                }
+            } else if flag.arg.is_none() {
+                flag.arg = Some(word.to_string());
+            }
+        }
+        // The description may be on the next, more indented line.
+        if flag.description.is_empty()
+            && let Some(next) = lines.get(i + 1)
+        {
+            let next_trimmed = next.trim_start();
+            if next.len() - next_trimmed.len() > indent && !next_trimmed.starts_with('-') {
+                flag.description = next_trimmed.trim().to_string();
+            }
+        }
+        if !flag.names.is_empty() && !flags.iter().any(|f| f.names == flag.names) {
+            flags.push(flag);
+        }
+    }
+    flags
+}
+
+/// The line starting `Usage:`.
+fn usage(text: &str) -> Option<&str> {
+    text.lines().map(str::trim).find(|line| line.to_lowercase().starts_with("usage"))
+}
+
+/// The first sentence is enough for a summary. (`. ` before a lowercase
+/// word, as in "entries starting with . and ..", doesn't end one.)
+fn first_sentence(text: &str) -> &str {
+    let ends = text.match_indices(". ").find(|(i, _)| text[i + 2..].starts_with(|c: char| c.is_uppercase()));
+    ends.map_or(text, |(i, _)| &text[..i])
+}
+
+/// Options of `cmd` starting with `word`, for completion.
+pub fn complete(cmd: &str, word: &str, source: Source) -> Vec<String> {
+    if source == Source::Off {
+        return Vec::new();
+    }
+    let Some(text) = help_text(cmd, source == Source::Run, false) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> =
+        parse(&text).into_iter().flat_map(|f| f.names).filter(|name| name.starts_with(word)).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// `hint [-r] <command>`
+pub fn run(args: &[&str]) -> i32 {
+    let (refresh, cmd) = match args {
+        ["-r", cmd] => (true, *cmd),
+        [cmd] if !cmd.starts_with('-') => (false, *cmd),
+        _ => {
+            println!("{}", msg!("usage.hint"));
+            return 2;
+        }
+    };
+    let Some(text) = help_text(cmd, true, refresh) else {
+        println!("hint: {}", msg!("hint.no_help", cmd));
+        return 1;
+    };
+    let flags = parse(&text);
+    if let Some(usage) = usage(&text) {
+        println!("{}", usage.bold());
+    }
+    if flags.is_empty() {
+        println!("{}", msg!("hint.no_options", cmd));
+        return 0;
+    }
+    let spell = |flag: &Flag| {
+        let arg = flag.arg.as_deref().map(|a| if a.starts_with(['=', '[']) { a.to_string() } else { format!(" {}", a) });
+        (flag.names.join(", "), arg.unwrap_or_default())
+    };
+    let width = flags.iter().map(spell).map(|(n, a)| n.chars().count() + a.chars().count()).max().unwrap_or(0);
+    let width = width.min(32);
+    let columns = crossterm::terminal::size().map_or(100, |(c, _)| c as usize);
+    for flag in &flags {
+        let (names, arg) = spell(flag);
+        let pad = width.saturating_sub(names.chars().count() + arg.chars().count());
+        let room = columns.saturating_sub(width + 6);
+        let description = first_sentence(&flag.description);
+        let description: String = if description.chars().count() > room {
+            description.chars().take(room.saturating_sub(1)).chain(['…']).collect()
+        } else {
+            description.to_string()
+        };
+        println!(
+            "  {}{}{}  {}",
+            names.color(term::adapt(Color::Cyan)).bold(),
+            arg.dimmed(),
+            " ".repeat(pad),
+            description
+        );
+    }
+    0
+}
@@ -0,0 +1,197 @@
+//! The `history` builtin.
+//!
+//! Entries are numbered from 1 for the oldest, the same numbers `!n` uses.
+//! The list is the history the line editor has (what `Up` walks through),
+//! so it ends with this session's commands. Deleting an entry or clearing
+//! the history changes the store too, so the next session doesn't see it
+//! either.
+
+use crate::Shell;
+use crate::histpolicy::{self, Dedup};
+use crate::messages::msg;
+
+/// Whether `entry` contains `pattern`, ignoring case unless the pattern has
+/// an uppercase letter.
+fn contains(entry: &str, pattern: &str) -> bool {
+    if pattern.chars().any(char::is_uppercase) {
+        entry.contains(pattern)
+    } else {
+        entry.to_lowercase().contains(pattern)
+    }
+}
+
+pub fn print(number: usize, entry: &str) {
+    // Later lines of a multi-line entry line up under the first.
+    println!("{:>5}  {}", number, entry.replace('\n', "\n       "));
+}
+
+/// `history [n] | history search <pattern> | history delete <n>... | history clear`
+pub fn run(shell: &mut Shell, args: &[&str]) -> i32 {
+    let count = match args {
+        [] => Some(usize::MAX),
+        [n] => n.parse::<usize>().ok(),
+        _ => None,
+    };
+    if let Some(count) = count {
+        let skip = shell.history_entries.len().saturating_sub(count);
+        for (i, entry) in shell.history_entries.iter().enumerate().skip(skip) {
+            print(i + 1, entry);
+        }
+        return 0;
+    }
+    match args {
+        ["search", pattern @ ..] if !pattern.is_empty() => {
+            let pattern = pattern.join(" ");
+            let mut found = false;
+            for (i, entry) in shell.history_entries.iter().enumerate() {
+                if contains(entry, &pattern) {
+                    print(i + 1, entry);
+                    found = true;
+                }
+            }
+            if found { 0 } else { 1 }
+        }
+        ["delete", numbers @ ..] if !numbers.is_empty() => {
+            let mut indexes = Vec::new();
+            for number in numbers {
+                match number.parse::<usize>().ok().filter(|&n| n >= 1 && n <= shell.history_entries.len()) {
+                    Some(n) => indexes.push(n - 1),
+                    None => {
+                        println!("history: {}", msg!("histcmd.no_such_entry", number));
+                        return 1;
+                    }
+                }
+            }
+            // From the newest, so the numbers of the others stay put.
+            indexes.sort_unstable();
+            indexes.dedup();
+            let dedup = histpolicy::Policy::from_config(&shell.config).dedup;
+            for &index in indexes.iter().rev() {
+                if let Some(store) = &mut shell.history
+                    && let Err(e) = delete_stored(store.as_mut(), &shell.history_entries, index, dedup)
+                {
+                    println!("{}", msg!("history.error", e));
+                }
+                shell.history_entries.remove(index);
+            }
+            shell.history_edited = true;
+            0
+        }
+        ["clear"] => {
+            shell.history_entries.clear();
+            shell.history_edited = true;
+            if let Some(store) = &mut shell.history
+                && let Err(e) = store.clear()
+            {
+                println!("{}", msg!("history.error", e));
+                return 1;
+            }
+            0
+        }
+        _ => {
+            println!("{}", msg!("usage.history"));
+            2
+        }
+    }
+}
+
+/// Deletes entry `index` of `entries`, the list before it is removed from
+/// it, from the store. The store may hold more than the list: older entries
+/// beyond `history.size`, commands other sessions recorded, and the copies
+/// `dedup` hides. So the entry is found by its text, counting copies from
+/// the newest, and the copies it stands for go with it: the run it heads
+/// with `"consecutive"`, and every older one with `"all"`.
+pub fn delete_stored(
+    store: &mut dyn rustshell::history::HistoryStore,
+    entries: &[String],
+    index: usize,
+    dedup: Dedup,
+) -> std::io::Result<()> {
+    let entry = &entries[index];
+    let newer = entries[index + 1..].iter().filter(|e| *e == entry).count();
+    // The store's copies of the entry, grouped as the list shows them.
+    let mut copies: Vec<Vec<usize>> = Vec::new();
+    for (i, _) in store.load()?.iter().enumerate().filter(|(_, e)| *e == entry) {
+        match copies.last_mut() {
+            Some(run) if dedup == Dedup::Consecutive && run.last().is_some_and(|&last| last + 1 == i) => run.push(i),
+            _ => copies.push(vec![i]),
+        }
+    }
+    let Some(n) = copies.len().checked_sub(newer + 1) else {
+        return Ok(());
+    };
+    let first = if dedup == Dedup::All { 0 } else { n };
+    copies[first..=n].concat().into_iter().rev().try_for_each(|i| store.delete(i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rustshell::history::HistoryStore;
+
+    #[derive(Default)]
+    struct Store(Vec<String>);
+
+    impl HistoryStore for Store {
+        fn load(&mut self) -> std::io::Result<Vec<String>> {
+            Ok(self.0.clone())
+        }
+
+        fn append(&mut self, entry: &str) -> std::io::Result<()> {
+            self.0.push(entry.to_string());
+            Ok(())
+        }
+
+        fn clear(&mut self) -> std::io::Result<()> {
+            self.0.clear();
+            Ok(())
+        }
+    }
+
+    fn strings(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    /// The store left after deleting entry `index` of `list` from `stored`.
+    fn delete(stored: &[&str], list: &[&str], index: usize, dedup: Dedup) -> Vec<String> {
+        let mut store = Store(strings(stored));
+        delete_stored(&mut store, &strings(list), index, dedup).unwrap();
+        store.0
+    }
+
+    #[test]
+    fn deletes_the_same_entry_when_dedup_is_all() {
+        // The list shows the store with only the newest copy of each.
+        assert_eq!(delete(&["b", "a", "x", "a"], &["b", "x", "a"], 0, Dedup::All), ["a", "x", "a"]);
+        assert_eq!(delete(&["b", "a", "x", "a"], &["b", "x", "a"], 1, Dedup::All), ["b", "a", "a"]);
+        // Every copy goes, or an older one would be back next session.
+        assert_eq!(delete(&["b", "a", "x", "a"], &["b", "x", "a"], 2, Dedup::All), ["b", "x"]);
+    }
+
+    #[test]
+    fn dropping_earlier_copies_keeps_the_newest() {
+        // `a` was just run again: the list has both copies until the
+        // earlier one is dropped.
+        assert_eq!(delete(&["a", "b", "a", "x", "a"], &["b", "a", "x", "a"], 1, Dedup::All), ["b", "x", "a"]);
+    }
+
+    #[test]
+    fn deletes_a_run_of_consecutive_copies() {
+        let stored = ["a", "a", "b", "a", "a"];
+        assert_eq!(delete(&stored, &["a", "b", "a"], 0, Dedup::Consecutive), ["b", "a", "a"]);
+        assert_eq!(delete(&stored, &["a", "b", "a"], 2, Dedup::Consecutive), ["a", "a", "b"]);
+    }
+
+    #[test]
+    fn counts_copies_from_the_newest() {
+        let stored = ["old", "a", "b", "a"];
+        assert_eq!(delete(&stored, &["a", "b", "a"], 0, Dedup::None), ["old", "b", "a"]);
+        assert_eq!(delete(&stored, &["a", "b", "a"], 2, Dedup::None), ["old", "a", "b"]);
+    }
+
+    #[test]
+    fn entries_not_stored_are_left_alone() {
+        assert_eq!(delete(&["a"], &["a", "secret"], 1, Dedup::None), ["a"]);
+    }
+}
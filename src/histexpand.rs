@@ -0,0 +1,116 @@
+//! History expansion at the prompt, as in bash.
+//!
+//! Before a line typed at the prompt runs, `!` references to earlier
+//! commands are replaced: `!!` is the previous command, `!n` command `n`,
+//! `!-n` the `n`th most recent, and `!prefix` the most recent starting with
+//! `prefix`. `!$`, `!^` and `!*` are the last argument, first argument and
+//! all arguments of the previous command. A `!` before a space, `=` or `(`,
+//! after `$`, or written `\!` is left alone.
+
+use crate::messages::msg;
+
+/// Characters that end a `!prefix` reference.
+const PREFIX_END: &[char] = &[';', '&', '|', '<', '>', '(', ')', '!'];
+
+fn not_found(reference: &str) -> String {
+    msg!("histexpand.not_found", format!("!{}", reference))
+}
+
+/// The entry a reference at the start of `tail` (just after the `!`)
+/// names, and how much of `tail` it takes up.
+fn event<'a>(tail: &str, history: &'a [String]) -> Result<(&'a str, usize), String> {
+    let digits = tail.strip_prefix('-').unwrap_or(tail);
+    let number_len = digits.len() - digits.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if number_len > 0 {
+        let len = tail.len() - digits.len() + number_len;
+        let n: usize = digits[..number_len].parse().map_err(|_| not_found(&tail[..len]))?;
+        let index = if tail.starts_with('-') { history.len().checked_sub(n) } else { n.checked_sub(1) };
+        let entry = index.and_then(|i| history.get(i)).ok_or_else(|| not_found(&tail[..len]))?;
+        return Ok((entry, len));
+    }
+    let len = tail.find(|c: char| c.is_whitespace() || PREFIX_END.contains(&c)).unwrap_or(tail.len());
+    let prefix = &tail[..len];
+    let entry = history.iter().rev().find(|entry| entry.starts_with(prefix)).ok_or_else(|| not_found(prefix))?;
+    Ok((entry, len))
+}
+
+/// The replacement for the reference at the start of `tail`, and how much of
+/// `tail` it takes up.
+fn reference(tail: &str, history: &[String]) -> Result<(String, usize), String> {
+    let previous = || history.last().ok_or_else(|| not_found(&tail[..1]));
+    match tail.as_bytes()[0] {
+        b'!' => Ok((previous()?.clone(), 1)),
+        b'$' => Ok((previous()?.split_whitespace().last().unwrap_or_default().to_string(), 1)),
+        b'^' => Ok((previous()?.split_whitespace().nth(1).unwrap_or_default().to_string(), 1)),
+        b'*' => Ok((previous()?.split_whitespace().skip(1).collect::<Vec<_>>().join(" "), 1)),
+        _ => event(tail, history).map(|(entry, len)| (entry.to_string(), len)),
+    }
+}
+
+/// Expands the history references in `line`, `history` being the earlier
+/// commands, oldest first. Returns `None` when there were none.
+pub fn expand(line: &str, history: &[String]) -> Result<Option<String>, String> {
+    if !line.contains('!') {
+        return Ok(None);
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut changed = false;
+    let mut i = 0;
+    while let Some(offset) = line[i..].find('!') {
+        let at = i + offset;
+        out.push_str(&line[i..at]);
+        let tail = &line[at + 1..];
+        let literal = tail.is_empty()
+            || tail.starts_with(|c: char| c.is_whitespace() || c == '=' || c == '(')
+            || line[..at].ends_with('$');
+        if line[..at].ends_with('\\') {
+            out.pop();
+            out.push('!');
+            changed = true;
+            i = at + 1;
+        } else if literal {
+            out.push('!');
+            i = at + 1;
+        } else {
+            let (text, len) = reference(tail, history)?;
+            out.push_str(&text);
+            changed = true;
+            i = at + 1 + len;
+        }
+    }
+    out.push_str(&line[i..]);
+    Ok(changed.then_some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        ["make build", "git commit -m fix", "ls -la src"].iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn references() {
+        let expand = |line| expand(line, &history()).unwrap();
+        assert_eq!(expand("sudo !!"), Some("sudo ls -la src".to_string()));
+        assert_eq!(expand("!1 && !-1"), Some("make build && ls -la src".to_string()));
+        assert_eq!(expand("!git; cd !$"), Some("git commit -m fix; cd src".to_string()));
+        assert_eq!(expand("echo !^ !*"), Some("echo -la -la src".to_string()));
+    }
+
+    #[test]
+    fn literal_exclamation_marks() {
+        let expand = |line| expand(line, &history()).unwrap();
+        assert_eq!(expand("echo hi"), None);
+        assert_eq!(expand("[ ! -f x ] && echo $! a!=b"), None);
+        assert_eq!(expand("echo \\!1"), Some("echo !1".to_string()));
+    }
+
+    #[test]
+    fn missing_events_are_errors() {
+        assert_eq!(expand("!9", &history()), Err(msg!("histexpand.not_found", "!9")));
+        assert_eq!(expand("!cargo", &history()), Err(msg!("histexpand.not_found", "!cargo")));
+        assert_eq!(expand("!!", &[]), Err(msg!("histexpand.not_found", "!!")));
+    }
+}
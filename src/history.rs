@@ -0,0 +1,497 @@
+//! Where command history is kept.
+//!
+//! The shell reads and records history through [`HistoryStore`], so the
+//! storage can be swapped without touching the line editor. Two stores
+//! ship with octane:
+//!
+//! * [`FileHistory`], a plain-text file with one command per line,
+//! * [`SyncedHistory`], which mirrors another store to a file on a remote
+//!   host over `ssh`, so several machines share one history.
+//!
+//! The shell picks one with `history.backend` and `history.sync`. Other
+//! programs can open the same files through these types, to read or add to
+//! a user's history.
+//!
+//! Several shells may share one store. Each only ever appends its own
+//! entries, and picks up those the others recorded with
+//! [`HistoryStore::load_new`], so no session overwrites another's history.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Storage for command history.
+///
+/// Entries are whole command lines and may contain newlines (a function
+/// typed over several lines is one entry).
+pub trait HistoryStore: Send {
+    /// Every stored entry, oldest first.
+    fn load(&mut self) -> io::Result<Vec<String>>;
+
+    /// Records `entry` after the existing ones.
+    fn append(&mut self, entry: &str) -> io::Result<()>;
+
+    /// Removes every entry.
+    fn clear(&mut self) -> io::Result<()>;
+
+    /// Removes the entry at `index`, counting from 0 for the oldest.
+    ///
+    /// The default rewrites the whole store; stores that can remove one
+    /// entry in place should do so.
+    fn delete(&mut self, index: usize) -> io::Result<()> {
+        let mut entries = self.load()?;
+        if index >= entries.len() {
+            return Ok(());
+        }
+        entries.remove(index);
+        self.clear()?;
+        entries.iter().try_for_each(|entry| self.append(entry))
+    }
+
+    /// Drops all but the newest `keep` entries.
+    ///
+    /// The default rewrites the whole store, like [`HistoryStore::delete`].
+    fn truncate(&mut self, keep: usize) -> io::Result<()> {
+        let entries = self.load()?;
+        if entries.len() <= keep {
+            return Ok(());
+        }
+        self.clear()?;
+        entries[entries.len() - keep..].iter().try_for_each(|entry| self.append(entry))
+    }
+
+    /// Entries other sessions recorded since this one loaded the store or
+    /// last asked, oldest first.
+    ///
+    /// The default finds none, for stores that aren't shared.
+    fn load_new(&mut self) -> io::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Finishes writing anything still pending. Called when the shell exits.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Escapes backslashes and newlines so each entry fits on one line.
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn parse_lines(text: &str) -> Vec<String> {
+    text.lines().filter(|line| !line.is_empty()).map(unescape).collect()
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+/// How long a lock file may stand before it is taken to be left over from
+/// a session that crashed while holding it.
+const STALE_LOCK: Duration = Duration::from_secs(2);
+
+/// An exclusive lock on a history file, held while a session writes to it:
+/// a `.lock` file next to it, created only if it doesn't exist yet, and
+/// removed when dropped.
+struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    fn acquire(history: &Path) -> io::Result<FileLock> {
+        create_parent(history)?;
+        let mut path = history.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(FileLock { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&path).and_then(|meta| meta.modified()).map(|time| time.elapsed());
+                    if matches!(age, Ok(Ok(age)) if age > STALE_LOCK) {
+                        let _ = fs::remove_file(&path);
+                    } else {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// History in a plain-text file, one entry per line. Newlines and
+/// backslashes inside an entry are written as `\n` and `\\`.
+///
+/// Entries are appended whole under a lock, and deleting rewrites the file
+/// under the same lock, so shells sharing the file never lose each other's
+/// entries. Each remembers how much of the file it has seen and reads only
+/// what was added after that.
+pub struct FileHistory {
+    path: PathBuf,
+    /// Bytes of the file this session has read or written, once loaded.
+    seen: Option<u64>,
+    /// Entries of other sessions read while appending, for the next
+    /// [`HistoryStore::load_new`].
+    unseen: Vec<String>,
+}
+
+impl FileHistory {
+    pub fn new(path: impl Into<PathBuf>) -> FileHistory {
+        FileHistory { path: path.into(), seen: None, unseen: Vec::new() }
+    }
+
+    /// Replaces the file with `entries`. Written whole and renamed, so a
+    /// crash never leaves half a file.
+    fn rewrite(&mut self, entries: &[String]) -> io::Result<()> {
+        let text: String = entries.iter().map(|entry| format!("{}\n", escape(entry))).collect();
+        let partial = self.path.with_extension(format!("{}", std::process::id()));
+        fs::write(&partial, &text)?;
+        fs::rename(&partial, &self.path)?;
+        self.seen = Some(text.len() as u64);
+        Ok(())
+    }
+
+    /// The whole lines added to `file` since this session last looked.
+    /// When the file shrank, or was rewritten so that the old end is no
+    /// longer the end of a line, another session deleted entries and what
+    /// is new can't be told; then nothing is.
+    fn read_added(&mut self, file: &mut fs::File) -> io::Result<Vec<String>> {
+        let len = file.metadata()?.len();
+        let Some(seen) = self.seen.filter(|&seen| seen <= len) else {
+            self.seen = Some(len);
+            return Ok(Vec::new());
+        };
+        if seen == len {
+            return Ok(Vec::new());
+        }
+        // From the byte before, to check it still ends a line.
+        let from = seen.saturating_sub(1);
+        let mut text = Vec::new();
+        file.seek(SeekFrom::Start(from))?;
+        file.read_to_end(&mut text)?;
+        if seen > 0 && text.first() != Some(&b'\n') {
+            self.seen = Some(len);
+            return Ok(Vec::new());
+        }
+        let text = &text[(seen - from) as usize..];
+        let end = text.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        self.seen = Some(seen + end as u64);
+        Ok(parse_lines(&String::from_utf8_lossy(&text[..end])))
+    }
+}
+
+impl HistoryStore for FileHistory {
+    fn load(&mut self) -> io::Result<Vec<String>> {
+        self.unseen.clear();
+        match fs::read_to_string(&self.path) {
+            Ok(text) => {
+                self.seen = Some(text.len() as u64);
+                Ok(parse_lines(&text))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.seen = Some(0);
+                Ok(Vec::new())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn append(&mut self, entry: &str) -> io::Result<()> {
+        let _lock = FileLock::acquire(&self.path)?;
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(&self.path)?;
+        if self.seen.is_some() {
+            let added = self.read_added(&mut file)?;
+            self.unseen.extend(added);
+        }
+        // One write, so a reader never sees half an entry.
+        let line = format!("{}\n", escape(entry));
+        file.write_all(line.as_bytes())?;
+        self.seen = Some(file.metadata()?.len());
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        let _lock = FileLock::acquire(&self.path)?;
+        self.seen = Some(0);
+        self.unseen.clear();
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn delete(&mut self, index: usize) -> io::Result<()> {
+        let _lock = FileLock::acquire(&self.path)?;
+        let unseen = self.load_new()?;
+        let mut entries = self.load()?;
+        self.unseen = unseen;
+        if index >= entries.len() {
+            return Ok(());
+        }
+        entries.remove(index);
+        self.rewrite(&entries)
+    }
+
+    fn truncate(&mut self, keep: usize) -> io::Result<()> {
+        let _lock = FileLock::acquire(&self.path)?;
+        let unseen = self.load_new()?;
+        let entries = self.load()?;
+        self.unseen = unseen;
+        if entries.len() <= keep {
+            return Ok(());
+        }
+        self.rewrite(&entries[entries.len() - keep..])
+    }
+
+    fn load_new(&mut self) -> io::Result<Vec<String>> {
+        let mut added = std::mem::take(&mut self.unseen);
+        match fs::File::open(&self.path) {
+            Ok(mut file) => added.extend(self.read_added(&mut file)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.seen = Some(0),
+            Err(e) => return Err(e),
+        }
+        Ok(added)
+    }
+}
+
+/// Mirrors another store to a plain-text history file on a remote host,
+/// reached with `ssh` (`BatchMode`, so it never prompts).
+///
+/// Loading reads the remote file, falling back to the local store when the
+/// host can't be reached. Each new entry is saved locally and then sent to
+/// the host in the background; entries recorded while the host is
+/// unreachable stay local. [`HistoryStore::flush`] waits for the uploads
+/// still under way.
+pub struct SyncedHistory {
+    local: Box<dyn HistoryStore>,
+    host: String,
+    path: String,
+    uploads: Vec<JoinHandle<()>>,
+}
+
+/// How long to wait for the remote host to answer.
+const CONNECT_TIMEOUT_SECS: u32 = 3;
+
+impl SyncedHistory {
+    /// `path` is relative to the remote user's home directory unless
+    /// absolute.
+    pub fn new(local: Box<dyn HistoryStore>, host: &str, path: &str) -> SyncedHistory {
+        SyncedHistory { local, host: host.to_string(), path: path.to_string(), uploads: Vec::new() }
+    }
+
+    fn ssh(&self, remote_command: &str) -> Command {
+        let mut cmd = Command::new("ssh");
+        cmd.args(["-o", "BatchMode=yes", "-o"])
+            .arg(format!("ConnectTimeout={}", CONNECT_TIMEOUT_SECS))
+            .args(["-T", &self.host, "--", remote_command]);
+        cmd
+    }
+
+    fn quoted_path(&self) -> String {
+        format!("'{}'", self.path.replace('\'', r"'\''"))
+    }
+}
+
+impl HistoryStore for SyncedHistory {
+    fn load(&mut self) -> io::Result<Vec<String>> {
+        let fetched = self
+            .ssh(&format!("cat {} 2>/dev/null || true", self.quoted_path()))
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match fetched {
+            Ok(output) if output.status.success() => Ok(parse_lines(&String::from_utf8_lossy(&output.stdout))),
+            _ => self.local.load(),
+        }
+    }
+
+    fn append(&mut self, entry: &str) -> io::Result<()> {
+        self.local.append(entry)?;
+        let child = self
+            .ssh(&format!("cat >> {}", self.quoted_path()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut child) = child {
+            let line = format!("{}\n", escape(entry));
+            self.uploads.retain(|upload| !upload.is_finished());
+            self.uploads.push(thread::spawn(move || {
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(line.as_bytes());
+                }
+                let _ = child.wait();
+            }));
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.local.clear()?;
+        let status = self.ssh(&format!("rm -f {}", self.quoted_path())).stdin(Stdio::null()).status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("{}: could not clear remote history", self.host)));
+        }
+        Ok(())
+    }
+
+    /// Removes the entry from the history loaded (the remote one when the
+    /// host is reachable) and uploads the rest in one go. The local store
+    /// loses the same command, if it has it.
+    fn delete(&mut self, index: usize) -> io::Result<()> {
+        // Uploads still under way would land after the rewrite.
+        for upload in self.uploads.drain(..) {
+            let _ = upload.join();
+        }
+        let mut entries = self.load()?;
+        if index >= entries.len() {
+            return Ok(());
+        }
+        let removed = entries.remove(index);
+        let local = self.local.load()?;
+        if let Some(local_index) = local.iter().rposition(|entry| *entry == removed) {
+            self.local.delete(local_index)?;
+        }
+        let mut child = self
+            .ssh(&format!("cat > {}", self.quoted_path()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let text: String = entries.iter().map(|entry| format!("{}\n", escape(entry))).collect();
+            stdin.write_all(text.as_bytes())?;
+        }
+        if !child.wait()?.success() {
+            return Err(io::Error::other(format!("{}: could not update remote history", self.host)));
+        }
+        Ok(())
+    }
+
+    /// Entries are one per line remotely too, so `tail` keeps the newest.
+    fn truncate(&mut self, keep: usize) -> io::Result<()> {
+        for upload in self.uploads.drain(..) {
+            let _ = upload.join();
+        }
+        self.local.truncate(keep)?;
+        let path = self.quoted_path();
+        let remote = format!("tail -n {keep} {path} > {path}.new 2>/dev/null && mv {path}.new {path}");
+        let status = self.ssh(&remote).stdin(Stdio::null()).status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("{}: could not update remote history", self.host)));
+        }
+        Ok(())
+    }
+
+    /// What other sessions on this machine recorded; entries from other
+    /// machines show up when the shell next starts.
+    fn load_new(&mut self) -> io::Result<Vec<String>> {
+        self.local.load_new()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for upload in self.uploads.drain(..) {
+            let _ = upload.join();
+        }
+        self.local.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history file of the test's own, in a fresh directory.
+    fn scratch(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("octane-history-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("history")
+    }
+
+    #[test]
+    fn entries_with_newlines_and_backslashes_survive() {
+        let entry = "f() {\n  echo 'a\\nb' C:\\dir\n}";
+        assert_eq!(unescape(&escape(entry)), entry);
+        assert!(!escape(entry).contains('\n'));
+    }
+
+    #[test]
+    fn file_history_keeps_what_was_appended() {
+        let path = scratch("appended");
+        let mut history = FileHistory::new(&path);
+        assert_eq!(history.load().unwrap(), Vec::<String>::new());
+        for entry in ["ls", "cd src\ncd ..", "make"] {
+            history.append(entry).unwrap();
+        }
+        assert_eq!(FileHistory::new(&path).load().unwrap(), ["ls", "cd src\ncd ..", "make"]);
+
+        history.delete(1).unwrap();
+        assert_eq!(FileHistory::new(&path).load().unwrap(), ["ls", "make"]);
+        history.append("git status").unwrap();
+        history.truncate(2).unwrap();
+        assert_eq!(FileHistory::new(&path).load().unwrap(), ["make", "git status"]);
+        history.clear().unwrap();
+        assert_eq!(FileHistory::new(&path).load().unwrap(), Vec::<String>::new());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn sessions_pick_up_each_others_entries() {
+        let path = scratch("sessions");
+        let (mut one, mut two) = (FileHistory::new(&path), FileHistory::new(&path));
+        one.load().unwrap();
+        two.load().unwrap();
+        one.append("from one").unwrap();
+        two.append("from two").unwrap();
+        one.append("again from one").unwrap();
+        assert_eq!(two.load_new().unwrap(), ["from one", "again from one"]);
+        assert_eq!(one.load_new().unwrap(), ["from two"]);
+        assert_eq!(one.load_new().unwrap(), Vec::<String>::new());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn deleting_keeps_other_sessions_entries() {
+        let path = scratch("deleting");
+        let (mut one, mut two) = (FileHistory::new(&path), FileHistory::new(&path));
+        one.load().unwrap();
+        one.append("a").unwrap();
+        two.load().unwrap();
+        two.append("b").unwrap();
+        one.delete(0).unwrap();
+        assert_eq!(FileHistory::new(&path).load().unwrap(), ["b"]);
+        assert_eq!(one.load_new().unwrap(), ["b"]);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,127 @@
+//! What goes into the history, set under `[history]`.
+//!
+//! `dedup` drops a command repeated right after itself (`"consecutive"`,
+//! the default), every earlier copy of a command run again (`"all"`), or
+//! nothing (`"none"`). With `ignore_space`, a line typed with a leading
+//! space isn't recorded, and lines with a match for one of `ignore`
+//! (regular expressions, see `regex`) never are. `file_size` caps the saved
+//! history, which is trimmed to the newest entries when the shell starts.
+
+use crate::config::Config;
+use crate::regex::Regex;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Dedup {
+    None,
+    Consecutive,
+    All,
+}
+
+pub struct Policy {
+    pub dedup: Dedup,
+    ignore_space: bool,
+    ignore: Vec<Regex>,
+    /// Most entries kept in the store.
+    pub file_size: Option<usize>,
+}
+
+impl Policy {
+    pub fn from_config(config: &Config) -> Policy {
+        let dedup = match config.get_str("history.dedup") {
+            Some("none") => Dedup::None,
+            Some("all") => Dedup::All,
+            _ => Dedup::Consecutive,
+        };
+        Policy {
+            dedup,
+            ignore_space: config.get_bool("history.ignore_space").unwrap_or(false),
+            ignore: ignore_patterns(config.get_str_list("history.ignore")),
+            file_size: config.get_int("history.file_size").filter(|&n| n > 0).map(|n| n as usize),
+        }
+    }
+
+    /// Whether `line` is left out of the history; `leading_space` is
+    /// whether it was typed with one.
+    pub fn ignored(&self, line: &str, leading_space: bool) -> bool {
+        (self.ignore_space && leading_space) || self.ignore.iter().any(|regex| regex.is_match(line))
+    }
+}
+
+/// Compiles the `ignore` patterns, reporting and leaving out invalid ones.
+fn ignore_patterns(patterns: Vec<String>) -> Vec<Regex> {
+    patterns
+        .into_iter()
+        .filter_map(|pattern| {
+            Regex::new(&pattern, false).map_err(|e| println!("history.ignore: {}: {}", pattern, e)).ok()
+        })
+        .collect()
+}
+
+/// The index of an earlier copy of the newest entry, which `dedup = "all"`
+/// drops.
+pub fn earlier_copy(entries: &[String]) -> Option<usize> {
+    let (newest, earlier) = entries.split_last()?;
+    earlier.iter().position(|entry| entry == newest)
+}
+
+/// `entries` with only the newest copy of each command.
+pub fn newest_copies(entries: Vec<String>) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut kept: Vec<String> = entries.into_iter().rev().filter(|entry| seen.insert(entry.clone())).collect();
+    kept.reverse();
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(ignore: &[&str]) -> Policy {
+        let ignore = ignore_patterns(ignore.iter().map(|p| p.to_string()).collect());
+        Policy { dedup: Dedup::Consecutive, ignore_space: true, ignore, file_size: None }
+    }
+
+    #[test]
+    fn ignore_matches_anywhere_in_the_line() {
+        let policy = policy(&["PASSWORD=", r"^\s*exit$"]);
+        assert!(policy.ignored("export PASSWORD=hunter2", false));
+        assert!(policy.ignored("PASSWORD=x ./deploy", false));
+        assert!(policy.ignored("exit", false));
+        assert!(!policy.ignored("echo password=x", false));
+        assert!(!policy.ignored("exit 1", false));
+    }
+
+    #[test]
+    fn invalid_patterns_are_left_out() {
+        let policy = policy(&["(unclosed", "ls"]);
+        assert_eq!(policy.ignore.len(), 1);
+        assert!(policy.ignored("ls -la", false));
+    }
+
+    #[test]
+    fn leading_space() {
+        assert!(policy(&[]).ignored("secret", true));
+        assert!(!policy(&[]).ignored("secret", false));
+    }
+
+    #[test]
+    fn copies() {
+        let entries = |list: &[&str]| list.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(earlier_copy(&entries(&["a", "b", "a"])), Some(0));
+        assert_eq!(earlier_copy(&entries(&["a", "b"])), None);
+        assert_eq!(newest_copies(entries(&["b", "a", "x", "a"])), ["b", "x", "a"]);
+    }
+
+    #[test]
+    fn read_from_the_config() {
+        let config = Config::parse("[history]\ndedup = \"all\"\nignore = [\"^ls$\"]\nfile_size = 100\n");
+        let policy = Policy::from_config(&config);
+        assert!(policy.dedup == Dedup::All);
+        assert!(policy.ignored("ls", false) && !policy.ignored("ls -l", true));
+        assert_eq!(policy.file_size, Some(100));
+
+        let policy = Policy::from_config(&Config::parse("[history]\nfile_size = 0\n"));
+        assert!(policy.dedup == Dedup::Consecutive);
+        assert_eq!(policy.file_size, None);
+    }
+}
@@ -0,0 +1,148 @@
+//! Word splitting of expansion results with `IFS`.
+//!
+//! By default `$NAME` expands to a single word however much whitespace its
+//! value holds; setting `IFS` asks for the value to be split on its
+//! characters. In POSIX mode (`set -o posix`) an unset `IFS` means the
+//! classic space, tab, and newline.
+//!
+//! The command line is split into words after expansion (see `words`), so
+//! whitespace, quotes and backslashes inside an expansion result are swapped
+//! for private-use characters until the words are separated.
+
+use std::collections::HashMap;
+
+use crate::vars;
+
+/// `IFS` when unset in POSIX mode.
+const DEFAULT_IFS: &str = " \t\n";
+
+/// Stand-ins for whitespace, quotes and backslashes that belong to an
+/// expansion result.
+const PROTECTED: [(char, char); 9] = [
+    (' ', '\u{E000}'),
+    ('\t', '\u{E001}'),
+    ('\n', '\u{E002}'),
+    ('\r', '\u{E003}'),
+    ('\u{B}', '\u{E004}'),
+    ('\u{C}', '\u{E005}'),
+    ('\'', '\u{E006}'),
+    ('"', '\u{E007}'),
+    ('\\', '\u{E008}'),
+];
+/// Keeps an empty field as a word of its own.
+const EMPTY_FIELD: char = '\u{E00F}';
+
+/// The separators to split expansion results on, or `None` to leave them
+/// whole.
+pub fn current(variables: &HashMap<String, String>, posix: bool) -> Option<String> {
+    vars::lookup(variables, "IFS").or_else(|| posix.then(|| DEFAULT_IFS.to_string()))
+}
+
+/// Splits `value` into fields the POSIX way: runs of `ifs` whitespace
+/// separate fields and are trimmed from the ends, while each other `ifs`
+/// character ends a field, so `a::b` with `IFS=:` has an empty middle field.
+pub fn split(value: &str, ifs: &str) -> Vec<String> {
+    split_at_most(value, ifs, usize::MAX)
+}
+
+/// Like `split`, but stops at `max` fields: the last one holds the rest of
+/// `value`, separators included, as `read` assigns its last variable.
+pub fn split_at_most(value: &str, ifs: &str, max: usize) -> Vec<String> {
+    if ifs.is_empty() || max <= 1 {
+        let value = if ifs.is_empty() { value } else { value.trim_matches(|c: char| c.is_whitespace() && ifs.contains(c)) };
+        return if value.is_empty() { Vec::new() } else { vec![value.to_string()] };
+    }
+    let is_space = |c: char| c.is_whitespace() && ifs.contains(c);
+    let is_delimiter = |c: char| !c.is_whitespace() && ifs.contains(c);
+
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let value = value.trim_matches(is_space);
+    let mut chars = value.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if !is_space(c) && !is_delimiter(c) {
+            field.push(c);
+            continue;
+        }
+        // Whitespace around a delimiter belongs to it.
+        let mut delimited = is_delimiter(c);
+        while let Some(&(_, next)) = chars.peek() {
+            if is_space(next) || (is_delimiter(next) && !delimited) {
+                delimited |= is_delimiter(next);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        fields.push(std::mem::take(&mut field));
+        if fields.len() == max - 1 {
+            if let Some(&(rest, _)) = chars.peek() {
+                fields.push(value[rest..].to_string());
+            }
+            return fields;
+        }
+    }
+    if !field.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+fn protect(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| PROTECTED.iter().find(|(from, _)| *from == c).map_or(c, |(_, to)| *to))
+        .collect()
+}
+
+/// The text to put on the command line for an expansion result: the
+/// fields of `value` joined by spaces, each kept whole.
+pub fn substitute(value: &str, ifs: Option<&str>) -> String {
+    let Some(ifs) = ifs else {
+        return protect(value);
+    };
+    split(value, ifs)
+        .iter()
+        .map(|field| if field.is_empty() { EMPTY_FIELD.to_string() } else { protect(field) })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Puts back what `substitute` hid.
+pub fn restore(word: &str) -> String {
+    word.chars()
+        .filter(|c| *c != EMPTY_FIELD)
+        .map(|c| PROTECTED.iter().find(|(_, to)| *to == c).map_or(c, |(from, _)| *from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_runs_are_one_separator() {
+        assert_eq!(split("  a \t b\n", DEFAULT_IFS), ["a", "b"]);
+        assert!(split("   ", DEFAULT_IFS).is_empty());
+    }
+
+    #[test]
+    fn other_separators_end_a_field_each() {
+        assert_eq!(split("a::b:", ":"), ["a", "", "b"]);
+        assert_eq!(split("a : b", ": "), ["a", "b"]);
+    }
+
+    #[test]
+    fn the_last_field_keeps_the_rest() {
+        assert_eq!(split_at_most("one two  three four", " ", 2), ["one", "two  three four"]);
+        assert_eq!(split_at_most("  whole line ", " ", 1), ["whole line"]);
+    }
+
+    #[test]
+    fn substituted_fields_stay_whole() {
+        let line = format!("echo {}", substitute("a 'b'::c", Some(":")));
+        let words: Vec<String> = crate::words::split(&line).iter().map(|word| restore(word)).collect();
+        assert_eq!(words, ["echo", "a 'b'", "", "c"]);
+        assert_eq!(restore(&substitute("x y", None)), "x y");
+    }
+}
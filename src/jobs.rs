@@ -0,0 +1,1091 @@
+//! Background jobs and job control.
+//!
+//! Every external command runs in its own process group. In an interactive
+//! session the foreground group is handed the terminal while it runs, so
+//! Ctrl-C and Ctrl-Z reach only that command; a job stopped with Ctrl-Z is
+//! moved to the jobs table and can be resumed with `fg` or `bg`.
+//!
+//! A reaper thread collects the exit status of background jobs as soon as
+//! they finish, so they never linger as zombies; finished jobs are reported
+//! and pruned from the table before the next prompt.
+//!
+//! Jobs are numbered from 1 and can be named by job spec: `%n` for job `n`,
+//! `%%` or `%+` for the current job, `%-` for the previous one, or a plain
+//! PID.
+
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::focus;
+use crate::messages::msg;
+use crate::signals;
+
+/// How often `wait` polls the jobs it is waiting for.
+const WAIT_POLL: Duration = Duration::from_millis(20);
+/// How often the reaper thread collects finished background jobs.
+const REAP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// PIDs of disowned jobs and `spawn`ed processes that are still running.
+/// They are not in the table, but still have to be waited for once they exit.
+#[cfg(unix)]
+static DISOWNED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JobState {
+    Running,
+    Stopped,
+    /// Finished with the given shell exit status.
+    Done(i32),
+}
+
+pub struct Job {
+    /// Job number, as in `%1`.
+    pub id: usize,
+    /// The first process's PID, which is also the job's process group.
+    pub pid: u32,
+    /// The command line as typed.
+    pub command: String,
+    /// Stopped while any process is, and done once all are, with the last
+    /// one's status.
+    pub state: JobState,
+    /// Not hung up when the shell exits: marked with `disown -h`, or started
+    /// with `nohup`.
+    pub nohup: bool,
+    /// Set while `fg` waits for the job; the reaper leaves it alone then.
+    foreground: bool,
+    /// The job's processes: its command, or each external command of a
+    /// pipeline in order.
+    processes: Vec<Process>,
+}
+
+struct Process {
+    pid: u32,
+    state: JobState,
+    /// On Unix processes are waited for by PID; Windows polls the handle.
+    #[cfg_attr(unix, allow(dead_code))]
+    child: Child,
+}
+
+impl Job {
+    /// Works out the job's state from its processes'.
+    fn update(&mut self) {
+        let states: Vec<JobState> = self.processes.iter().map(|p| p.state).collect();
+        self.state = combined(&states);
+    }
+
+    /// PIDs of the processes that haven't finished.
+    fn unfinished(&self) -> Vec<u32> {
+        self.processes.iter().filter(|p| !matches!(p.state, JobState::Done(_))).map(|p| p.pid).collect()
+    }
+}
+
+/// The state of a job whose processes are in `states`.
+fn combined(states: &[JobState]) -> JobState {
+    if states.contains(&JobState::Stopped) {
+        JobState::Stopped
+    } else if states.iter().all(|state| matches!(state, JobState::Done(_))) {
+        states.last().copied().unwrap_or(JobState::Done(0))
+    } else {
+        JobState::Running
+    }
+}
+
+/// Jobs ordered by when they were last started, stopped or resumed, so the
+/// last unfinished one is the current job. Shared with anything that needs
+/// to inspect or signal them.
+pub type JobTable = Arc<Mutex<Vec<Job>>>;
+
+pub fn new_table() -> JobTable {
+    Arc::new(Mutex::new(Vec::new()))
+}
+
+/// Spawns the command in a new process group, so terminal signals aimed at
+/// the shell's group don't reach it. On Unix a foreground command also takes
+/// the terminal itself before it execs, so it can read from it straight away.
+/// On Windows a foreground command stays in the console's group instead:
+/// Ctrl-C is switched off in new groups, and the console delivers it to
+/// every process in the console's group, the shell included.
+pub fn spawn(command: &mut Command, foreground: bool) -> std::io::Result<Child> {
+    spawn_in(command, 0, foreground)
+}
+
+/// Like `spawn`, but in the process group `group` if it isn't 0: the
+/// commands of a pipeline share the first one's group.
+#[cfg_attr(windows, allow(unused_variables))]
+pub fn spawn_in(command: &mut Command, group: u32, foreground: bool) -> std::io::Result<Child> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(group as i32);
+        if foreground && let Some(fd) = unix::terminal_fd() {
+            // SAFETY: the hook only makes async-signal-safe libc calls.
+            unsafe {
+                command.pre_exec(move || {
+                    unix::claim_terminal(fd, libc::getpgrp());
+                    Ok(())
+                });
+            }
+        }
+    }
+    #[cfg(windows)]
+    if !foreground {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    let result = command.spawn();
+    // The child may have taken the terminal before failing to exec.
+    #[cfg(unix)]
+    if result.is_err() {
+        unix::reclaim_terminal();
+    }
+    result
+}
+
+/// Adds a job to the table under the lowest free number above all current
+/// ones and returns that number. `states` are its processes', in order.
+fn insert(jobs: &mut Vec<Job>, children: Vec<Child>, states: &[JobState], command: &str) -> usize {
+    let id = jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+    let processes: Vec<Process> = children
+        .into_iter()
+        .zip(states)
+        .map(|(child, &state)| Process { pid: child.id(), state, child })
+        .collect();
+    jobs.push(Job {
+        id,
+        pid: processes.first().map_or(0, |p| p.pid),
+        command: command.to_string(),
+        state: combined(states),
+        nohup: command.split_whitespace().next() == Some("nohup"),
+        foreground: false,
+        processes,
+    });
+    id
+}
+
+/// Adds a running background job and prints its number and PID.
+pub fn add(table: &JobTable, child: Child, command: &str) {
+    add_pipeline(table, vec![child], command);
+}
+
+/// Adds the running commands of a background pipeline as one job, led by
+/// the first, and prints its number and PID.
+pub fn add_pipeline(table: &JobTable, children: Vec<Child>, command: &str) {
+    let Some(pid) = children.first().map(Child::id) else {
+        return;
+    };
+    let states = vec![JobState::Running; children.len()];
+    let id = insert(&mut table.lock().unwrap(), children, &states, command);
+    println!("[{}] {}", id, pid);
+}
+
+/// Moves a job to the end of the table, making it the current job.
+#[cfg(unix)]
+fn make_current(jobs: &mut Vec<Job>, pid: u32) {
+    if let Some(index) = jobs.iter().position(|j| j.pid == pid) {
+        let job = jobs.remove(index);
+        jobs.push(job);
+    }
+}
+
+/// A reference to a job as accepted by `fg`, `bg` and `kill`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Spec {
+    Id(usize),
+    Current,
+    Previous,
+    Pid(u32),
+}
+
+pub fn parse_spec(arg: &str) -> Result<Spec, String> {
+    match arg.strip_prefix('%') {
+        Some("%" | "+" | "") => Ok(Spec::Current),
+        Some("-") => Ok(Spec::Previous),
+        Some(n) => n
+            .parse()
+            .map(Spec::Id)
+            .map_err(|_| msg!("jobs.no_such_spec", arg)),
+        None => arg
+            .parse()
+            .map(Spec::Pid)
+            .map_err(|_| msg!("jobs.not_a_spec", arg)),
+    }
+}
+
+fn find(jobs: &mut [Job], spec: Spec) -> Option<&mut Job> {
+    let (current, previous) = markers(jobs);
+    let pid = match spec {
+        Spec::Current => current?,
+        Spec::Previous => previous?,
+        Spec::Id(id) => return jobs.iter_mut().find(|j| j.id == id),
+        Spec::Pid(pid) => pid,
+    };
+    jobs.iter_mut().find(|j| j.pid == pid)
+}
+
+/// The PID a job spec names: a plain PID as is, or a job's PID.
+pub fn pid_of(table: &JobTable, spec: Spec) -> Option<u32> {
+    match spec {
+        Spec::Pid(pid) => Some(pid),
+        spec => find(&mut table.lock().unwrap(), spec).map(|job| job.pid),
+    }
+}
+
+/// Formats a job the way `jobs` lists it: `[1]+  Running     sleep 10 &`,
+/// optionally with its PID after the marker.
+fn describe(job: &Job, marker: char, with_pid: bool) -> String {
+    let pid = if with_pid { format!(" {}", job.pid) } else { String::new() };
+    format!("[{}]{}{}  {:<12}{}", job.id, marker, pid, state_name(job.state), job.command)
+}
+
+/// The `+`/`-` markers for the current and previous job, keyed by PID.
+fn markers(jobs: &[Job]) -> (Option<u32>, Option<u32>) {
+    let mut unfinished = jobs
+        .iter()
+        .rev()
+        .filter(|j| !matches!(j.state, JobState::Done(_)))
+        .map(|j| j.pid);
+    (unfinished.next(), unfinished.next())
+}
+
+fn marker_for(pid: u32, markers: (Option<u32>, Option<u32>)) -> char {
+    if markers.0 == Some(pid) {
+        '+'
+    } else if markers.1 == Some(pid) {
+        '-'
+    } else {
+        ' '
+    }
+}
+
+/// `jobs [-l|-p]`: lists jobs by number with their state and command line
+/// (`-l` adds PIDs, `-p` prints only PIDs). Finished jobs are reported once
+/// and then forgotten.
+pub fn list(table: &JobTable, args: &[&str]) -> i32 {
+    let (long, pids_only) = match args.first() {
+        None => (false, false),
+        Some(&"-l") => (true, false),
+        Some(&"-p") => (false, true),
+        Some(_) => {
+            println!("{}", msg!("usage.jobs"));
+            return 2;
+        }
+    };
+    refresh(table);
+    let mut jobs = table.lock().unwrap();
+    let markers = markers(&jobs);
+    let mut sorted: Vec<&Job> = jobs.iter().collect();
+    sorted.sort_by_key(|j| j.id);
+    for job in sorted {
+        if pids_only {
+            println!("{}", job.pid);
+        } else {
+            println!("{}", describe(job, marker_for(job.pid, markers), long));
+        }
+    }
+    jobs.retain(|j| !matches!(j.state, JobState::Done(_)));
+    0
+}
+
+/// Reports jobs that finished since the last prompt, e.g.
+/// `[1]   Done        cargo build &` or `[2]   Exit 101    cargo test &`, and
+/// removes them from the table. Called before each prompt; held back while
+/// a focus session is active.
+pub fn notify(table: &JobTable) {
+    if focus::active() {
+        return;
+    }
+    refresh(table);
+    let mut jobs = table.lock().unwrap();
+    let mut finished: Vec<&Job> = jobs
+        .iter()
+        .filter(|j| matches!(j.state, JobState::Done(_)))
+        .collect();
+    finished.sort_by_key(|j| j.id);
+    for job in finished {
+        println!("{}", describe(job, ' ', false));
+    }
+    jobs.retain(|j| !matches!(j.state, JobState::Done(_)));
+}
+
+/// Starts the thread that periodically collects finished background and
+/// disowned jobs.
+pub fn start_reaper(table: &JobTable) {
+    let table = Arc::clone(table);
+    thread::spawn(move || {
+        loop {
+            thread::sleep(REAP_INTERVAL);
+            refresh(&table);
+            #[cfg(unix)]
+            unix::reap_disowned();
+        }
+    });
+}
+
+/// Leaves a child the shell doesn't track to the reaper, which waits for
+/// it once it exits as for a disowned job.
+pub fn reap_later(child: Child) {
+    #[cfg(unix)]
+    DISOWNED.lock().unwrap().push(child.id());
+    #[cfg(windows)]
+    drop(child);
+}
+
+/// Resolves each argument to a job PID, or selects every job matching
+/// `default` when there are no arguments.
+fn targets(
+    table: &JobTable,
+    args: &[&str],
+    default: impl Fn(&Job) -> bool,
+) -> Result<Vec<u32>, String> {
+    let mut jobs = table.lock().unwrap();
+    if args.is_empty() {
+        return Ok(jobs.iter().filter(|j| default(j)).map(|j| j.pid).collect());
+    }
+    args.iter()
+        .map(|arg| {
+            let spec = parse_spec(arg)?;
+            find(&mut jobs, spec)
+                .map(|j| j.pid)
+                .ok_or_else(|| msg!("jobs.no_such_spec", arg))
+        })
+        .collect()
+}
+
+/// `wait [job...]`: blocks until the given jobs (or all jobs that aren't
+/// stopped) finish and removes them from the table. Returns the status of the last
+/// job waited for, 0 when waiting for all jobs, or 130 when interrupted.
+pub fn wait(table: &JobTable, args: &[&str]) -> i32 {
+    refresh(table);
+    let pids = match targets(table, args, |j| j.state != JobState::Stopped) {
+        Ok(pids) => pids,
+        Err(e) => {
+            println!("wait: {}", e);
+            return 127;
+        }
+    };
+    signals::take_interrupt();
+    let mut status = 0;
+    for pid in pids {
+        loop {
+            let state = table
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|j| j.pid == pid)
+                .map(|j| j.state);
+            match state {
+                Some(JobState::Done(code)) => {
+                    table.lock().unwrap().retain(|j| j.pid != pid);
+                    status = code;
+                    break;
+                }
+                Some(JobState::Stopped) => {
+                    status = 148;
+                    break;
+                }
+                Some(JobState::Running) => {}
+                None => break,
+            }
+            if signals::take_interrupt() {
+                println!();
+                return 130;
+            }
+            thread::sleep(WAIT_POLL);
+            refresh(table);
+        }
+    }
+    if args.is_empty() { 0 } else { status }
+}
+
+/// `disown [-a] [-h] [job...]`: forgets jobs (the current job by default,
+/// every job with `-a`) so the shell no longer tracks, lists or signals
+/// them. With `-h` the jobs stay, but aren't hung up when the shell exits.
+pub fn disown(table: &JobTable, args: &[&str]) -> i32 {
+    let mark = args.contains(&"-h");
+    let args: Vec<&str> = args.iter().copied().filter(|a| *a != "-h").collect();
+    let args = args.as_slice();
+    let all = args.first() == Some(&"-a");
+    let result = if all {
+        targets(table, &[], |_| true)
+    } else if args.is_empty() {
+        targets(table, &["%%"], |_| false)
+    } else {
+        targets(table, args, |_| false)
+    };
+    match result {
+        Ok(pids) if mark => {
+            for job in table.lock().unwrap().iter_mut().filter(|j| pids.contains(&j.pid)) {
+                job.nohup = true;
+            }
+            0
+        }
+        Ok(pids) => {
+            let mut jobs = table.lock().unwrap();
+            #[cfg(unix)]
+            DISOWNED
+                .lock()
+                .unwrap()
+                .extend(jobs.iter().filter(|j| pids.contains(&j.pid)).flat_map(Job::unfinished));
+            jobs.retain(|j| !pids.contains(&j.pid));
+            0
+        }
+        Err(e) => {
+            println!("disown: {}", e);
+            1
+        }
+    }
+}
+
+pub fn state_name(state: JobState) -> String {
+    match state {
+        JobState::Running => msg!("jobs.running"),
+        JobState::Stopped => msg!("jobs.stopped"),
+        JobState::Done(0) => msg!("jobs.done"),
+        #[cfg(unix)]
+        JobState::Done(code) if code > 128 => signals::describe(code - 128),
+        JobState::Done(code) => msg!("jobs.exit", code),
+    }
+}
+
+/// Sends `signal` to a process, or to a job's whole process group when
+/// `group` is set. Windows has no signals: there `INT` to a job becomes a
+/// Ctrl-Break for its group, and anything else terminates the process —
+/// with `group`, along with every process it started.
+pub fn send_signal(pid: u32, signal: i32, group: bool) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let target = if group { -(pid as libc::pid_t) } else { pid as libc::pid_t };
+        if unsafe { libc::kill(target, signal) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(windows)]
+    {
+        if group && signal == signals::SIGINT {
+            return windows::interrupt(pid);
+        }
+        if group {
+            return windows::terminate_tree(pid);
+        }
+        windows::terminate(pid)
+    }
+}
+
+/// Sends `signal` to every process of a job: its process group on Unix,
+/// and on Windows, where each command is in a group of its own, to each of
+/// them that is still running.
+fn signal_processes(job: &Job, signal: i32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        send_signal(job.pid, signal, true)
+    }
+    #[cfg(windows)]
+    {
+        let mut result = Ok(());
+        for pid in job.unfinished() {
+            result = result.and(send_signal(pid, signal, true));
+        }
+        result
+    }
+}
+
+/// Signals a job's process group. A stopped job is also continued so that
+/// it can act on the signal (unless the signal is itself a stop signal).
+pub fn signal_job(table: &JobTable, spec: Spec, signal: i32) -> Option<std::io::Result<()>> {
+    let mut jobs = table.lock().unwrap();
+    let job = find(&mut jobs, spec)?;
+    if matches!(job.state, JobState::Done(_)) {
+        return Some(Ok(()));
+    }
+    let result = signal_processes(job, signal);
+    #[cfg(unix)]
+    if result.is_ok()
+        && job.state == JobState::Stopped
+        && ![libc::SIGSTOP, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU, libc::SIGCONT].contains(&signal)
+    {
+        unix::continue_group(job.pid);
+    }
+    Some(result)
+}
+
+/// How many jobs are running and how many are stopped.
+pub fn unfinished(table: &JobTable) -> (usize, usize) {
+    refresh(table);
+    let jobs = table.lock().unwrap();
+    let count = |state| jobs.iter().filter(|j| j.state == state).count();
+    (count(JobState::Running), count(JobState::Stopped))
+}
+
+/// Sends `SIGHUP` to every unfinished job, or only to the stopped ones,
+/// which would otherwise never run again once the shell is gone. Jobs
+/// marked `nohup` are spared.
+pub fn hang_up(table: &JobTable, stopped_only: bool) {
+    refresh(table);
+    let jobs = table.lock().unwrap();
+    for job in jobs.iter().filter(|j| !j.nohup) {
+        if job.state == JobState::Stopped || (job.state == JobState::Running && !stopped_only) {
+            let _ = signal_processes(job, signals::SIGHUP);
+            #[cfg(unix)]
+            if job.state == JobState::Stopped {
+                unix::continue_group(job.pid);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{init, refresh, run_foreground, run_pipeline};
+
+#[cfg(unix)]
+mod unix {
+    use std::io::{self, IsTerminal};
+    use std::process::Child;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    use super::{JobState, JobTable, Spec};
+    use crate::messages::msg;
+    use crate::timecmd;
+
+    /// The controlling terminal and the shell's own process group, recorded
+    /// only when the shell runs interactively on a terminal.
+    struct Terminal {
+        fd: libc::c_int,
+        shell_pgid: libc::pid_t,
+    }
+
+    static TERMINAL: OnceLock<Option<Terminal>> = OnceLock::new();
+
+    extern "C" fn ignore_signal(_: libc::c_int) {}
+
+    fn terminal() -> Option<&'static Terminal> {
+        TERMINAL.get().and_then(Option::as_ref)
+    }
+
+    /// Takes control of the terminal: waits until the shell is in the
+    /// foreground, moves it into its own process group and claims the
+    /// terminal for it. Job-control signals get no-op handlers so the shell
+    /// itself is never stopped (handlers reset to the default in children).
+    pub fn init() {
+        let fd = libc::STDIN_FILENO;
+        if !io::stdin().is_terminal() {
+            let _ = TERMINAL.set(None);
+            return;
+        }
+        unsafe {
+            loop {
+                let pgid = libc::getpgrp();
+                if libc::tcgetpgrp(fd) == pgid {
+                    break;
+                }
+                libc::kill(-pgid, libc::SIGTTIN);
+            }
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = ignore_signal as *const () as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            for signal in [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU] {
+                libc::sigaction(signal, &action, std::ptr::null_mut());
+            }
+
+            let pid = libc::getpid();
+            libc::setpgid(pid, pid);
+            let _ = TERMINAL.set(Some(Terminal { fd, shell_pgid: pid }));
+            give_terminal_to(pid);
+        }
+    }
+
+    pub(super) fn terminal_fd() -> Option<libc::c_int> {
+        terminal().map(|t| t.fd)
+    }
+
+    /// Makes `pgid` the terminal's foreground process group. SIGTTOU is
+    /// blocked for the call, since the caller may not be in the foreground.
+    pub(super) fn claim_terminal(fd: libc::c_int, pgid: libc::pid_t) {
+        unsafe {
+            let mut block: libc::sigset_t = std::mem::zeroed();
+            let mut old: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut block);
+            libc::sigaddset(&mut block, libc::SIGTTOU);
+            libc::sigprocmask(libc::SIG_BLOCK, &block, &mut old);
+            libc::tcsetpgrp(fd, pgid);
+            libc::sigprocmask(libc::SIG_SETMASK, &old, std::ptr::null_mut());
+        }
+    }
+
+    fn give_terminal_to(pgid: libc::pid_t) {
+        if let Some(terminal) = terminal() {
+            claim_terminal(terminal.fd, pgid);
+        }
+    }
+
+    pub(super) fn reclaim_terminal() {
+        if let Some(terminal) = terminal() {
+            claim_terminal(terminal.fd, terminal.shell_pgid);
+        }
+    }
+
+    pub(super) fn continue_group(pid: u32) {
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGCONT);
+        }
+    }
+
+    fn decode(status: libc::c_int) -> JobState {
+        if libc::WIFSTOPPED(status) {
+            JobState::Stopped
+        } else if libc::WIFSIGNALED(status) {
+            JobState::Done(128 + libc::WTERMSIG(status))
+        } else if libc::WIFEXITED(status) {
+            JobState::Done(libc::WEXITSTATUS(status))
+        } else {
+            JobState::Running
+        }
+    }
+
+    /// Waits until the process exits or is stopped, and records what it
+    /// used for `time` once it exits.
+    fn wait_for(pid: u32) -> JobState {
+        let mut status = 0;
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            let result = unsafe { libc::wait4(pid as libc::pid_t, &mut status, libc::WUNTRACED, &mut usage) };
+            if result == pid as libc::pid_t {
+                let state = decode(status);
+                if matches!(state, JobState::Done(_)) {
+                    record_usage(&usage);
+                }
+                return state;
+            }
+            if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                return JobState::Done(1);
+            }
+        }
+    }
+
+    fn record_usage(usage: &libc::rusage) {
+        let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+        // Kilobytes, except on macOS.
+        let scale = if cfg!(target_os = "macos") { 1 } else { 1024 };
+        timecmd::record(timecmd::Usage {
+            user: time(usage.ru_utime),
+            sys: time(usage.ru_stime),
+            peak: (usage.ru_maxrss > 0).then(|| usage.ru_maxrss as u64 * scale),
+        });
+    }
+
+    /// Gives the terminal to the process group `group`, waits for each of
+    /// `pids` and then reclaims the terminal (restoring the shell's terminal
+    /// modes, which full-screen programs may have changed before being
+    /// stopped). Returns the state of each: as soon as one is stopped, the
+    /// ones not waited for yet are left `Running`.
+    fn wait_in_foreground(group: u32, pids: &[u32]) -> Vec<JobState> {
+        let saved = terminal().map(|t| unsafe {
+            let mut modes: libc::termios = std::mem::zeroed();
+            libc::tcgetattr(t.fd, &mut modes);
+            modes
+        });
+        let group = group as libc::pid_t;
+        for &pid in pids {
+            unsafe {
+                // Also done in the child; whichever runs first wins the race.
+                libc::setpgid(pid as libc::pid_t, group);
+            }
+        }
+        give_terminal_to(group);
+        let mut states = vec![JobState::Running; pids.len()];
+        for (state, &pid) in states.iter_mut().zip(pids) {
+            *state = wait_for(pid);
+            if *state == JobState::Stopped {
+                break;
+            }
+        }
+        reclaim_terminal();
+        if let (Some(t), Some(modes)) = (terminal(), saved) {
+            unsafe {
+                libc::tcsetattr(t.fd, libc::TCSADRAIN, &modes);
+            }
+        }
+        states
+    }
+
+    /// Runs an already spawned child in the foreground. A child stopped with
+    /// Ctrl-Z is added to the jobs table; returns the shell exit status
+    /// (148 for a stopped job, like other shells).
+    pub fn run_foreground(table: &JobTable, child: Child, command: &str) -> i32 {
+        run_pipeline(table, vec![child], command)
+    }
+
+    /// Runs the already spawned commands of a pipeline in the foreground,
+    /// in the process group of the first, and returns the status of the
+    /// last. A stopped pipeline is added to the jobs table as one job, led
+    /// by the first command.
+    pub fn run_pipeline(table: &JobTable, children: Vec<Child>, command: &str) -> i32 {
+        let pids: Vec<u32> = children.iter().map(Child::id).collect();
+        let Some(&group) = pids.first() else {
+            return 0;
+        };
+        let states = wait_in_foreground(group, &pids);
+        match super::combined(&states) {
+            JobState::Done(code) => {
+                if code == 128 + libc::SIGINT {
+                    println!();
+                }
+                code
+            }
+            _ => {
+                let mut jobs = table.lock().unwrap();
+                super::insert(&mut jobs, children, &states, command);
+                report_stopped(&jobs, group);
+                128 + libc::SIGTSTP
+            }
+        }
+    }
+
+    fn report_stopped(jobs: &[super::Job], pid: u32) {
+        println!();
+        if let Some(job) = jobs.iter().find(|j| j.pid == pid) {
+            println!("{}", super::describe(job, '+', false));
+        }
+    }
+
+    /// Polls every unfinished job without blocking and records state changes.
+    pub fn refresh(table: &JobTable) {
+        let mut jobs = table.lock().unwrap();
+        for job in jobs.iter_mut() {
+            if job.foreground || matches!(job.state, JobState::Done(_)) {
+                continue;
+            }
+            for process in job.processes.iter_mut().filter(|p| !matches!(p.state, JobState::Done(_))) {
+                let mut status = 0;
+                let flags = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+                let result = unsafe { libc::waitpid(process.pid as libc::pid_t, &mut status, flags) };
+                if result == process.pid as libc::pid_t {
+                    process.state = decode(status);
+                } else if result < 0 {
+                    process.state = JobState::Done(1);
+                }
+            }
+            job.update();
+        }
+    }
+
+    pub(super) fn reap_disowned() {
+        super::DISOWNED.lock().unwrap().retain(|&pid| {
+            let mut status = 0;
+            let result = unsafe { libc::waitpid(pid as libc::pid_t, &mut status, libc::WNOHANG) };
+            result == 0
+        });
+    }
+
+    /// `fg [job]`: continues a job in the foreground and waits for it.
+    pub fn foreground(table: &JobTable, spec: Spec) -> Result<i32, String> {
+        let (pid, command, unfinished) = {
+            let mut jobs = table.lock().unwrap();
+            let job = super::find(&mut jobs, spec).ok_or_else(|| msg!("jobs.no_such_job"))?;
+            if let JobState::Done(code) = job.state {
+                return Err(msg!("jobs.already_finished", code));
+            }
+            let unfinished = job.unfinished();
+            for process in job.processes.iter_mut().filter(|p| unfinished.contains(&p.pid)) {
+                process.state = JobState::Running;
+            }
+            job.update();
+            job.foreground = true;
+            (job.pid, job.command.clone(), unfinished)
+        };
+        println!("{}", command.trim_end_matches(['&', ' ']));
+        give_terminal_to(pid as libc::pid_t);
+        continue_group(pid);
+
+        let states = wait_in_foreground(pid, &unfinished);
+        let mut jobs = table.lock().unwrap();
+        let Some(job) = jobs.iter_mut().find(|j| j.pid == pid) else {
+            return Ok(0);
+        };
+        for (waited, state) in unfinished.iter().zip(states) {
+            if let Some(process) = job.processes.iter_mut().find(|p| p.pid == *waited) {
+                process.state = state;
+            }
+        }
+        job.update();
+        job.foreground = false;
+        match job.state {
+            JobState::Done(code) => {
+                jobs.retain(|j| j.pid != pid);
+                if code == 128 + libc::SIGINT {
+                    println!();
+                }
+                Ok(code)
+            }
+            _ => {
+                super::make_current(&mut jobs, pid);
+                report_stopped(&jobs, pid);
+                Ok(128 + libc::SIGTSTP)
+            }
+        }
+    }
+
+    /// `bg [job]`: continues a stopped job in the background.
+    pub fn background(table: &JobTable, spec: Option<Spec>) -> Result<i32, String> {
+        let mut jobs = table.lock().unwrap();
+        let job = match spec {
+            Some(spec) => super::find(&mut jobs, spec),
+            None => jobs.iter_mut().rev().find(|j| j.state == JobState::Stopped),
+        }
+        .ok_or_else(|| msg!("jobs.no_such_job"))?;
+        if job.state != JobState::Stopped {
+            return Err(msg!("jobs.already_running", job.id));
+        }
+        for process in job.processes.iter_mut().filter(|p| p.state == JobState::Stopped) {
+            process.state = JobState::Running;
+        }
+        job.update();
+        continue_group(job.pid);
+        let pid = job.pid;
+        println!("[{}]+ {} &", job.id, job.command.trim_end_matches(['&', ' ']));
+        super::make_current(&mut jobs, pid);
+        Ok(0)
+    }
+}
+
+#[cfg(windows)]
+pub fn init() {}
+
+#[cfg(windows)]
+pub fn refresh(table: &JobTable) {
+    let mut jobs = table.lock().unwrap();
+    for job in jobs.iter_mut().filter(|j| !j.foreground && j.state == JobState::Running) {
+        for process in job.processes.iter_mut().filter(|p| p.state == JobState::Running) {
+            if let Ok(Some(status)) = process.child.try_wait() {
+                process.state = JobState::Done(status.code().unwrap_or(1));
+            }
+        }
+        job.update();
+    }
+}
+
+/// Waits for a foreground child. Windows has no job control, so the child
+/// simply runs to completion.
+#[cfg(windows)]
+pub fn run_foreground(_table: &JobTable, mut child: Child, _command: &str) -> i32 {
+    match child.wait() {
+        Ok(status) => {
+            windows::record_usage(&child);
+            if crate::signals::killed_by_interrupt(status) {
+                println!();
+            }
+            status.code().unwrap_or(1)
+        }
+        Err(e) => {
+            println!("{}", msg!("error.waiting", e));
+            1
+        }
+    }
+}
+
+/// Runs the already spawned commands of a pipeline, returning the status
+/// of the last.
+#[cfg(windows)]
+pub fn run_pipeline(table: &JobTable, children: Vec<Child>, command: &str) -> i32 {
+    let mut status = 0;
+    for child in children {
+        status = run_foreground(table, child, command);
+    }
+    status
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::collections::HashMap;
+    use std::io;
+
+    use windows_sys::Win32::Foundation::{CloseHandle, FILETIME, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, GenerateConsoleCtrlEvent};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, PROCESSENTRY32W, Process32FirstW, Process32NextW, TH32CS_SNAPPROCESS,
+    };
+    use windows_sys::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows_sys::Win32::System::Threading::{
+        GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_TERMINATE, TerminateProcess,
+    };
+
+    /// Sends Ctrl-Break to the process group `pgid`. Ctrl-C can't be aimed
+    /// at one group, and is switched off in the groups background jobs get.
+    pub fn interrupt(pgid: u32) -> io::Result<()> {
+        if unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pgid) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn terminate(pid: u32) -> io::Result<()> {
+        unsafe {
+            let handle = OpenProcess(PROCESS_TERMINATE, 0, pid);
+            if handle == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let ok = TerminateProcess(handle, 1);
+            CloseHandle(handle);
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Terminates `pid` and everything it started. Killing only the process
+    /// would leave its children running, orphaned, with no way to name them
+    /// as a job any more.
+    pub fn terminate_tree(pid: u32) -> io::Result<()> {
+        let descendants = descendants(pid);
+        let result = terminate(pid);
+        for child in descendants {
+            let _ = terminate(child);
+        }
+        result
+    }
+
+    /// Records what `child`, which has exited, used for `time`.
+    pub fn record_usage(child: &std::process::Child) {
+        use std::os::windows::io::AsRawHandle;
+        let handle = child.as_raw_handle() as HANDLE;
+        let zero = FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 };
+        let (mut created, mut exited, mut kernel, mut user) = (zero, zero, zero, zero);
+        let mut memory: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+        memory.cb = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+        unsafe {
+            if GetProcessTimes(handle, &mut created, &mut exited, &mut kernel, &mut user) == 0 {
+                return;
+            }
+            let peak = (GetProcessMemoryInfo(handle, &mut memory, memory.cb) != 0).then_some(memory.PeakWorkingSetSize as u64);
+            crate::timecmd::record(crate::timecmd::Usage {
+                user: crate::timecmd::filetime(user),
+                sys: crate::timecmd::filetime(kernel),
+                peak,
+            });
+        }
+    }
+
+    /// When a process was started, as a number that orders start times.
+    fn started(pid: u32) -> Option<u64> {
+        unsafe {
+            let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if handle == 0 {
+                return None;
+            }
+            let zero = FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 };
+            let (mut created, mut exited, mut kernel, mut user) = (zero, zero, zero, zero);
+            let ok = GetProcessTimes(handle, &mut created, &mut exited, &mut kernel, &mut user);
+            CloseHandle(handle);
+            (ok != 0).then(|| (u64::from(created.dwHighDateTime) << 32) | u64::from(created.dwLowDateTime))
+        }
+    }
+
+    /// Every process descended from `pid`, parents before their children.
+    fn descendants(pid: u32) -> Vec<u32> {
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        unsafe {
+            let snapshot: HANDLE = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return Vec::new();
+            }
+            let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+            let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+            while more {
+                children.entry(entry.th32ParentProcessID).or_default().push(entry.th32ProcessID);
+                more = Process32NextW(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+        }
+        // Windows reuses process IDs, and a process keeps its parent's ID
+        // after the parent exits, so a "child" that started before its
+        // parent belongs to some earlier process with the same ID.
+        let mut found = Vec::new();
+        let mut queue = vec![(pid, started(pid))];
+        while let Some((parent, parent_started)) = queue.pop() {
+            for &child in children.get(&parent).into_iter().flatten() {
+                if child == parent || found.contains(&child) {
+                    continue;
+                }
+                let child_started = started(child);
+                if let (Some(parent), Some(child)) = (parent_started, child_started)
+                    && child < parent
+                {
+                    continue;
+                }
+                found.push(child);
+                queue.push((child, child_started));
+            }
+        }
+        found
+    }
+}
+
+fn parse_arg(args: &[&str]) -> Result<Option<Spec>, String> {
+    args.first().map(|arg| parse_spec(arg)).transpose()
+}
+
+/// `fg [job]`
+pub fn fg(table: &JobTable, args: &[&str]) -> i32 {
+    refresh(table);
+    let result = parse_arg(args).and_then(|spec| {
+        #[cfg(unix)]
+        {
+            unix::foreground(table, spec.unwrap_or(Spec::Current))
+        }
+        #[cfg(windows)]
+        {
+            let _ = spec;
+            Err(msg!("jobs.unsupported"))
+        }
+    });
+    result.unwrap_or_else(|e| {
+        println!("fg: {}", e);
+        1
+    })
+}
+
+/// `bg [job]`
+pub fn bg(table: &JobTable, args: &[&str]) -> i32 {
+    refresh(table);
+    let result = parse_arg(args).and_then(|spec| {
+        #[cfg(unix)]
+        {
+            unix::background(table, spec)
+        }
+        #[cfg(windows)]
+        {
+            let _ = spec;
+            Err(msg!("jobs.unsupported"))
+        }
+    });
+    result.unwrap_or_else(|e| {
+        println!("bg: {}", e);
+        1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_job_is_done_when_all_its_processes_are() {
+        assert_eq!(combined(&[JobState::Done(0), JobState::Running]), JobState::Running);
+        assert_eq!(combined(&[JobState::Running, JobState::Done(3)]), JobState::Running);
+        assert_eq!(combined(&[JobState::Done(1), JobState::Done(3)]), JobState::Done(3));
+    }
+
+    #[test]
+    fn a_job_is_stopped_while_any_process_is() {
+        assert_eq!(combined(&[JobState::Done(0), JobState::Stopped, JobState::Running]), JobState::Stopped);
+    }
+}
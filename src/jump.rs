@@ -0,0 +1,166 @@
+//! Frecency-based directory jumping: the `j` builtin.
+//!
+//! Every directory the shell changes into is recorded in a small database
+//! (`jump` in the data directory, one `path|rank|time` line per directory,
+//! the format of `z`). Each visit adds one to a directory's rank; when the
+//! ranks add up to more than [`MAX_TOTAL`], they all age, and directories
+//! that are rarely visited drop out. A directory's score is its rank
+//! weighted by how recently it was visited.
+//!
+//! `j proj api` jumps to the highest-scoring directory whose path contains
+//! `proj` and then `api`; `j -l` lists the candidates instead. Matching
+//! ignores case unless a fragment has an uppercase letter.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, Config};
+use crate::messages::msg;
+use crate::term;
+
+/// When the ranks add up to more than this, they are all aged.
+const MAX_TOTAL: f64 = 9000.0;
+
+struct Entry {
+    path: PathBuf,
+    rank: f64,
+    /// Seconds since the epoch of the last visit.
+    time: u64,
+}
+
+fn database() -> PathBuf {
+    config::data_dir().join("jump")
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn load() -> Vec<Entry> {
+    let text = fs::read_to_string(database()).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '|');
+            let time = fields.next()?.parse().ok()?;
+            let rank = fields.next()?.parse().ok()?;
+            Some(Entry { path: PathBuf::from(fields.next()?), rank, time })
+        })
+        .collect()
+}
+
+fn save(entries: &[Entry]) {
+    let text: String =
+        entries.iter().map(|e| format!("{}|{}|{}\n", e.path.display(), e.rank, e.time)).collect();
+    let path = database();
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    // Written whole and renamed, so another shell never reads half a file.
+    let partial = path.with_extension(format!("{}", std::process::id()));
+    if fs::write(&partial, text).is_ok() {
+        let _ = fs::rename(&partial, &path);
+    }
+}
+
+/// Whether directories are recorded (`jump.track`, on by default).
+pub fn tracking(config: &Config) -> bool {
+    config.get_bool("jump.track").unwrap_or(true)
+}
+
+/// Records a visit to `dir`. The home directory isn't worth recording.
+pub fn visit(dir: &Path) {
+    if config::home_dir().is_some_and(|home| home == dir) {
+        return;
+    }
+    let mut entries = load();
+    let time = now();
+    match entries.iter_mut().find(|e| e.path == dir) {
+        Some(entry) => {
+            entry.rank += 1.0;
+            entry.time = time;
+        }
+        None => entries.push(Entry { path: dir.to_path_buf(), rank: 1.0, time }),
+    }
+    if entries.iter().map(|e| e.rank).sum::<f64>() > MAX_TOTAL {
+        for entry in &mut entries {
+            entry.rank *= 0.99;
+        }
+        entries.retain(|e| e.rank >= 1.0);
+    }
+    save(&entries);
+}
+
+/// Rank weighted by recency: visits in the last hour count four times,
+/// in the last day twice, and older than a week a quarter.
+fn frecency(entry: &Entry, now: u64) -> f64 {
+    match now.saturating_sub(entry.time) {
+        age if age < 3600 => entry.rank * 4.0,
+        age if age < 86400 => entry.rank * 2.0,
+        age if age < 604800 => entry.rank / 2.0,
+        _ => entry.rank / 4.0,
+    }
+}
+
+/// Whether `path` contains every fragment, in order.
+fn matches(path: &str, fragments: &[&str]) -> bool {
+    let fold = !fragments.iter().any(|f| f.chars().any(char::is_uppercase));
+    let path = if fold { path.to_lowercase() } else { path.to_string() };
+    let mut rest = path.as_str();
+    for fragment in fragments {
+        match rest.find(fragment) {
+            Some(i) => rest = &rest[i + fragment.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Directories matching `fragments`, best first, with their scores.
+/// Directories that no longer exist are dropped from the database.
+fn candidates(fragments: &[&str]) -> Vec<(PathBuf, f64)> {
+    let mut entries = load();
+    let before = entries.len();
+    entries.retain(|e| e.path.is_dir());
+    if entries.len() != before {
+        save(&entries);
+    }
+    let time = now();
+    let mut found: Vec<(PathBuf, f64)> = entries
+        .iter()
+        .filter(|e| matches(&e.path.to_string_lossy(), fragments))
+        .map(|e| (e.path.clone(), frecency(e, time)))
+        .collect();
+    found.sort_by(|a, b| b.1.total_cmp(&a.1));
+    found
+}
+
+/// `j [-l] [fragment...]`: jumps to the best match, or lists the matches
+/// with their scores (also without fragments). Returns the directory to
+/// change to, if any, and the status.
+pub fn run(args: &[&str]) -> (Option<PathBuf>, i32) {
+    let (list, fragments) = match args {
+        ["-l", rest @ ..] => (true, rest),
+        rest => (rest.is_empty(), rest),
+    };
+    if fragments.iter().any(|f| f.starts_with('-')) {
+        println!("{}", msg!("usage.j"));
+        return (None, 2);
+    }
+    let found = candidates(fragments);
+    if found.is_empty() && fragments.is_empty() {
+        println!("j: {}", msg!("jump.empty"));
+        return (None, 1);
+    }
+    if found.is_empty() {
+        println!("j: {}", msg!("jump.no_match", fragments.join(" ")));
+        return (None, 1);
+    }
+    if list {
+        for (path, score) in found.iter().rev() {
+            println!("{:>8.1}  {}", score, term::display_path(path));
+        }
+        return (None, 0);
+    }
+    (found.into_iter().next().map(|(path, _)| path), 0)
+}
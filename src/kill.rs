@@ -0,0 +1,74 @@
+use crate::jobs::{self, JobTable, Spec};
+use crate::signals;
+
+const USAGE: &str = "Usage: kill [-s signal | -signal] <pid|%job>... | kill -l";
+
+fn list_signals() {
+    for (name, number) in signals::list() {
+        println!("{:>2}) SIG{}", number, name);
+    }
+}
+
+/// `kill [-s signal | -signal] <pid|%job>...`
+///
+/// Sends `signal` (TERM by default, as a name like `HUP`/`SIGHUP` or a
+/// number) to each target. Job specs signal the job's whole process group;
+/// a PID can name any process, not only the shell's own jobs. `kill -l`
+/// lists the signal names.
+pub fn run(table: &JobTable, args: &[&str]) -> i32 {
+    let mut args = args;
+    let mut signal = signals::SIGTERM;
+    match args.first() {
+        Some(&"-l") => {
+            list_signals();
+            return 0;
+        }
+        Some(&"-s") | Some(&"-n") => {
+            let Some(parsed) = args.get(1).and_then(|s| signals::parse(s)) else {
+                println!("kill: invalid signal: {}", args.get(1).unwrap_or(&""));
+                return 2;
+            };
+            signal = parsed;
+            args = &args[2..];
+        }
+        Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+            let Some(parsed) = signals::parse(&arg[1..]) else {
+                println!("kill: invalid signal: {}", &arg[1..]);
+                return 2;
+            };
+            signal = parsed;
+            args = &args[1..];
+        }
+        _ => {}
+    }
+    if args.is_empty() {
+        println!("{}", USAGE);
+        return 2;
+    }
+
+    jobs::refresh(table);
+    let mut status = 0;
+    for arg in args {
+        let result = match jobs::parse_spec(arg) {
+            Ok(Spec::Pid(pid)) => Some(jobs::send_signal(pid, signal, false)),
+            Ok(spec) => jobs::signal_job(table, spec, signal),
+            Err(e) => {
+                println!("kill: {}", e);
+                status = 1;
+                continue;
+            }
+        };
+        match result {
+            Some(Ok(())) => {}
+            Some(Err(e)) => {
+                println!("kill: {}: {}", arg, e);
+                status = 1;
+            }
+            None => {
+                println!("kill: {}: no such job", arg);
+                status = 1;
+            }
+        }
+    }
+    status
+}
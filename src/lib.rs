@@ -0,0 +1,11 @@
+//! Parts of octane that other programs can build on.
+//!
+//! The `octane` binary is built on these too: [`builtins`] is the
+//! registry of builtin commands, which code built into the shell can add
+//! to, [`history`] holds the stores the shell keeps its history in, so
+//! other programs can read or add to it, and [`repl`] drives a shell from
+//! another program.
+
+pub mod builtins;
+pub mod history;
+pub mod repl;
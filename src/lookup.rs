@@ -0,0 +1,365 @@
+//! What a command name refers to, for the `type`, `which` and `command`
+//! builtins.
+//!
+//! The shell expands an alias first, then runs a builtin, a function, or a
+//! program from `PATH`, in that order. `type` reports which of these a name
+//! is (`-a` lists every match, shadowed ones included), `which` prints the
+//! same in a compact form, and `command name args...` runs `name` without
+//! its function, so a function can wrap the program it is named after.
+//!
+//! Programs in `PATH` are looked up in a table built by listing every `PATH`
+//! directory once, so running a command or checking that one exists doesn't
+//! search the directories again. The table is rebuilt when `PATH` changes, a
+//! directory in it changes (a program was installed or removed), or with
+//! `rehash`.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use rustshell::builtins;
+
+use crate::condition;
+use crate::messages::msg;
+
+/// One thing a name can refer to.
+pub enum Kind {
+    Alias(String),
+    Builtin,
+    /// A shell function, with its body.
+    Function(String),
+    /// A program, with its path.
+    File(PathBuf),
+}
+
+impl Kind {
+    /// The word `type -t` prints.
+    fn word(&self) -> &'static str {
+        match self {
+            Kind::Alias(_) => "alias",
+            Kind::Builtin => "builtin",
+            Kind::Function(_) => "function",
+            Kind::File(_) => "file",
+        }
+    }
+}
+
+/// Extensions of programs on Windows, from `PATHEXT`, lowercased.
+fn extensions() -> Vec<String> {
+    let extensions = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    extensions.split(';').filter(|e| !e.is_empty()).map(str::to_lowercase).collect()
+}
+
+/// Names `name` could be found under in a directory: itself, and on Windows
+/// with each extension from `PATHEXT`.
+fn candidates(name: &str) -> Vec<String> {
+    let mut names = vec![name.to_string()];
+    if cfg!(windows) && Path::new(name).extension().is_none() {
+        names.extend(extensions().into_iter().map(|e| format!("{}{}", name, e)));
+    }
+    names
+}
+
+fn is_program(path: &Path) -> bool {
+    path.is_file() && condition::executable(&path.to_string_lossy())
+}
+
+/// The programs in `PATH` by name, and what they were listed from.
+struct Cache {
+    path: OsString,
+    /// Each directory with when it last changed.
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+    programs: HashMap<String, PathBuf>,
+}
+
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+fn modified(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(dir).and_then(|m| m.modified()).ok()
+}
+
+/// How a program is found in the table: Windows names are case-insensitive.
+fn key(name: &str) -> String {
+    if cfg!(windows) { name.to_lowercase() } else { name.to_string() }
+}
+
+/// Lists the programs in each directory of `path`; the first directory
+/// with a name wins, as when the OS searches `PATH`. On Windows a program
+/// is found with or without its extension, the one earliest in `PATHEXT`
+/// winning within a directory. Relative directories depend on the working
+/// directory, so they are left to [`find_program`] to search each time.
+fn scan(path: OsString) -> Cache {
+    let extensions = if cfg!(windows) { extensions() } else { Vec::new() };
+    let mut dirs = Vec::new();
+    let mut programs = HashMap::new();
+    for dir in env::split_paths(&path).filter(|dir| dir.is_absolute()) {
+        dirs.push((dir.clone(), modified(&dir)));
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<(usize, String, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let rank = if cfg!(windows) {
+                    let extension = Path::new(&name).extension()?.to_string_lossy().to_lowercase();
+                    extensions.iter().position(|e| e[1..] == extension)?
+                } else {
+                    0
+                };
+                let path = entry.path();
+                is_program(&path).then_some((rank, name, path))
+            })
+            .collect();
+        found.sort_by_key(|(rank, _, _)| *rank);
+        for (_, name, path) in found {
+            if cfg!(windows)
+                && let Some(stem) = Path::new(&name).file_stem()
+            {
+                programs.entry(key(&stem.to_string_lossy())).or_insert_with(|| path.clone());
+            }
+            programs.entry(key(&name)).or_insert(path);
+        }
+    }
+    Cache { path, dirs, programs }
+}
+
+/// Forgets the table of programs, for `rehash`. It is listed again at the
+/// next lookup.
+pub fn rehash() -> i32 {
+    *CACHE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    0
+}
+
+/// The table for `path`, listed again if it is out of date.
+fn table(path: &OsString) -> MutexGuard<'static, Option<Cache>> {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let stale = cache
+        .as_ref()
+        .is_none_or(|cache| cache.path != *path || cache.dirs.iter().any(|(dir, time)| modified(dir) != *time));
+    if stale {
+        *cache = Some(scan(path.clone()));
+    }
+    cache
+}
+
+/// The program `name` runs, if any.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    if name.contains(std::path::is_separator) {
+        return find_programs(name).into_iter().next();
+    }
+    let path = env::var_os("PATH")?;
+    let found = table(&path).as_ref()?.programs.get(&key(name)).cloned();
+    found.or_else(|| {
+        // Only relative directories are left to search.
+        env::split_paths(&path).any(|dir| !dir.is_absolute()).then(|| find_programs(name).into_iter().next())?
+    })
+}
+
+/// The names of every program in `PATH`.
+pub fn program_names() -> Vec<String> {
+    let Some(path) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    table(&path).as_ref().map(|cache| cache.programs.keys().cloned().collect()).unwrap_or_default()
+}
+
+/// A `Command` for `name`, started from the path in the table so the OS
+/// doesn't search `PATH` again. `argv[0]` is still `name`.
+pub fn command_for(name: &str) -> Command {
+    match find_program(name).filter(|_| !name.contains(std::path::is_separator)) {
+        #[cfg(unix)]
+        Some(path) => {
+            use std::os::unix::process::CommandExt;
+            let mut command = Command::new(path);
+            command.arg0(name);
+            command
+        }
+        #[cfg(windows)]
+        Some(path) => Command::new(path),
+        None => Command::new(name),
+    }
+}
+
+/// Every program `name` refers to, in `PATH` order. A name with a path
+/// separator is only checked as given.
+pub fn find_programs(name: &str) -> Vec<PathBuf> {
+    if name.contains(std::path::is_separator) {
+        return candidates(name).into_iter().map(PathBuf::from).filter(|p| is_program(p)).take(1).collect();
+    }
+    let Some(path) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for dir in env::split_paths(&path) {
+        let dir = if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir };
+        if let Some(program) = candidates(name).into_iter().map(|n| dir.join(n)).find(|p| is_program(p)) {
+            found.push(program);
+        }
+    }
+    found
+}
+
+/// What `name` refers to, in the order the shell tries them. Only the first
+/// match unless `all` is set.
+pub fn resolve(
+    name: &str,
+    aliases: &HashMap<String, String>,
+    functions: &HashMap<String, String>,
+    all: bool,
+) -> Vec<Kind> {
+    let mut kinds = Vec::new();
+    if let Some(value) = aliases.get(name) {
+        kinds.push(Kind::Alias(value.clone()));
+    }
+    if builtins::get(name).is_some() {
+        kinds.push(Kind::Builtin);
+    }
+    if let Some(body) = functions.get(name) {
+        kinds.push(Kind::Function(body.clone()));
+    }
+    if all {
+        kinds.extend(find_programs(name).into_iter().map(Kind::File));
+    } else if kinds.is_empty() {
+        kinds.extend(find_program(name).map(Kind::File));
+    }
+    if !all {
+        kinds.truncate(1);
+    }
+    kinds
+}
+
+/// Splits leading single-letter flags (`-a`, `-tp`) from the names.
+fn flags<'a>(args: &'a [&'a str], known: &str) -> Result<(String, &'a [&'a str]), String> {
+    let mut flags = String::new();
+    let mut rest = args;
+    while let Some((first, tail)) = rest.split_first() {
+        let Some(letters) = first.strip_prefix('-').filter(|l| !l.is_empty()) else {
+            break;
+        };
+        rest = tail;
+        if letters == "-" {
+            break;
+        }
+        match letters.chars().find(|c| !known.contains(*c)) {
+            Some(bad) => return Err(format!("-{}", bad)),
+            None => flags.push_str(letters),
+        }
+    }
+    Ok((flags, rest))
+}
+
+fn describe(name: &str, kind: &Kind) {
+    match kind {
+        Kind::Alias(value) => println!("{}", msg!("lookup.alias", name, value)),
+        Kind::Builtin => println!("{}", msg!("lookup.builtin", name)),
+        Kind::Function(body) => {
+            println!("{}", msg!("lookup.function", name));
+            println!("{}() {{ {} }}", name, body.trim());
+        }
+        Kind::File(path) => println!("{}", msg!("lookup.file", name, path.display())),
+    }
+}
+
+/// `type [-a] [-t|-p] name...`: says what each name is. `-t` prints just
+/// `alias`, `builtin`, `function` or `file`, `-p` just a program's path.
+pub fn type_of(args: &[&str], aliases: &HashMap<String, String>, functions: &HashMap<String, String>) -> i32 {
+    let (flags, names) = match flags(args, "atp") {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("type: {}", msg!("lookup.bad_option", e));
+            println!("{}", msg!("usage.type"));
+            return 2;
+        }
+    };
+    if names.is_empty() {
+        println!("{}", msg!("usage.type"));
+        return 2;
+    }
+    let mut status = 0;
+    for name in names {
+        let kinds = resolve(name, aliases, functions, flags.contains('a'));
+        if kinds.is_empty() {
+            if !flags.contains('t') && !flags.contains('p') {
+                println!("type: {}", msg!("lookup.not_found", name));
+            }
+            status = 1;
+        }
+        for kind in &kinds {
+            if flags.contains('t') {
+                println!("{}", kind.word());
+            } else if flags.contains('p') {
+                if let Kind::File(path) = kind {
+                    println!("{}", path.display());
+                }
+            } else {
+                describe(name, kind);
+            }
+        }
+    }
+    status
+}
+
+/// `which [-a] name...`: prints the program each name runs, or what it is
+/// when the shell handles it itself.
+pub fn which(args: &[&str], aliases: &HashMap<String, String>, functions: &HashMap<String, String>) -> i32 {
+    let (flags, names) = match flags(args, "a") {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("which: {}", msg!("lookup.bad_option", e));
+            println!("{}", msg!("usage.which"));
+            return 2;
+        }
+    };
+    if names.is_empty() {
+        println!("{}", msg!("usage.which"));
+        return 2;
+    }
+    let mut status = 0;
+    for name in names {
+        let kinds = resolve(name, aliases, functions, flags.contains('a'));
+        if kinds.is_empty() {
+            println!("which: {}", msg!("lookup.not_found", name));
+            status = 1;
+        }
+        for kind in kinds {
+            match kind {
+                Kind::Alias(value) => println!("{}", msg!("lookup.which_alias", name, value)),
+                Kind::Builtin => println!("{}", msg!("lookup.which_builtin", name)),
+                Kind::Function(_) => println!("{}", msg!("lookup.which_function", name)),
+                Kind::File(path) => println!("{}", path.display()),
+            }
+        }
+    }
+    status
+}
+
+/// `command -v name` prints how the shell would run `name`: a program's
+/// path, an alias definition, or just the name; `command -V name` is `type`.
+/// (`command name args...` itself is handled where commands run.)
+pub fn command(args: &[&str], aliases: &HashMap<String, String>, functions: &HashMap<String, String>) -> i32 {
+    match args.split_first() {
+        Some((&"-V", names)) if !names.is_empty() => type_of(names, aliases, functions),
+        Some((&"-v", names)) if !names.is_empty() => {
+            let mut status = 0;
+            for name in names {
+                match resolve(name, aliases, functions, false).first() {
+                    Some(Kind::Alias(value)) => println!("alias {}='{}'", name, value),
+                    Some(Kind::File(path)) => println!("{}", path.display()),
+                    Some(_) => println!("{}", name),
+                    None => status = 1,
+                }
+            }
+            status
+        }
+        _ => {
+            println!("{}", msg!("usage.command"));
+            2
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Detects whether the shell runs inside a container, WSL, or a virtual
+//! machine, for the prompt's context badge.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+static BADGE: OnceLock<Option<String>> = OnceLock::new();
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+fn container() -> Option<String> {
+    if Path::new("/.dockerenv").exists() {
+        return Some("docker".to_string());
+    }
+    if Path::new("/run/.containerenv").exists() {
+        return Some("podman".to_string());
+    }
+    // Set by systemd-nspawn, LXC, Podman and others for PID 1.
+    if let Ok(name) = env::var("container")
+        && !name.is_empty()
+    {
+        return Some(name);
+    }
+    if env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        return Some("k8s".to_string());
+    }
+    let cgroup = read("/proc/1/cgroup");
+    [
+        ("kubepods", "k8s"),
+        ("docker", "docker"),
+        ("libpod", "podman"),
+        ("lxc", "lxc"),
+        ("containerd", "container"),
+    ]
+    .iter()
+    .find(|(marker, _)| cgroup.contains(marker))
+    .map(|(_, name)| name.to_string())
+}
+
+fn wsl() -> Option<String> {
+    if let Ok(distro) = env::var("WSL_DISTRO_NAME") {
+        return Some(format!("wsl:{}", distro));
+    }
+    let release = read("/proc/sys/kernel/osrelease").to_lowercase();
+    (release.contains("microsoft") || release.contains("wsl")).then(|| "wsl".to_string())
+}
+
+/// Names the hypervisor from the DMI vendor strings, falling back to the
+/// CPU's `hypervisor` flag.
+fn virtual_machine() -> Option<String> {
+    let dmi = format!(
+        "{} {} {}",
+        read("/sys/class/dmi/id/sys_vendor"),
+        read("/sys/class/dmi/id/product_name"),
+        read("/sys/class/dmi/id/bios_vendor"),
+    )
+    .to_lowercase();
+    let known = [
+        ("qemu", "qemu"),
+        ("kvm", "kvm"),
+        ("vmware", "vmware"),
+        ("virtualbox", "virtualbox"),
+        ("innotek", "virtualbox"),
+        ("xen", "xen"),
+        ("parallels", "parallels"),
+        ("amazon ec2", "ec2"),
+        ("google compute engine", "gce"),
+        ("virtual machine", "hyper-v"),
+    ];
+    if let Some((_, name)) = known.iter().find(|(marker, _)| dmi.contains(marker)) {
+        return Some(name.to_string());
+    }
+    read("/proc/cpuinfo")
+        .lines()
+        .any(|l| l.starts_with("flags") && l.split_whitespace().any(|f| f == "hypervisor"))
+        .then(|| "vm".to_string())
+}
+
+/// A short name for the environment the shell runs in (`docker`, `wsl`,
+/// `qemu`, ...), or None on bare metal. Detected once per session; a
+/// container is reported in preference to the VM hosting it.
+pub fn badge() -> Option<&'static str> {
+    BADGE
+        .get_or_init(|| {
+            if !cfg!(target_os = "linux") {
+                return None;
+            }
+            container().or_else(wsl).or_else(virtual_machine)
+        })
+        .as_deref()
+}
@@ -1,239 +1,1888 @@
 use std::collections::HashMap;
 use std::env;
-use std::io::{self, Write};
-use std::process::{Command, Child};
-use git2::Repository;
-use colored::*;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Instant, SystemTime};
 
-use std::sync::{Arc, Mutex};
+mod abbr;
+mod agent;
+mod alias;
+mod bulkrename;
+mod bundle;
+mod calc;
+mod capture;
+mod collate;
+mod clock;
+mod cloud;
+mod cmdhooks;
+mod complete;
+mod condition;
+mod config;
+mod containers;
+mod demo;
+mod dirhooks;
+mod dirstack;
+mod dotenv;
+mod dotfiles;
+mod editline;
+mod envfile;
+mod exec;
+mod fc;
+mod filter;
+mod focus;
+mod functions;
+mod helpcmd;
+mod hint;
+mod histcmd;
+mod histexpand;
+mod histpolicy;
+mod ifs;
+mod jobs;
+mod jump;
+mod kill;
+mod lookup;
+mod machine;
+mod messages;
+mod notify;
+mod pager;
+mod parallel;
+mod penv;
+mod print;
+mod procsub;
+mod picker;
+mod pipeline;
+mod profile;
+mod prompt;
+mod queue;
+mod redact;
+mod regex;
+mod record;
+mod reload;
+mod relay;
+mod remote;
+mod secret;
+mod session;
+mod shutdown;
+mod signals;
+mod slowfs;
+mod spawn;
+mod stamp;
+mod suggest;
+mod syntax;
+mod ssh;
+mod sshm;
+mod term;
+mod theme;
+mod timecmd;
+mod took;
+mod vars;
+mod watch;
+mod weather;
+mod wizard;
+mod words;
 
-fn get_git_branch() -> Option<(String, bool)> {
-    let repo = Repository::discover(".").ok()?;
-    let head = repo.head().ok()?;
-    if !head.is_branch() {
-        return None;
-    }
-    let branch_name = head.shorthand()?.to_string();
-
-    let statuses = repo.statuses(None).ok()?;
-    let dirty = statuses.iter().any(|entry| {
-        let s = entry.status();
-        s.is_index_new() || s.is_index_modified() || s.is_wt_modified() || s.is_wt_new()
-    });
+use complete::ShellHelper;
+use config::Config;
+use messages::msg;
+use profile::Profile;
+use shutdown::JobPolicy;
+use redact::Redactor;
+use rustshell::builtins::{self, Builtin};
+use rustshell::history::{FileHistory, HistoryStore, SyncedHistory};
+use rustshell::repl;
+use rustyline::config::Configurer;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, EditMode, Editor, Event, EventHandler, KeyEvent};
+use theme::Theme;
 
-    Some((branch_name, dirty))
-}
+const MAX_SOURCE_DEPTH: usize = 32;
+/// Status of a command killed by Ctrl-C (128 + SIGINT).
+const INTERRUPTED: i32 = 130;
+/// `$0` outside a script.
+const SHELL_NAME: &str = "octane";
 
-fn expand_env_vars(input: &str) -> String {
+/// Expands `$NAME`, `$?`, the positional parameters `$0`-`$9`, `$#`, `$@`
+/// and `$*`, and `$__`, the last program's output as a word per line.
+/// Results are split into words on `ifs`, or kept as one word each when it
+/// is `None` or they are in double quotes; see the `ifs` module. Nothing in
+/// single quotes or after a backslash is expanded, and the quotes are left
+/// for `words` to remove.
+fn expand_env_vars(input: &str, shell: &Shell, ifs: Option<&str>) -> String {
     let mut result = String::new();
     let mut chars = input.chars().peekable();
+    let mut quote = None;
 
     while let Some(c) = chars.next() {
-        if c == '$' {
-            let mut var = String::new();
-            while let Some(&ch) = chars.peek() {
-                if ch.is_alphanumeric() || ch == '_' {
-                    var.push(ch);
-                    chars.next();
-                } else {
-                    break;
+        match (c, quote) {
+            (c, Some(q)) if c == q => quote = None,
+            ('\'' | '"', None) => quote = Some(c),
+            ('\\', None | Some('"')) => {
+                result.push(c);
+                result.extend(chars.next());
+                continue;
+            }
+            _ => {}
+        }
+        if c != '$' || quote == Some('\'') {
+            result.push(c);
+            continue;
+        }
+        let ifs = if quote.is_some() { None } else { ifs };
+        match chars.peek() {
+            Some('?') => {
+                chars.next();
+                result.push_str(&shell.last_status.to_string());
+                continue;
+            }
+            Some('#') => {
+                chars.next();
+                result.push_str(&shell.positional.len().to_string());
+                continue;
+            }
+            // Every parameter is a word of its own, even `"$@"` in quotes.
+            Some(&special @ ('@' | '*')) => {
+                chars.next();
+                let words: Vec<String> = shell.positional.iter().map(|arg| ifs::substitute(arg, ifs)).collect();
+                let separator = if quote.is_some() && special == '@' { "\" \"" } else { " " };
+                result.push_str(&words.join(separator));
+                continue;
+            }
+            // `$10` is `$1` followed by `0`, as in other shells.
+            Some(&digit @ '0'..='9') => {
+                chars.next();
+                let value = match digit.to_digit(10).unwrap() as usize {
+                    0 => Some(&shell.script_name),
+                    n => shell.positional.get(n - 1),
+                };
+                if let Some(value) = value {
+                    result.push_str(&ifs::substitute(value, ifs));
                 }
+                continue;
             }
-            if let Ok(val) = env::var(&var) {
-                result.push_str(&val);
+            _ => {}
+        }
+        let mut var = String::new();
+        while let Some(&ch) = chars.peek() {
+            if ch.is_alphanumeric() || ch == '_' {
+                var.push(ch);
+                chars.next();
             } else {
-                result.push_str(&format!("${}", var));
+                break;
             }
+        }
+        if var == "__"
+            && let Some(val) = shell.variables.get("__")
+        {
+            // Every line of the last output is a word of its own.
+            let words: Vec<String> = val.lines().map(|line| ifs::substitute(line, ifs)).collect();
+            result.push_str(&words.join(" "));
+        } else if let Some(val) = vars::lookup(&shell.variables, &var) {
+            result.push_str(&ifs::substitute(&val, ifs));
         } else {
-            result.push(c);
+            result.push_str(&format!("${}", var));
         }
     }
 
     result
 }
 
-fn run_builtin(cmd: &str, args: &[&str], bg_processes: &Arc<Mutex<HashMap<u32, Child>>>) -> bool {
-    match cmd {
-        "calc" => {
-            if args.is_empty() {
-                println!("Usage: calc <expression>");
-                return true;
-            }
-            let expression = args.join(" ");
-            match meval::eval_str(&expression) {
-                Ok(result) => println!("{}", result),
-                Err(e) => println!("Error evaluating expression: {}", e),
-            }
-            true
+/// How a command in a list is joined to the one before it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Connector {
+    /// First command, or after `;`: always runs.
+    Always,
+    /// After `&&`: runs if the previous command succeeded.
+    And,
+    /// After `||`: runs if the previous command failed.
+    Or,
+}
+
+impl Connector {
+    /// Whether the command runs, given the status of the one before it.
+    fn should_run(self, last_status: i32) -> bool {
+        match self {
+            Connector::Always => true,
+            Connector::And => last_status == 0,
+            Connector::Or => last_status != 0,
         }
-        "exit" => {
-            std::process::exit(0);
+    }
+}
+
+/// Splits a line on `&&`, `||` and `;` into the commands to run and how each
+/// one depends on the status of the previous command. Quoted text and
+/// blocks such as `{ ...; }` groups and `if ...; fi` stay in one piece.
+fn split_command_list(input: &str) -> Vec<(Connector, String)> {
+    let mut list = Vec::new();
+    let mut connector = Connector::Always;
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut changes = syntax::depth_changes(input).into_iter().peekable();
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if let Some((_, change)) = changes.next_if(|(at, _)| *at == i) {
+            depth += change;
         }
-        "cd" => {
-            let new_dir = args.get(0).cloned().unwrap_or(".");
-            if let Err(e) = env::set_current_dir(new_dir) {
-                println!("Error: {}", e);
+        match (c, quote) {
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('\'' | '"', None) => quote = Some(c),
+            _ => {}
+        }
+        let next = match (c, chars.peek()) {
+            _ if depth > 0 || quote.is_some() => None,
+            ('&', Some((_, '&'))) => Some(Connector::And),
+            ('|', Some((_, '|'))) => Some(Connector::Or),
+            (';', _) => Some(Connector::Always),
+            _ => None,
+        };
+        match next {
+            Some(next) => {
+                if c != ';' {
+                    chars.next();
+                }
+                list.push((connector, current.trim().to_string()));
+                current.clear();
+                connector = next;
             }
-            true
+            None => current.push(c),
         }
-        "pwd" => {
-            match env::current_dir() {
-                Ok(path) => println!("{}", path.display()),
-                Err(e) => println!("Error: {}", e),
+    }
+    list.push((connector, current.trim().to_string()));
+    list.retain(|(_, command)| !command.is_empty());
+    list
+}
+
+/// State shared by the main loop and the builtins.
+struct Shell {
+    config: Config,
+    theme: Theme,
+    /// Active prompt profile, switched with `prompt use`.
+    profile: Profile,
+    /// Set while `demo on` is active.
+    demo: Option<demo::Demo>,
+    aliases: HashMap<String, String>,
+    /// Shell variables, which commands don't inherit until exported.
+    variables: HashMap<String, String>,
+    jobs: jobs::JobTable,
+    /// Exit status of the last command, exposed as `$?`.
+    last_status: i32,
+    /// Shell functions by name, holding the text of their bodies.
+    functions: HashMap<String, String>,
+    /// Nesting of `source` and function calls, to stop runaway recursion.
+    source_depth: usize,
+    /// Set by `return`, `break` or `continue` while the commands up to the
+    /// function, file or loop they leave are skipped.
+    unwinding: Option<Unwind>,
+    /// Nesting of loops in the running function or file, for `break`.
+    loop_depth: usize,
+    /// Set by `set -e` (or `octane -e`): scripts stop at the first failure.
+    errexit: bool,
+    /// `$0`: the script being run, or the shell itself.
+    script_name: String,
+    /// Arguments of the running script, `source`d file, or `-c` commands:
+    /// `$1`, `$2`, ...
+    positional: Vec<String>,
+    /// Set by `set -o posix` (or `shell.posix`): classic behavior where
+    /// octane's defaults differ, such as splitting `$NAME` on whitespace.
+    posix: bool,
+    /// Driven by a `Repl`, whose commands arrive on stdin, so commands the
+    /// shell starts must not read it.
+    embedded: bool,
+    /// Reading commands at a prompt.
+    interactive: bool,
+    /// Where history is kept; `None` in scripts or with `history.backend = "none"`.
+    history: Option<Box<dyn HistoryStore>>,
+    /// The line editor's history, oldest first, as `history` lists it.
+    history_entries: Vec<String>,
+    /// Set by `history` when it changed the entries, so the line editor
+    /// takes them over.
+    history_edited: bool,
+    /// Actions set with `trap`, by condition.
+    traps: HashMap<String, String>,
+    /// Set when `exit` warned about jobs; the next `exit` leaves anyway.
+    exit_warned: bool,
+    /// Set by `set -o huponexit` (or `jobs.huponexit`): jobs are hung up
+    /// whenever the shell exits, not only when the terminal goes away.
+    huponexit: bool,
+    /// Set by `set -o autocd` (or `shell.autocd`): a directory typed as a
+    /// command is changed into.
+    autocd: bool,
+    /// Set by `set -o timestamps` (or `shell.timestamps`), or by `ts` for
+    /// one command: output lines are timestamped.
+    timestamps: Option<stamp::Stamping>,
+    /// Set by `set -o pipetags` (or `shell.pipetags`): the output of
+    /// pipelines is tagged with the command that wrote it.
+    pipetags: bool,
+    /// Set while a pipeline runs one of its commands.
+    stage: Option<pipeline::Stage>,
+    /// Output filters, set in `[filters]` or with `filter`.
+    filters: filter::Filters,
+    /// Commands run when the working directory changes.
+    dir_hooks: dirhooks::Hooks,
+    cmd_hooks: cmdhooks::Hooks,
+    env_file: envfile::State,
+    dotenv: dotenv::Loaded,
+    /// `pushd`'s directory stack, below the current directory.
+    dir_stack: Vec<PathBuf>,
+    /// The other named sessions, put aside by `session switch`.
+    sessions: session::Sessions,
+    /// What was loaded from the dotfiles repository.
+    dotfiles: dotfiles::Loaded,
+    /// When `config.toml` was saved, as last read.
+    config_modified: Option<SystemTime>,
+    /// Set by `reload`, so the interactive loop reads its settings again.
+    config_reloaded: bool,
+}
+
+/// Why commands are being skipped.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Unwind {
+    Return,
+    /// Leaving this many loops.
+    Break(usize),
+    /// Leaving this many loops minus one, then starting the next iteration.
+    Continue(usize),
+}
+
+/// Converts a child's exit status to a shell status: the exit code, or
+/// 128 + the signal number when it was killed by a signal.
+fn status_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+impl builtins::Context for Shell {
+    fn var(&self, name: &str) -> Option<String> {
+        vars::lookup(&self.variables, name)
+    }
+
+    fn set_var(&mut self, name: &str, value: &str) {
+        vars::assign(&mut self.variables, name, value);
+    }
+
+    fn run(&mut self, commands: &str) -> i32 {
+        run_lines(self, commands)
+    }
+
+    fn as_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Runs `cmd` if it is a builtin, returning its exit status.
+fn run_builtin(shell: &mut Shell, cmd: &str, args: &[&str]) -> Option<i32> {
+    let builtin = builtins::get(cmd)?;
+    if pager::wanted(shell, cmd) {
+        return Some(pager::page(shell, |shell| (builtin.handler)(shell, cmd, args)));
+    }
+    Some((builtin.handler)(shell, cmd, args))
+}
+
+/// Where `cd dir` goes when `dir` is found under an entry of `$CDPATH`,
+/// tried in order; an empty entry is the current directory. Paths that are
+/// absolute or start with `.`, `..` or `~` are never looked up.
+fn search_cdpath(shell: &Shell, dir: &str) -> Option<PathBuf> {
+    let first = Path::new(dir).components().next()?;
+    if !matches!(first, std::path::Component::Normal(_)) || dir.starts_with('~') {
+        return None;
+    }
+    let cdpath = vars::lookup(&shell.variables, "CDPATH")?;
+    env::split_paths(&cdpath)
+        .map(|base| if base.as_os_str().is_empty() { PathBuf::from(".") } else { base })
+        .map(|base| base.join(dir))
+        .find(|candidate| candidate.is_dir())
+}
+
+/// `cd [dir | -]`: `-` goes back to `$OLDPWD` and prints where that is, as
+/// does a directory found through `$CDPATH`. Every change sets `OLDPWD` and
+/// `PWD`.
+fn change_dir(shell: &Shell, args: &[&str]) -> i32 {
+    let back = args.first() == Some(&"-");
+    let mut announce = back;
+    let new_dir = if back {
+        match env::var("OLDPWD") {
+            Ok(dir) if !dir.is_empty() => dir,
+            _ => {
+                println!("cd: {}", msg!("shell.oldpwd_not_set"));
+                return 1;
             }
-            true
         }
-        "clear" => {
-            if cfg!(windows) {
-                Command::new("cmd").args(["/C", "cls"]).status().unwrap();
-            } else {
-                Command::new("clear").status().unwrap();
+    } else {
+        let dir = args.first().cloned().unwrap_or(".");
+        match search_cdpath(shell, dir) {
+            Some(found) => {
+                // Found in the current directory is no surprise.
+                announce = !found.starts_with(".");
+                found.display().to_string()
             }
-            true
+            None => dir.to_string(),
         }
-"jobs" => {
-    let bg = bg_processes.lock().unwrap();
-    if bg.is_empty() {
-        println!("No background jobs");
+    };
+    let previous = env::current_dir();
+    if let Err(e) = env::set_current_dir(&new_dir) {
+        println!("{}", msg!("error.generic", e));
+        return 1;
+    }
+    if let Ok(previous) = previous {
+        vars::set_env("OLDPWD", &previous.display().to_string());
+    }
+    if let Ok(cwd) = env::current_dir() {
+        vars::set_env("PWD", &cwd.display().to_string());
+        if announce {
+            println!("{}", cwd.display());
+        }
+    }
+    0
+}
+
+/// Whether `cmd`, typed alone, names a directory rather than a program:
+/// `src` is changed into only if there is no `src` in `PATH`.
+fn is_autocd_target(cmd: &str) -> bool {
+    Path::new(cmd).is_dir() && (cmd.contains(std::path::is_separator) || lookup::find_program(cmd).is_none())
+}
+
+fn print_dir() -> i32 {
+    match env::current_dir() {
+        Ok(path) => {
+            println!("{}", path.display());
+            0
+        }
+        Err(e) => {
+            println!("{}", msg!("error.generic", e));
+            1
+        }
+    }
+}
+
+fn clear_screen() -> i32 {
+    let status = if cfg!(windows) {
+        Command::new("cmd").args(["/C", "cls"]).status()
     } else {
-        for (pid, _child) in bg.iter() {
-            println!("PID {} - Running", pid);
+        Command::new("clear").status()
+    };
+    status.map(status_code).unwrap_or(1)
+}
+
+/// `set` with the options octane knows, or listing the variables.
+fn set_options(shell: &mut Shell, args: &[&str]) -> i32 {
+    match args {
+        ["-e"] => shell.errexit = true,
+        ["+e"] => shell.errexit = false,
+        ["-o", "posix"] => shell.posix = true,
+        ["+o", "posix"] => shell.posix = false,
+        ["-o", "huponexit"] => shell.huponexit = true,
+        ["+o", "huponexit"] => shell.huponexit = false,
+        ["-o", "autocd"] => shell.autocd = true,
+        ["+o", "autocd"] => shell.autocd = false,
+        ["-o", "timestamps"] => {
+            shell.timestamps = Some(stamp::Stamping { mode: stamp::Mode::from_config(&shell.config), since: None })
         }
+        ["+o", "timestamps"] => shell.timestamps = None,
+        ["-o", "pipetags"] => shell.pipetags = true,
+        ["+o", "pipetags"] => shell.pipetags = false,
+        ["--", args @ ..] => shell.positional = args.iter().map(|arg| arg.to_string()).collect(),
+        _ => return vars::set(args, &shell.variables),
+    }
+    0
+}
+
+fn switch_theme(shell: &mut Shell, args: &[&str]) -> i32 {
+    match args.first() {
+        None => {
+            for name in Theme::available() {
+                let marker = if name == shell.theme.name { "*" } else { " " };
+                println!("{} {}", marker, name);
+            }
+            0
+        }
+        Some(name) => match Theme::load(name) {
+            Ok(theme) => {
+                shell.theme = theme;
+                0
+            }
+            Err(e) => {
+                println!("{}", msg!("error.generic", e));
+                1
+            }
+        },
+    }
+}
+
+/// Candidates from `names` starting with `word`, for the first argument only.
+fn first_argument(names: Vec<String>, word: &str, index: usize) -> Vec<String> {
+    if index != 1 {
+        return Vec::new();
     }
-    true
+    names.into_iter().filter(|name| name.starts_with(word)).collect()
+}
+
+/// One of octane's own builtins, which are given the whole shell.
+fn own(
+    name: &str,
+    usage: &str,
+    help: &str,
+    handler: impl Fn(&mut Shell, &str, &[&str]) -> i32 + Send + Sync + 'static,
+) -> Builtin {
+    Builtin::new(name, usage, help, move |context, cmd, args| {
+        let shell = context.as_any().downcast_mut::<Shell>().expect("builtins run in the shell");
+        handler(shell, cmd, args)
+    })
 }
 
-        "kill" => {
-            if args.is_empty() {
-                println!("Usage: kill <pid>");
-                return true;
+/// Registers octane's own builtins.
+fn register_builtins(config: &Config) {
+    let runtime = config.get_str("containers.runtime").map(str::to_string);
+    let pod_runtime = runtime.clone();
+    let builtin_names = |word: &str, index: usize| {
+        first_argument(builtins::all().iter().map(|b| b.name.clone()).collect(), word, index)
+    };
+    let table = [
+        own("calc", "calc [expression]", &msg!("help.calc"), |_, _, args| calc::run(args)),
+        own("exit", "exit [n]", &msg!("help.exit"), |shell, _, args| {
+            let code = args.first().and_then(|a| a.parse().ok()).unwrap_or(shell.last_status);
+            if !try_exit(shell) {
+                return 1;
             }
-            let pid = match args[0].parse::<u32>() {
-                Ok(p) => p,
-                Err(_) => {
-                    println!("Invalid PID");
-                    return true;
+            shut_down(shell, code, false)
+        }),
+        own("type", "type [-a] [-t|-p] <name...>", &msg!("help.type"), |shell, _, args| {
+            lookup::type_of(args, &shell.aliases, &shell.functions)
+        }),
+        own("which", "which [-a] <name...>", &msg!("help.which"), |shell, _, args| {
+            lookup::which(args, &shell.aliases, &shell.functions)
+        }),
+        own("command", "command [-v|-V] <name> [args...]", &msg!("help.command"), |shell, _, args| {
+            lookup::command(args, &shell.aliases, &shell.functions)
+        }),
+        own("rehash", "rehash", &msg!("help.rehash"), |_, _, _| lookup::rehash()),
+        own("hint", "hint [-r] <command>", &msg!("help.hint"), |_, _, args| hint::run(args)),
+        own("trap", "trap [action] EXIT", &msg!("help.trap"), |shell, _, args| {
+            shutdown::trap(args, &mut shell.traps)
+        }),
+        own("cd", "cd [dir | -]", &msg!("help.cd"), |shell, _, args| change_dir(shell, args)),
+        own("pwd", "pwd", &msg!("help.pwd"), |_, _, _| print_dir()),
+        own("j", "j [-l] [fragment...]", &msg!("help.j"), |shell, _, args| match jump::run(args) {
+            (Some(dir), _) => change_dir(shell, &[&dir.display().to_string()]),
+            (None, status) => status,
+        }),
+        own("pushd", "pushd [dir | +N | -N]", &msg!("help.pushd"), |shell, _, args| dirstack::pushd(shell, args)),
+        own("popd", "popd [+N | -N]", &msg!("help.popd"), |shell, _, args| dirstack::popd(shell, args)),
+        own("dirs", "dirs [-c] [-l] [-p] [-v] [+N | -N]", &msg!("help.dirs"), |shell, _, args| {
+            dirstack::dirs(shell, args)
+        }),
+        own(
+            "on-dir-change",
+            "on-dir-change [--enter|--leave dir] <command...> | on-dir-change -d n",
+            &msg!("help.on_dir_change"),
+            |shell, _, args| dirhooks::run(&mut shell.dir_hooks, args),
+        ),
+        own(
+            "add-hook",
+            "add-hook <preexec|precmd> <command...> | add-hook -d <preexec|precmd> n",
+            &msg!("help.add_hook"),
+            |shell, _, args| cmdhooks::run(&mut shell.cmd_hooks, args),
+        ),
+        own("session", "session [list] | session switch <name> | session close <name>", &msg!("help.session"), |shell, _, args| {
+            session::run(shell, args)
+        }),
+        own("clear", "clear", &msg!("help.clear"), |_, _, _| clear_screen()),
+        own("bulkrename", "bulkrename <glob>...", &msg!("help.bulkrename"), |_, _, args| bulkrename::run(args)),
+        own("source", "source <file> [args...]", &msg!("help.source"), |shell, _, args| source(shell, args)),
+        own(".", ". <file> [args...]", &msg!("help.source"), |shell, _, args| source(shell, args)),
+        own("eval", "eval [words...]", &msg!("help.eval"), |shell, _, args| eval(shell, args)),
+        own("exec", "exec [redirection...] [command [args...]]", &msg!("help.exec"), |shell, _, args| {
+            exec::run(shell, args)
+        }),
+        own("shift", "shift [n]", &msg!("help.shift"), |shell, _, args| shift(shell, args)),
+        own("return", "return [n]", &msg!("help.return"), |shell, _, args| return_from(shell, args)),
+        own("break", "break [n]", &msg!("help.break"), leave_loop),
+        own("continue", "continue [n]", &msg!("help.continue"), leave_loop),
+        own("jobs", "jobs [-l|-p]", &msg!("help.jobs"), |shell, _, args| jobs::list(&shell.jobs, args)),
+        own("fg", "fg [job]", &msg!("help.fg"), |shell, _, args| jobs::fg(&shell.jobs, args)),
+        own("bg", "bg [job]", &msg!("help.bg"), |shell, _, args| jobs::bg(&shell.jobs, args)),
+        own("wait", "wait [job...]", &msg!("help.wait"), |shell, _, args| jobs::wait(&shell.jobs, args)),
+        own("disown", "disown [-a] [-h] [job...]", &msg!("help.disown"), |shell, _, args| {
+            jobs::disown(&shell.jobs, args)
+        }),
+        own("penv", "penv <pid|%job> [name...]", &msg!("help.penv"), |shell, _, args| {
+            penv::run(&shell.jobs, args)
+        }),
+        own("spawn", "spawn [-o log] <command> [args...]", &msg!("help.spawn"), |_, _, args| spawn::run(args)),
+        own("kill", "kill [-s signal | -signal] <pid|%job>...", &msg!("help.kill"), |shell, _, args| {
+            kill::run(&shell.jobs, args)
+        }),
+        own("test", "test <expr>", &msg!("help.test"), |_, cmd, args| condition::run(cmd, args)),
+        own("[", "[ <expr> ]", &msg!("help.test"), |_, cmd, args| condition::run(cmd, args)),
+        own("echo", "echo [-neE] [words...]", &msg!("help.echo"), |_, _, args| print::echo(args)),
+        own("printf", "printf <format> [args...]", &msg!("help.printf"), |_, _, args| print::printf(args)),
+        own("read", "read [-r] [-s] [-p prompt] [name...]", &msg!("help.read"), |shell, _, args| {
+            vars::read(args, &mut shell.variables)
+        }),
+        own("export", "export [NAME[=value]...]", &msg!("help.export"), |shell, _, args| {
+            vars::export(args, &mut shell.variables)
+        }),
+        own("unset", "unset <name...>", &msg!("help.unset"), |shell, _, args| {
+            vars::unset(args, &mut shell.variables)
+        }),
+        own("set", "set [-e|+e] [-o|+o posix|huponexit|autocd|timestamps|pipetags] [-- args...]", &msg!("help.set"), |shell, _, args| set_options(shell, args)),
+        own("alias", "alias [-s] [name[=value]]", &msg!("help.alias"), |shell, _, args| {
+            alias::alias(args, &mut shell.aliases, &mut shell.config)
+        }),
+        own("unalias", "unalias [-s] <name...> | unalias -a", &msg!("help.unalias"), |shell, _, args| {
+            alias::unalias(args, &mut shell.aliases, &mut shell.config)
+        }),
+        own(
+            "abbr",
+            "abbr [-a [-s] <word> <expansion...>] [-e [-s] <word...>]",
+            &msg!("help.abbr"),
+            |shell, _, args| abbr::run(args, &mut shell.config),
+        ),
+        own("weather", "weather [refresh]", &msg!("help.weather"), |shell, _, args| {
+            weather::run(args, &shell.config)
+        }),
+        own("clock", "clock [HH:MM] <city|zone>...", &msg!("help.clock"), |shell, _, args| {
+            clock::run(args, &shell.config)
+        }),
+        own(
+            "secret",
+            "secret set|get|rm <name> | secret run <VAR=name>... -- <command>",
+            &msg!("help.secret"),
+            |shell, _, args| secret::run(args, &shell.config),
+        ),
+        own("sshm", "sshm [list] [-b] [query]", &msg!("help.sshm"), |shell, _, args| {
+            sshm::run(args, &shell.config, &shell.aliases)
+        }),
+        own("on", "on <host|group>[,host...] -- <command>", &msg!("help.on"), |shell, _, args| {
+            remote::run(args, &shell.config)
+        }),
+        own("indocker", "indocker [name] [-- cmd...]", &msg!("help.indocker"), |shell, _, args| {
+            containers::run(containers::Kind::Container, args, &shell.config)
+        })
+        .with_completer(move |word, index| {
+            first_argument(containers::list(containers::Kind::Container, runtime.as_deref()), word, index)
+        }),
+        own("inpod", "inpod [name] [-- cmd...]", &msg!("help.inpod"), |shell, _, args| {
+            containers::run(containers::Kind::Pod, args, &shell.config)
+        })
+        .with_completer(move |word, index| {
+            first_argument(containers::list(containers::Kind::Pod, pod_runtime.as_deref()), word, index)
+        }),
+        own(
+            "queue",
+            "queue add -- <command> | list | cancel <id|all> | log [n]",
+            &msg!("help.queue"),
+            |_, _, args| queue::run(args),
+        ),
+        own("focus", "focus [on <duration>|off]", &msg!("help.focus"), |_, _, args| focus::run(args)),
+        own("filter", "filter [highlight [-c color] | redact | collapse] <pattern> | filter -d n", &msg!("help.filter"), |shell, _, args| {
+            filter::run(&mut shell.filters, args)
+        }),
+        own(
+            "history",
+            "history [n] | history search <pattern> | history delete <n>... | history clear",
+            &msg!("help.history"),
+            |shell, _, args| histcmd::run(shell, args),
+        ),
+        own(
+            "fc",
+            "fc [-e editor] [-r] [first [last]] | fc -l [-n] [-r] [first [last]] | fc -s [old=new] [command]",
+            &msg!("help.fc"),
+            |shell, _, args| fc::run(shell, args),
+        ),
+        own("marks", "marks [n] | marks show <id>", &msg!("help.marks"), |_, _, args| capture::run(args)),
+        own("lastout", "lastout [line]", &msg!("help.lastout"), |_, _, args| capture::lastout(args)),
+        own("demo", "demo [on [delay]|off]", &msg!("help.demo"), |shell, _, args| {
+            demo::run(args, &shell.config, &mut shell.demo, &mut shell.profile)
+        }),
+        own("prompt", "prompt [use <profile>]", &msg!("help.prompt"), |shell, _, args| {
+            profile::run(args, &shell.config, &mut shell.profile)
+        }),
+        own("config", "config export <file.tar> | config import [--dry-run] <file.tar>", &msg!("help.config"), |shell, _, args| {
+            bundle::run(&shell.theme, args)
+        }),
+        own("reload", "reload", &msg!("help.reload"), |shell, _, _| reload::run(shell)),
+        own("setup", "setup", &msg!("help.setup"), |shell, _, _| wizard::run(shell)),
+        own("dotenv", "dotenv [--unload] [file]", &msg!("help.dotenv"), |shell, _, args| {
+            dotenv::run(&mut shell.dotenv, args)
+        }),
+        own("envfile", "envfile [status] | envfile allow [file] | envfile deny [file] | envfile reload", &msg!("help.envfile"), |shell, _, args| {
+            envfile::run(shell, args)
+        }),
+        own("parallel", "parallel [-j n] <command...> ::: <input...>", &msg!("help.parallel"), |shell, _, args| parallel::run(&shell.functions, args)),
+        own("watch", "watch [-n seconds] <command...>", &msg!("help.watch"), |shell, _, args| watch::run(shell, args)),
+        own("record", "record [status] | record start <file> | record stop", &msg!("help.record"), |shell, _, args| {
+            record::run(&shell.config, args)
+        }),
+        own("dotfiles", "dotfiles [status] | dotfiles update [-y]", &msg!("help.dotfiles"), |shell, _, args| {
+            dotfiles::run(shell, args)
+        }),
+        own("theme", "theme [name]", &msg!("help.theme"), |shell, _, args| switch_theme(shell, args))
+            .with_completer(|word, index| first_argument(Theme::available(), word, index)),
+        own("help", "help [builtin...]", &msg!("help.help"), |_, _, args| helpcmd::help(args))
+            .with_completer(builtin_names),
+        own("enable", "enable [name...]", &msg!("help.enable"), |_, _, args| helpcmd::enable(args, true))
+            .with_completer(|word, _| first_argument(builtins::disabled(), word, 1)),
+        own("disable", "disable [-b] <name...>", &msg!("help.disable"), |_, _, args| {
+            helpcmd::enable(args, false)
+        })
+        .with_completer(move |word, _| builtin_names(word, 1)),
+    ];
+    for builtin in table {
+        builtins::register(builtin);
+    }
+}
+
+/// Checks whether the shell may exit now. At the prompt the first attempt
+/// with jobs left only warns about them, as `jobs.on_exit = "warn"` asks.
+fn try_exit(shell: &mut Shell) -> bool {
+    if !shell.interactive || shell.exit_warned {
+        return true;
+    }
+    shell.exit_warned = shutdown::refuse_exit(&shell.jobs, JobPolicy::from_config(&shell.config), shell.huponexit);
+    !shell.exit_warned
+}
+
+/// Runs the `EXIT` trap, flushes history and saves the session: what
+/// happens whenever the shell goes away, whether it exits or `exec`
+/// replaces it.
+fn before_exit(shell: &mut Shell) {
+    // Taken first, so `exit` in the trap doesn't run it again.
+    if let Some(action) = shell.traps.remove("EXIT") {
+        shell.unwinding = None;
+        run_lines(shell, &action);
+    }
+    if shell.interactive {
+        session::save_last(shell);
+    }
+    if let Some(store) = &mut shell.history
+        && let Err(e) = store.flush()
+    {
+        println!("{}", msg!("history.error", e));
+    }
+}
+
+/// Leaves the shell with `status`: runs the `EXIT` trap, flushes history,
+/// deals with the jobs that are left and restores the terminal. `hangup`
+/// is set when leaving because of `SIGTERM` or `SIGHUP`, or the terminal
+/// closing.
+fn shut_down(shell: &mut Shell, status: i32, hangup: bool) -> ! {
+    before_exit(shell);
+    let hangup = hangup || shell.huponexit;
+    shutdown::finish_jobs(&shell.jobs, JobPolicy::from_config(&shell.config), hangup);
+    shutdown::restore_terminal();
+    let _ = io::stdout().flush();
+    std::process::exit(status);
+}
+
+/// Shuts down if `SIGTERM` or `SIGHUP` arrived, exiting with 128 plus the
+/// signal number.
+fn check_termination(shell: &mut Shell) {
+    if let Some(signal) = signals::termination() {
+        shut_down(shell, 128 + signal, true);
+    }
+}
+
+/// Runs a command list such as `make && make install`, returning the
+/// status of the last command run.
+fn run_list(shell: &mut Shell, line: &str) -> i32 {
+    for (connector, command) in split_command_list(&alias::expand(&shell.aliases, line)) {
+        if connector.should_run(shell.last_status) {
+            shell.last_status = run_command(shell, &command, false);
+            run_dir_hooks(shell);
+        }
+        if shell.unwinding.is_some() {
+            break;
+        }
+    }
+    shell.last_status
+}
+
+/// Runs the directory change hooks if the last command changed directory,
+/// and records the visit for `j`. They don't change `$?`.
+fn run_dir_hooks(shell: &mut Shell) {
+    let Some((old, new, hooks)) = shell.dir_hooks.changed() else {
+        return;
+    };
+    if shell.interactive && jump::tracking(&shell.config) {
+        jump::visit(&new);
+    }
+    let status = shell.last_status;
+    shell.dir_hooks.set_running(true);
+    envfile::update(shell);
+    let mut hooks = hooks;
+    // As in zsh, a `chpwd` function runs on every change.
+    if shell.functions.contains_key("chpwd") {
+        hooks.push("chpwd".to_string());
+    }
+    let (old, new) = (old.display().to_string(), new.display().to_string());
+    for hook in hooks {
+        match shell.functions.get(hook.trim()).cloned() {
+            Some(body) => call_function(shell, hook.trim(), &body, &[&old, &new]),
+            None => call_function(shell, "on-dir-change", &hook, &[&old, &new]),
+        };
+    }
+    // A hook that moved on records where it went, without running them again.
+    shell.dir_hooks.changed();
+    shell.dir_hooks.set_running(false);
+    shell.last_status = status;
+}
+
+/// Runs an `if`, `while`, `until` or `for` command.
+fn run_compound(shell: &mut Shell, compound: syntax::Compound) -> i32 {
+    match compound {
+        syntax::Compound::If { branches, otherwise } => {
+            for (condition, body) in branches {
+                let status = run_lines(shell, condition);
+                if shell.unwinding.is_some() || status == INTERRUPTED {
+                    return status;
                 }
-            };
-            let mut bg = bg_processes.lock().unwrap();
-            if let Some(mut child) = bg.remove(&pid) {
-                match child.kill() {
-                    Ok(_) => println!("Killed process {}", pid),
-                    Err(e) => println!("Failed to kill {}: {}", pid, e),
+                if status == 0 {
+                    return run_lines(shell, body);
                 }
+            }
+            otherwise.map_or(0, |body| run_lines(shell, body))
+        }
+        syntax::Compound::Loop { until, condition, body } => run_loop(shell, |shell| {
+            let status = run_lines(shell, condition);
+            (shell.unwinding.is_none() && status != INTERRUPTED && (status == 0) != until).then_some(body)
+        }),
+        syntax::Compound::For { name, words, body } => {
+            let mut values = match words {
+                Some(words) => {
+                    let ifs = ifs::current(&shell.variables, shell.posix);
+                    let expanded = expand_env_vars(words, shell, ifs.as_deref());
+                    // A glob that matches nothing stays as it is.
+                    words::split_globs(&expanded)
+                        .into_iter()
+                        .flat_map(|(word, glob)| {
+                            let word = ifs::restore(&word);
+                            let found = if glob { bulkrename::expand(&word) } else { Vec::new() };
+                            if found.is_empty() { vec![word] } else { found }
+                        })
+                        .collect()
+                }
+                None => shell.positional.clone(),
+            }
+            .into_iter();
+            run_loop(shell, |shell| {
+                let value = values.next()?;
+                vars::assign(&mut shell.variables, name, &value);
+                Some(body)
+            })
+        }
+    }
+}
+
+/// Runs the body returned by `next` until it returns `None`, handling
+/// `break` and `continue`. Returns the status of the last body run, or 0.
+fn run_loop<'a>(shell: &mut Shell, mut next: impl FnMut(&mut Shell) -> Option<&'a str>) -> i32 {
+    let mut status = 0;
+    shell.loop_depth += 1;
+    while let Some(body) = next(shell) {
+        status = run_lines(shell, body);
+        match shell.unwinding {
+            Some(Unwind::Break(levels)) => {
+                shell.unwinding = (levels > 1).then(|| Unwind::Break(levels - 1));
+                break;
+            }
+            Some(Unwind::Continue(levels)) if levels > 1 => {
+                shell.unwinding = Some(Unwind::Continue(levels - 1));
+                break;
+            }
+            Some(Unwind::Continue(_)) => shell.unwinding = None,
+            Some(Unwind::Return) => break,
+            None => {}
+        }
+        // Ctrl-C stops the loop, whether it reached a command or the shell.
+        if status == INTERRUPTED || signals::take_interrupt() {
+            status = INTERRUPTED;
+            break;
+        }
+    }
+    shell.loop_depth -= 1;
+    status
+}
+
+/// `break [n]` / `continue [n]`: leaves `n` (default 1) enclosing loops,
+/// or starts the next iteration of the `n`th.
+fn leave_loop(shell: &mut Shell, cmd: &str, args: &[&str]) -> i32 {
+    let levels = match args {
+        [] => 1,
+        [n] => match n.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                println!("{}", msg!("usage.loop", cmd));
+                return 2;
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.loop", cmd));
+            return 2;
+        }
+    };
+    if shell.loop_depth == 0 {
+        println!("{}", msg!("shell.loop_outside", cmd));
+        return 1;
+    }
+    let levels = levels.min(shell.loop_depth);
+    shell.unwinding = Some(if cmd == "break" { Unwind::Break(levels) } else { Unwind::Continue(levels) });
+    0
+}
+
+/// Runs the function `body` with `args` as its positional parameters.
+fn call_function(shell: &mut Shell, name: &str, body: &str, args: &[&str]) -> i32 {
+    if shell.source_depth >= MAX_SOURCE_DEPTH {
+        println!("{}", msg!("error.nested", name));
+        return 1;
+    }
+    let saved = std::mem::replace(&mut shell.positional, args.iter().map(|a| a.to_string()).collect());
+    let loop_depth = std::mem::take(&mut shell.loop_depth);
+    shell.source_depth += 1;
+    let status = run_lines(shell, body);
+    shell.source_depth -= 1;
+    shell.unwinding = None;
+    shell.loop_depth = loop_depth;
+    shell.positional = saved;
+    status
+}
+
+/// `return [n]`: leaves the running function or sourced file with status
+/// `n` (by default that of the last command).
+fn return_from(shell: &mut Shell, args: &[&str]) -> i32 {
+    if shell.source_depth == 0 {
+        println!("{}", msg!("shell.return_outside"));
+        return 1;
+    }
+    let status = match args {
+        [] => shell.last_status,
+        [n] => match n.parse() {
+            Ok(n) => n,
+            Err(_) => {
+                println!("{}", msg!("usage.return"));
+                return 2;
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.return"));
+            return 2;
+        }
+    };
+    shell.unwinding = Some(Unwind::Return);
+    status
+}
+
+/// `source <file> [args...]` / `. <file> [args...]`: runs each line of
+/// `file` in this shell, so it can change the directory, variables and
+/// aliases. Blank lines and `#` comments are skipped. Arguments become the
+/// positional parameters while the file runs.
+fn source(shell: &mut Shell, args: &[&str]) -> i32 {
+    let Some((file, file_args)) = args.split_first() else {
+        println!("{}", msg!("usage.source"));
+        return 2;
+    };
+    if shell.source_depth >= MAX_SOURCE_DEPTH {
+        println!("source: {}", msg!("error.nested", file));
+        return 1;
+    }
+    let path = config::expand_home(file);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("source: {}: {}", path, e);
+            return 1;
+        }
+    };
+    shell.source_depth += 1;
+    let saved = (!file_args.is_empty())
+        .then(|| std::mem::replace(&mut shell.positional, file_args.iter().map(|a| a.to_string()).collect()));
+    let loop_depth = std::mem::take(&mut shell.loop_depth);
+    let status = run_lines(shell, &text);
+    if let Some(saved) = saved {
+        shell.positional = saved;
+    }
+    shell.source_depth -= 1;
+    shell.unwinding = None;
+    shell.loop_depth = loop_depth;
+    status
+}
+
+/// `eval [words...]`: joins its arguments with spaces and runs the result
+/// as commands in this shell, expanding it a second time.
+fn eval(shell: &mut Shell, args: &[&str]) -> i32 {
+    if args.is_empty() {
+        return 0;
+    }
+    run_lines(shell, &args.join(" "))
+}
+
+/// `shift [n]`: drops the first `n` (default 1) positional parameters, so
+/// `$2` becomes `$1`.
+fn shift(shell: &mut Shell, args: &[&str]) -> i32 {
+    let count = match args {
+        [] => 1,
+        [n] => match n.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                println!("{}", msg!("usage.shift"));
+                return 2;
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.shift"));
+            return 2;
+        }
+    };
+    if count > shell.positional.len() {
+        println!("{}", msg!("shell.shift_range"));
+        return 1;
+    }
+    shell.positional.drain(..count);
+    0
+}
+
+/// Runs a script, sourced file or function body line by line, returning
+/// the status of the last command run. Blank lines and `#` comments
+/// (including a `#!` line) are skipped, a `{` group continues until its
+/// `}`, and an `if`, `while` or `for` until its `fi` or `done`. Stops early
+/// at `return`, `break` or `continue`, when a command is interrupted with
+/// Ctrl-C, or at the first failing line under `set -e`.
+fn run_lines(shell: &mut Shell, text: &str) -> i32 {
+    let mut status = 0;
+    let mut pending = String::new();
+    for line in text.lines().map(str::trim) {
+        if pending.is_empty() && (line.is_empty() || line.starts_with('#')) {
+            continue;
+        }
+        if !pending.is_empty() {
+            pending.push('\n');
+        }
+        pending.push_str(line);
+        if syntax::open_blocks(&pending) > 0 {
+            continue;
+        }
+        status = run_list(shell, &std::mem::take(&mut pending));
+        check_termination(shell);
+        if shell.unwinding.is_some() || status == INTERRUPTED || (status != 0 && shell.errexit) {
+            return status;
+        }
+    }
+    if !pending.is_empty() {
+        println!("{}", msg!("error.end_of_input", pending.lines().next().unwrap_or("")));
+        status = 2;
+    }
+    status
+}
+
+/// Where the shell reads its commands from.
+enum Input {
+    /// The line editor, with a prompt.
+    Interactive,
+    /// `octane script [args...]`
+    Script(String),
+    /// `octane -c "commands"`
+    Command(String),
+    /// Commands piped to stdin: `echo ls | octane`.
+    Stdin,
+    /// `octane --embedded`: driven line by line by `rustshell::repl::Repl`.
+    Embedded,
+}
+
+/// How the shell was started:
+/// `octane [-e] [--restore] [-c commands [name [args...]] | script [args...]]`.
+struct Invocation {
+    /// `-e`: stop at the first failing line.
+    errexit: bool,
+    /// `--restore`: start where the last interactive shell left off.
+    restore: bool,
+    input: Input,
+    /// `$0`: the script, or the name given after `-c` commands.
+    script_name: String,
+    /// The arguments that follow, for the positional parameters.
+    args: Vec<String>,
+}
+
+fn parse_invocation() -> Result<Invocation, String> {
+    let mut errexit = false;
+    let mut restore = false;
+    let mut args = env::args().skip(1);
+    // Everything after the script name or `-c` commands belongs to them.
+    while let Some(arg) = args.next() {
+        let input = match arg.as_str() {
+            "-e" => {
+                errexit = true;
+                continue;
+            }
+            "--restore" => {
+                restore = true;
+                continue;
+            }
+            "--" => continue,
+            "--embedded" => {
+                let script_name = SHELL_NAME.to_string();
+                return Ok(Invocation { errexit, restore, input: Input::Embedded, script_name, args: Vec::new() });
+            }
+            "-c" => Input::Command(args.next().ok_or_else(|| msg!("shell.missing_commands"))?),
+            // `octane config ...` runs the `config` builtin.
+            "config" => Input::Command(format!("config {}", args.by_ref().collect::<Vec<_>>().join(" "))),
+            option if option.starts_with('-') => return Err(msg!("shell.unknown_option", option)),
+            script => Input::Script(script.to_string()),
+        };
+        let script_name = match &input {
+            Input::Script(path) => path.clone(),
+            _ => args.next().unwrap_or_else(|| SHELL_NAME.to_string()),
+        };
+        return Ok(Invocation { errexit, restore, input, script_name, args: args.collect() });
+    }
+    let input = if io::stdin().is_terminal() { Input::Interactive } else { Input::Stdin };
+    Ok(Invocation { errexit, restore, input, script_name: SHELL_NAME.to_string(), args: Vec::new() })
+}
+
+/// Serves a `rustshell::repl::Repl`: runs each line from stdin as it
+/// arrives, then marks the end of its output on stdout and stderr with the
+/// status and working directory, or asks for more while a block is open.
+fn run_embedded(shell: &mut Shell) -> ! {
+    let reply = |reply: &str| {
+        let marker = format!("{}{}", repl::MARKER, reply);
+        let _ = io::stdout().flush();
+        println!("{}", marker);
+        eprintln!("{}", marker);
+    };
+    let mut pending = String::new();
+    loop {
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => shut_down(shell, shell.last_status, false),
+            Ok(_) => pending.push_str(&line),
+        }
+        if syntax::open_blocks(&pending) > 0 {
+            reply(repl::MORE);
+            continue;
+        }
+        let text = std::mem::take(&mut pending);
+        let blank = text.lines().map(str::trim).all(|line| line.is_empty() || line.starts_with('#'));
+        if !blank {
+            shell.last_status = run_lines(shell, &text);
+        }
+        let cwd = env::current_dir().unwrap_or_default();
+        reply(&format!("{} {} {}", repl::DONE, shell.last_status, cwd.display()));
+    }
+}
+
+/// Runs a script, `-c` commands, or piped commands with no prompt, and
+/// exits with the status of the last command.
+fn run_batch(shell: &mut Shell, input: &Input) -> ! {
+    let text = match input {
+        Input::Interactive => unreachable!("the prompt is not a batch"),
+        Input::Embedded => run_embedded(shell),
+        Input::Command(commands) => Ok(commands.clone()),
+        Input::Script(path) => fs::read_to_string(path).map_err(|e| (path.as_str(), e, 127)),
+        Input::Stdin => io::read_to_string(io::stdin()).map_err(|e| ("stdin", e, 1)),
+    };
+    let text = match text {
+        Ok(text) => text,
+        Err((source, e, status)) => {
+            eprintln!("octane: {}: {}", source, e);
+            std::process::exit(status);
+        }
+    };
+    let status = run_lines(shell, &text);
+    shut_down(shell, status, false);
+}
+
+/// Splits `@dir cmd...` or `in dir -- cmd...` into the directory and the
+/// command to run there.
+fn directory_override(command: &str) -> Option<(&str, &str)> {
+    let command = command.trim_start();
+    if let Some(rest) = command.strip_prefix('@') {
+        let (dir, cmd) = rest.split_once(char::is_whitespace)?;
+        return (!dir.is_empty()).then(|| (dir, cmd.trim_start()));
+    }
+    let (dir, cmd) = command.strip_prefix("in ")?.split_once(" -- ")?;
+    Some((dir.trim(), cmd.trim_start()))
+}
+
+/// Runs `command` with `dir` as the working directory, then returns to the
+/// shell's own directory.
+fn run_in(shell: &mut Shell, dir: &str, command: &str, set_title: bool) -> i32 {
+    let dir = ifs::restore(&words::unquote(&expand_env_vars(dir, shell, None)));
+    let dir = config::expand_home(&dir);
+    let previous = env::current_dir();
+    if let Err(e) = env::set_current_dir(&dir) {
+        println!("{}", msg!("error.path", dir, e));
+        return 1;
+    }
+    let status = run_command(shell, command, set_title);
+    if let Ok(previous) = previous
+        && let Err(e) = env::set_current_dir(&previous)
+    {
+        println!("{}", msg!("error.path", previous.display(), e));
+    }
+    status
+}
+
+/// Expands and runs a single command, returning its exit status.
+fn run_command(shell: &mut Shell, command: &str, set_title: bool) -> i32 {
+    // Function bodies and groups are expanded when they run, not here.
+    if let Some((name, body)) = functions::parse_definition(command) {
+        shell.functions.insert(name.to_string(), body.to_string());
+        return 0;
+    }
+    match pipeline::split(command) {
+        Some(Ok(stages)) => return pipeline::run(shell, &stages, command, set_title),
+        Some(Err(e)) => {
+            println!("{}", msg!("error.generic", e));
+            return 2;
+        }
+        None => {}
+    }
+    if let Some(body) = syntax::group_body(command) {
+        return run_lines(shell, body);
+    }
+    match syntax::parse_compound(command) {
+        Some(Ok(compound)) => return run_compound(shell, compound),
+        Some(Err(e)) => {
+            println!("{}", msg!("error.generic", e));
+            return 2;
+        }
+        None => {}
+    }
+    if let Some(rest) = command.trim_start().strip_prefix("! ") {
+        return (run_command(shell, rest, set_title) == 0) as i32;
+    }
+    if let Some(word) = command.split_whitespace().next().filter(|w| syntax::DANGLING.contains(w)) {
+        println!("{}", msg!("error.generic", msg!("error.syntax", word)));
+        return 2;
+    }
+    // The command after a prefix wasn't in command position for aliases.
+    if let Some((dir, command)) = directory_override(command) {
+        return run_in(shell, dir, &alias::expand(&shell.aliases, command), set_title);
+    }
+    if let Some((posix, command)) = timecmd::prefix(command) {
+        return timecmd::run(shell, posix, &alias::expand(&shell.aliases, command), set_title);
+    }
+    if let Some((mode, command)) = stamp::prefix(command) {
+        let mode = mode.unwrap_or_else(|| stamp::Mode::from_config(&shell.config));
+        let saved = shell.timestamps.replace(stamp::Stamping { mode, since: Some(Instant::now()) });
+        let status = run_command(shell, &alias::expand(&shell.aliases, command), set_title);
+        shell.timestamps = saved;
+        return status;
+    }
+    // Only the command itself is a pipeline's, not those run to expand it.
+    let stage = shell.stage.take();
+    // Assignment values are expanded, `=(...)` included, but never split
+    // into words: `i==($i+1)`.
+    let (raw_assignments, rest) = vars::take_assignments(command);
+    let mut assignments = Vec::new();
+    for (name, value) in raw_assignments {
+        let value = match calc::expand_inline(&expand_env_vars(&value, shell, None)) {
+            Ok(value) => value,
+            Err(e) => {
+                println!("{}", msg!("error.evaluating", e));
+                return 1;
+            }
+        };
+        assignments.push((name, ifs::restore(&words::unquote(&value))));
+    }
+    if rest.is_empty() {
+        for (name, value) in &assignments {
+            vars::assign(&mut shell.variables, name, value);
+        }
+        return 0;
+    }
+
+    // Substituted processes start now and last until the command is done.
+    let (substituted, mut substitutions) = match procsub::substitute(shell, rest) {
+        Some(Ok((text, substitutions))) => (Some(text), substitutions),
+        Some(Err(e)) => {
+            println!("{}", msg!("error.generic", e));
+            return 2;
+        }
+        None => (None, procsub::Substitutions::default()),
+    };
+    let ifs = ifs::current(&shell.variables, shell.posix);
+    let mut input = expand_env_vars(substituted.as_deref().unwrap_or(rest), shell, ifs.as_deref());
+    input = match calc::expand_inline(&input) {
+        Ok(expanded) => expanded,
+        Err(e) => {
+            println!("{}", msg!("error.evaluating", e));
+            return 1;
+        }
+    };
+
+    let words: Vec<String> = words::split(&input).iter().map(|word| ifs::restore(word)).collect();
+    let mut parts: Vec<&str> = words.iter().map(String::as_str).collect();
+    if parts.is_empty() {
+        return 0;
+    }
+
+    // A quoted `'&'` is an argument.
+    let background = if parts.last() == Some(&"&") && input.trim_end().ends_with('&') {
+        parts.pop();
+        substitutions.detach();
+        true
+    } else {
+        stage.as_ref().is_some_and(|s| s.background)
+    };
+    // A pipeline's command is left running when this returns.
+    if stage.is_some() {
+        substitutions.detach();
+    }
+    if parts.is_empty() {
+        return 0;
+    }
+
+    // `command name args...` runs `name` without its function.
+    let skip_function = parts[0] == "command" && parts.get(1).is_some_and(|word| !word.starts_with('-'));
+    if skip_function {
+        parts.remove(0);
+    }
+    let cmd = parts[0];
+    let args = &parts[1..];
+
+    let saved = vars::apply(&assignments);
+    let builtin = run_builtin(shell, cmd, args).or_else(|| {
+        let body = shell.functions.get(cmd).filter(|_| !skip_function)?.clone();
+        Some(call_function(shell, cmd, &body, args))
+    });
+    vars::restore(saved);
+    if let Some(status) = builtin {
+        return status;
+    }
+    if shell.autocd && args.is_empty() && !background && assignments.is_empty() && is_autocd_target(cmd) {
+        return change_dir(shell, &[cmd]);
+    }
+
+    if set_title && !background {
+        let cwd = env::current_dir().unwrap_or_else(|_| ".".into());
+        term::set_title(&format!("{} — {}", term::display_path(&cwd), ifs::restore(&input)));
+    }
+
+    // `PATH=... cmd` searches the new `PATH`, not the table.
+    let mut process = if assignments.iter().any(|(name, _)| name == "PATH") {
+        Command::new(cmd)
+    } else {
+        lookup::command_for(cmd)
+    };
+    process.args(args).envs(assignments.iter().map(|(name, value)| (name, value)));
+    if shell.embedded && !stage.as_ref().is_some_and(|s| s.piped_in) {
+        process.stdin(Stdio::null());
+    }
+    let stamping =
+        shell.timestamps.filter(|s| !background && (s.forced() || !capture::excluded(&shell.config, cmd)));
+    let filters = (!background && !shell.filters.is_empty() && !capture::excluded(&shell.config, cmd))
+        .then(|| shell.filters.clone());
+    // The stdout of a pipeline's command but the last goes to the next.
+    let piped = stage.as_ref().is_some_and(|s| s.piped_out);
+    let capture = !background && !piped && capture::wanted(&shell.config, cmd);
+    let last = !background && !piped && capture::keeps_last(&shell.config, cmd);
+    let record = !background && record::active() && !capture::excluded(&shell.config, cmd);
+    let tag =
+        stage.as_ref().and_then(|s| s.tag.clone()).filter(|_| !background && !capture::excluded(&shell.config, cmd));
+    let relayed = capture || last || record || stamping.is_some() || filters.is_some() || tag.is_some();
+    if piped {
+        process.stdout(Stdio::piped());
+    }
+    if relayed {
+        relay::pipe(&mut process, !piped);
+    }
+
+    let group = stage.as_ref().map_or(0, |s| s.group);
+    match jobs::spawn_in(&mut process, group, !background) {
+        Ok(mut child) => {
+            if let Some(mut stage) = stage {
+                let stdout = if piped { child.stdout.take() } else { None };
+                let stamping = stamping.map(|s| (s.mode, s.since.unwrap_or_else(Instant::now)));
+                let options = relay::Options { capture, last, record, stamping, filters, tag };
+                let relay = relayed.then(|| relay::start(&mut child, options));
+                stage.started = Some(pipeline::Started { child, stdout, relay, last });
+                shell.stage = Some(stage);
+                0
+            } else if background {
+                jobs::add(&shell.jobs, child, command.trim());
+                0
             } else {
-                println!("No such background process: {}", pid);
+                let output = relayed.then(|| {
+                    let stamping = stamping.map(|s| (s.mode, s.since.unwrap_or_else(Instant::now)));
+                    relay::start(&mut child, relay::Options { capture, last, record, stamping, filters, tag })
+                });
+                let status = jobs::run_foreground(&shell.jobs, child, command.trim());
+                if let Some(output) = output {
+                    output.finish();
+                }
+                if last && let Some(output) = capture::last_output() {
+                    shell.variables.insert("__".to_string(), output);
+                }
+                status
             }
-            true
         }
-        _ => false,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !cmd.contains(std::path::is_separator) => {
+            let typed = rest.trim_start().strip_prefix(cmd).map(|tail| (&command[..command.len() - rest.len()], tail));
+            command_not_found(shell, cmd, typed, set_title)
+        }
+        Err(e) => {
+            println!("{}", msg!("error.running_command", e));
+            if e.kind() == io::ErrorKind::NotFound { 127 } else { 126 }
+        }
+    }
+}
+
+/// Reports that `cmd` isn't a command and suggests similar names. With
+/// `shell.correct = "prompt"` at the prompt, pressing `y` runs the command
+/// again with the best one; `typed` is the command line around the name
+/// (assignments before it, arguments after), when it was typed as is.
+fn command_not_found(shell: &mut Shell, cmd: &str, typed: Option<(&str, &str)>, set_title: bool) -> i32 {
+    println!("{}", msg!("suggest.not_found", cmd));
+    let mode = suggest::Mode::from_config(&shell.config);
+    if mode == suggest::Mode::Off {
+        return 127;
+    }
+    let similar = suggest::similar(cmd, &shell.aliases, &shell.functions);
+    let Some(best) = similar.first() else {
+        return 127;
+    };
+    if mode == suggest::Mode::Prompt
+        && shell.interactive
+        && let Some((before, after)) = typed
+    {
+        if term::confirm(&msg!("suggest.run", best)) {
+            let corrected = format!("{}{}{}", before, best, after);
+            return run_command(shell, &alias::expand(&shell.aliases, &corrected), set_title);
+        }
+        return 127;
+    }
+    let quoted: Vec<String> = similar.iter().map(|name| format!("'{}'", name)).collect();
+    println!("{}", msg!("suggest.did_you_mean", quoted.join(", ")));
+    127
+}
+
+/// Settings of the interactive loop, read from the config at startup and
+/// again after `reload`.
+struct LoopSettings {
+    policy: histpolicy::Policy,
+    redactor: Redactor,
+    set_title: bool,
+    osc7: bool,
+    osc133: bool,
+    diff_repeats: bool,
+    history_expansion: bool,
+    share_history: bool,
+    edit_mode: EditMode,
+}
+
+impl LoopSettings {
+    fn from_config(config: &Config) -> LoopSettings {
+        let redactor = Redactor::from_config(config);
+        redactor.report_invalid();
+        LoopSettings {
+            policy: histpolicy::Policy::from_config(config),
+            redactor,
+            set_title: config.get_bool("terminal.title").unwrap_or(true),
+            osc7: config.get_bool("terminal.osc7").unwrap_or(true),
+            osc133: config.get_bool("terminal.osc133").unwrap_or(true),
+            diff_repeats: config.get_bool("capture.diff_repeats").unwrap_or(false),
+            history_expansion: config.get_bool("history.expansion").unwrap_or(true),
+            share_history: config.get_bool("history.share").unwrap_or(true),
+            edit_mode: match config.get_str("shell.edit_mode") {
+                Some("vi") => EditMode::Vi,
+                _ => EditMode::Emacs,
+            },
+        }
+    }
+}
+
+/// Adds the commands other sessions saved since the last look to the line
+/// editor, so `Up` and `!!` find them.
+fn pick_up_history(shell: &mut Shell, editor: &mut Editor<ShellHelper, DefaultHistory>, history_size: usize) {
+    let Some(store) = &mut shell.history else {
+        return;
+    };
+    match store.load_new() {
+        Ok(entries) => {
+            for entry in entries {
+                if editor.add_history_entry(entry.as_str()).unwrap_or(false) {
+                    shell.history_entries.push(entry);
+                }
+            }
+            let over = shell.history_entries.len().saturating_sub(history_size);
+            shell.history_entries.drain(..over);
+        }
+        Err(e) => println!("{}", msg!("history.error", e)),
+    }
+}
+
+/// The history store chosen by `[history]` in the config, or `None` when
+/// `history.backend = "none"`.
+fn open_history(config: &Config) -> Option<Box<dyn HistoryStore>> {
+    let path = |default: &str| match config.get_str("history.path") {
+        Some(path) => PathBuf::from(config::expand_home(path)),
+        None => config::data_dir().join(default),
+    };
+    let store: Box<dyn HistoryStore> = match config.get_str("history.backend").unwrap_or("file") {
+        "none" => return None,
+        "file" => Box::new(FileHistory::new(path("history"))),
+        other => {
+            println!("{}", msg!("history.unknown_backend", other));
+            Box::new(FileHistory::new(path("history")))
+        }
+    };
+    match config.get_str("history.sync") {
+        // `host` or `host:path`, the path relative to the remote home.
+        Some(target) => {
+            let (host, remote_path) = target.split_once(':').unwrap_or((target, ".octane_history"));
+            Some(Box::new(SyncedHistory::new(store, host, remote_path)))
+        }
+        None => Some(store),
     }
 }
 
 fn main() {
-    let mut aliases = HashMap::new();
-    aliases.insert("ll", "ls -la");
-    aliases.insert("..", "cd ..");
-    aliases.insert("h", "cd ~");
+    if agent::answer_askpass() {
+        return;
+    }
+    messages::init();
+    let invocation = match parse_invocation() {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            eprintln!("octane: {}", e);
+            eprintln!("{}", msg!("usage.shell"));
+            std::process::exit(2);
+        }
+    };
 
     #[cfg(windows)]
-    {
+    let console_ok = {
         use windows_sys::Win32::System::Console::{
             GetConsoleMode, SetConsoleMode, GetStdHandle,
             ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_OUTPUT_HANDLE,
         };
-    
+
         unsafe {
             let handle = GetStdHandle(STD_OUTPUT_HANDLE);
             let mut mode = 0;
-            if GetConsoleMode(handle, &mut mode) != 0 {
-                SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
-            }
+            GetConsoleMode(handle, &mut mode) != 0
+                && SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
         }
-    }    
+    };
+    #[cfg(not(windows))]
+    let console_ok = true;
+
+    let interactive = matches!(invocation.input, Input::Interactive);
+    term::init(console_ok, interactive);
+    signals::init();
+    jobs::init();
+    shutdown::init();
+
+    if interactive {
+        wizard::first_run();
+    }
+    let config = Config::load();
+    collate::init(&config);
+    register_builtins(&config);
+    term::init_vision(&config);
+    // Scripts inherit the agents of the shell that started them.
+    if interactive {
+        agent::init(&config);
+    }
+    let mut shell = Shell {
+        theme: Theme::from_config(&config),
+        profile: Profile::from_config(&config),
+        demo: None,
+        aliases: alias::load(&config),
+        variables: HashMap::new(),
+        posix: config.get_bool("shell.posix").unwrap_or(false),
+        huponexit: config.get_bool("jobs.huponexit").unwrap_or(false),
+        autocd: config.get_bool("shell.autocd").unwrap_or(false),
+        pipetags: config.get_bool("shell.pipetags").unwrap_or(false),
+        stage: None,
+        timestamps: config
+            .get_bool("shell.timestamps")
+            .unwrap_or(false)
+            .then(|| stamp::Stamping { mode: stamp::Mode::from_config(&config), since: None }),
+        filters: filter::Filters::from_config(&config),
+        dir_hooks: dirhooks::Hooks::from_config(&config),
+        cmd_hooks: cmdhooks::Hooks::from_config(&config),
+        env_file: envfile::State::default(),
+        dotenv: dotenv::Loaded::default(),
+        dir_stack: Vec::new(),
+        sessions: session::Sessions::default(),
+        dotfiles: dotfiles::Loaded::default(),
+        config_modified: config::modified(),
+        config_reloaded: false,
+        config,
+        jobs: jobs::new_table(),
+        last_status: 0,
+        functions: HashMap::new(),
+        source_depth: 0,
+        unwinding: None,
+        loop_depth: 0,
+        errexit: invocation.errexit,
+        script_name: invocation.script_name,
+        positional: invocation.args,
+        embedded: matches!(invocation.input, Input::Embedded),
+        interactive,
+        history: None,
+        history_entries: Vec::new(),
+        history_edited: false,
+        traps: HashMap::new(),
+        exit_warned: false,
+    };
+    jobs::start_reaper(&shell.jobs);
+    dotfiles::load(&mut shell);
+    if interactive && (invocation.restore || shell.config.get_bool("session.restore").unwrap_or(false)) {
+        session::restore_last(&mut shell);
+        run_dir_hooks(&mut shell);
+    }
+    envfile::update(&mut shell);
+    if !interactive {
+        run_batch(&mut shell, &invocation.input);
+    }
 
-    let bg_processes: Arc<Mutex<HashMap<u32, Child>>> = Arc::new(Mutex::new(HashMap::new()));
+    let history_size = shell.config.get_int("history.size").unwrap_or(10_000).max(1) as usize;
+    let mut settings = LoopSettings::from_config(&shell.config);
+    let editor_config = rustyline::Config::builder()
+        .completion_type(CompletionType::List)
+        .edit_mode(settings.edit_mode)
+        .max_history_size(history_size)
+        .expect("history size is positive")
+        .history_ignore_dups(settings.policy.dedup != histpolicy::Dedup::None)
+        .expect("history settings are valid")
+        .build();
+    let mut editor: Editor<ShellHelper, DefaultHistory> =
+        Editor::with_config(editor_config).expect("failed to initialize line editor");
+    shell.history = open_history(&shell.config);
+    if let Some(store) = &mut shell.history {
+        match store.load() {
+            Ok(mut entries) => {
+                if let Some(max) = settings.policy.file_size
+                    && entries.len() > max
+                {
+                    if let Err(e) = store.truncate(max) {
+                        println!("{}", msg!("history.error", e));
+                    }
+                    entries.drain(..entries.len() - max);
+                }
+                if settings.policy.dedup == histpolicy::Dedup::All {
+                    entries = histpolicy::newest_copies(entries);
+                }
+                for entry in entries {
+                    let _ = editor.add_history_entry(entry);
+                }
+            }
+            Err(e) => println!("{}", msg!("history.error", e)),
+        }
+    }
+    shell.history_entries = editor.history().iter().cloned().collect();
+    editor.bind_sequence(
+        KeyEvent::alt('m'),
+        EventHandler::Conditional(Box::new(capture::BookmarkKey)),
+    );
+    editor.bind_sequence(
+        KeyEvent::from(' '),
+        EventHandler::Conditional(Box::new(abbr::ExpandKey)),
+    );
+    editor.bind_sequence(
+        Event::KeySeq(vec![KeyEvent::ctrl('x'), KeyEvent::ctrl('e')]),
+        EventHandler::Conditional(Box::new(editline::EditKey)),
+    );
+    // What Ctrl-X Ctrl-E brought back from the editor.
+    let mut initial = String::new();
+    abbr::init(&shell.config);
+    editor.set_helper(Some(ShellHelper::from_config(&shell.config)));
 
     loop {
-        let cwd = env::current_dir().unwrap_or_else(|_| ".".into());
-        let cwd_str = cwd.to_string_lossy();
-
-        let git_info = get_git_branch();
-
-        let prompt = match git_info {
-            Some((branch, true)) => format!(
-                "{}{} ({branch}*) $ ",
-                "octane:".blue().bold(),
-                cwd_str
-            ),
-            Some((branch, false)) => format!(
-                "{}{} ({branch}) $ ",
-                "octane:".blue().bold(),
-                cwd_str
-            ),
-            None => format!(
-                "{}{} $ ",
-                "octane:".blue().bold(),
-                cwd_str
-            ),
-        };               
-
-        print!("{prompt}");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_err() {
-            break;
+        jobs::notify(&shell.jobs);
+        queue::notify();
+        if std::mem::take(&mut shell.config_reloaded) {
+            settings = LoopSettings::from_config(&shell.config);
+            let _ = editor.set_history_ignore_dups(settings.policy.dedup != histpolicy::Dedup::None);
+            editor.set_edit_mode(settings.edit_mode);
+            editor.set_helper(Some(ShellHelper::from_config(&shell.config)));
         }
-        let mut input = input.trim().to_string();
-        if input.is_empty() {
-            continue;
+        if settings.share_history {
+            pick_up_history(&mut shell, &mut editor, history_size);
         }
 
-        input = expand_env_vars(&input);
-
-        if let Some(replacement) = aliases.get(input.as_str()) {
-            input = replacement.to_string();
+        let status = shell.last_status.to_string();
+        cmdhooks::fire(&mut shell, cmdhooks::Event::Precmd, &[&status]);
+        let cwd = env::current_dir().unwrap_or_else(|_| ".".into());
+        if settings.osc7 {
+            term::report_cwd(&cwd);
+        }
+        if settings.set_title {
+            term::set_title(&term::display_path(&cwd));
         }
 
-        let mut parts: Vec<&str> = input.split_whitespace().collect();
-        if parts.is_empty() {
-            continue;
+        for _ in 0..shell.profile.spacing {
+            println!();
+        }
+        let prompt = prompt::build_prompt(&shell.config, &shell.theme, &shell.profile, shell.last_status);
+        if settings.osc133 {
+            term::mark_prompt_start();
         }
 
-        let background = if parts.last() == Some(&"&") {
-            parts.pop();
-            true
-        } else {
-            false
+        let plain_prompt = term::strip_ansi(&prompt);
+        let input = editor.readline_with_initial(&(plain_prompt.clone(), prompt), (&std::mem::take(&mut initial), ""));
+        check_termination(&mut shell);
+        let input = match input {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                if let Some(edited) = editline::edit_pending() {
+                    initial = edited;
+                }
+                continue;
+            }
+            Err(ReadlineError::Eof) if !try_exit(&mut shell) => continue,
+            // Any other error means the terminal went away.
+            Err(e) => {
+                let status = shell.last_status;
+                shut_down(&mut shell, status, !matches!(e, ReadlineError::Eof))
+            }
         };
-
-        let cmd = parts[0];
-        let args = &parts[1..];
-
-        if run_builtin(cmd, args, &bg_processes) {
+        // Saved while the prompt was up: the line runs with the new config.
+        reload::watch(&mut shell);
+        if shell.config_reloaded {
+            settings = LoopSettings::from_config(&shell.config);
+        }
+        let leading_space = input.starts_with(' ');
+        let mut input = abbr::expand_line(input.trim());
+        if input.is_empty() {
             continue;
         }
+        if settings.history_expansion {
+            match histexpand::expand(&input, &shell.history_entries) {
+                Ok(Some(expanded)) => {
+                    // Show what is actually run.
+                    println!("{}", expanded);
+                    input = expanded;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    println!("{}", e);
+                    shell.last_status = 1;
+                    continue;
+                }
+            }
+        }
+        let entry = settings.redactor.for_history(&input);
+        if settings.share_history {
+            // Before this entry, in the order they were saved.
+            pick_up_history(&mut shell, &mut editor, history_size);
+        }
+        if let Some(entry) = entry
+            && !settings.policy.ignored(&input, leading_space)
+            && editor.add_history_entry(&entry).unwrap_or(false)
+        {
+            shell.history_entries.push(entry.clone());
+            let over = shell.history_entries.len().saturating_sub(history_size);
+            shell.history_entries.drain(..over);
+            if let Some(store) = &mut shell.history
+                && let Err(e) = store.append(&entry)
+            {
+                println!("{}", msg!("history.error", e));
+            }
+            if settings.policy.dedup == histpolicy::Dedup::All
+                && let Some(index) = histpolicy::earlier_copy(&shell.history_entries)
+            {
+                if let Some(store) = &mut shell.history
+                    && let Err(e) =
+                        histcmd::delete_stored(store.as_mut(), &shell.history_entries, index, histpolicy::Dedup::All)
+                {
+                    println!("{}", msg!("history.error", e));
+                }
+                shell.history_entries.remove(index);
+                shell.history_edited = true;
+            }
+        }
 
-        match Command::new(cmd).args(args).spawn() {
-            Ok(mut child) => {
-                if background {
-                    let pid = child.id();
-                    println!("Started background job with PID {}", pid);
-                    bg_processes.lock().unwrap().insert(pid, child);
-                } else {
-                    match child.wait() {
-                        Ok(_status) => {}
-                        Err(e) => {
-                            println!("Error waiting on process: {}", e);
-                        }
-                    }
+        record::command(&plain_prompt, &settings.redactor.redact(&input));
+        cmdhooks::fire(&mut shell, cmdhooks::Event::Preexec, &[&input]);
+        if settings.osc133 {
+            term::mark_command_start();
+        }
+        // Another command between two `exit`s means warning again.
+        let warned = shell.exit_warned;
+        let started = Instant::now();
+        for (connector, command) in split_command_list(&alias::expand(&shell.aliases, &input)) {
+            if connector.should_run(shell.last_status) {
+                let (command, diff_last) = capture::take_diff_flag(&command);
+                if let Some(demo) = &shell.demo {
+                    demo.announce(&shell.theme, &settings.redactor.redact(&command));
+                }
+                shell.last_status = run_command(&mut shell, &command, settings.set_title);
+                run_dir_hooks(&mut shell);
+                capture::record(&settings.redactor.redact(&command), shell.last_status);
+                if diff_last || settings.diff_repeats {
+                    capture::show_diff(&shell.theme, diff_last);
                 }
             }
-            Err(e) => {
-                println!("Error running command: {}", e);
+        }
+        if settings.osc133 {
+            term::mark_command_end(shell.last_status);
+        }
+        let elapsed = started.elapsed();
+        took::finished(&shell.config, &input, elapsed);
+        record::finished(shell.last_status, elapsed);
+        notify::finished(&shell.config, &settings.redactor.redact(&input), shell.last_status, elapsed);
+        if warned {
+            shell.exit_warned = false;
+        }
+        if std::mem::take(&mut shell.history_edited) {
+            let _ = editor.clear_history();
+            for entry in &shell.history_entries {
+                let _ = editor.add_history_entry(entry.as_str());
             }
+            shell.history_entries = editor.history().iter().cloned().collect();
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(line: &str) -> Vec<(Connector, String)> {
+        split_command_list(line)
+    }
+
+    fn commands(line: &str) -> Vec<String> {
+        list(line).into_iter().map(|(_, command)| command).collect()
+    }
+
+    #[test]
+    fn command_list_splits_on_connectors() {
+        let split = list("make && make install || echo failed; echo done");
+        let connectors: Vec<Connector> = split.iter().map(|(connector, _)| *connector).collect();
+        assert!(connectors == [Connector::Always, Connector::And, Connector::Or, Connector::Always]);
+        let commands: Vec<&str> = split.iter().map(|(_, command)| command.as_str()).collect();
+        assert_eq!(commands, ["make", "make install", "echo failed", "echo done"]);
+    }
+
+    #[test]
+    fn command_list_keeps_quoted_separators() {
+        assert_eq!(commands("echo 'a;b'"), ["echo 'a;b'"]);
+        assert_eq!(commands("echo \"a && b\" && echo 'c || d'"), ["echo \"a && b\"", "echo 'c || d'"]);
+        assert_eq!(commands("alias b='echo one && echo two'; b"), ["alias b='echo one && echo two'", "b"]);
+        assert_eq!(commands("echo \"it's; fine\"; echo x"), ["echo \"it's; fine\"", "echo x"]);
+    }
+
+    #[test]
+    fn command_list_keeps_blocks_whole() {
+        assert_eq!(commands("if true; then echo a; fi; echo b"), ["if true; then echo a; fi", "echo b"]);
+        assert_eq!(commands("{ echo a; echo b; } && echo c"), ["{ echo a; echo b; }", "echo c"]);
+    }
+
+    #[test]
+    fn command_list_drops_empty_commands() {
+        assert_eq!(commands(";; echo a ;"), ["echo a"]);
+    }
+}
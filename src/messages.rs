@@ -0,0 +1,168 @@
+//! The message catalog: user-facing text looked up by key, so it can be
+//! translated.
+//!
+//! English ships in `messages/en.toml`. The language comes from `LC_ALL`,
+//! `LC_MESSAGES` or `LANG` (`de_DE.UTF-8` tries `de_DE`, then `de`), and a
+//! catalog is read from `~/.config/octane/messages/<lang>.toml` or the
+//! built-in ones. Keys a translation lacks fall back to English.
+//!
+//! Messages use `{}` for their arguments in order, or `{1}`, `{2}`, ... when
+//! a translation needs them in a different order.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::sync::OnceLock;
+
+use toml::{Table, Value};
+
+use crate::config;
+
+const ENGLISH: &str = include_str!("../messages/en.toml");
+/// Catalogs compiled in, besides English.
+const BUILTIN_CATALOGS: [(&str, &str); 0] = [];
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Looks up a message and fills in its arguments:
+/// `msg!("jobs.no_such_job", spec)`.
+macro_rules! msg {
+    ($key:expr $(, $arg:expr)* $(,)?) => {
+        $crate::messages::format($key, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+pub(crate) use msg;
+
+/// Flattens nested tables into `section.key` entries.
+fn flatten(table: &Table, prefix: &str, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::String(text) => {
+                out.insert(key, text.clone());
+            }
+            Value::Table(table) => flatten(table, &key, out),
+            _ => {}
+        }
+    }
+}
+
+fn parse(text: &str) -> HashMap<String, String> {
+    let mut messages = HashMap::new();
+    if let Ok(table) = text.parse::<Table>() {
+        flatten(&table, "", &mut messages);
+    }
+    messages
+}
+
+/// Languages to try for the locale in the environment, most specific first.
+fn languages() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    // `de_DE.UTF-8@euro` → `de_DE`
+    let locale = locale.split(['.', '@']).next().unwrap_or("");
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+    let mut languages = vec![locale.to_string()];
+    if let Some((language, _)) = locale.split_once('_') {
+        languages.push(language.to_string());
+    }
+    languages
+}
+
+fn load(language: &str) -> Option<HashMap<String, String>> {
+    let path = config::config_dir().join("messages").join(format!("{}.toml", language));
+    if let Ok(text) = fs::read_to_string(path) {
+        return Some(parse(&text));
+    }
+    BUILTIN_CATALOGS
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map(|(_, text)| parse(text))
+}
+
+/// Picks the catalog for the user's locale. Call once at startup; messages
+/// looked up before that are English.
+pub fn init() {
+    let mut messages = parse(ENGLISH);
+    if let Some(translated) = languages().iter().find_map(|language| load(language)) {
+        messages.extend(translated);
+    }
+    let _ = CATALOG.set(messages);
+}
+
+fn lookup(key: &str) -> String {
+    if let Some(text) = CATALOG.get().and_then(|catalog| catalog.get(key)) {
+        return text.clone();
+    }
+    // Before `init`, or a key missing from the catalog.
+    parse(ENGLISH).remove(key).unwrap_or_else(|| key.to_string())
+}
+
+/// The message for `key` with `args` filled in. Use the `msg!` macro.
+pub fn format(key: &str, args: &[&dyn Display]) -> String {
+    let text = lookup(key);
+    let mut out = String::with_capacity(text.len());
+    let mut next = 0;
+    let mut rest = text.as_str();
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            out.push_str(&rest[open..]);
+            rest = "";
+            break;
+        };
+        let index = match &after[..close] {
+            "" => {
+                next += 1;
+                Some(next - 1)
+            }
+            n => n.parse::<usize>().ok().and_then(|n| n.checked_sub(1)),
+        };
+        match index.and_then(|i| args.get(i)) {
+            Some(arg) => out.push_str(&arg.to_string()),
+            None => out.push_str(&rest[open..open + close + 2]),
+        }
+        rest = &after[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments_fill_placeholders() {
+        assert_eq!(format("error.generic", &[&"boom"]), "Error: boom");
+        assert_eq!(format("error.path", &[&"a", &1]), "Error: a: 1");
+        assert_eq!(format("error.path", &[&"a"]), "Error: a: {}");
+        assert_eq!(format("no.such.key", &[]), "no.such.key");
+    }
+
+    /// Every `msg!("key", ...)` in the source has an English message.
+    #[test]
+    fn every_key_used_is_in_english() {
+        let english = parse(ENGLISH);
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut missing = Vec::new();
+        for entry in fs::read_dir(dir).unwrap().flatten().filter(|entry| entry.file_name() != "messages.rs") {
+            let text = fs::read_to_string(entry.path()).unwrap();
+            for (at, _) in text.match_indices("msg!(\"") {
+                let key = &text[at + 6..];
+                let key = &key[..key.find('"').unwrap()];
+                if !english.contains_key(key) {
+                    missing.push(format!("{}: {}", entry.file_name().to_string_lossy(), key));
+                }
+            }
+        }
+        assert!(missing.is_empty(), "{:?}", missing);
+    }
+}
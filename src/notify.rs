@@ -0,0 +1,150 @@
+//! Desktop notifications for long-running commands.
+//!
+//! When a command line at the prompt took longer than `notify.after`
+//! seconds (30 by default) and the terminal isn't the focused window, a
+//! notification with the command and its status goes out: through
+//! `notify-send` on Linux and the BSDs, Notification Center on macOS, or a
+//! toast on Windows. `notify.method = "osc9"` or `"osc777"` asks the
+//! terminal to show it instead, which also works over ssh.
+//!
+//! Whether the terminal has focus is asked of the system where it can
+//! tell: the frontmost app on macOS, the active X11 window (with `xdotool`)
+//! for terminals that set `$WINDOWID`, the foreground window for the classic
+//! Windows console. Elsewhere a notification is always sent. Nothing is
+//! sent during a focus session, for a command interrupted with Ctrl-C, or
+//! for interactive programs (`capture.exclude`).
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::capture;
+use crate::config::Config;
+use crate::focus;
+use crate::jobs;
+use crate::messages::msg;
+use crate::queue;
+
+/// Exit statuses of commands the user stopped while watching.
+const STOPPED: [i32; 2] = [130, 148];
+
+/// Set once a notification couldn't be sent, so the error shows only once.
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the terminal is the focused window, if that can be told.
+#[cfg(target_os = "macos")]
+fn focused() -> Option<bool> {
+    let normalize = |name: &str| {
+        let name = name.trim().to_lowercase();
+        let name = name.strip_prefix("apple_").unwrap_or(&name).to_string();
+        name.strip_suffix(".app").unwrap_or(&name).to_string()
+    };
+    let terminal = normalize(&std::env::var("TERM_PROGRAM").ok()?);
+    let output = Command::new("osascript")
+        .args(["-e", "tell application \"System Events\" to get name of first process whose frontmost is true"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let front = normalize(&String::from_utf8_lossy(&output.stdout));
+    (output.status.success() && !front.is_empty()).then(|| front.contains(&terminal) || terminal.contains(&front))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn focused() -> Option<bool> {
+    let window = std::env::var("WINDOWID").ok()?;
+    let output = Command::new("xdotool").arg("getactivewindow").stderr(Stdio::null()).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim() == window.trim())
+}
+
+#[cfg(windows)]
+fn focused() -> Option<bool> {
+    use windows_sys::Win32::System::Console::GetConsoleWindow;
+    use windows_sys::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    // Windows Terminal draws consoles in a window of its own.
+    if std::env::var_os("WT_SESSION").is_some() {
+        return None;
+    }
+    // SAFETY: neither call takes arguments or has preconditions.
+    let (console, foreground) = unsafe { (GetConsoleWindow(), GetForegroundWindow()) };
+    (console != 0).then_some(console == foreground)
+}
+
+/// Shows `title` and `body` the way the system does.
+fn system(title: &str, body: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.args(["-e", "on run argv", "-e", "display notification (item 2 of argv) with title (item 1 of argv)"]);
+        command.args(["-e", "end run", title, body]);
+        command
+    } else if cfg!(windows) {
+        let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+        let script = format!(
+            "$m = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]; \
+             $x = $m::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+             $t = $x.GetElementsByTagName('text'); \
+             [void]$t.Item(0).AppendChild($x.CreateTextNode({})); \
+             [void]$t.Item(1).AppendChild($x.CreateTextNode({})); \
+             $id = '{{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}}\\WindowsPowerShell\\v1.0\\powershell.exe'; \
+             $m::CreateToastNotifier($id).Show([Windows.UI.Notifications.ToastNotification]::new($x))",
+            quote(title),
+            quote(body)
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=octane", title, body]);
+        command
+    };
+    let child = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    jobs::reap_later(child);
+    Ok(())
+}
+
+/// Asks the terminal to show the notification, with OSC 9 (iTerm2, Windows
+/// Terminal, kitty, WezTerm) or OSC 777 (foot, urxvt, WezTerm).
+fn terminal(osc: &str, title: &str, body: &str) {
+    let clean = |text: &str| text.replace(|c: char| c.is_control(), " ");
+    let sequence = match osc {
+        "osc777" => format!("\x1b]777;notify;{};{}\x07", clean(title).replace(';', ","), clean(body).replace(';', ",")),
+        _ => format!("\x1b]9;{}: {}\x07", clean(title), clean(body)),
+    };
+    print!("{}", sequence);
+    let _ = io::stdout().flush();
+}
+
+/// Called when `command`, as shown to the user, finished with `status`
+/// after `elapsed`.
+pub fn finished(config: &Config, command: &str, status: i32, elapsed: Duration) {
+    let after = config.get_int("notify.after").unwrap_or(30).max(0) as u64;
+    let method = config.get_str("notify.method").unwrap_or("system");
+    if !config.get_bool("notify.enabled").unwrap_or(true)
+        || elapsed < Duration::from_secs(after)
+        || STOPPED.contains(&status)
+        || focus::active()
+    {
+        return;
+    }
+    let program = command.split_whitespace().next().unwrap_or("");
+    if capture::excluded(config, program) || focused() == Some(true) {
+        return;
+    }
+    let title = match status {
+        0 => msg!("notify.done"),
+        status => msg!("notify.failed", status),
+    };
+    let body = msg!("notify.body", command, queue::format_elapsed(elapsed));
+    match method {
+        "osc9" | "osc777" => terminal(method, &title, &body),
+        _ => {
+            if let Err(e) = system(&title, &body)
+                && !FAILED.swap(true, Ordering::Relaxed)
+            {
+                println!("{}", msg!("notify.error", e));
+            }
+        }
+    }
+}
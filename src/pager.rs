@@ -0,0 +1,170 @@
+//! Paging long builtin output.
+//!
+//! At an interactive terminal, the output of `history`, `jobs`, `alias`,
+//! `help` and the other builtins in `pager.builtins` is held back until the
+//! builtin is done. If it's taller than the terminal, it's shown through
+//! `$PAGER`, or when that isn't set, a small built-in pager: Space and
+//! PageDown go a page down, `b` and PageUp a page up, Enter and the arrow
+//! keys a line, `g` and `G` to either end, and `q` quits. `pager.enabled =
+//! false` prints everything straight away.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::queue;
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+
+use crate::Shell;
+use crate::exec;
+use crate::jobs;
+use crate::lookup;
+use crate::messages::msg;
+use crate::term;
+
+const BUILTINS: [&str; 9] = ["history", "jobs", "alias", "abbr", "help", "marks", "set", "export", "dirs"];
+
+/// Whether the output of the builtin `name` should be paged when long.
+pub fn wanted(shell: &Shell, name: &str) -> bool {
+    if !shell.interactive || !shell.config.get_bool("pager.enabled").unwrap_or(true) || !io::stdout().is_terminal() {
+        return false;
+    }
+    if shell.config.has("pager.builtins") {
+        shell.config.get_str_list("pager.builtins").iter().any(|b| b == name)
+    } else {
+        BUILTINS.contains(&name)
+    }
+}
+
+/// Splits `line` into rows of at most `width` visible characters, keeping
+/// escape sequences with the text they color.
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let mut rows = vec![String::new()];
+    let mut count = 0;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let row = rows.last_mut().unwrap();
+        if c == '\x1b' {
+            row.push(c);
+            if chars.next_if_eq(&'[').is_some() {
+                row.push('[');
+                for c in chars.by_ref() {
+                    row.push(c);
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        if count == width {
+            rows.push(String::new());
+            count = 0;
+        }
+        rows.last_mut().unwrap().push(c);
+        count += 1;
+    }
+    rows
+}
+
+fn rows(text: &str, width: usize) -> Vec<String> {
+    text.lines().flat_map(|line| wrap(&line.replace('\t', "        "), width.max(1))).collect()
+}
+
+/// Runs `builtin`, paging its output if it doesn't fit on the screen.
+pub fn page(shell: &mut Shell, builtin: impl FnOnce(&mut Shell) -> i32) -> i32 {
+    let _ = io::stdout().flush();
+    // Only stdout: a builtin's errors show at once, not in the pager.
+    let Some((file, path, saved)) = term::temp_file("page")
+        .ok()
+        .and_then(|(file, path)| exec::redirect_stdout(&file).ok().map(|saved| (file, path, saved)))
+    else {
+        return builtin(shell);
+    };
+    let status = builtin(shell);
+    let _ = io::stdout().flush();
+    exec::restore_output(saved);
+    drop(file);
+
+    let text = String::from_utf8_lossy(&fs::read(&path).unwrap_or_default()).into_owned();
+    let (width, height) = terminal::size().map_or((80, 24), |(w, h)| (w as usize, h as usize));
+    if rows(&text, width).len() < height {
+        print!("{}", text);
+        let _ = io::stdout().flush();
+    } else if let Some(pager) = env::var("PAGER").ok().filter(|p| !p.trim().is_empty()) {
+        if let Err(e) = external(shell, &pager, &path) {
+            println!("{}: {}", pager, e);
+            print!("{}", text);
+        }
+    } else if builtin_pager(&text).is_err() {
+        print!("{}", text);
+    }
+    let _ = fs::remove_file(&path);
+    status
+}
+
+/// Shows the file at `path` with the `$PAGER` command `pager`, in the
+/// foreground. `less` is told to keep colors, as git does.
+fn external(shell: &Shell, pager: &str, path: &std::path::Path) -> io::Result<()> {
+    let words: Vec<&str> = pager.split_whitespace().collect();
+    let mut command = lookup::command_for(words[0]);
+    command.args(&words[1..]).stdin(File::open(path)?);
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let child = jobs::spawn(&mut command, true)?;
+    jobs::run_foreground(&shell.jobs, child, pager);
+    Ok(())
+}
+
+fn builtin_pager(text: &str) -> io::Result<()> {
+    terminal::enable_raw_mode()?;
+    let mut out = io::stdout();
+    let _ = crossterm::execute!(out, EnterAlternateScreen, Hide);
+    let result = show(text);
+    let _ = crossterm::execute!(out, SetAttribute(Attribute::Reset), Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
+
+fn show(text: &str) -> io::Result<()> {
+    let mut out = io::stdout();
+    let mut top = 0;
+    loop {
+        let (width, height) = terminal::size().map_or((80, 24), |(w, h)| (w as usize, h as usize));
+        let rows = rows(text, width);
+        let page = height.saturating_sub(1).max(1);
+        let last = rows.len().saturating_sub(page);
+        top = top.min(last);
+
+        queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+        for (i, row) in rows.iter().skip(top).take(page).enumerate() {
+            queue!(out, MoveTo(0, i as u16), Print(row), SetAttribute(Attribute::Reset))?;
+        }
+        let status = msg!("pager.status", top + 1, (top + page).min(rows.len()), rows.len());
+        queue!(out, MoveTo(0, page as u16), SetAttribute(Attribute::Reverse), Print(status))?;
+        queue!(out, SetAttribute(Attribute::Reset))?;
+        out.flush()?;
+
+        let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+            continue;
+        };
+        if kind == KeyEventKind::Release {
+            continue;
+        }
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Char(' ' | 'f') | KeyCode::PageDown => top += page,
+            KeyCode::Char('b') | KeyCode::PageUp => top = top.saturating_sub(page),
+            KeyCode::Enter | KeyCode::Down | KeyCode::Char('j') => top += 1,
+            KeyCode::Up | KeyCode::Char('k') => top = top.saturating_sub(1),
+            KeyCode::Char('g') | KeyCode::Home => top = 0,
+            KeyCode::Char('G') | KeyCode::End => top = last,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! The `parallel` builtin: runs a command for each of a list of inputs,
+//! several at a time.
+//!
+//! `parallel -j4 'convert {} {}.png' ::: *.svg` runs the template once per
+//! input with at most four running at once (by default, one per CPU). In
+//! the template `{}` is the input, `{.}` the input without its extension,
+//! `{/}` its file name, `{//}` its directory, `{/.}` the file name without
+//! extension and `{#}` the number of the run; a template without any of
+//! them gets the input as its last argument. Inputs with `*` or `?` are
+//! expanded to the matching files.
+//!
+//! Only programs can be run: the runs are processes of their own, so
+//! builtins and functions are refused. The filled-in template is split into
+//! words as a command line is, with each input as one word.
+//!
+//! Output is shown as it arrives, each line labelled with its input. When
+//! all runs are done, the failed ones are listed; the status is the number
+//! that failed (at most 101, as with GNU parallel). Each run is in a
+//! process group of its own, so Ctrl-Z leaves them alone and Ctrl-C reaches
+//! the shell, which interrupts the runs in progress and starts no more.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use colored::*;
+
+use crate::alias;
+use crate::bulkrename;
+use crate::jobs;
+use crate::lookup::{self, Kind};
+use crate::messages::msg;
+use crate::signals;
+use crate::words;
+
+/// Fills in the replacement strings of `template` for `input`, run `number`,
+/// each quoted so that it stays one word.
+fn fill(template: &str, input: &str, number: usize) -> String {
+    let path = Path::new(input);
+    let stem = |p: &Path| p.with_extension("").to_string_lossy().into_owned();
+    let name = path.file_name().map_or_else(|| input.to_string(), |n| n.to_string_lossy().into_owned());
+    let dir = match path.parent().map(|p| p.to_string_lossy().into_owned()) {
+        Some(dir) if !dir.is_empty() => dir,
+        _ => ".".to_string(),
+    };
+    let replacements = [
+        ("{#}", number.to_string()),
+        ("{//}", alias::quote(&dir)),
+        ("{/.}", alias::quote(&stem(Path::new(&name)))),
+        ("{/}", alias::quote(&name)),
+        ("{.}", alias::quote(&stem(path))),
+        ("{}", alias::quote(input)),
+    ];
+    if !replacements.iter().any(|(token, _)| template.contains(token)) {
+        return format!("{} {}", template, alias::quote(input));
+    }
+    // One pass, so an input containing `{}` isn't replaced again.
+    let mut out = String::new();
+    let mut rest = template;
+    'scan: while !rest.is_empty() {
+        for (token, value) in &replacements {
+            if let Some(after) = rest.strip_prefix(token) {
+                out.push_str(value);
+                rest = after;
+                continue 'scan;
+            }
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Copies the lines from `stream` to the terminal behind `label`.
+fn relay(stream: impl Read + Send + 'static, label: String, stderr: bool) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\r');
+            if stderr {
+                eprintln!("{} {}", label, line);
+            } else {
+                println!("{} {}", label, line);
+            }
+        }
+    })
+}
+
+/// Why the program of `command` can't be run, if it is a builtin or a
+/// function.
+fn unsupported(command: &str, functions: &HashMap<String, String>) -> Option<String> {
+    let words = words::split(command);
+    let name = words.first()?;
+    match lookup::resolve(name, &HashMap::new(), functions, false).first()? {
+        Kind::Builtin => Some(msg!("parallel.builtin", name)),
+        Kind::Function(_) => Some(msg!("parallel.function", name)),
+        _ => None,
+    }
+}
+
+/// Runs `command` with its output labelled, returning its status. Its PID
+/// is in `running` while it runs, and it is interrupted straight away if
+/// `stop` is set by then.
+fn run_one(command: &str, label: &str, running: &Mutex<Vec<u32>>, stop: &AtomicBool) -> Result<i32, io::Error> {
+    let words = words::split(command);
+    let Some((program, args)) = words.split_first() else {
+        return Ok(0);
+    };
+    let mut process = lookup::command_for(program);
+    process.args(args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = {
+        let mut running = running.lock().unwrap();
+        let child = jobs::spawn(&mut process, false)?;
+        running.push(child.id());
+        if stop.load(Ordering::SeqCst) {
+            let _ = jobs::send_signal(child.id(), signals::SIGINT, true);
+        }
+        child
+    };
+    let label = format!("[{}]", label).dimmed().to_string();
+    let readers = [
+        child.stdout.take().map(|out| relay(out, label.clone(), false)),
+        child.stderr.take().map(|err| relay(err, label, true)),
+    ];
+    let status = child.wait();
+    running.lock().unwrap().retain(|&pid| pid != child.id());
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    Ok(crate::status_code(status?))
+}
+
+/// `parallel [-j n] <command...> ::: <input...>`
+pub fn run(functions: &HashMap<String, String>, args: &[&str]) -> i32 {
+    let usage = || {
+        println!("{}", msg!("usage.parallel"));
+        2
+    };
+    let (jobs, args) = match args {
+        ["-j", n, rest @ ..] => (n.parse::<usize>().ok(), rest),
+        [flag, rest @ ..] if flag.starts_with("-j") => (flag[2..].parse::<usize>().ok(), rest),
+        args => (thread::available_parallelism().map(|n| n.get()).ok().or(Some(1)), args),
+    };
+    let Some(jobs) = jobs.filter(|&n| n > 0) else {
+        return usage();
+    };
+    let Some(separator) = args.iter().position(|&arg| arg == ":::") else {
+        return usage();
+    };
+    let template = args[..separator].join(" ");
+    if template.trim().is_empty() {
+        return usage();
+    }
+    if let Some(reason) = unsupported(&template, functions) {
+        println!("parallel: {}", reason);
+        return 1;
+    }
+    let inputs: Vec<String> = args[separator + 1..]
+        .iter()
+        .flat_map(|&input| {
+            let matches = if input.contains(['*', '?']) { bulkrename::expand(input) } else { Vec::new() };
+            if matches.is_empty() { vec![input.to_string()] } else { matches }
+        })
+        .collect();
+
+    let next = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let started = Arc::new(AtomicUsize::new(0));
+    let running = Arc::new(Mutex::new(Vec::new()));
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let inputs = Arc::new(inputs);
+    let _ = signals::take_interrupt();
+    let workers: Vec<_> = (0..jobs.min(inputs.len()))
+        .map(|_| {
+            let (next, stop, started, running, failures, inputs, template) = (
+                next.clone(),
+                stop.clone(),
+                started.clone(),
+                running.clone(),
+                failures.clone(),
+                inputs.clone(),
+                template.clone(),
+            );
+            thread::spawn(move || {
+                loop {
+                    let n = next.fetch_add(1, Ordering::SeqCst);
+                    if n >= inputs.len() || stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    started.fetch_add(1, Ordering::SeqCst);
+                    let command = fill(&template, &inputs[n], n + 1);
+                    match run_one(&command, &inputs[n], &running, &stop) {
+                        Ok(0) => {}
+                        // Interrupted.
+                        Ok(130) => {
+                            stop.store(true, Ordering::SeqCst);
+                            failures.lock().unwrap().push((n, msg!("jobs.exit", 130)));
+                        }
+                        Ok(status) => failures.lock().unwrap().push((n, msg!("jobs.exit", status))),
+                        Err(e) => failures.lock().unwrap().push((n, e.to_string())),
+                    }
+                }
+            })
+        })
+        .collect();
+
+    while !workers.iter().all(|worker| worker.is_finished()) {
+        if signals::take_interrupt() {
+            stop.store(true, Ordering::SeqCst);
+            for &pid in running.lock().unwrap().iter() {
+                let _ = jobs::send_signal(pid, signals::SIGINT, true);
+            }
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    let mut failures = std::mem::take(&mut *failures.lock().unwrap());
+    failures.sort();
+    for (n, outcome) in &failures {
+        println!("parallel: {}", msg!("parallel.failed", inputs[*n], outcome));
+    }
+    let skipped = inputs.len() - started.load(Ordering::SeqCst);
+    if stop.load(Ordering::SeqCst) && skipped > 0 {
+        println!("parallel: {}", msg!("parallel.stopped", skipped));
+        return 130;
+    }
+    if !failures.is_empty() {
+        println!("parallel: {}", msg!("parallel.summary", failures.len(), inputs.len()));
+    }
+    failures.len().min(101) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_the_replacement_strings() {
+        assert_eq!(fill("convert {} {.}.png", "img/a.svg", 1), "convert 'img/a.svg' 'img/a'.png");
+        assert_eq!(fill("mv {} {//}/{#}-{/}", "a/b.txt", 3), "mv 'a/b.txt' 'a'/3-'b.txt'");
+        assert_eq!(fill("gzip -k", "x.log", 1), "gzip -k 'x.log'");
+    }
+
+    #[test]
+    fn inputs_stay_one_word() {
+        assert_eq!(words::split(&fill("touch {}.bak", "my file", 1)), ["touch", "my file.bak"]);
+        assert_eq!(words::split(&fill("rm {/}", "it's", 1)), ["rm", "it's"]);
+        assert_eq!(words::split(&fill("echo", "{} {}", 1)), ["echo", "{} {}"]);
+    }
+
+    #[test]
+    fn functions_are_refused() {
+        let functions = HashMap::from([("build".to_string(), "make".to_string())]);
+        assert!(unsupported("build {}", &functions).is_some());
+        assert!(unsupported("make {}", &functions).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+//! The `penv` builtin: shows another process's environment and working
+//! directory.
+//!
+//! A process's environment is fixed when it starts, so a long-running job
+//! doesn't see later `export`s; `penv %1 PATH` shows what it actually has.
+//! Linux (and other systems with `/proc`) reads `/proc/<pid>`; macOS asks
+//! the kernel for the process's arguments, which end with its environment.
+//! Other processes' environments can only be read with permission to
+//! inspect them, usually only one's own processes.
+
+use std::io;
+use std::path::PathBuf;
+
+use crate::jobs::{self, JobTable};
+use crate::messages::msg;
+
+/// The working directory and `NAME=value` environment of `pid`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn inspect(pid: u32) -> io::Result<(PathBuf, Vec<String>)> {
+    if !std::path::Path::new("/proc/self").exists() {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, msg!("penv.unsupported")));
+    }
+    let proc = PathBuf::from("/proc").join(pid.to_string());
+    let environ = std::fs::read(proc.join("environ"))?;
+    let cwd = std::fs::read_link(proc.join("cwd"))?;
+    let vars = environ
+        .split(|&b| b == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| String::from_utf8_lossy(entry).into_owned())
+        .collect();
+    Ok((cwd, vars))
+}
+
+/// The most a process's arguments and environment can take up.
+#[cfg(target_os = "macos")]
+fn arg_max() -> usize {
+    let mut mib = [libc::CTL_KERN, libc::KERN_ARGMAX];
+    let mut max: libc::c_int = 0;
+    let mut size = std::mem::size_of::<libc::c_int>();
+    let ptr = (&mut max as *mut libc::c_int).cast();
+    unsafe { libc::sysctl(mib.as_mut_ptr(), 2, ptr, &mut size, std::ptr::null_mut(), 0) };
+    (max as usize).max(4096)
+}
+
+#[cfg(target_os = "macos")]
+fn inspect(pid: u32) -> io::Result<(PathBuf, Vec<String>)> {
+    // KERN_PROCARGS2: argc, the executable path, padding, the arguments,
+    // then the environment, each NUL-terminated.
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid as libc::c_int];
+    let mut buffer = vec![0u8; arg_max()];
+    let mut size = buffer.len();
+    let ptr = buffer.as_mut_ptr().cast();
+    if unsafe { libc::sysctl(mib.as_mut_ptr(), 3, ptr, &mut size, std::ptr::null_mut(), 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    buffer.truncate(size);
+    let (argc, rest) = buffer.split_at(std::mem::size_of::<libc::c_int>().min(buffer.len()));
+    let argc = i32::from_ne_bytes(argc.try_into().unwrap_or([0; 4])) as usize;
+    let mut fields = rest.split(|&b| b == 0).filter(|field| !field.is_empty());
+    // The executable path, then the arguments.
+    for _ in 0..=argc {
+        fields.next();
+    }
+    let vars = fields.map(|entry| String::from_utf8_lossy(entry).into_owned()).collect();
+
+    // `lsof` knows the working directory without an extra FFI struct.
+    let output = std::process::Command::new("lsof")
+        .args(["-a", "-d", "cwd", "-Fn", "-p", &pid.to_string()])
+        .output()?;
+    let cwd = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix('n').map(PathBuf::from))
+        .ok_or_else(|| io::Error::from(io::ErrorKind::PermissionDenied))?;
+    Ok((cwd, vars))
+}
+
+#[cfg(windows)]
+fn inspect(_pid: u32) -> io::Result<(PathBuf, Vec<String>)> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, msg!("penv.unsupported")))
+}
+
+/// `penv <pid|%job> [name...]`: the working directory and environment of a
+/// process, or only the named variables.
+pub fn run(table: &JobTable, args: &[&str]) -> i32 {
+    let Some((target, names)) = args.split_first() else {
+        println!("{}", msg!("usage.penv"));
+        return 2;
+    };
+    jobs::refresh(table);
+    let pid = match jobs::parse_spec(target) {
+        Ok(spec) => match jobs::pid_of(table, spec) {
+            Some(pid) => pid,
+            None => {
+                println!("penv: {}", msg!("jobs.no_such_spec", target));
+                return 1;
+            }
+        },
+        Err(e) => {
+            println!("penv: {}", e);
+            return 2;
+        }
+    };
+    let (cwd, mut vars) = match inspect(pid) {
+        Ok(found) => found,
+        Err(e) => {
+            println!("penv: {}: {}", pid, e);
+            return 1;
+        }
+    };
+    vars.sort();
+    if names.is_empty() {
+        println!("{}", msg!("penv.cwd", cwd.display()));
+        for var in &vars {
+            println!("{}", var);
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for name in names {
+        match vars.iter().find(|var| var.split_once('=').is_some_and(|(n, _)| n == *name)) {
+            Some(var) => println!("{}", var),
+            None => {
+                println!("penv: {}", msg!("penv.not_set", name));
+                status = 1;
+            }
+        }
+    }
+    status
+}
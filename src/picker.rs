@@ -0,0 +1,183 @@
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::cursor::{self, MoveToColumn, MoveUp};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+    MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::queue;
+
+const VISIBLE_ROWS: usize = 10;
+
+/// Scores `candidate` against `query` as a case-insensitive subsequence
+/// match, rewarding consecutive and word-start hits. `None` if it doesn't
+/// match at all.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0i64;
+    let mut pos = 0;
+    let mut last_match: Option<usize> = None;
+    for q in query.to_lowercase().chars() {
+        let found = candidate[pos..].iter().position(|&c| c == q)? + pos;
+        score += 1;
+        if last_match.is_some_and(|l| l + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - pos) as i64 / 4;
+        last_match = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Indices of `items` matching `query`, best first; ties keep input order.
+pub fn fuzzy_filter(query: &str, items: &[String]) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| Some((fuzzy_score(query, item)?, i)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Lets the user choose one of `items`, returning its index.
+///
+/// On a terminal this is an inline fuzzy finder: typing filters, Up/Down (or
+/// Ctrl-P/Ctrl-N, or the mouse wheel) move, Enter or a click accepts, Esc or
+/// Ctrl-C cancels. Otherwise the items are printed as a numbered list and a
+/// number is read from stdin.
+pub fn pick(prompt: &str, items: &[String]) -> Option<usize> {
+    if items.is_empty() {
+        return None;
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return pick_numbered(prompt, items);
+    }
+    terminal::enable_raw_mode().ok()?;
+    let _ = crossterm::execute!(io::stdout(), EnableMouseCapture);
+    let result = run_picker(prompt, items);
+    let _ = crossterm::execute!(io::stdout(), DisableMouseCapture);
+    let _ = terminal::disable_raw_mode();
+    result.unwrap_or(None)
+}
+
+fn pick_numbered(prompt: &str, items: &[String]) -> Option<usize> {
+    for (i, item) in items.iter().enumerate() {
+        println!("{:>3}) {}", i + 1, item);
+    }
+    print!("{}: ", prompt);
+    io::stdout().flush().ok()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).ok()?;
+    let n: usize = line.trim().parse().ok()?;
+    (1..=items.len()).contains(&n).then(|| n - 1)
+}
+
+fn run_picker(prompt: &str, items: &[String]) -> io::Result<Option<usize>> {
+    let mut out = io::stdout();
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut scroll = 0;
+
+    loop {
+        let matches = fuzzy_filter(&query, items);
+        selected = selected.min(matches.len().saturating_sub(1));
+        if selected < scroll {
+            scroll = selected;
+        } else if selected >= scroll + VISIBLE_ROWS {
+            scroll = selected + 1 - VISIBLE_ROWS;
+        }
+
+        queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+        queue!(out, Print(format!("{}> {}", prompt, query)))?;
+        let shown: Vec<usize> = matches.iter().skip(scroll).take(VISIBLE_ROWS).copied().collect();
+        for (row, &index) in shown.iter().enumerate() {
+            queue!(out, Print("\r\n"))?;
+            if scroll + row == selected {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+                queue!(out, Print(format!("> {}", items[index])))?;
+                queue!(out, SetAttribute(Attribute::Reset))?;
+            } else {
+                queue!(out, Print(format!("  {}", items[index])))?;
+            }
+        }
+        if !shown.is_empty() {
+            queue!(out, MoveUp(shown.len() as u16))?;
+        }
+        let column = prompt.chars().count() + 2 + query.chars().count();
+        queue!(out, MoveToColumn(column as u16))?;
+        out.flush()?;
+        // Drawing may have scrolled the screen, so find the query line again
+        // to map clicks to rows.
+        let query_row = cursor::position().map(|(_, row)| row).ok();
+
+        let (code, modifiers, kind) = match event::read()? {
+            Event::Key(KeyEvent { code, modifiers, kind, .. }) => (code, modifiers, kind),
+            Event::Mouse(MouseEvent { kind, row, .. }) => {
+                match kind {
+                    MouseEventKind::ScrollUp => selected = selected.saturating_sub(1),
+                    MouseEventKind::ScrollDown => selected += 1,
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        let clicked = query_row
+                            .and_then(|top| row.checked_sub(top + 1))
+                            .and_then(|offset| shown.get(offset as usize));
+                        if let Some(&index) = clicked {
+                            queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+                            out.flush()?;
+                            return Ok(Some(index));
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        let choice = match code {
+            KeyCode::Enter => Some(matches.get(selected).copied()),
+            KeyCode::Esc => Some(None),
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => Some(None),
+            KeyCode::Up => {
+                selected = selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Char('p') if ctrl => {
+                selected = selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Down | KeyCode::Tab => {
+                selected += 1;
+                None
+            }
+            KeyCode::Char('n') if ctrl => {
+                selected += 1;
+                None
+            }
+            KeyCode::Backspace => {
+                query.pop();
+                None
+            }
+            KeyCode::Char(c) if !ctrl => {
+                query.push(c);
+                selected = 0;
+                None
+            }
+            _ => None,
+        };
+        if let Some(choice) = choice {
+            queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+            out.flush()?;
+            return Ok(choice);
+        }
+    }
+}
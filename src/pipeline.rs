@@ -0,0 +1,273 @@
+//! Pipelines: `a | b | c` runs the commands together, each one's stdout
+//! feeding the next one's stdin. The status is the last command's, for
+//! `$?`, `&&`, `||` and `set -e`; `! a | b` negates it, and a pipeline
+//! ending in `&` runs in the background as one job.
+//!
+//! External commands run at the same time, in one process group, so Ctrl-C
+//! and Ctrl-Z reach all of them and a stopped pipeline is one job. Builtins,
+//! functions and `{ ...; }` groups run in the shell itself, one after the
+//! other, so `echo hi | read greeting` sets `greeting`; what they write is
+//! passed on when they finish.
+//!
+//! With `set -o pipetags` (or `shell.pipetags`) the output the commands
+//! write to the terminal, each one's stderr and the last one's stdout, is
+//! copied line by line behind a colored `[n:name]` tag for its stage, so
+//! lines written at the same time by different stages can be told apart.
+//! Full-screen and interactive programs (`capture.exclude`) aren't tagged.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, Seek, Write};
+use std::process::{Child, ChildStdout};
+
+use colored::*;
+
+use crate::Shell;
+use crate::capture;
+use crate::exec;
+use crate::jobs;
+use crate::messages::msg;
+use crate::relay::Relay;
+use crate::syntax;
+use crate::term;
+use crate::vars;
+
+/// Colors of the stage tags, in turn.
+const TAG_COLORS: [Color; 5] = [Color::Cyan, Color::Magenta, Color::Yellow, Color::Green, Color::Blue];
+
+/// How `run_command` runs a command of a pipeline.
+pub struct Stage {
+    /// The pipeline's process group: its first external command's PID, or
+    /// 0 until one has started.
+    pub group: u32,
+    /// Reads the previous command's output rather than the shell's stdin.
+    pub piped_in: bool,
+    /// Writes to the next command rather than the terminal.
+    pub piped_out: bool,
+    pub background: bool,
+    /// Put before each line it writes to the terminal, with `set -o pipetags`.
+    pub tag: Option<String>,
+    /// Set by `run_command` when it started an external command, which it
+    /// leaves running.
+    pub started: Option<Started>,
+}
+
+/// An external command of a pipeline, running.
+pub struct Started {
+    pub child: Child,
+    /// Its stdout, for the next command to read.
+    pub stdout: Option<ChildStdout>,
+    /// The copying of its output to the terminal.
+    pub relay: Option<Relay>,
+    /// Its stdout is kept for `$__`.
+    pub last: bool,
+}
+
+/// Splits `command` on `|` into the commands of a pipeline, if it has more
+/// than one. A `|` in quotes, parentheses or a block such as `{ ...; }`
+/// doesn't count. An empty command is a syntax error.
+pub fn split(command: &str) -> Option<Result<Vec<&str>, String>> {
+    let mut stages = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut parens = 0;
+    let mut quote = None;
+    let mut changes = syntax::depth_changes(command).into_iter().peekable();
+    let mut chars = command.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if let Some((_, change)) = changes.next_if(|(at, _)| *at == i) {
+            depth += change;
+        }
+        match (c, quote) {
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('\'' | '"', None) => quote = Some(c),
+            ('(', None) => parens += 1,
+            (')', None) => parens -= 1,
+            // `||` isn't a pipe.
+            ('|', None) if depth == 0 && parens == 0 && chars.next_if(|(_, next)| *next == '|').is_none() => {
+                stages.push(&command[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if stages.is_empty() {
+        return None;
+    }
+    stages.push(&command[start..]);
+    if stages.iter().any(|stage| stage.trim().is_empty()) {
+        return Some(Err(msg!("error.syntax", "|")));
+    }
+    Some(Ok(stages))
+}
+
+#[cfg(unix)]
+fn into_file(stream: impl Into<std::os::fd::OwnedFd>) -> File {
+    File::from(stream.into())
+}
+
+#[cfg(windows)]
+fn into_file(stream: impl Into<std::os::windows::io::OwnedHandle>) -> File {
+    File::from(stream.into())
+}
+
+/// Throws away what a builtin read ahead from stdin and didn't use, which
+/// would otherwise be read later in place of the terminal: stdin is pointed
+/// at an empty pipe until what was buffered is used up.
+fn discard_read_ahead() -> io::Result<()> {
+    let (reader, writer) = io::pipe()?;
+    drop(writer);
+    let saved = exec::redirect_stdio(Some(&into_file(reader)), None)?;
+    let mut stdin = io::stdin().lock();
+    while let Ok(buffered) = stdin.fill_buf()
+        && !buffered.is_empty()
+    {
+        let n = buffered.len();
+        stdin.consume(n);
+    }
+    exec::restore_output(saved);
+    Ok(())
+}
+
+/// The tag for the `n`th stage, `command`.
+fn tag(n: usize, command: &str) -> String {
+    let (_, rest) = vars::take_assignments(command);
+    let name = rest.split_whitespace().next().unwrap_or_default();
+    let color = term::adapt(TAG_COLORS[(n - 1) % TAG_COLORS.len()]);
+    format!("{} ", format!("[{}:{}]", n, name).color(color))
+}
+
+/// Runs the commands of a pipeline, the text of which is `command`,
+/// returning the status of the last.
+pub fn run(shell: &mut Shell, stages: &[&str], command: &str, set_title: bool) -> i32 {
+    let mut stages = stages.to_vec();
+    let negate = match stages[0].trim_start().strip_prefix("! ") {
+        Some(rest) => {
+            stages[0] = rest;
+            true
+        }
+        None => false,
+    };
+    let last = stages.len() - 1;
+    let background = match stages[last].trim_end().strip_suffix('&') {
+        Some(rest) => {
+            stages[last] = rest;
+            true
+        }
+        None => false,
+    };
+
+    let mut status = 0;
+    let mut group = 0;
+    let mut started: Vec<Started> = Vec::new();
+    let mut last_started = false;
+    let mut temp_files = Vec::new();
+    // What the next command reads: the previous one's stdout, or the file
+    // a builtin wrote to.
+    let mut input: Option<File> = None;
+    for (n, stage) in stages.iter().enumerate() {
+        let piped_out = n < last;
+        let output = if piped_out {
+            match term::temp_file("pipe") {
+                Ok((file, path)) => {
+                    temp_files.push(path);
+                    Some(file)
+                }
+                Err(e) => {
+                    println!("{}", msg!("error.generic", e));
+                    status = 1;
+                    break;
+                }
+            }
+        } else {
+            None
+        };
+        let _ = io::stdout().flush();
+        let saved = match exec::redirect_stdio(input.as_ref(), output.as_ref()) {
+            Ok(saved) => saved,
+            Err(e) => {
+                println!("{}", msg!("error.generic", e));
+                status = 1;
+                break;
+            }
+        };
+
+        let tag = shell.pipetags.then(|| tag(n + 1, stage));
+        shell.stage = Some(Stage { group, piped_in: n > 0, piped_out, background, tag, started: None });
+        status = crate::run_command(shell, stage, set_title);
+        let _ = io::stdout().flush();
+        let stage = shell.stage.take();
+
+        if input.take().is_some() && stage.as_ref().is_none_or(|s| s.started.is_none()) {
+            let _ = discard_read_ahead();
+        }
+        exec::restore_output(saved);
+
+        last_started = false;
+        input = match stage.and_then(|s| s.started) {
+            Some(mut running) => {
+                if group == 0 {
+                    group = running.child.id();
+                }
+                last_started = true;
+                let stdout = running.stdout.take();
+                started.push(running);
+                stdout.map(into_file)
+            }
+            None => output.and_then(|mut file| file.rewind().ok().map(|()| file)),
+        };
+    }
+    drop(input);
+
+    if !started.is_empty() {
+        let (children, outputs): (Vec<Child>, Vec<_>) =
+            started.into_iter().map(|s| (s.child, (s.relay, s.last))).unzip();
+        if background {
+            jobs::add_pipeline(&shell.jobs, children, command.trim());
+            status = 0;
+        } else {
+            let waited = jobs::run_pipeline(&shell.jobs, children, command.trim());
+            for (relay, last) in outputs {
+                if let Some(relay) = relay {
+                    relay.finish();
+                }
+                if last && let Some(output) = capture::last_output() {
+                    shell.variables.insert("__".to_string(), output);
+                }
+            }
+            if last_started {
+                status = waited;
+            }
+        }
+    }
+    for path in temp_files {
+        let _ = fs::remove_file(path);
+    }
+    if negate { (status == 0) as i32 } else { status }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_pipes() {
+        assert_eq!(split("grep -r TODO src | sort | head"), Some(Ok(vec!["grep -r TODO src ", " sort ", " head"])));
+        assert_eq!(split("a|b"), Some(Ok(vec!["a", "b"])));
+    }
+
+    #[test]
+    fn other_bars_are_not_pipes() {
+        assert_eq!(split("make || echo failed"), None);
+        assert_eq!(split("echo 'a|b' \"c|d\""), None);
+        assert_eq!(split("echo $(ls | wc -l)"), None);
+        assert_eq!(split("if true; then echo a | cat; fi"), None);
+    }
+
+    #[test]
+    fn empty_commands_are_errors() {
+        assert_eq!(split("ls |"), Some(Err(msg!("error.syntax", "|"))));
+        assert_eq!(split("| ls"), Some(Err(msg!("error.syntax", "|"))));
+        assert_eq!(split("ls | | wc"), Some(Err(msg!("error.syntax", "|"))));
+    }
+}
@@ -0,0 +1,409 @@
+//! The `echo` and `printf` builtins.
+//!
+//! Running them in the shell avoids starting a process for every line of
+//! output, and behaves the same everywhere (Windows has no `echo.exe`).
+
+use std::io::{self, Write};
+
+use crate::messages::msg;
+
+/// Expands backslash escapes: `\n`, `\t`, `\\`, `\0nnn` (octal), `\xHH`,
+/// and so on. Returns the text and whether a `\c` asked to stop all
+/// further output.
+fn unescape(text: &str, octal_needs_zero: bool) -> (String, bool) {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        match escape {
+            'a' => out.push('\u{7}'),
+            'b' => out.push('\u{8}'),
+            'c' => return (out, true),
+            'e' | 'E' => out.push('\u{1b}'),
+            'f' => out.push('\u{c}'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            'v' => out.push('\u{b}'),
+            '\\' => out.push('\\'),
+            'x' => {
+                let mut value = 0;
+                let mut digits = 0;
+                while digits < 2
+                    && let Some(d) = chars.peek().and_then(|c| c.to_digit(16))
+                {
+                    value = value * 16 + d;
+                    digits += 1;
+                    chars.next();
+                }
+                if digits == 0 {
+                    out.push_str("\\x");
+                } else {
+                    out.push(char::from(value as u8));
+                }
+            }
+            '0'..='7' if escape == '0' || !octal_needs_zero => {
+                // `echo -e` and `%b` write octal as `\0nnn`, a `printf`
+                // format as `\nnn`.
+                let (mut value, mut digits) = if octal_needs_zero { (0, 0) } else { (escape.to_digit(8).unwrap_or(0), 1) };
+                while digits < 3
+                    && let Some(d) = chars.peek().and_then(|c| c.to_digit(8))
+                {
+                    value = value * 8 + d;
+                    digits += 1;
+                    chars.next();
+                }
+                out.push(char::from((value & 0xff) as u8));
+            }
+            other => {
+                out.push('\\');
+                out.push(other);
+            }
+        }
+    }
+    (out, false)
+}
+
+fn write_out(text: &str) -> i32 {
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush()) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    }
+}
+
+/// `echo [-neE] [words...]`: prints the words separated by spaces. `-n`
+/// leaves off the newline, `-e` expands backslash escapes, `-E` (the
+/// default) doesn't.
+pub fn echo(args: &[&str]) -> i32 {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut words = args;
+    while let Some(flags) = words.first().and_then(|w| w.strip_prefix('-')) {
+        if flags.is_empty() || !flags.chars().all(|c| "neE".contains(c)) {
+            break;
+        }
+        for flag in flags.chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        words = &words[1..];
+    }
+    let mut text = words.join(" ");
+    if escapes {
+        let (expanded, stop) = unescape(&text, true);
+        text = expanded;
+        if stop {
+            return write_out(&text);
+        }
+    }
+    if newline {
+        text.push('\n');
+    }
+    write_out(&text)
+}
+
+/// A `%` conversion: flags, width, precision, and the conversion letter.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Pads `body` (with `sign` in front) to the width. Zero padding goes
+    /// between the sign and the digits.
+    fn pad(&self, sign: &str, body: &str, numeric: bool) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        let fill = self.width.unwrap_or(0).saturating_sub(len);
+        if self.left {
+            format!("{}{}{}", sign, body, " ".repeat(fill))
+        } else if self.zero && numeric {
+            format!("{}{}{}", sign, "0".repeat(fill), body)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), sign, body)
+        }
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+}
+
+/// Parses a number argument: decimal, `0x` hex, `0` octal, or `'c` for the
+/// character code of `c`.
+fn parse_integer(arg: &str) -> Option<i64> {
+    let arg = arg.trim();
+    if let Some(c) = arg.strip_prefix(['\'', '"']).and_then(|rest| rest.chars().next()) {
+        return Some(c as i64);
+    }
+    if arg.is_empty() {
+        return Some(0);
+    }
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, arg.strip_prefix('+').unwrap_or(arg)),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_float(arg: &str) -> Option<f64> {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return Some(0.0);
+    }
+    arg.parse().ok().or_else(|| parse_integer(arg).map(|n| n as f64))
+}
+
+/// `{:e}` in C style: `1.500000e+03`.
+fn exponential(value: f64, precision: usize, upper: bool) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+/// `%g`: the shorter of `%e` and `%f` for `precision` significant digits,
+/// without trailing zeros unless `#` was given.
+fn general(value: f64, precision: usize, upper: bool, alternate: bool) -> String {
+    let precision = precision.max(1);
+    if value == 0.0 {
+        return if alternate { format!("{:.*}", precision - 1, 0.0) } else { "0".to_string() };
+    }
+    let exponent = format!("{:.*e}", precision - 1, value)
+        .split_once('e')
+        .and_then(|(_, e)| e.parse::<i32>().ok())
+        .unwrap_or(0);
+    let text = if exponent < -4 || exponent >= precision as i32 {
+        exponential(value, precision - 1, upper)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent).max(0) as usize, value)
+    };
+    if alternate {
+        return text;
+    }
+    let (number, suffix) = match text.find(['e', 'E']) {
+        Some(i) => text.split_at(i),
+        None => (text.as_str(), ""),
+    };
+    let number = if number.contains('.') { number.trim_end_matches('0').trim_end_matches('.') } else { number };
+    format!("{}{}", number, suffix)
+}
+
+/// Formats one conversion. Bad numbers are reported and printed as 0.
+fn convert(spec: &Spec, conversion: char, arg: Option<&str>, errors: &mut Vec<String>) -> (String, bool) {
+    let arg = arg.unwrap_or("");
+    let mut integer = || {
+        parse_integer(arg).unwrap_or_else(|| {
+            errors.push(msg!("print.invalid_number", arg));
+            0
+        })
+    };
+    match conversion {
+        's' => {
+            let text: String = match spec.precision {
+                Some(p) => arg.chars().take(p).collect(),
+                None => arg.to_string(),
+            };
+            (spec.pad("", &text, false), false)
+        }
+        'b' => {
+            let (text, stop) = unescape(arg, true);
+            (spec.pad("", &text, false), stop)
+        }
+        'c' => (spec.pad("", &arg.chars().next().map(String::from).unwrap_or_default(), false), false),
+        'd' | 'i' => {
+            let value = integer();
+            let mut digits = value.unsigned_abs().to_string();
+            if let Some(p) = spec.precision {
+                digits = format!("{:0>width$}", digits, width = p);
+            }
+            (spec.pad(spec.sign(value < 0), &digits, spec.precision.is_none()), false)
+        }
+        'u' | 'o' | 'x' | 'X' => {
+            let value = integer() as u64;
+            let mut digits = match conversion {
+                'o' => format!("{:o}", value),
+                'x' => format!("{:x}", value),
+                'X' => format!("{:X}", value),
+                _ => value.to_string(),
+            };
+            if let Some(p) = spec.precision {
+                digits = format!("{:0>width$}", digits, width = p);
+            }
+            let prefix = match conversion {
+                'o' if spec.alternate && !digits.starts_with('0') => "0",
+                'x' if spec.alternate && value != 0 => "0x",
+                'X' if spec.alternate && value != 0 => "0X",
+                _ => "",
+            };
+            (spec.pad(prefix, &digits, spec.precision.is_none()), false)
+        }
+        'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+            let value = parse_float(arg).unwrap_or_else(|| {
+                errors.push(msg!("print.invalid_number", arg));
+                0.0
+            });
+            let precision = spec.precision.unwrap_or(6);
+            let magnitude = value.abs();
+            let body = if !magnitude.is_finite() {
+                let text = if magnitude.is_nan() { "nan" } else { "inf" };
+                if conversion.is_ascii_uppercase() { text.to_uppercase() } else { text.to_string() }
+            } else {
+                match conversion {
+                    'f' | 'F' => format!("{:.*}", precision, magnitude),
+                    'e' | 'E' => exponential(magnitude, precision, conversion == 'E'),
+                    _ => general(magnitude, precision, conversion == 'G', spec.alternate),
+                }
+            };
+            let negative = value.is_sign_negative() && !value.is_nan();
+            (spec.pad(spec.sign(negative), &body, magnitude.is_finite()), false)
+        }
+        _ => unreachable!("checked by the caller"),
+    }
+}
+
+/// Reads a width or precision: digits, or `*` to take it from the next
+/// argument.
+fn number<'a>(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    args: &mut impl Iterator<Item = &'a str>,
+) -> Option<usize> {
+    if chars.peek() == Some(&'*') {
+        chars.next();
+        return Some(args.next().and_then(parse_integer).unwrap_or(0).max(0) as usize);
+    }
+    let mut digits = String::new();
+    while let Some(d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        digits.push(*d);
+        chars.next();
+    }
+    digits.parse().ok()
+}
+
+/// Formats `args` with `format` once. Returns the output, whether a `\c`
+/// stopped it, and how many arguments were used.
+fn format_once(format: &str, args: &[&str], errors: &mut Vec<String>) -> Result<(String, bool, usize), String> {
+    let mut out = String::new();
+    let mut used = args.iter().copied();
+    let mut consumed = 0;
+    let mut chars = format.chars().peekable();
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            literal.push(c);
+            if let Some(next) = chars.next() {
+                literal.push(next);
+            }
+            continue;
+        }
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+        let (text, stop) = unescape(&std::mem::take(&mut literal), false);
+        out.push_str(&text);
+        if stop {
+            return Ok((out, true, consumed));
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            out.push('%');
+            continue;
+        }
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero = true,
+                '#' => spec.alternate = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        let before = used.clone().count();
+        spec.width = number(&mut chars, &mut used);
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(number(&mut chars, &mut used).unwrap_or(0));
+        }
+        let Some(conversion) = chars.next().filter(|c| "sbcdiuoxXfFeEgG".contains(*c)) else {
+            return Err(msg!("print.bad_conversion", format));
+        };
+        let arg = used.next();
+        consumed += before - used.clone().count();
+        let (text, stop) = convert(&spec, conversion, arg, errors);
+        out.push_str(&text);
+        if stop {
+            return Ok((out, true, consumed));
+        }
+    }
+    let (text, stop) = unescape(&literal, false);
+    out.push_str(&text);
+    Ok((out, stop, consumed))
+}
+
+/// `printf format [args...]`: formats the arguments like C's `printf`,
+/// reusing `format` until every argument has been used.
+pub fn printf(args: &[&str]) -> i32 {
+    let Some((format, mut rest)) = args.split_first() else {
+        println!("{}", msg!("usage.printf"));
+        return 2;
+    };
+    let mut out = String::new();
+    let mut errors = Vec::new();
+    loop {
+        match format_once(format, rest, &mut errors) {
+            Ok((text, stop, consumed)) => {
+                out.push_str(&text);
+                rest = &rest[consumed.min(rest.len())..];
+                if stop || consumed == 0 || rest.is_empty() {
+                    break;
+                }
+            }
+            Err(e) => {
+                write_out(&out);
+                println!("printf: {}", e);
+                return 1;
+            }
+        }
+    }
+    let status = write_out(&out);
+    for error in &errors {
+        println!("printf: {}", error);
+    }
+    if errors.is_empty() { status } else { 1 }
+}
@@ -0,0 +1,187 @@
+//! Process substitution: `<(cmd)` and `>(cmd)`.
+//!
+//! A word `<(cmd)` is replaced by the name of a file that reads what `cmd`
+//! prints, so `diff <(ls a) <(ls b)` compares two listings; `>(cmd)` names
+//! a file whose writes go to `cmd`'s input. On Unix the file is
+//! `/dev/fd/N`, one end of a pipe the command inherits. Windows has no such
+//! names, so there `<(cmd)` runs `cmd` to completion into a temporary file
+//! first, and `>(cmd)` reads its temporary file after the command is done.
+//!
+//! The substitutions last as long as the command using them: when it
+//! finishes, the shell closes its ends of the pipes, waits for the `>(cmd)`
+//! readers so their output comes before the next prompt, and leaves the
+//! `<(cmd)` writers to the reaper.
+
+use std::process::{Child, Command, Stdio};
+
+use crate::Shell;
+use crate::ifs;
+use crate::jobs;
+use crate::lookup;
+use crate::messages::msg;
+use crate::words;
+
+/// The processes and pipes of one command's substitutions.
+#[derive(Default)]
+pub struct Substitutions {
+    /// `<(cmd)` processes, writing.
+    writers: Vec<Child>,
+    /// `>(cmd)` processes, reading.
+    readers: Vec<Child>,
+    /// The shell's ends of the pipes, open until the command is done.
+    #[cfg(unix)]
+    ends: Vec<std::os::fd::OwnedFd>,
+    /// Temporary files, each with the `>(cmd)` to feed it to.
+    #[cfg(windows)]
+    files: Vec<(std::path::PathBuf, Option<Vec<String>>)>,
+    /// Set for a background command, which outlives the substitution.
+    background: bool,
+}
+
+impl Substitutions {
+    /// Keeps the `>(cmd)` readers running once the command is started,
+    /// rather than waiting for them.
+    pub fn detach(&mut self) {
+        self.background = true;
+    }
+
+    #[cfg(unix)]
+    fn open(&mut self, words: &[String], input: bool) -> std::io::Result<String> {
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+        let mut fds = [0; 2];
+        // SAFETY: `pipe` fills in two new descriptors, owned from here on.
+        let (read, write) = unsafe {
+            if libc::pipe(fds.as_mut_ptr()) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for fd in fds {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+            (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))
+        };
+        // The command using the substitution gets the shell's end; the
+        // process gets the other as its stdin or stdout.
+        let (ours, theirs) = if input { (read, write) } else { (write, read) };
+        let path = format!("/dev/fd/{}", ours.as_raw_fd());
+        self.ends.push(ours);
+        let mut command = command_for(words);
+        if input {
+            command.stdin(Stdio::null()).stdout(theirs);
+        } else {
+            command.stdin(theirs);
+        }
+        let child = command.spawn()?;
+        if input { self.writers.push(child) } else { self.readers.push(child) }
+        Ok(path)
+    }
+
+    #[cfg(windows)]
+    fn open(&mut self, words: &[String], input: bool) -> std::io::Result<String> {
+        let (file, path) = crate::term::temp_file("procsub")?;
+        self.files.push((path.clone(), (!input).then(|| words.to_vec())));
+        if input {
+            command_for(words).stdin(Stdio::null()).stdout(file).status()?;
+        }
+        Ok(path.display().to_string())
+    }
+
+    /// Lets the command using the substitutions inherit the shell's ends,
+    /// once every process is started (so none of them holds another's).
+    #[cfg(unix)]
+    fn share(&self) {
+        use std::os::fd::AsRawFd;
+        for end in &self.ends {
+            // SAFETY: clears a flag on a descriptor the shell owns.
+            unsafe { libc::fcntl(end.as_raw_fd(), libc::F_SETFD, 0) };
+        }
+    }
+}
+
+impl Drop for Substitutions {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        self.ends.clear();
+        #[cfg(windows)]
+        for (path, reader) in self.files.drain(..) {
+            if let Some(words) = reader
+                && let Ok(file) = std::fs::File::open(&path)
+                && let Ok(child) = command_for(&words).stdin(file).spawn()
+            {
+                self.readers.push(child);
+            }
+            let _ = std::fs::remove_file(path);
+        }
+        for child in self.writers.drain(..) {
+            jobs::reap_later(child);
+        }
+        for mut child in self.readers.drain(..) {
+            if self.background {
+                jobs::reap_later(child);
+            } else {
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+fn command_for(words: &[String]) -> Command {
+    let mut command = lookup::command_for(&words[0]);
+    command.args(&words[1..]);
+    command
+}
+
+/// The byte offset of the `)` closing the `(` just before `text`.
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(i),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Replaces each `<(cmd)` and `>(cmd)` word in `text` with a file name,
+/// starting the processes behind them. `None` if there are none.
+pub fn substitute(shell: &Shell, text: &str) -> Option<Result<(String, Substitutions), String>> {
+    let starts = |i: usize| {
+        (text[i..].starts_with("<(") || text[i..].starts_with(">("))
+            && text[..i].chars().next_back().is_none_or(char::is_whitespace)
+    };
+    let first = text.char_indices().map(|(i, _)| i).find(|&i| starts(i))?;
+    let ifs = ifs::current(&shell.variables, shell.posix);
+    let mut substitutions = Substitutions::default();
+    let mut out = text[..first].to_string();
+    let mut rest = &text[first..];
+    while !rest.is_empty() {
+        let at_word = out.chars().next_back().is_none_or(char::is_whitespace);
+        if at_word && (rest.starts_with("<(") || rest.starts_with(">(")) {
+            let Some(end) = closing_paren(&rest[2..]) else {
+                return Some(Err(msg!("procsub.unterminated")));
+            };
+            let inner = crate::expand_env_vars(&rest[2..2 + end], shell, ifs.as_deref());
+            let words: Vec<String> = words::split(&inner).iter().map(|word| ifs::restore(word)).collect();
+            if words.is_empty() {
+                return Some(Err(msg!("procsub.empty")));
+            }
+            match substitutions.open(&words, rest.starts_with('<')) {
+                Ok(path) => out.push_str(&path),
+                Err(e) => {
+                    println!("{}: {}", words[0], e);
+                    out.push_str(if cfg!(windows) { "NUL" } else { "/dev/null" });
+                }
+            }
+            rest = &rest[2 + end + 1..];
+            continue;
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    #[cfg(unix)]
+    substitutions.share();
+    Some(Ok((out, substitutions)))
+}
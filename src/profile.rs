@@ -0,0 +1,164 @@
+use std::env;
+
+use crate::config::Config;
+
+/// Profiles available without any configuration.
+const BUILTIN_PROFILES: [&str; 4] = ["default", "minimal", "full", "presentation"];
+
+/// Applied at startup when `prompt.auto` is unset: the first `VAR=profile`
+/// whose variable is set in the environment picks the profile.
+const DEFAULT_RULES: [&str; 4] = [
+    "ASCIINEMA_REC=presentation",
+    "OCTANE_PRESENTATION=presentation",
+    "SSH_CONNECTION=minimal",
+    "SSH_TTY=minimal",
+];
+
+/// Which parts of the prompt to draw.
+///
+/// Built-in profiles can be overridden, and new ones defined, under
+/// `[prompt.profiles.<name>]` with the same keys as the fields below.
+pub struct Profile {
+    pub name: String,
+    /// Optional segments to show; `None` uses `prompt.segments`.
+    pub segments: Option<Vec<String>>,
+    pub git: bool,
+    /// The container/VM badge.
+    pub machine: bool,
+    /// `user@host` before the working directory.
+    pub hostname: bool,
+    /// Only the last component of the working directory.
+    pub short_path: bool,
+    /// Blank lines printed above each prompt.
+    pub spacing: usize,
+    /// Segments on one line and the input on the next; `None` uses
+    /// `prompt.multiline`.
+    pub multiline: Option<bool>,
+}
+
+impl Profile {
+    fn builtin(name: &str) -> Option<Profile> {
+        let mut profile = Profile {
+            name: name.to_string(),
+            segments: None,
+            git: true,
+            machine: true,
+            hostname: false,
+            short_path: false,
+            spacing: 0,
+            multiline: None,
+        };
+        match name {
+            "default" => {}
+            "minimal" => {
+                profile.segments = Some(Vec::new());
+                profile.machine = false;
+                profile.short_path = true;
+            }
+            "full" => profile.hostname = true,
+            "presentation" => {
+                profile.segments = Some(Vec::new());
+                profile.machine = false;
+                profile.spacing = 1;
+            }
+            _ => return None,
+        }
+        Some(profile)
+    }
+
+    /// Loads a profile by name, layering `[prompt.profiles.<name>]` over the
+    /// built-in profile of the same name (or `default`).
+    pub fn load(name: &str, config: &Config) -> Result<Profile, String> {
+        let key = format!("prompt.profiles.{}", name);
+        let configured = config.has(&key);
+        let mut profile = match Profile::builtin(name) {
+            Some(profile) => profile,
+            None if configured => Profile {
+                name: name.to_string(),
+                ..Profile::builtin("default").unwrap()
+            },
+            None => return Err(format!("unknown prompt profile: {}", name)),
+        };
+        if configured {
+            if config.has(&format!("{}.segments", key)) {
+                profile.segments = Some(config.get_str_list(&format!("{}.segments", key)));
+            }
+            let flag = |field: &str, default: bool| {
+                config.get_bool(&format!("{}.{}", key, field)).unwrap_or(default)
+            };
+            profile.git = flag("git", profile.git);
+            profile.machine = flag("machine", profile.machine);
+            profile.hostname = flag("hostname", profile.hostname);
+            profile.short_path = flag("short_path", profile.short_path);
+            if let Some(multiline) = config.get_bool(&format!("{}.multiline", key)) {
+                profile.multiline = Some(multiline);
+            }
+            if let Some(spacing) = config.get_int(&format!("{}.spacing", key)) {
+                profile.spacing = spacing.max(0) as usize;
+            }
+        }
+        Ok(profile)
+    }
+
+    /// The profile to start with: `prompt.profile` if set, else the first
+    /// matching `prompt.auto` rule, else `default`.
+    pub fn from_config(config: &Config) -> Profile {
+        if let Some(name) = config.get_str("prompt.profile") {
+            match Profile::load(name, config) {
+                Ok(profile) => return profile,
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+        let rules = if config.has("prompt.auto") {
+            config.get_str_list("prompt.auto")
+        } else {
+            DEFAULT_RULES.iter().map(|r| r.to_string()).collect()
+        };
+        let matched = rules.iter().find_map(|rule| {
+            let (var, name) = rule.split_once('=')?;
+            env::var_os(var.trim()).is_some().then(|| name.trim())
+        });
+        matched
+            .and_then(|name| Profile::load(name, config).ok())
+            .unwrap_or_else(|| Profile::builtin("default").unwrap())
+    }
+
+    /// Built-in and configured profile names.
+    pub fn available(config: &Config) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_PROFILES.iter().map(|n| n.to_string()).collect();
+        for name in config.keys("prompt.profiles") {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
+
+/// `prompt [use <name>]`: lists the prompt profiles or switches to one for
+/// this session.
+pub fn run(args: &[&str], config: &Config, current: &mut Profile) -> i32 {
+    match args {
+        [] => {
+            for name in Profile::available(config) {
+                let marker = if name == current.name { "*" } else { " " };
+                println!("{} {}", marker, name);
+            }
+            0
+        }
+        ["use", name] => match Profile::load(name, config) {
+            Ok(profile) => {
+                *current = profile;
+                0
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+                1
+            }
+        },
+        _ => {
+            println!("Usage: prompt [use <profile>]");
+            2
+        }
+    }
+}
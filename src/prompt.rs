@@ -0,0 +1,126 @@
+use std::env;
+
+use git2::Repository;
+
+use crate::clock;
+use crate::cloud;
+use crate::config::Config;
+use crate::focus;
+use crate::machine;
+use crate::profile::Profile;
+use crate::slowfs;
+use crate::term;
+use crate::theme::Theme;
+use crate::took;
+use crate::weather;
+
+/// The current branch and whether the working tree is dirty, `None` when
+/// that isn't known (yet) in a slow place.
+fn get_git_branch(mode: slowfs::Git) -> Option<(String, Option<bool>)> {
+    let repo = Repository::discover(".").ok()?;
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+    let branch_name = head.shorthand()?.to_string();
+
+    let dirty = match mode {
+        slowfs::Git::Full => Some(is_dirty(&repo)?),
+        slowfs::Git::Defer => slowfs::deferred(repo.path(), |path| is_dirty(&Repository::open(path).ok()?)),
+        _ => None,
+    };
+
+    Some((branch_name, dirty))
+}
+
+fn is_dirty(repo: &Repository) -> Option<bool> {
+    let statuses = repo.statuses(None).ok()?;
+    Some(statuses.iter().any(|entry| {
+        let s = entry.status();
+        s.is_index_new() || s.is_index_modified() || s.is_wt_modified() || s.is_wt_new()
+    }))
+}
+
+/// Optional segments enabled through `prompt.segments` in the config.
+fn render_segment(name: &str, config: &Config) -> Option<String> {
+    match name {
+        "weather" => weather::segment(config),
+        "clock" => clock::segment(config),
+        "kube" => cloud::kube_segment(),
+        "aws" => cloud::aws_segment(),
+        "focus" => focus::segment(),
+        "took" => took::segment(),
+        _ => None,
+    }
+}
+
+fn with_icon(icon: &str, text: String) -> String {
+    if icon.is_empty() {
+        text
+    } else {
+        format!("{} {}", icon, text)
+    }
+}
+
+fn user() -> String {
+    env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_default()
+}
+
+pub fn build_prompt(config: &Config, theme: &Theme, profile: &Profile, last_status: i32) -> String {
+    let cwd = env::current_dir().unwrap_or_else(|_| ".".into());
+    let path = if profile.short_path {
+        match cwd.file_name() {
+            Some(name) if term::display_path(&cwd) != "~" => name.to_string_lossy().to_string(),
+            _ => term::display_path(&cwd),
+        }
+    } else {
+        cwd.to_string_lossy().to_string()
+    };
+
+    let mut segments = vec![("label".to_string(), String::new())];
+    if profile.hostname {
+        let host = match user() {
+            user if user.is_empty() => term::hostname(),
+            user => format!("{}@{}", user, term::hostname()),
+        };
+        segments.push(("host".to_string(), host));
+    }
+    segments.push(("cwd".to_string(), path));
+
+    let git = if profile.git { slowfs::git_mode(config, &cwd) } else { slowfs::Git::Off };
+    if git != slowfs::Git::Off
+        && let Some((branch, dirty)) = get_git_branch(git)
+    {
+        let role = if dirty == Some(true) { "git_dirty" } else { "git_clean" };
+        // A `?` marks a branch whose state isn't known.
+        let branch = if dirty.is_some() { branch } else { format!("{}?", branch) };
+        segments.push((role.to_string(), with_icon(theme.icon("git"), branch)));
+    }
+
+    if profile.machine
+        && config.get_bool("prompt.machine").unwrap_or(true)
+        && let Some(badge) = machine::badge()
+    {
+        segments.push(("machine".to_string(), with_icon(theme.icon("machine"), badge.to_string())));
+    }
+
+    let names = match &profile.segments {
+        Some(names) => names.clone(),
+        None => config.get_str_list("prompt.segments"),
+    };
+    for name in names {
+        let text = match name.as_str() {
+            "status" => (last_status != 0).then(|| last_status.to_string()),
+            _ => render_segment(&name, config),
+        };
+        if let Some(text) = text {
+            let text = with_icon(theme.icon(&name), text);
+            segments.push((name, text));
+        }
+    }
+
+    let multiline = profile
+        .multiline
+        .unwrap_or_else(|| config.get_bool("prompt.multiline").unwrap_or(false));
+    theme.render(&segments, multiline)
+}
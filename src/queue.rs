@@ -0,0 +1,272 @@
+//! A FIFO of long-running commands, run one after another in the background.
+//!
+//! `queue add -- cmd` appends a command; a worker thread runs the queue in
+//! order with all output appended to a single log (`queue.log` in the data
+//! directory), and a notice is shown at the prompt as each command finishes.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+
+use crate::config;
+use crate::focus;
+use crate::jobs;
+use crate::messages::msg;
+use crate::signals;
+
+struct Task {
+    id: usize,
+    command: Vec<String>,
+}
+
+struct Running {
+    id: usize,
+    command: String,
+    pid: u32,
+    started: Instant,
+    cancelled: bool,
+}
+
+struct State {
+    pending: VecDeque<Task>,
+    running: Option<Running>,
+    next_id: usize,
+    /// Notices for commands that finished since the last prompt.
+    finished: Vec<String>,
+    worker_started: bool,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    pending: VecDeque::new(),
+    running: None,
+    next_id: 1,
+    finished: Vec::new(),
+    worker_started: false,
+});
+/// Wakes the worker when a command is queued.
+static QUEUED: Condvar = Condvar::new();
+
+fn log_path() -> PathBuf {
+    config::data_dir().join("queue.log")
+}
+
+fn open_log() -> std::io::Result<File> {
+    let path = log_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// `45s`, `3m07s`, `2h05m`.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{:02}s", m, s),
+        (h, m, _) => format!("{}h{:02}m", h, m),
+    }
+}
+
+/// Runs one task to completion, returning the notice to show for it.
+fn run_task(task: Task) -> String {
+    let line = task.command.join(" ");
+    let mut log = match open_log() {
+        Ok(log) => log,
+        Err(e) => return msg!("queue.failed", task.id, line, e),
+    };
+    let _ = writeln!(log, "=== [{}] {} (started {})", task.id, line, Local::now().format("%Y-%m-%d %H:%M:%S"));
+
+    let mut process = Command::new(&task.command[0]);
+    process.args(&task.command[1..]).stdin(Stdio::null());
+    if let (Ok(out), Ok(err)) = (log.try_clone(), log.try_clone()) {
+        process.stdout(out).stderr(err);
+    }
+    // Keep the command out of the shell's process group so Ctrl-C at the
+    // prompt doesn't reach it. (Not `jobs::spawn`, which may hand the
+    // terminal back to the shell under a foreground job.)
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        process.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        process.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    let started = Instant::now();
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = writeln!(log, "=== [{}] failed to start: {}", task.id, e);
+            return msg!("queue.failed", task.id, line, e);
+        }
+    };
+    STATE.lock().unwrap().running = Some(Running {
+        id: task.id,
+        command: line.clone(),
+        pid: child.id(),
+        started,
+        cancelled: false,
+    });
+    let status = child.wait().map(crate::status_code).unwrap_or(1);
+    let cancelled = STATE.lock().unwrap().running.take().is_some_and(|r| r.cancelled);
+    let elapsed = format_elapsed(started.elapsed());
+    let _ = writeln!(log, "=== [{}] exit {} after {}", task.id, status, elapsed);
+
+    let outcome = if cancelled {
+        msg!("queue.cancelled")
+    } else if status == 0 {
+        msg!("jobs.done")
+    } else {
+        msg!("jobs.exit", status)
+    };
+    msg!("queue.finished", task.id, format!("{:<12}", outcome), line, elapsed)
+}
+
+fn start_worker() {
+    thread::spawn(|| {
+        loop {
+            let task = {
+                let mut state = STATE.lock().unwrap();
+                loop {
+                    if let Some(task) = state.pending.pop_front() {
+                        break task;
+                    }
+                    state = QUEUED.wait(state).unwrap();
+                }
+            };
+            let notice = run_task(task);
+            STATE.lock().unwrap().finished.push(notice);
+        }
+    });
+}
+
+/// Reports queued commands that finished since the last prompt. Held back
+/// while a focus session is active.
+pub fn notify() {
+    if focus::active() {
+        return;
+    }
+    let finished = std::mem::take(&mut STATE.lock().unwrap().finished);
+    for notice in finished {
+        println!("{}", notice);
+    }
+}
+
+fn list() {
+    let state = STATE.lock().unwrap();
+    if let Some(running) = &state.running {
+        println!(
+            "[{}] {} {:<8} {}",
+            running.id,
+            msg!("queue.running"),
+            format_elapsed(running.started.elapsed()),
+            running.command
+        );
+    }
+    for task in &state.pending {
+        println!("[{}] {} {:<8} {}", task.id, msg!("queue.pending"), "", task.command.join(" "));
+    }
+}
+
+fn cancel(which: &str) -> i32 {
+    let mut state = STATE.lock().unwrap();
+    if which == "all" {
+        state.pending.clear();
+        if let Some(running) = &mut state.running {
+            running.cancelled = true;
+            let _ = jobs::send_signal(running.pid, signals::SIGTERM, true);
+        }
+        return 0;
+    }
+    let Ok(id) = which.parse::<usize>() else {
+        println!("{}", msg!("usage.queue_cancel"));
+        return 2;
+    };
+    if let Some(running) = state.running.as_mut().filter(|r| r.id == id) {
+        running.cancelled = true;
+        if let Err(e) = jobs::send_signal(running.pid, signals::SIGTERM, true) {
+            println!("queue: {}", e);
+            return 1;
+        }
+        return 0;
+    }
+    let before = state.pending.len();
+    state.pending.retain(|t| t.id != id);
+    if state.pending.len() == before {
+        println!("{}", msg!("queue.no_such_command", id));
+        return 1;
+    }
+    0
+}
+
+fn show_log(lines: usize) -> i32 {
+    let text = match fs::read_to_string(log_path()) {
+        Ok(text) => text,
+        Err(_) => {
+            println!("{}", msg!("queue.nothing_run"));
+            return 1;
+        }
+    };
+    let all: Vec<&str> = text.lines().collect();
+    for line in &all[all.len().saturating_sub(lines)..] {
+        println!("{}", line);
+    }
+    0
+}
+
+/// `queue add [--] <command>` / `queue list` / `queue cancel <id|all>` /
+/// `queue log [n]`
+pub fn run(args: &[&str]) -> i32 {
+    match args {
+        ["add", rest @ ..] => {
+            let command = rest.strip_prefix(&["--"]).unwrap_or(rest);
+            if command.is_empty() {
+                println!("{}", msg!("usage.queue_add"));
+                return 2;
+            }
+            let mut state = STATE.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.pending.push_back(Task {
+                id,
+                command: command.iter().map(|s| s.to_string()).collect(),
+            });
+            if !state.worker_started {
+                state.worker_started = true;
+                start_worker();
+            }
+            QUEUED.notify_one();
+            println!("{}", msg!("queue.queued", id, state.pending.len() - 1 + state.running.is_some() as usize));
+            0
+        }
+        [] | ["list"] => {
+            list();
+            0
+        }
+        ["cancel", which] => cancel(which),
+        ["log"] => show_log(20),
+        ["log", n] => match n.parse() {
+            Ok(n) => show_log(n),
+            Err(_) => {
+                println!("{}", msg!("usage.queue_log"));
+                2
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.queue"));
+            2
+        }
+    }
+}
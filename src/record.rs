@@ -0,0 +1,148 @@
+//! The `record` builtin: a log of the session, for auditing and for
+//! reproducing what happened while debugging.
+//!
+//! `record start session.log` appends every command line from then on to
+//! the file, after the prompt it was typed at, then the output of the
+//! programs it ran and its exit status, each line with the time.
+//! `record stop` ends it. Secrets are masked in output as in command lines
+//! (`redact.patterns`). Output is taken from the pipes the shell copies
+//! captured output from (see `capture`), so the output of builtins and of
+//! full-screen and interactive programs (`capture.exclude`) isn't recorded;
+//! their command lines and statuses are.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::config::{self, Config};
+use crate::messages::msg;
+use crate::redact::Redactor;
+use crate::term;
+use crate::took;
+
+struct Recording {
+    file: File,
+    path: PathBuf,
+    /// Masks secrets in output, as in command lines.
+    redactor: Redactor,
+    /// A command line was logged and its status isn't yet.
+    pending: bool,
+    /// The last output written didn't end its line.
+    mid_line: bool,
+}
+
+static RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+
+fn stamp() -> String {
+    format!("[{}]", Local::now().format("%H:%M:%S%.3f"))
+}
+
+/// Appends `text` to the log, ending the recording if that fails.
+fn write(recording: &mut Option<Recording>, text: &str) {
+    if let Some(r) = recording
+        && let Err(e) = r.file.write_all(text.as_bytes())
+    {
+        println!("record: {}", msg!("error.path", r.path.display(), e));
+        *recording = None;
+    }
+}
+
+pub fn active() -> bool {
+    RECORDING.lock().unwrap().is_some()
+}
+
+/// Logs the command line `line`, typed at `prompt`.
+pub fn command(prompt: &str, line: &str) {
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(r) = recording.as_mut() else {
+        return;
+    };
+    r.pending = true;
+    r.mid_line = false;
+    let prompt = prompt.lines().last().unwrap_or_default();
+    write(&mut recording, &format!("{} {}{}\n", stamp(), prompt, line));
+}
+
+/// Logs a line of output, or the end of the output when it has no newline,
+/// without its colors and with secrets masked.
+pub fn output(text: &str, newline: bool) {
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(r) = recording.as_mut() else {
+        return;
+    };
+    let start = if r.mid_line { String::new() } else { format!("{}   ", stamp()) };
+    r.mid_line = !newline;
+    let text = r.redactor.redact(&term::strip_ansi(text));
+    write(&mut recording, &format!("{}{}{}", start, text, if newline { "\n" } else { "" }));
+}
+
+/// Logs the status of the command line, which took `elapsed`.
+pub fn finished(status: i32, elapsed: Duration) {
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(r) = recording.as_mut().filter(|r| r.pending) else {
+        return;
+    };
+    let end = if r.mid_line { "\n" } else { "" };
+    r.pending = false;
+    r.mid_line = false;
+    write(&mut recording, &format!("{}{} {}\n", end, stamp(), msg!("record.status", status, took::format(elapsed))));
+}
+
+/// `record [status]`, `record start <file>` or `record stop`
+pub fn run(config: &Config, args: &[&str]) -> i32 {
+    let mut recording = RECORDING.lock().unwrap();
+    match args {
+        [] | ["status"] => match recording.as_ref() {
+            Some(r) => println!("{}", msg!("record.recording", r.path.display())),
+            None => println!("{}", msg!("record.not_recording")),
+        },
+        ["start", file] => {
+            // Absolute, so `cd` doesn't change which file is meant.
+            let path = std::env::current_dir().unwrap_or_default().join(config::expand_home(file));
+            let file = match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    println!("record: {}", msg!("error.path", path.display(), e));
+                    return 1;
+                }
+            };
+            // Starting again moves the recording to the new file.
+            let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+            write(&mut recording, &format!("=== {} ===\n", msg!("record.stopped", now)));
+            *recording = Some(Recording {
+                file,
+                path: path.clone(),
+                redactor: Redactor::from_config(config),
+                pending: false,
+                mid_line: false,
+            });
+            write(&mut recording, &format!("=== {} ===\n", msg!("record.started", now)));
+            if recording.is_some() {
+                println!("{}", msg!("record.recording", path.display()));
+            }
+        }
+        ["stop"] => {
+            let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+            if let Some(r) = recording.as_mut() {
+                r.pending = false;
+            }
+            write(&mut recording, &format!("=== {} ===\n", msg!("record.stopped", now)));
+            match recording.take() {
+                Some(r) => println!("{}", msg!("record.saved", r.path.display())),
+                None => {
+                    println!("record: {}", msg!("record.not_recording"));
+                    return 1;
+                }
+            }
+        }
+        _ => {
+            println!("{}", msg!("usage.record"));
+            return 2;
+        }
+    }
+    0
+}
@@ -0,0 +1,562 @@
+//! A small regular expression engine, for output filters.
+//!
+//! The usual syntax is understood: literal characters, `.`, classes such
+//! as `[a-z_]` and `[^0-9]`, the escapes `\d`, `\w` and `\s` and their
+//! negations `\D`, `\W` and `\S`, the word boundaries `\b` and `\B`, the
+//! anchors `^` and `$`, groups `(...)` and `(?:...)`, alternation `|`, and
+//! the quantifiers `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}`, greedy or,
+//! followed by `?`, lazy. Any other character after a backslash stands for
+//! itself (`\.`, `\|`). There are no captures or backreferences.
+//!
+//! Matching backtracks over the characters of a line. It gives up on a
+//! line after a fixed amount of work, so a pattern such as `(a*)*b` can't
+//! stall the output.
+
+use std::cell::Cell;
+use std::ops::Range;
+
+use crate::messages::msg;
+
+/// Matching steps allowed per line.
+const MAX_STEPS: usize = 200_000;
+/// Nested matching calls allowed, to stay well within a thread's stack.
+const MAX_DEPTH: usize = 1_000;
+
+#[derive(Clone)]
+enum Item {
+    Range(char, char),
+    /// `\d`, `\w`, `\s`, or with `false` their negations.
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+#[derive(Clone)]
+enum Node {
+    Char(char),
+    Any,
+    /// The items, and whether the class is negated.
+    Class(Vec<Item>, bool),
+    Start,
+    End,
+    /// `\b`, or with `false` `\B`.
+    Boundary(bool),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Repeat),
+}
+
+/// A quantified node.
+#[derive(Clone)]
+struct Repeat {
+    node: Box<Node>,
+    min: usize,
+    max: Option<usize>,
+    greedy: bool,
+}
+
+#[derive(Clone)]
+pub struct Regex {
+    node: Node,
+    fold: bool,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.eat('|') {
+            alternatives.push(self.sequence()?);
+        }
+        Ok(if alternatives.len() == 1 { alternatives.remove(0) } else { Node::Alt(alternatives) })
+    }
+
+    fn sequence(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek()
+            && c != '|'
+            && c != ')'
+        {
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    /// `{n}`, `{n,}` or `{n,m}` at the parser's position, consumed if it is
+    /// one; otherwise `{` is an ordinary character.
+    fn counts(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest: String = self.chars[self.at..].iter().collect();
+        let end = rest.find('}')?;
+        let (min, max) = match rest[1..end].split_once(',') {
+            None => {
+                let n = rest[1..end].parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        self.at += rest[..=end].chars().count();
+        Some((min, max))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.counts() {
+                Some((min, max)) if max.is_none_or(|max| min <= max) => {
+                    let greedy = !self.eat('?');
+                    return Ok(Node::Repeat(Repeat { node: Box::new(atom), min, max, greedy }));
+                }
+                Some(_) => return Err(msg!("regex.bad_count")),
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        self.at += 1;
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat(Repeat { node: Box::new(atom), min, max, greedy }))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().unwrap_or_default();
+        self.at += 1;
+        Ok(match c {
+            '(' => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err(msg!("regex.nothing_to_repeat", '?'));
+                }
+                let inner = self.alternation()?;
+                if !self.eat(')') {
+                    return Err(msg!("regex.unclosed_group"));
+                }
+                inner
+            }
+            '[' => self.class()?,
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '*' | '+' | '?' => return Err(msg!("regex.nothing_to_repeat", c)),
+            '\\' => match self.escape()? {
+                Escape::Char(c) => Node::Char(c),
+                Escape::Item(item) => Node::Class(vec![item], false),
+                Escape::Boundary(word) => Node::Boundary(word),
+            },
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Escape, String> {
+        let c = self.peek().ok_or_else(|| msg!("regex.trailing_backslash"))?;
+        self.at += 1;
+        Ok(match c {
+            'd' | 'D' => Escape::Item(Item::Digit(c == 'd')),
+            'w' | 'W' => Escape::Item(Item::Word(c == 'w')),
+            's' | 'S' => Escape::Item(Item::Space(c == 's')),
+            'b' | 'B' => Escape::Boundary(c == 'b'),
+            'n' => Escape::Char('\n'),
+            't' => Escape::Char('\t'),
+            'r' => Escape::Char('\r'),
+            c => Escape::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| msg!("regex.unclosed_class"))?;
+            self.at += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = match c {
+                '\\' => match self.escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Item(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                    Escape::Boundary(word) => {
+                        if word {
+                            'b'
+                        } else {
+                            'B'
+                        }
+                    }
+                },
+                c => c,
+            };
+            if self.peek() == Some('-') && self.chars.get(self.at + 1).is_some_and(|&c| c != ']') {
+                self.at += 1;
+                let high = match self.peek() {
+                    Some('\\') => {
+                        self.at += 1;
+                        match self.escape()? {
+                            Escape::Char(c) => c,
+                            _ => return Err(msg!("regex.bad_range")),
+                        }
+                    }
+                    Some(c) => {
+                        self.at += 1;
+                        c
+                    }
+                    None => return Err(msg!("regex.unclosed_class")),
+                };
+                if high < low {
+                    return Err(msg!("regex.bad_range"));
+                }
+                items.push(Item::Range(low, high));
+            } else {
+                items.push(Item::Range(low, low));
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+}
+
+enum Escape {
+    Char(char),
+    Item(Item),
+    Boundary(bool),
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn upper(c: char) -> char {
+    c.to_uppercase().next().unwrap_or(c)
+}
+
+struct Matcher<'a> {
+    chars: &'a [char],
+    fold: bool,
+    steps: Cell<usize>,
+    depth: Cell<usize>,
+}
+
+impl Matcher<'_> {
+    fn same(&self, a: char, b: char) -> bool {
+        a == b || (self.fold && lower(a) == lower(b))
+    }
+
+    fn in_item(&self, item: &Item, c: char) -> bool {
+        match *item {
+            Item::Range(low, high) => {
+                let inside = |c: char| low <= c && c <= high;
+                inside(c) || (self.fold && (inside(lower(c)) || inside(upper(c))))
+            }
+            Item::Digit(yes) => c.is_ascii_digit() == yes,
+            Item::Word(yes) => is_word(c) == yes,
+            Item::Space(yes) => c.is_whitespace() == yes,
+        }
+    }
+
+    /// Whether `node` matches the one character at `i`, for nodes that
+    /// always match exactly one.
+    fn single(&self, node: &Node, i: usize) -> Option<bool> {
+        let c = self.chars.get(i).copied();
+        match node {
+            Node::Char(want) => Some(c.is_some_and(|c| self.same(c, *want))),
+            Node::Any => Some(c.is_some_and(|c| c != '\n')),
+            Node::Class(items, negated) => {
+                Some(c.is_some_and(|c| items.iter().any(|item| self.in_item(item, c)) != *negated))
+            }
+            _ => None,
+        }
+    }
+
+    /// Matches `node` at `i`, then calls `next` with where the match ended,
+    /// trying the alternatives in order until `next` accepts one.
+    fn matches(&self, node: &Node, i: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        self.steps.set(self.steps.get() + 1);
+        if self.steps.get() > MAX_STEPS || self.depth.get() > MAX_DEPTH {
+            return false;
+        }
+        self.depth.set(self.depth.get() + 1);
+        let found = self.step(node, i, next);
+        self.depth.set(self.depth.get() - 1);
+        found
+    }
+
+    fn step(&self, node: &Node, i: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        if let Some(one) = self.single(node, i) {
+            return one && next(i + 1);
+        }
+        match node {
+            Node::Start => i == 0 && next(i),
+            Node::End => i == self.chars.len() && next(i),
+            Node::Boundary(want) => {
+                let before = i > 0 && is_word(self.chars[i - 1]);
+                let after = i < self.chars.len() && is_word(self.chars[i]);
+                (before != after) == *want && next(i)
+            }
+            Node::Concat(nodes) => self.sequence(nodes, i, next),
+            Node::Alt(alternatives) => alternatives.iter().any(|a| self.matches(a, i, next)),
+            Node::Repeat(repeat) => {
+                if self.single(&repeat.node, i).is_some() {
+                    self.run(repeat, i, next)
+                } else {
+                    self.repeat(repeat, 0, i, next)
+                }
+            }
+            Node::Char(_) | Node::Any | Node::Class(..) => {
+                unreachable!("single-character nodes are matched above")
+            }
+        }
+    }
+
+    fn sequence(&self, nodes: &[Node], i: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        match nodes.split_first() {
+            None => next(i),
+            Some((first, rest)) => self.matches(first, i, &mut |j| self.sequence(rest, j, next)),
+        }
+    }
+
+    /// A repeated single character, without recursing per repetition: how
+    /// far it can go is counted first, then the lengths tried in order.
+    fn run(&self, repeat: &Repeat, i: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        let Repeat { node, min, max, greedy } = repeat;
+        let limit = max.unwrap_or(usize::MAX);
+        let mut count = 0;
+        while count < limit && self.single(node, i + count) == Some(true) {
+            count += 1;
+        }
+        if count < *min {
+            return false;
+        }
+        let mut attempt = |n: usize| {
+            self.steps.set(self.steps.get() + 1);
+            self.steps.get() <= MAX_STEPS && next(i + n)
+        };
+        if *greedy { (*min..=count).rev().any(&mut attempt) } else { (*min..=count).any(&mut attempt) }
+    }
+
+    /// Any other repetition, `count` times matched so far.
+    fn repeat(&self, repeat: &Repeat, count: usize, i: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        let more = repeat.max.is_none_or(|max| count < max);
+        // A repetition that matched nothing can't help once `min` is met,
+        // and would go round forever.
+        let again = |next: &mut dyn FnMut(usize) -> bool| {
+            more && self.matches(&repeat.node, i, &mut |j| {
+                (j != i || count < repeat.min) && self.repeat(repeat, count + 1, j, next)
+            })
+        };
+        if repeat.greedy {
+            return again(next) || (count >= repeat.min && next(i));
+        }
+        (count >= repeat.min && next(i)) || again(next)
+    }
+}
+
+impl Regex {
+    /// Compiles `pattern`. With `fold`, letters match in either case.
+    pub fn new(pattern: &str, fold: bool) -> Result<Regex, String> {
+        let mut parser = Parser { chars: pattern.chars().collect(), at: 0 };
+        let node = parser.alternation()?;
+        if parser.at < parser.chars.len() {
+            return Err(msg!("regex.unmatched_paren"));
+        }
+        Ok(Regex { node, fold })
+    }
+
+    fn matcher<'a>(&self, chars: &'a [char]) -> Matcher<'a> {
+        Matcher { chars, fold: self.fold, steps: Cell::new(0), depth: Cell::new(0) }
+    }
+
+    /// Byte ranges of the matches in `text`, leftmost first, not
+    /// overlapping, and not empty.
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        let (offsets, chars): (Vec<usize>, Vec<char>) = text.char_indices().unzip();
+        let offset = |i: usize| offsets.get(i).copied().unwrap_or(text.len());
+        let matcher = self.matcher(&chars);
+        let mut found = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let mut end = None;
+            let matched = matcher.matches(&self.node, i, &mut |j| {
+                end = Some(j);
+                j > i
+            });
+            match end.filter(|_| matched) {
+                Some(j) => {
+                    found.push(offset(i)..offset(j));
+                    i = j;
+                }
+                None => i += 1,
+            }
+        }
+        found
+    }
+
+    /// Whether the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let matcher = self.matcher(&chars);
+        (0..=chars.len()).any(|i| matcher.matches(&self.node, i, &mut |_| true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of each match of `pattern` in `text`.
+    fn find<'a>(pattern: &str, text: &'a str) -> Vec<&'a str> {
+        Regex::new(pattern, false).unwrap().find_all(text).into_iter().map(|span| &text[span]).collect()
+    }
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern, false).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn literals_and_dot() {
+        assert_eq!(find("ab", "xabyab"), ["ab", "ab"]);
+        assert_eq!(find("a.c", "abc a-c ac a\nc"), ["abc", "a-c"]);
+        assert_eq!(find(r"\.", "a.b"), ["."]);
+        assert_eq!(find(r"\|", "a|b"), ["|"]);
+        assert!(!is_match("abc", "ab"));
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(find("[a-c]+", "xabcay"), ["abca"]);
+        assert_eq!(find("[^0-9 ]+", "ab12 cd"), ["ab", "cd"]);
+        assert_eq!(find("[-_]", "a-b_c"), ["-", "_"]);
+        assert_eq!(find("[]x]", "a]x"), ["]", "x"]);
+        assert_eq!(find(r"[\d.]+", "v1.25 x"), ["1.25"]);
+        assert_eq!(find(r"[\w-]+", "glpat-ab_c !"), ["glpat-ab_c"]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(find(r"\d+", "a12b3"), ["12", "3"]);
+        assert_eq!(find(r"\D+", "a12b3"), ["a", "b"]);
+        assert_eq!(find(r"\w+", "héllo, wörld_1"), ["héllo", "wörld_1"]);
+        assert_eq!(find(r"\W+", "a, b"), [", "]);
+        assert_eq!(find(r"\s+", "a \t b"), [" \t "]);
+        assert_eq!(find(r"\S+", "a \t bc"), ["a", "bc"]);
+        assert_eq!(find(r"a\tb", "a\tb"), ["a\tb"]);
+    }
+
+    #[test]
+    fn anchors_and_boundaries() {
+        assert!(is_match("^ab", "abc"));
+        assert!(!is_match("^ab", "cab"));
+        assert!(is_match("ab$", "cab"));
+        assert!(!is_match("ab$", "abc"));
+        assert!(is_match("^$", ""));
+        assert_eq!(find(r"\bcat\b", "cat concat cat."), ["cat", "cat"]);
+        assert_eq!(find(r"\Bcat", "cat concat"), ["cat"]);
+    }
+
+    #[test]
+    fn alternation_and_groups() {
+        assert_eq!(find("error|warning", "warning: error"), ["warning", "error"]);
+        assert_eq!(find("gr(a|e)y", "gray grey groy"), ["gray", "grey"]);
+        assert_eq!(find("(?:ab)+", "ababa"), ["abab"]);
+        assert_eq!(find("a|", "ba"), ["a"]);
+        // The first alternative that lets the rest match wins.
+        assert_eq!(find("(a|ab)c", "abc"), ["abc"]);
+    }
+
+    #[test]
+    fn repetition() {
+        assert_eq!(find("ab*", "a ab abbb"), ["a", "ab", "abbb"]);
+        assert_eq!(find("ab+", "a ab abbb"), ["ab", "abbb"]);
+        assert_eq!(find("colou?r", "color colour"), ["color", "colour"]);
+        assert_eq!(find("a{2}", "aaaaa"), ["aa", "aa"]);
+        assert_eq!(find("a{2,}", "a aaaaa"), ["aaaaa"]);
+        assert_eq!(find("a{1,2}", "aaa"), ["aa", "a"]);
+        assert_eq!(find("(ab){2}", "ababab"), ["abab"]);
+        assert_eq!(find("x{a}", "x{a}"), ["x{a}"]);
+    }
+
+    #[test]
+    fn lazy_repetition() {
+        assert_eq!(find("<.+>", "<a><b>"), ["<a><b>"]);
+        assert_eq!(find("<.+?>", "<a><b>"), ["<a>", "<b>"]);
+        assert_eq!(find("(ab)*?c", "ababc"), ["ababc"]);
+        assert_eq!(find("a{2,}?", "aaaaa"), ["aa", "aa"]);
+    }
+
+    #[test]
+    fn empty_repetitions_end() {
+        assert_eq!(find("(a*)*b", "aab"), ["aab"]);
+        assert_eq!(find("(a?)+", "aa"), ["aa"]);
+        assert!(find("x*", "yyy").is_empty());
+    }
+
+    #[test]
+    fn folding_case() {
+        let regex = Regex::new("error [a-c]", true).unwrap();
+        assert!(regex.is_match("ERROR B"));
+        assert!(regex.is_match("Error a"));
+        assert!(!Regex::new("error", false).unwrap().is_match("ERROR"));
+    }
+
+    #[test]
+    fn byte_ranges() {
+        let regex = Regex::new("ö+", false).unwrap();
+        assert_eq!(regex.find_all("aöö b ö"), [1..5, 8..10]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |pattern: &str| Regex::new(pattern, false).err();
+        assert_eq!(error("(ab"), Some(msg!("regex.unclosed_group")));
+        assert_eq!(error("ab)"), Some(msg!("regex.unmatched_paren")));
+        assert_eq!(error("[ab"), Some(msg!("regex.unclosed_class")));
+        assert_eq!(error("[z-a]"), Some(msg!("regex.bad_range")));
+        assert_eq!(error("a{3,1}"), Some(msg!("regex.bad_count")));
+        assert_eq!(error("*a"), Some(msg!("regex.nothing_to_repeat", '*')));
+        assert_eq!(error("a|+"), Some(msg!("regex.nothing_to_repeat", '+')));
+        assert_eq!(error("(?=a)"), Some(msg!("regex.nothing_to_repeat", '?')));
+        assert_eq!(error("ab\\"), Some(msg!("regex.trailing_backslash")));
+    }
+
+    #[test]
+    fn gives_up_on_runaway_backtracking() {
+        let line = "a".repeat(40);
+        let regex = Regex::new("(a*)*b", false).unwrap();
+        assert!(!regex.is_match(&line));
+        assert!(regex.find_all(&line).is_empty());
+        // Once it gives up, the rest of the line goes unmatched too.
+        let regex = Regex::new("(a|a)*x", false).unwrap();
+        assert!(!regex.is_match(&(line + " x")));
+        // The limit is per line: the next one is matched as usual.
+        assert!(regex.is_match("aax"));
+    }
+
+    #[test]
+    fn long_runs_of_one_character() {
+        let line = "x".repeat(100_000);
+        assert_eq!(Regex::new("x+", false).unwrap().find_all(&line), vec![0..line.len()]);
+    }
+}
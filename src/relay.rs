@@ -0,0 +1,267 @@
+//! Copying a command's output from pipes to the terminal.
+//!
+//! Commands whose output is captured, timestamped, filtered or tagged with
+//! their pipeline stage write to pipes instead of the terminal, and the
+//! shell copies what they write, line by line. Text without a newline yet,
+//! such as a prompt, is shown when no more arrives for a moment; filters
+//! only apply to whole lines.
+
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::capture;
+use crate::filter::{Filters, Lines};
+use crate::record;
+use crate::stamp::{self, Mode};
+
+/// How long to wait for the output pipes to drain after the command exits.
+/// Background processes started by the command may hold them open.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// What to do with the output on its way.
+pub struct Options {
+    /// Keep it for `marks`.
+    pub capture: bool,
+    /// Keep stdout for `$__`.
+    pub last: bool,
+    /// Copy both to the session log.
+    pub record: bool,
+    /// Timestamp each line, timing from the instant given.
+    pub stamping: Option<(Mode, Instant)>,
+    pub filters: Option<Filters>,
+    /// Put before each line: the stage of a pipeline it came from.
+    pub tag: Option<String>,
+}
+
+/// Routes the command's stderr through a pipe, and its stdout unless that
+/// goes to the next command of a pipeline.
+pub fn pipe(command: &mut Command, stdout: bool) {
+    if stdout {
+        command.stdout(Stdio::piped());
+    }
+    command.stderr(Stdio::piped());
+}
+
+/// How long text without a newline, such as a prompt, waits for the rest of
+/// its line before it is shown anyway.
+const HOLD: Duration = Duration::from_millis(50);
+
+/// One stream being copied.
+struct Stream<W> {
+    sink: W,
+    stamping: Option<(Mode, Instant)>,
+    tag: Option<String>,
+    lines: Option<Lines>,
+    buffer: Option<Arc<Mutex<Vec<u8>>>>,
+    last: Option<Arc<Mutex<Vec<u8>>>>,
+    record: bool,
+}
+
+/// The length of the start of `bytes` that can be decoded now: all of it,
+/// unless it ends partway through a character.
+fn complete(bytes: &[u8]) -> usize {
+    match std::str::from_utf8(bytes) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => bytes.len(),
+    }
+}
+
+impl<W: Write> Stream<W> {
+    fn write(&mut self, text: &str, at_start: bool, newline: bool) {
+        let mut out = String::with_capacity(text.len() + 16);
+        if at_start && let Some((mode, since)) = self.stamping {
+            out.push_str(&stamp::stamp(mode, since));
+        }
+        if at_start && let Some(tag) = &self.tag {
+            out.push_str(tag);
+        }
+        out.push_str(text);
+        if newline {
+            out.push('\n');
+        }
+        let _ = self.sink.write_all(out.as_bytes());
+    }
+
+    /// Keeps a whole line of output for `marks` and the session log: as the
+    /// command wrote it, without highlighting or collapsing, but with
+    /// secrets redacted.
+    fn keep(&mut self, text: &str, newline: bool) {
+        let text = match &self.lines {
+            Some(lines) => lines.redact(text),
+            None => text.to_string(),
+        };
+        if let Some(buffer) = &self.buffer {
+            let mut buffer = buffer.lock().unwrap();
+            capture::keep(&mut buffer, text.as_bytes());
+            if newline {
+                capture::keep(&mut buffer, b"\n");
+            }
+        }
+        if self.record {
+            record::output(&text, newline);
+        }
+    }
+
+    /// Shows the start of a line before the rest has arrived. Filters only
+    /// see whole lines, so it is shown as it is.
+    fn show_partial(&mut self, bytes: &[u8], at_start: bool) {
+        if at_start && let Some(summary) = self.lines.as_mut().and_then(Lines::finish) {
+            self.write(&summary, true, true);
+        }
+        self.write(&String::from_utf8_lossy(bytes), at_start, false);
+    }
+
+    /// Handles a line, ended by a newline unless the output ended first, of
+    /// which the first `shown` bytes are already on the screen.
+    fn line(&mut self, bytes: &[u8], shown: usize, newline: bool) {
+        let text = String::from_utf8_lossy(bytes);
+        self.keep(&text, newline);
+        if shown > 0 {
+            let rest = String::from_utf8_lossy(&bytes[shown..]).into_owned();
+            self.write(&rest, false, newline);
+            return;
+        }
+        let shown = match &mut self.lines {
+            Some(lines) => {
+                let (summary, shown) = lines.filter(&text, newline);
+                if let Some(summary) = summary {
+                    self.write(&summary, true, true);
+                }
+                shown
+            }
+            None => Some(text.into_owned()),
+        };
+        if let Some(shown) = shown {
+            self.write(&shown, true, newline);
+        }
+    }
+
+    fn run(mut self, chunks: Receiver<Vec<u8>>) {
+        // The line being read, and how much of it is on the screen.
+        let mut pending: Vec<u8> = Vec::new();
+        let mut shown = 0;
+        // Since when part of it has waited to be shown.
+        let mut held: Option<Instant> = None;
+        loop {
+            let chunk = match held {
+                None => chunks.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                Some(since) => chunks.recv_timeout(HOLD.saturating_sub(since.elapsed())),
+            };
+            match chunk {
+                Ok(chunk) => {
+                    // `$__` is fed back into commands, so it gets the bytes
+                    // exactly as written.
+                    if let Some(last) = &self.last {
+                        capture::keep(&mut last.lock().unwrap(), &chunk);
+                    }
+                    pending.extend_from_slice(&chunk);
+                    while let Some(end) = pending[shown..].iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = pending.drain(..=shown + end).collect();
+                        self.line(&line[..line.len() - 1], shown, true);
+                        shown = 0;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            held = if pending.len() > shown { Some(held.unwrap_or_else(Instant::now)) } else { None };
+            if let Some(since) = held
+                && since.elapsed() >= HOLD
+            {
+                let ready = complete(&pending[shown..]);
+                if ready > 0 {
+                    self.show_partial(&pending[shown..shown + ready], shown == 0);
+                    shown += ready;
+                }
+                held = None;
+            }
+            let _ = self.sink.flush();
+        }
+        if !pending.is_empty() {
+            self.line(&pending, shown, false);
+        }
+        if let Some(summary) = self.lines.as_mut().and_then(Lines::finish) {
+            self.write(&summary, true, true);
+        }
+        let _ = self.sink.flush();
+    }
+}
+
+fn relay<W: Write + Send + 'static>(
+    mut source: impl Read + Send + 'static,
+    sink: W,
+    options: &Options,
+    buffer: &Option<Arc<Mutex<Vec<u8>>>>,
+    last: Option<Arc<Mutex<Vec<u8>>>>,
+    done: mpsc::Sender<()>,
+) {
+    let stream = Stream {
+        sink,
+        stamping: options.stamping,
+        tag: options.tag.clone(),
+        lines: options.filters.clone().map(Lines::new),
+        buffer: buffer.clone(),
+        last,
+        record: options.record,
+    };
+    let (tx, chunks) = mpsc::channel();
+    thread::spawn(move || {
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = source.read(&mut chunk) {
+            if n == 0 || tx.send(chunk[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    thread::spawn(move || {
+        stream.run(chunks);
+        let _ = done.send(());
+    });
+}
+
+/// Output being copied from a running child.
+pub struct Relay {
+    buffer: Option<Arc<Mutex<Vec<u8>>>>,
+    last: Option<Arc<Mutex<Vec<u8>>>>,
+    done: Receiver<()>,
+    streams: usize,
+}
+
+/// Starts copying a child's piped output to the terminal.
+pub fn start(child: &mut Child, options: Options) -> Relay {
+    let buffer = options.capture.then(|| Arc::new(Mutex::new(Vec::new())));
+    let last = options.last.then(|| Arc::new(Mutex::new(Vec::new())));
+    let (tx, done) = mpsc::channel();
+    let mut streams = 0;
+    if let Some(stdout) = child.stdout.take() {
+        relay(stdout, io::stdout(), &options, &buffer, last.clone(), tx.clone());
+        streams += 1;
+    }
+    if let Some(stderr) = child.stderr.take() {
+        relay(stderr, io::stderr(), &options, &buffer, None, tx);
+        streams += 1;
+    }
+    Relay { buffer, last, done, streams }
+}
+
+impl Relay {
+    /// Waits briefly for the pipes to drain, and keeps the output for the
+    /// command being recorded and for `$__` if it was captured.
+    pub fn finish(self) {
+        for _ in 0..self.streams {
+            if self.done.recv_timeout(DRAIN_TIMEOUT).is_err() {
+                break;
+            }
+        }
+        if let Some(buffer) = self.buffer {
+            capture::store(&buffer.lock().unwrap());
+        }
+        if let Some(last) = self.last {
+            capture::store_last(&last.lock().unwrap());
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! `reload`: re-reads the config and applies it to the running shell.
+//!
+//! Every setting that changed is listed. Aliases, the theme, the prompt
+//! profile, abbreviations, shell options, filters, directory hooks and the
+//! interactive settings (history, terminal integration, redaction) take
+//! effect at once; an alias or option changed in the session since is left
+//! as it is. Settings read only at startup are marked as needing a
+//! restart. The dotfiles repository's fragments are loaded again too.
+//!
+//! With `reload.watch = true` the shell reloads by itself before a prompt
+//! when `config.toml` was saved since.
+
+use colored::*;
+
+use crate::Shell;
+use crate::abbr;
+use crate::alias;
+use crate::cmdhooks;
+use crate::config::{self, Config};
+use crate::dirhooks;
+use crate::dotfiles;
+use crate::filter;
+use crate::messages::msg;
+use crate::profile::Profile;
+use crate::stamp;
+use crate::term;
+use crate::theme::Theme;
+
+/// Settings read once when the shell starts.
+const AT_STARTUP: [&str; 7] = [
+    "history.backend",
+    "history.path",
+    "history.sync",
+    "history.size",
+    "shell.collation",
+    "agent",
+    "containers",
+];
+
+fn under(key: &str, prefix: &str) -> bool {
+    key == prefix || key.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Reloads the config if `reload.watch` is set and the file was saved since
+/// it was last read.
+pub fn watch(shell: &mut Shell) {
+    if shell.config.get_bool("reload.watch").unwrap_or(false) && config::modified() != shell.config_modified {
+        run(shell);
+    }
+}
+
+/// `reload`
+pub fn run(shell: &mut Shell) -> i32 {
+    shell.config_modified = config::modified();
+    let config = Config::load();
+    let changes = shell.config.changes(&config);
+    if changes.is_empty() {
+        println!("{}", msg!("reload.unchanged"));
+        return 0;
+    }
+    for (key, old, new) in &changes {
+        let line = match (old, new) {
+            (Some(old), Some(new)) => format!("~ {} = {} → {}", key, old, new),
+            (None, Some(new)) => shell.theme.paint("diff_added", &format!("+ {} = {}", key, new)),
+            (Some(old), None) => shell.theme.paint("diff_removed", &format!("- {} = {}", key, old)),
+            (None, None) => continue,
+        };
+        if AT_STARTUP.iter().any(|prefix| under(key, prefix)) {
+            println!("{} {}", line, msg!("reload.restart").dimmed());
+        } else {
+            println!("{}", line);
+        }
+    }
+    let changed = |prefix: &str| changes.iter().any(|(key, _, _)| under(key, prefix));
+
+    // Aliases the session hasn't touched follow the config.
+    let before = alias::load(&shell.config);
+    let after = alias::load(&config);
+    for name in before.keys().chain(after.keys()) {
+        if before.get(name) != after.get(name) && shell.aliases.get(name) == before.get(name) {
+            match after.get(name) {
+                Some(value) => shell.aliases.insert(name.clone(), value.clone()),
+                None => shell.aliases.remove(name),
+            };
+        }
+    }
+    if changed("theme") || changed("color_vision") {
+        term::init_vision(&config);
+        shell.theme = Theme::from_config(&config);
+    }
+    if changed("prompt") {
+        shell.profile = Profile::from_config(&config);
+    }
+    if changed("abbr") {
+        abbr::init(&config);
+    }
+    let option = |key: &str, current: bool| {
+        if changed(key) { config.get_bool(key).unwrap_or(false) } else { current }
+    };
+    shell.posix = option("shell.posix", shell.posix);
+    shell.huponexit = option("jobs.huponexit", shell.huponexit);
+    shell.autocd = option("shell.autocd", shell.autocd);
+    shell.pipetags = option("shell.pipetags", shell.pipetags);
+    if changed("shell.timestamps") {
+        shell.timestamps = config
+            .get_bool("shell.timestamps")
+            .unwrap_or(false)
+            .then(|| stamp::Stamping { mode: stamp::Mode::from_config(&config), since: None });
+    } else if changed("ts")
+        && let Some(stamping) = &mut shell.timestamps
+    {
+        stamping.mode = stamp::Mode::from_config(&config);
+    }
+    if changed("filters") {
+        shell.filters = filter::Filters::from_config(&config);
+    }
+    if changed("hooks") {
+        shell.dir_hooks = dirhooks::Hooks::from_config(&config);
+        shell.cmd_hooks = cmdhooks::Hooks::from_config(&config);
+    }
+    shell.config = config;
+    dotfiles::load(shell);
+    shell.config_reloaded = true;
+    0
+}
@@ -0,0 +1,118 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use colored::*;
+
+use crate::config::Config;
+use crate::term;
+
+const PREFIX_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
+/// Expands `target` into host names: a group from `ssh.groups.<name>` in the
+/// config, or a comma-separated list of hosts.
+fn resolve_hosts(target: &str, config: &Config) -> Vec<String> {
+    let group = config.get_str_list(&format!("ssh.groups.{}", target));
+    if !group.is_empty() {
+        return group;
+    }
+    target
+        .split(',')
+        .filter(|h| !h.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn relay(stream: impl Read + Send + 'static, prefix: String) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            println!("{} {}", prefix, line);
+        }
+    })
+}
+
+/// `on <host|group|host1,host2> -- <command>`
+///
+/// Runs `command` over ssh on every host in parallel, printing each output
+/// line prefixed with its host, then a per-host summary. Returns 0 when every
+/// host succeeded, otherwise the highest exit status seen.
+pub fn run(args: &[&str], config: &Config) -> i32 {
+    let split = args.iter().position(|a| *a == "--");
+    let (targets, command) = match split {
+        Some(i) if i > 0 && i + 1 < args.len() => (&args[..i], args[i + 1..].join(" ")),
+        _ => {
+            println!("Usage: on <host|group>[,host...] -- <command>");
+            return 2;
+        }
+    };
+
+    let mut hosts: Vec<String> = Vec::new();
+    for target in targets {
+        for host in resolve_hosts(target, config) {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+    let width = hosts.iter().map(|h| h.len()).max().unwrap_or(0);
+
+    let mut running = Vec::new();
+    for (i, host) in hosts.iter().enumerate() {
+        let prefix = format!("[{:<width$}]", host, width = width)
+            .color(term::adapt(PREFIX_COLORS[i % PREFIX_COLORS.len()]))
+            .to_string();
+        let child = Command::new("ssh")
+            .args(["-o", "BatchMode=yes", "-T", host.as_str(), "--", command.as_str()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                let readers = vec![
+                    relay(child.stdout.take().unwrap(), prefix.clone()),
+                    relay(child.stderr.take().unwrap(), prefix),
+                ];
+                running.push((host, Some(child), readers));
+            }
+            Err(e) => {
+                println!("{} Error running ssh: {}", prefix, e);
+                running.push((host, None, Vec::new()));
+            }
+        }
+    }
+
+    let mut results = Vec::new();
+    for (host, child, readers) in running {
+        let code = match child {
+            Some(mut child) => child.wait().map(|s| s.code().unwrap_or(255)).unwrap_or(255),
+            None => 255,
+        };
+        for reader in readers {
+            let _ = reader.join();
+        }
+        results.push((host, code));
+    }
+
+    println!();
+    let mut worst = 0;
+    for (host, code) in &results {
+        let outcome = if *code == 0 {
+            "ok".color(term::adapt(Color::Green)).to_string()
+        } else {
+            format!("exit {}", code).color(term::adapt(Color::Red)).to_string()
+        };
+        println!("{:<width$}  {}", host, outcome, width = width);
+        worst = worst.max(*code);
+    }
+    let failed = results.iter().filter(|(_, c)| *c != 0).count();
+    println!("{} of {} hosts succeeded", results.len() - failed, results.len());
+    worst
+}
@@ -0,0 +1,266 @@
+//! Driving octane from another program.
+//!
+//! A [`Repl`] runs the shell as a child process (`octane --embedded`) and
+//! talks to it over pipes, so TUI applications and test harnesses get a
+//! real shell session — variables, functions, the working directory all
+//! persist between lines — without a terminal:
+//!
+//! ```no_run
+//! use rustshell::repl::Repl;
+//!
+//! let mut repl = Repl::new(std::io::empty(), Vec::new()).unwrap();
+//! repl.feed("x=5").unwrap();
+//! let output = repl.feed("echo $x").unwrap().expect("a complete command");
+//! assert_eq!(output.stdout, "5\n");
+//! assert_eq!(output.status, 0);
+//! ```
+//!
+//! [`Repl::run`] instead reads lines from the reader and writes the prompt
+//! and each command's output to the writer, like an interactive session.
+//! Commands started by the shell get no stdin.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Starts each reply on the shell's stdout and stderr, so it can't be
+/// mistaken for command output.
+#[doc(hidden)]
+pub const MARKER: &str = "\u{1e}octane:";
+/// After [`MARKER`]: the command finished, with its status and the working
+/// directory, e.g. `done 0 /home/me`.
+#[doc(hidden)]
+pub const DONE: &str = "done";
+/// After [`MARKER`]: the line opened a block and the shell waits for more.
+#[doc(hidden)]
+pub const MORE: &str = "more";
+
+/// What one command printed, and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    /// The exit status, as `$?` sees it.
+    pub status: i32,
+    /// The shell's working directory afterwards.
+    pub cwd: PathBuf,
+}
+
+/// What the prompt callback is told.
+#[derive(Debug, Clone)]
+pub struct PromptState {
+    /// The status of the last command.
+    pub status: i32,
+    /// The shell's working directory.
+    pub cwd: PathBuf,
+    /// The previous line opened a block (`if`, `{`, ...) that isn't closed.
+    pub continuation: bool,
+}
+
+type PromptFn = Box<dyn FnMut(&PromptState) -> String>;
+
+fn default_prompt(state: &PromptState) -> String {
+    if state.continuation { "> ".to_string() } else { "$ ".to_string() }
+}
+
+/// An octane session driven through pipes. See the [module docs](self).
+pub struct Repl<R, W> {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: Receiver<String>,
+    reader: R,
+    writer: W,
+    prompt: PromptFn,
+    state: PromptState,
+    exited: bool,
+}
+
+/// Reads the shell's stderr, sending one message per reply.
+fn split_stderr(mut stderr: impl Read + Send + 'static) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut pending = Vec::new();
+        let mut buf = [0; 4096];
+        while let Ok(n) = stderr.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            pending.extend_from_slice(&buf[..n]);
+            // A reply's marker line ends with a newline.
+            while let Some(start) = find(&pending, MARKER.as_bytes())
+                && let Some(end) = pending[start..].iter().position(|b| *b == b'\n')
+            {
+                let text = String::from_utf8_lossy(&pending[..start]).into_owned();
+                pending.drain(..start + end + 1);
+                if sender.send(text).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = sender.send(String::from_utf8_lossy(&pending).into_owned());
+    });
+    receiver
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+impl<R: BufRead, W: Write> Repl<R, W> {
+    /// Starts the shell named by `OCTANE_BIN`, or the installed `octane`
+    /// binary from `PATH`.
+    pub fn new(reader: R, writer: W) -> io::Result<Repl<R, W>> {
+        let program = env::var_os("OCTANE_BIN").unwrap_or_else(|| OsString::from("octane"));
+        Repl::with_program(program, reader, writer)
+    }
+
+    /// Starts `program` as the shell, e.g. a freshly built binary in a test.
+    pub fn with_program(program: impl Into<OsString>, reader: R, writer: W) -> io::Result<Repl<R, W>> {
+        let mut child = Command::new(program.into())
+            .arg("--embedded")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let stderr = split_stderr(child.stderr.take().expect("piped stderr"));
+        let cwd = env::current_dir().unwrap_or_default();
+        Ok(Repl {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            reader,
+            writer,
+            prompt: Box::new(default_prompt),
+            state: PromptState { status: 0, cwd, continuation: false },
+            exited: false,
+        })
+    }
+
+    /// Sets the callback that draws the prompt for [`Repl::step`].
+    pub fn set_prompt(&mut self, prompt: impl FnMut(&PromptState) -> String + 'static) {
+        self.prompt = Box::new(prompt);
+    }
+
+    /// The status and directory after the last command.
+    pub fn state(&self) -> &PromptState {
+        &self.state
+    }
+
+    /// Whether the shell has exited (after `exit`, or when it failed).
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Runs `line` (or several, separated by newlines). Returns `None` when
+    /// a block is left open and more lines are needed before it runs.
+    pub fn feed(&mut self, line: &str) -> io::Result<Option<Output>> {
+        if self.exited {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "the shell has exited"));
+        }
+        let lines: Vec<&str> = if line.is_empty() { vec![""] } else { line.lines().collect() };
+        let mut combined: Option<Output> = None;
+        for line in lines {
+            writeln!(self.stdin, "{}", line)?;
+            self.stdin.flush()?;
+            if let Some(output) = self.read_reply()? {
+                combined = Some(match combined {
+                    Some(mut previous) => {
+                        previous.stdout.push_str(&output.stdout);
+                        previous.stderr.push_str(&output.stderr);
+                        Output { status: output.status, cwd: output.cwd, ..previous }
+                    }
+                    None => output,
+                });
+            }
+            if self.exited {
+                break;
+            }
+        }
+        Ok(combined)
+    }
+
+    fn read_reply(&mut self) -> io::Result<Option<Output>> {
+        let mut stdout = Vec::new();
+        let reply = loop {
+            let mut line = Vec::new();
+            if self.stdout.read_until(b'\n', &mut line)? == 0 {
+                break None;
+            }
+            match find(&line, MARKER.as_bytes()) {
+                Some(start) => {
+                    stdout.extend_from_slice(&line[..start]);
+                    break Some(String::from_utf8_lossy(&line[start + MARKER.len()..]).trim_end().to_string());
+                }
+                None => stdout.extend_from_slice(&line),
+            }
+        };
+        let stderr = self.stderr.recv().unwrap_or_default();
+        let stdout = String::from_utf8_lossy(&stdout).into_owned();
+        let Some(reply) = reply else {
+            // The shell exited, e.g. after `exit 3`.
+            self.exited = true;
+            let status = self.child.wait().ok().and_then(|s| s.code()).unwrap_or(1);
+            self.state.status = status;
+            self.state.continuation = false;
+            return Ok(Some(Output { stdout, stderr, status, cwd: self.state.cwd.clone() }));
+        };
+        if reply == MORE {
+            self.state.continuation = true;
+            return Ok(None);
+        }
+        let rest = reply.strip_prefix(DONE).unwrap_or(&reply).trim_start();
+        let (status, cwd) = rest.split_once(' ').unwrap_or((rest, ""));
+        self.state = PromptState {
+            status: status.parse().unwrap_or(1),
+            cwd: PathBuf::from(cwd),
+            continuation: false,
+        };
+        Ok(Some(Output { stdout, stderr, status: self.state.status, cwd: self.state.cwd.clone() }))
+    }
+
+    /// Writes the prompt, reads a line from the reader, runs it, and writes
+    /// its output. Returns `false` at the end of the input or once the
+    /// shell has exited.
+    pub fn step(&mut self) -> io::Result<bool> {
+        if self.exited {
+            return Ok(false);
+        }
+        let prompt = (self.prompt)(&self.state);
+        self.writer.write_all(prompt.as_bytes())?;
+        self.writer.flush()?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+        if let Some(output) = self.feed(line.trim_end_matches(['\n', '\r']))? {
+            self.writer.write_all(output.stdout.as_bytes())?;
+            self.writer.write_all(output.stderr.as_bytes())?;
+            self.writer.flush()?;
+        }
+        Ok(!self.exited)
+    }
+
+    /// Runs lines from the reader until it ends or the shell exits, and
+    /// returns the last status.
+    pub fn run(&mut self) -> io::Result<i32> {
+        while self.step()? {}
+        Ok(self.state.status)
+    }
+}
+
+impl<R, W> Drop for Repl<R, W> {
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
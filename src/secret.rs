@@ -0,0 +1,323 @@
+//! Secrets kept out of history and plain-text config.
+//!
+//! Values live in the OS keychain (macOS Keychain via `security`, the Secret
+//! Service via `secret-tool` on Linux, Credential Manager on Windows), or with
+//! `secret.backend = "age"` in age-encrypted files under the data directory.
+//! They are typed at a hidden prompt and only ever reach a command through
+//! its environment.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::{self, Config};
+use crate::term;
+
+/// Service name the secrets are filed under in the keychain.
+const SERVICE: &str = "octane";
+
+enum Backend {
+    Keychain,
+    Age {
+        /// Public key (or `-R` recipients file) to encrypt to; without one
+        /// age asks for a passphrase.
+        recipient: Option<String>,
+        /// Identity file to decrypt with.
+        identity: Option<String>,
+    },
+}
+
+impl Backend {
+    fn from_config(config: &Config) -> Result<Backend, String> {
+        match config.get_str("secret.backend").unwrap_or("keychain") {
+            "keychain" => Ok(Backend::Keychain),
+            "age" => Ok(Backend::Age {
+                recipient: config.get_str("secret.age_recipient").map(str::to_string),
+                identity: config.get_str("secret.age_identity").map(config::expand_home),
+            }),
+            other => Err(format!("unknown secret backend: {}", other)),
+        }
+    }
+
+    fn store(&self, name: &str, value: &str) -> Result<(), String> {
+        match self {
+            Backend::Keychain => keychain::store(name, value),
+            Backend::Age { recipient, .. } => {
+                let dir = secrets_dir();
+                fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let _ = fs::set_permissions(&dir, fs::Permissions::from_mode(0o700));
+                }
+                let mut cmd = Command::new("age");
+                match recipient {
+                    Some(r) if r.starts_with("age1") || r.starts_with("ssh-") => cmd.args(["-r", r]),
+                    Some(file) => cmd.args(["-R", &config::expand_home(file)]),
+                    None => cmd.arg("-p"),
+                };
+                cmd.arg("-o").arg(age_file(name));
+                pipe(&mut cmd, value).map(|_| ())
+            }
+        }
+    }
+
+    fn fetch(&self, name: &str) -> Result<String, String> {
+        match self {
+            Backend::Keychain => keychain::fetch(name),
+            Backend::Age { identity, .. } => {
+                let file = age_file(name);
+                if !file.exists() {
+                    return Err(format!("no such secret: {}", name));
+                }
+                let mut cmd = Command::new("age");
+                cmd.arg("-d");
+                if let Some(identity) = identity {
+                    cmd.args(["-i", identity]);
+                }
+                cmd.arg(file);
+                pipe(&mut cmd, "")
+            }
+        }
+    }
+
+    fn remove(&self, name: &str) -> Result<(), String> {
+        match self {
+            Backend::Keychain => keychain::remove(name),
+            Backend::Age { .. } => fs::remove_file(age_file(name)).map_err(|e| format!("{}: {}", name, e)),
+        }
+    }
+}
+
+fn secrets_dir() -> PathBuf {
+    config::data_dir().join("secrets")
+}
+
+fn age_file(name: &str) -> PathBuf {
+    secrets_dir().join(format!("{}.age", name))
+}
+
+/// Runs `cmd` with `input` on stdin and returns its stdout. Errors carry the
+/// command's stderr. Prompts (age passphrases) go straight to the terminal.
+fn pipe(cmd: &mut Command, input: &str) -> Result<String, String> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: {}", program, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|e| format!("{}: {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}: {}", program, stderr.trim()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.strip_suffix('\n').unwrap_or(&stdout).to_string())
+}
+
+#[cfg(target_os = "macos")]
+mod keychain {
+    use std::process::Command;
+
+    use super::{SERVICE, pipe};
+
+    /// `security -i` reads the command from stdin, keeping the value out of
+    /// the process list.
+    pub fn store(name: &str, value: &str) -> Result<(), String> {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let line = format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            SERVICE,
+            quote(name),
+            quote(value)
+        );
+        pipe(Command::new("security").arg("-i"), &line).map(|_| ())
+    }
+
+    pub fn fetch(name: &str) -> Result<String, String> {
+        pipe(Command::new("security").args(["find-generic-password", "-s", SERVICE, "-a", name, "-w"]), "")
+    }
+
+    pub fn remove(name: &str) -> Result<(), String> {
+        pipe(Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", name]), "")
+            .map(|_| ())
+    }
+}
+
+#[cfg(windows)]
+mod keychain {
+    use std::io;
+    use std::ptr;
+
+    use windows_sys::Win32::Security::Credentials::{
+        CRED_PERSIST_LOCAL_MACHINE, CRED_TYPE_GENERIC, CREDENTIALW, CredDeleteW, CredFree, CredReadW, CredWriteW,
+    };
+
+    use super::SERVICE;
+
+    fn target(name: &str) -> Vec<u16> {
+        format!("{}:{}", SERVICE, name).encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn last_error(name: &str) -> String {
+        format!("{}: {}", name, io::Error::last_os_error())
+    }
+
+    pub fn store(name: &str, value: &str) -> Result<(), String> {
+        let mut target = target(name);
+        let mut blob = value.as_bytes().to_vec();
+        // SAFETY: CREDENTIALW is plain data; all-zero is a valid empty value.
+        let mut credential: CREDENTIALW = unsafe { std::mem::zeroed() };
+        credential.Type = CRED_TYPE_GENERIC;
+        credential.TargetName = target.as_mut_ptr();
+        credential.CredentialBlobSize = blob.len() as u32;
+        credential.CredentialBlob = blob.as_mut_ptr();
+        credential.Persist = CRED_PERSIST_LOCAL_MACHINE;
+        // SAFETY: the pointers in `credential` outlive the call.
+        if unsafe { CredWriteW(&credential, 0) } == 0 {
+            return Err(last_error(name));
+        }
+        Ok(())
+    }
+
+    pub fn fetch(name: &str) -> Result<String, String> {
+        let target = target(name);
+        let mut credential: *mut CREDENTIALW = ptr::null_mut();
+        // SAFETY: on success `credential` points to a buffer we free below.
+        if unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) } == 0 {
+            return Err(last_error(name));
+        }
+        let value = unsafe {
+            let c = &*credential;
+            let blob = std::slice::from_raw_parts(c.CredentialBlob, c.CredentialBlobSize as usize);
+            let value = String::from_utf8_lossy(blob).into_owned();
+            CredFree(credential as *const _);
+            value
+        };
+        Ok(value)
+    }
+
+    pub fn remove(name: &str) -> Result<(), String> {
+        let target = target(name);
+        // SAFETY: `target` is a NUL-terminated UTF-16 string.
+        if unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) } == 0 {
+            return Err(last_error(name));
+        }
+        Ok(())
+    }
+}
+
+/// The freedesktop Secret Service (GNOME Keyring, KWallet) via libsecret's
+/// `secret-tool`.
+#[cfg(not(any(target_os = "macos", windows)))]
+mod keychain {
+    use std::process::Command;
+
+    use super::{SERVICE, pipe};
+
+    pub fn store(name: &str, value: &str) -> Result<(), String> {
+        let label = format!("{}: {}", SERVICE, name);
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["store", "--label", &label, "service", SERVICE, "name", name]);
+        pipe(&mut cmd, value).map(|_| ())
+    }
+
+    pub fn fetch(name: &str) -> Result<String, String> {
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["lookup", "service", SERVICE, "name", name]);
+        match pipe(&mut cmd, "") {
+            // `lookup` exits 1 without a message when nothing matches.
+            Err(e) if e.ends_with(": ") => Err(format!("no such secret: {}", name)),
+            result => result,
+        }
+    }
+
+    pub fn remove(name: &str) -> Result<(), String> {
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["clear", "service", SERVICE, "name", name]);
+        pipe(&mut cmd, "").map(|_| ())
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+}
+
+/// `secret run VAR=NAME... -- cmd`: runs `cmd` with each secret `NAME` in
+/// the environment variable `VAR` (a bare `NAME` sets `$NAME`).
+fn run_with(backend: &Backend, args: &[&str]) -> i32 {
+    let Some(split) = args.iter().position(|a| *a == "--") else {
+        println!("Usage: secret run <VAR=name|name>... -- <command>");
+        return 2;
+    };
+    let (bindings, command) = (&args[..split], &args[split + 1..]);
+    if command.is_empty() {
+        println!("Usage: secret run <VAR=name|name>... -- <command>");
+        return 2;
+    }
+    let mut env = Vec::new();
+    for binding in bindings {
+        let (var, name) = binding.split_once('=').unwrap_or((binding, binding));
+        match backend.fetch(name) {
+            Ok(value) => env.push((var.to_string(), value)),
+            Err(e) => {
+                println!("secret: {}", e);
+                return 1;
+            }
+        }
+    }
+    let mut cmd = Command::new(command[0]);
+    cmd.args(&command[1..]).envs(env);
+    match cmd.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            println!("Error running {}: {}", command[0], e);
+            127
+        }
+    }
+}
+
+/// `secret set|get|rm <name>` / `secret run <VAR=name>... -- <command>`
+pub fn run(args: &[&str], config: &Config) -> i32 {
+    let backend = match Backend::from_config(config) {
+        Ok(backend) => backend,
+        Err(e) => {
+            println!("Error: {}", e);
+            return 1;
+        }
+    };
+    if let Some(name) = args.get(1)
+        && args[0] != "run"
+        && !valid_name(name)
+    {
+        println!("secret: invalid name: {}", name);
+        return 2;
+    }
+    let result = match args {
+        ["set", name] => {
+            let Some(value) = term::read_hidden(&format!("Value for {}: ", name)) else {
+                return 1;
+            };
+            backend.store(name, &value)
+        }
+        ["get", name] => backend.fetch(name).map(|value| println!("{}", value)),
+        ["rm", name] => backend.remove(name),
+        ["run", rest @ ..] => return run_with(&backend, rest),
+        _ => {
+            println!("Usage: secret set|get|rm <name> | secret run <VAR=name>... -- <command>");
+            return 2;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            println!("secret: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Named sessions: several working contexts in one shell.
+//!
+//! Each session has its own working directory (and `OLDPWD`), directory
+//! stack, and shell variables; exported variables belong to the process and
+//! are shared. `session switch name` puts the current session aside and
+//! picks `name` up where it was left, or starts it where the current one
+//! is, with a copy of its variables and an empty directory stack. The first
+//! session is called `main`.
+//!
+//! An interactive shell saves its current session when it exits, and
+//! `octane --restore` (or `session.restore = true`) starts the next one
+//! there. Variables that look like secrets (see `redact`) aren't saved.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use toml::{Table, Value};
+
+use crate::Shell;
+use crate::config;
+use crate::messages::msg;
+use crate::redact::Redactor;
+use crate::term;
+use crate::vars;
+
+/// A session put aside.
+#[derive(Clone)]
+struct Saved {
+    cwd: PathBuf,
+    oldpwd: Option<String>,
+    dir_stack: Vec<PathBuf>,
+    variables: HashMap<String, String>,
+}
+
+pub struct Sessions {
+    current: String,
+    saved: BTreeMap<String, Saved>,
+}
+
+impl Default for Sessions {
+    fn default() -> Sessions {
+        Sessions { current: "main".to_string(), saved: BTreeMap::new() }
+    }
+}
+
+fn last_path() -> PathBuf {
+    config::data_dir().join("last_session.toml")
+}
+
+/// Saves the current session for the next shell to restore.
+pub fn save_last(shell: &Shell) {
+    let redactor = Redactor::from_config(&shell.config);
+    let mut table = Table::new();
+    if let Ok(cwd) = env::current_dir() {
+        table.insert("cwd".into(), Value::String(cwd.display().to_string()));
+    }
+    if let Ok(oldpwd) = env::var("OLDPWD") {
+        table.insert("oldpwd".into(), Value::String(oldpwd));
+    }
+    let stack = shell.dir_stack.iter().map(|dir| Value::String(dir.display().to_string())).collect();
+    table.insert("dir_stack".into(), Value::Array(stack));
+    // `$__` is the last program's output, not something set.
+    let variables = shell
+        .variables
+        .iter()
+        .filter(|(name, value)| *name != "__" && !redactor.secret_variable(name, value))
+        .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+        .collect();
+    table.insert("variables".into(), Value::Table(variables));
+    // The data directory may not exist yet, or may have been removed
+    // since the shell started.
+    let path = last_path();
+    let written = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&path, table.to_string()));
+    if let Err(e) = written {
+        println!("session: {}", msg!("error.path", path.display(), e));
+    }
+}
+
+fn load_last() -> Option<Saved> {
+    let table = fs::read_to_string(last_path()).ok()?.parse::<Table>().ok()?;
+    let string = |value: &Value| value.as_str().map(str::to_string);
+    Some(Saved {
+        cwd: PathBuf::from(table.get("cwd").and_then(string)?),
+        oldpwd: table.get("oldpwd").and_then(string),
+        dir_stack: table
+            .get("dir_stack")
+            .and_then(Value::as_array)
+            .map(|dirs| dirs.iter().filter_map(string).map(PathBuf::from).collect())
+            .unwrap_or_default(),
+        variables: table
+            .get("variables")
+            .and_then(Value::as_table)
+            .map(|vars| vars.iter().filter_map(|(name, value)| Some((name.clone(), string(value)?))).collect())
+            .unwrap_or_default(),
+    })
+}
+
+/// Picks up where the last interactive shell left off: its working
+/// directory, directory stack and shell variables.
+pub fn restore_last(shell: &mut Shell) {
+    let Some(last) = load_last() else {
+        return;
+    };
+    if let Err(e) = env::set_current_dir(&last.cwd) {
+        println!("session: {}", msg!("error.path", last.cwd.display(), e));
+        return;
+    }
+    vars::set_env("PWD", &last.cwd.display().to_string());
+    if let Some(dir) = &last.oldpwd {
+        vars::set_env("OLDPWD", dir);
+    }
+    // Directories removed since are dropped, as `popd` would fail on them.
+    shell.dir_stack = last.dir_stack.into_iter().filter(|dir| dir.is_dir()).collect();
+    shell.variables.extend(last.variables);
+    println!("{}", msg!("session.restored", term::display_path(&last.cwd)));
+}
+
+fn list(shell: &Shell) -> i32 {
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let mut names: Vec<(&str, &PathBuf)> = shell.sessions.saved.iter().map(|(n, s)| (n.as_str(), &s.cwd)).collect();
+    names.push((&shell.sessions.current, &cwd));
+    names.sort();
+    for (name, dir) in names {
+        let marker = if name == shell.sessions.current { "*" } else { " " };
+        println!("{} {:<12} {}", marker, name, term::display_path(dir));
+    }
+    0
+}
+
+fn switch(shell: &mut Shell, name: &str) -> i32 {
+    if name == shell.sessions.current {
+        return 0;
+    }
+    let here = Saved {
+        cwd: env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        oldpwd: env::var("OLDPWD").ok(),
+        dir_stack: shell.dir_stack.clone(),
+        variables: shell.variables.clone(),
+    };
+    let existing = shell.sessions.saved.get(name).cloned();
+    let fresh = existing.is_none();
+    let next = existing.unwrap_or_else(|| Saved { dir_stack: Vec::new(), ..here.clone() });
+    if let Err(e) = env::set_current_dir(&next.cwd) {
+        println!("session: {}", msg!("error.path", next.cwd.display(), e));
+        return 1;
+    }
+    vars::set_env("PWD", &next.cwd.display().to_string());
+    match &next.oldpwd {
+        Some(dir) => vars::set_env("OLDPWD", dir),
+        None => vars::remove_env("OLDPWD"),
+    }
+    shell.sessions.saved.remove(name);
+    let previous = std::mem::replace(&mut shell.sessions.current, name.to_string());
+    shell.sessions.saved.insert(previous, here);
+    shell.dir_stack = next.dir_stack;
+    shell.variables = next.variables;
+    let key = if fresh { "session.started" } else { "session.switched" };
+    println!("{}", msg!(key, name, term::display_path(&next.cwd)));
+    0
+}
+
+/// `session [list] | session switch <name> | session close <name>`
+pub fn run(shell: &mut Shell, args: &[&str]) -> i32 {
+    match args {
+        [] | ["list"] => list(shell),
+        ["switch", name] => switch(shell, name),
+        ["close", name] if *name == shell.sessions.current => {
+            println!("session: {}", msg!("session.close_current", name));
+            1
+        }
+        ["close", name] => match shell.sessions.saved.remove(*name) {
+            Some(_) => 0,
+            None => {
+                println!("session: {}", msg!("session.no_such_session", name));
+                1
+            }
+        },
+        _ => {
+            println!("{}", msg!("usage.session"));
+            2
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Leaving the shell.
+//!
+//! `exit`, the end of input, `SIGTERM`/`SIGHUP` and the terminal closing
+//! all end in the same shutdown: the `EXIT` trap runs, jobs are dealt with
+//! as `jobs.on_exit` says, history is flushed, and the terminal is put back
+//! the way the shell found it. This module holds the pieces that don't need
+//! the rest of the shell: the `trap` builtin, the job policy, and the
+//! terminal state.
+
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::jobs::{self, JobTable};
+use crate::messages::msg;
+
+/// What happens to background jobs when the shell exits (`jobs.on_exit`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JobPolicy {
+    /// The first `exit` with jobs left only warns; a second one leaves the
+    /// running jobs alone and hangs up the stopped ones.
+    Warn,
+    /// Hang up every job.
+    Hup,
+    /// Leave every job as it is.
+    Leave,
+}
+
+impl JobPolicy {
+    pub fn from_config(config: &Config) -> JobPolicy {
+        match config.get_str("jobs.on_exit") {
+            Some("hup") => JobPolicy::Hup,
+            Some("leave") => JobPolicy::Leave,
+            _ => JobPolicy::Warn,
+        }
+    }
+}
+
+/// Conditions `trap` accepts. Only leaving the shell can be trapped.
+const CONDITIONS: [&str; 2] = ["EXIT", "0"];
+
+/// `trap [action] EXIT`: runs `action` when the shell exits; `-` or an empty
+/// action removes the trap. Without arguments, lists the traps.
+pub fn trap(args: &[&str], traps: &mut HashMap<String, String>) -> i32 {
+    if args.is_empty() {
+        for (condition, action) in traps.iter() {
+            println!("trap -- '{}' {}", action.replace('\'', "'\\''"), condition);
+        }
+        return 0;
+    }
+    let split = args.iter().rposition(|a| !CONDITIONS.contains(a)).map_or(0, |i| i + 1);
+    let (action, conditions) = args.split_at(split);
+    let action = action.strip_prefix(&["--"]).unwrap_or(action);
+    if conditions.is_empty() {
+        match action.last() {
+            Some(other) if other.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) => {
+                println!("trap: {}", msg!("shutdown.unsupported_trap", other))
+            }
+            _ => println!("{}", msg!("usage.trap")),
+        }
+        return 2;
+    }
+    let action = action.join(" ");
+    if action.is_empty() || action == "-" {
+        traps.remove("EXIT");
+    } else {
+        traps.insert("EXIT".to_string(), action.to_string());
+    }
+    0
+}
+
+/// Whether `exit` should stop and warn about the jobs that are left
+/// instead of leaving, as the first attempt does under [`JobPolicy::Warn`].
+/// `huponexit` says whether leaving will hang up the running jobs.
+pub fn refuse_exit(table: &JobTable, policy: JobPolicy, huponexit: bool) -> bool {
+    if policy != JobPolicy::Warn {
+        return false;
+    }
+    let (running, stopped) = jobs::unfinished(table);
+    if stopped > 0 {
+        println!("{}", msg!("shutdown.stopped_jobs"));
+    } else if running > 0 && huponexit {
+        println!("{}", msg!("shutdown.running_jobs_hup"));
+    } else if running > 0 {
+        println!("{}", msg!("shutdown.running_jobs"));
+    }
+    running + stopped > 0
+}
+
+/// Deals with the jobs that are left. `hangup` is set when the shell is
+/// leaving because of `SIGTERM`/`SIGHUP`, when every job is hung up unless
+/// the policy is to leave them.
+pub fn finish_jobs(table: &JobTable, policy: JobPolicy, hangup: bool) {
+    match policy {
+        JobPolicy::Leave => {}
+        JobPolicy::Hup => jobs::hang_up(table, false),
+        JobPolicy::Warn => jobs::hang_up(table, !hangup),
+    }
+}
+
+/// The terminal settings from when the shell started.
+#[cfg(unix)]
+static SAVED: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+/// Records the terminal settings to restore on exit. Call once at startup.
+#[cfg(unix)]
+pub fn init() {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+            *SAVED.lock().unwrap_or_else(|e| e.into_inner()) = Some(termios);
+        }
+    }
+}
+
+#[cfg(windows)]
+static SAVED: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Records the console input mode to restore on exit. Call once at startup.
+#[cfg(windows)]
+pub fn init() {
+    use windows_sys::Win32::System::Console::{GetConsoleMode, GetStdHandle, STD_INPUT_HANDLE};
+    unsafe {
+        let mut mode = 0;
+        if GetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), &mut mode) != 0 {
+            *SAVED.lock().unwrap_or_else(|e| e.into_inner()) = Some(mode);
+        }
+    }
+}
+
+/// Puts the terminal back the way the shell found it: the saved settings,
+/// no mouse reporting, and a visible cursor.
+pub fn restore_terminal() {
+    let saved = *SAVED.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(saved) = saved else {
+        return;
+    };
+    #[cfg(unix)]
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &saved);
+    }
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::System::Console::{GetStdHandle, STD_INPUT_HANDLE, SetConsoleMode};
+        SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), saved);
+    }
+    if io::stdout().is_terminal() {
+        let _ = crossterm::execute!(io::stdout(), crossterm::event::DisableMouseCapture, crossterm::cursor::Show);
+    }
+    let _ = io::stdout().flush();
+}
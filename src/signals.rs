@@ -0,0 +1,178 @@
+//! Signal handling for the interactive shell.
+//!
+//! The shell must survive Ctrl-C while a foreground child runs: the child
+//! should receive the interrupt and the shell should simply return to the
+//! prompt. On Unix this is done with a no-op `SIGINT`/`SIGQUIT` handler
+//! (handlers, unlike ignored dispositions, are reset to the default when a
+//! child execs); on Windows with a console control handler that reports the
+//! event as handled. Either way the interrupt is recorded, so builtins that
+//! block (like `wait`) can give up when the user presses Ctrl-C.
+//!
+//! `SIGTERM` and `SIGHUP` (on Windows, the console window closing) are
+//! recorded too, and the shell shuts down properly at the next chance
+//! instead of dying on the spot.
+
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// The signal asking the shell to terminate, or 0.
+static TERMINATED: AtomicI32 = AtomicI32::new(0);
+
+/// Whether Ctrl-C reached the shell since the last call.
+pub fn take_interrupt() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+/// The signal (`SIGTERM` or `SIGHUP`) that asked the shell to terminate,
+/// if one arrived.
+pub fn termination() -> Option<i32> {
+    Some(TERMINATED.load(Ordering::SeqCst)).filter(|&signal| signal != 0)
+}
+
+#[cfg(unix)]
+extern "C" fn ignore_signal(_: libc::c_int) {}
+
+#[cfg(unix)]
+extern "C" fn record_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// The thread that reads commands, which is interrupted on termination.
+#[cfg(unix)]
+static MAIN_THREAD: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Also sends `SIGINT` to the main thread (the signal may have been handled
+/// on another), which ends a pending `readline` (rustyline retries reads
+/// interrupted by other signals) and stops loops and `wait`.
+#[cfg(unix)]
+extern "C" fn record_termination(signal: libc::c_int) {
+    TERMINATED.store(signal, Ordering::SeqCst);
+    unsafe {
+        libc::pthread_kill(MAIN_THREAD.load(Ordering::SeqCst) as libc::pthread_t, libc::SIGINT);
+    }
+}
+
+/// Installs the shell's signal handlers. Call once at startup, from the
+/// main thread.
+#[cfg(unix)]
+pub fn init() {
+    unsafe {
+        MAIN_THREAD.store(libc::pthread_self() as usize, Ordering::SeqCst);
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = ignore_signal as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGQUIT, &action, std::ptr::null_mut());
+        action.sa_sigaction = record_interrupt as *const () as libc::sighandler_t;
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+        // Without SA_RESTART, so the read the main thread is blocked in
+        // fails and rustyline sees the SIGINT.
+        action.sa_sigaction = record_termination as *const () as libc::sighandler_t;
+        action.sa_flags = 0;
+        libc::sigaction(libc::SIGTERM, &action, std::ptr::null_mut());
+        libc::sigaction(libc::SIGHUP, &action, std::ptr::null_mut());
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn console_handler(ctrl_type: u32) -> windows_sys::Win32::Foundation::BOOL {
+    use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT, CTRL_CLOSE_EVENT};
+    if ctrl_type == CTRL_CLOSE_EVENT {
+        // The process is killed soon after this returns; give the main
+        // thread, whose console reads now fail, time to shut down.
+        TERMINATED.store(SIGHUP, Ordering::SeqCst);
+        std::thread::sleep(std::time::Duration::from_secs(4));
+        return 1;
+    }
+    let handled = ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT;
+    if handled {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    handled as i32
+}
+
+/// Installs the shell's console control handler. Call once at startup.
+#[cfg(windows)]
+pub fn init() {
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+    unsafe {
+        SetConsoleCtrlHandler(Some(console_handler), 1);
+    }
+}
+
+/// Whether a child was terminated by Ctrl-C, in which case the shell moves
+/// to a fresh line before printing the next prompt. (On Unix the job table
+/// decodes wait statuses itself.)
+#[cfg(windows)]
+pub fn killed_by_interrupt(status: std::process::ExitStatus) -> bool {
+    // STATUS_CONTROL_C_EXIT
+    status.code() == Some(0xC000013Au32 as i32)
+}
+
+/// Signals `kill` knows by name. Windows has no signals; there `INT`
+/// becomes a Ctrl-Break and every other signal terminates the process.
+#[cfg(unix)]
+const SIGNALS: [(&str, i32); 18] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ABRT", libc::SIGABRT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("WINCH", libc::SIGWINCH),
+];
+
+#[cfg(windows)]
+const SIGNALS: [(&str, i32); 4] = [("HUP", 1), ("INT", 2), ("KILL", 9), ("TERM", 15)];
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGTERM: i32 = 15;
+
+/// Parses `TERM`, `SIGTERM`, `term` or `15`.
+pub fn parse(spec: &str) -> Option<i32> {
+    if let Ok(number) = spec.parse::<i32>() {
+        return (number >= 0).then_some(number);
+    }
+    let upper = spec.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS.iter().find(|(n, _)| *n == name).map(|(_, number)| *number)
+}
+
+#[cfg(unix)]
+pub fn name(number: i32) -> Option<&'static str> {
+    SIGNALS.iter().find(|(_, n)| *n == number).map(|(name, _)| *name)
+}
+
+/// Signal names with their numbers, in numeric order, for `kill -l`.
+pub fn list() -> Vec<(&'static str, i32)> {
+    let mut signals = SIGNALS.to_vec();
+    signals.sort_by_key(|(_, number)| *number);
+    signals
+}
+
+/// How `jobs` describes a job killed by `signal`, as in other shells.
+#[cfg(unix)]
+pub fn describe(signal: i32) -> String {
+    match name(signal) {
+        Some("HUP") => "Hangup".to_string(),
+        Some("INT") => "Interrupt".to_string(),
+        Some("KILL") => "Killed".to_string(),
+        Some("TERM") => "Terminated".to_string(),
+        Some("QUIT") => "Quit".to_string(),
+        Some("SEGV") => "Segfault".to_string(),
+        Some(name) => format!("SIG{}", name),
+        None => format!("Signal {}", signal),
+    }
+}
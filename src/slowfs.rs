@@ -0,0 +1,175 @@
+//! Keeping the prompt quick on slow filesystems.
+//!
+//! To tell whether a repository is dirty, the git segment reads the whole
+//! working tree, which takes seconds on a network filesystem (NFS, SMB,
+//! sshfs, ...) or in a very large directory. In such a place the check runs
+//! in the background instead, and its result shows from the next prompt on
+//! (`slow_fs.git = "defer"`, the default); or it is skipped and only the
+//! branch shown (`"branch"`); or the git segment is left out (`"off"`).
+//!
+//! A directory is slow when it is on a network filesystem or holds more
+//! than `slow_fs.max_entries` entries (5000 by default), found out once per
+//! directory. `[slow_fs.paths]` marks directories, and everything below
+//! them, `"slow"` or `"fast"` whatever is detected; `slow_fs.detect = false`
+//! leaves only those.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::config::{self, Config};
+
+/// Filesystem types reached over the network, as Linux and macOS name them.
+#[cfg(unix)]
+const NETWORK_FS: [&str; 12] = [
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "afpfs", "9p", "davfs", "webdav", "fuse.sshfs", "sshfs", "fuse.rclone",
+];
+
+/// Whether each directory seen so far is slow.
+static SLOW: Mutex<Option<HashMap<PathBuf, bool>>> = Mutex::new(None);
+/// Results of checks run in the background, by repository.
+static DEFERRED: Mutex<Option<HashMap<PathBuf, bool>>> = Mutex::new(None);
+/// Repositories with a check under way.
+static RUNNING: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// What the git segment does in the current directory.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Git {
+    /// Branch and dirty state.
+    Full,
+    /// Dirty state from a check in the background.
+    Defer,
+    /// Branch only.
+    Branch,
+    Off,
+}
+
+pub fn git_mode(config: &Config, cwd: &Path) -> Git {
+    if !slow(config, cwd) {
+        return Git::Full;
+    }
+    match config.get_str("slow_fs.git") {
+        Some("branch") => Git::Branch,
+        Some("off") => Git::Off,
+        _ => Git::Defer,
+    }
+}
+
+/// Whether `dir` is slow: as marked in `[slow_fs.paths]`, or else as
+/// detected.
+fn slow(config: &Config, dir: &Path) -> bool {
+    let marked = config
+        .get_str_map("slow_fs.paths")
+        .into_iter()
+        .map(|(path, speed)| (PathBuf::from(config::expand_home(&path)), speed))
+        .filter(|(path, _)| dir.starts_with(path))
+        .max_by_key(|(path, _)| path.components().count());
+    if let Some((_, speed)) = marked {
+        return speed == "slow";
+    }
+    if !config.get_bool("slow_fs.detect").unwrap_or(true) {
+        return false;
+    }
+    let mut known = SLOW.lock().unwrap();
+    let known = known.get_or_insert_with(HashMap::new);
+    if let Some(&slow) = known.get(dir) {
+        return slow;
+    }
+    let max_entries = config.get_int("slow_fs.max_entries").unwrap_or(5000).max(1) as usize;
+    let slow = network_fs(dir) || fs::read_dir(dir).is_ok_and(|entries| entries.take(max_entries + 1).count() > max_entries);
+    known.insert(dir.to_path_buf(), slow);
+    slow
+}
+
+/// Undoes the octal escapes of `/proc/self/mounts` (`\040` for a space).
+#[cfg(target_os = "linux")]
+fn unescape(field: &str) -> String {
+    let mut out = String::new();
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        match rest.get(i + 1..i + 4).and_then(|code| u8::from_str_radix(code, 8).ok()) {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The mount points and filesystem types of the mounted filesystems.
+#[cfg(target_os = "linux")]
+fn mounts() -> Vec<(PathBuf, String)> {
+    let text = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let point = fields.nth(1)?;
+            Some((PathBuf::from(unescape(point)), fields.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// The same from `mount`, whose lines read `what on /point (type, ...)`.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn mounts() -> Vec<(PathBuf, String)> {
+    let Ok(output) = std::process::Command::new("mount").output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (point, options) = rest.rsplit_once(" (")?;
+            let kind = options.split([',', ')']).next()?.trim();
+            Some((PathBuf::from(point), kind.to_string()))
+        })
+        .collect()
+}
+
+/// Whether `dir` is on a filesystem in [`NETWORK_FS`]: the one mounted at
+/// the longest mount point containing it.
+#[cfg(unix)]
+fn network_fs(dir: &Path) -> bool {
+    mounts()
+        .into_iter()
+        .filter(|(point, _)| dir.starts_with(point))
+        .max_by_key(|(point, _)| point.components().count())
+        .is_some_and(|(_, kind)| NETWORK_FS.contains(&kind.as_str()))
+}
+
+/// On Windows, a network share reached by its UNC path (`\\server\share`).
+#[cfg(windows)]
+fn network_fs(dir: &Path) -> bool {
+    use std::path::{Component, Prefix};
+    matches!(
+        dir.components().next(),
+        Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::UNC(..) | Prefix::VerbatimUNC(..))
+    )
+}
+
+/// The result of `check` for repository `repo`, run in the background: the
+/// last one finished, if any. A new check starts unless one is under way.
+pub fn deferred(repo: &Path, check: fn(&Path) -> Option<bool>) -> Option<bool> {
+    let mut running = RUNNING.lock().unwrap();
+    if running.get_or_insert_with(HashSet::new).insert(repo.to_path_buf()) {
+        let repo = repo.to_path_buf();
+        thread::spawn(move || {
+            if let Some(result) = check(&repo) {
+                DEFERRED.lock().unwrap().get_or_insert_with(HashMap::new).insert(repo.clone(), result);
+            }
+            if let Some(running) = RUNNING.lock().unwrap().as_mut() {
+                running.remove(&repo);
+            }
+        });
+    }
+    DEFERRED.lock().unwrap().as_ref()?.get(repo).copied()
+}
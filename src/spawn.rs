@@ -0,0 +1,83 @@
+//! The `spawn` builtin: starts a command fully detached from the shell.
+//!
+//! Unlike `cmd &`, a spawned command is not a job. It runs in a session of
+//! its own (on Windows, detached from the console), so it neither receives
+//! Ctrl-C nor is hung up when the terminal closes or the shell exits. Its
+//! input is empty and its output goes to a log file, by default in the
+//! `spawn` directory under the data directory.
+
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use chrono::Local;
+
+use crate::config;
+use crate::jobs;
+use crate::lookup;
+use crate::messages::msg;
+
+/// Where output goes without `-o`: `<data dir>/spawn/<program>-<time>.log`.
+fn default_log(program: &str) -> PathBuf {
+    let name = Path::new(program).file_stem().map_or_else(|| program.into(), |s| s.to_string_lossy());
+    let file = format!("{}-{}.log", name, Local::now().format("%Y%m%d-%H%M%S"));
+    config::data_dir().join("spawn").join(file)
+}
+
+/// `spawn [-o log] <command> [args...]`
+pub fn run(args: &[&str]) -> i32 {
+    let (log, command) = match args {
+        ["-o", log, command @ ..] => (Some(PathBuf::from(config::expand_home(log))), command),
+        ["-o"] => (None, &[][..]),
+        command => (None, command),
+    };
+    let Some((program, program_args)) = command.split_first() else {
+        println!("{}", msg!("usage.spawn"));
+        return 2;
+    };
+    let log = log.unwrap_or_else(|| default_log(program));
+    let output = log.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| {
+        let file = OpenOptions::new().create(true).append(true).open(&log)?;
+        Ok((file.try_clone()?, file))
+    });
+    let (stdout, stderr) = match output {
+        Ok(files) => files,
+        Err(e) => {
+            println!("spawn: {}", msg!("error.path", log.display(), e));
+            return 1;
+        }
+    };
+
+    let mut process = lookup::command_for(program);
+    process.args(program_args).stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid is async-signal-safe.
+        unsafe {
+            process.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        process.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+
+    match process.spawn() {
+        Ok(child) => {
+            println!("{}", msg!("spawn.started", child.id(), log.display()));
+            jobs::reap_later(child);
+            0
+        }
+        Err(e) => {
+            println!("spawn: {}: {}", program, e);
+            if e.kind() == std::io::ErrorKind::NotFound { 127 } else { 126 }
+        }
+    }
+}
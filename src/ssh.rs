@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+/// Remote listings are reused for this long, failures included, so a dead
+/// host doesn't stall every Tab press.
+const LISTING_TTL: Duration = Duration::from_secs(120);
+const LISTING_TIMEOUT: Duration = Duration::from_secs(3);
+
+type Listing = (Instant, Vec<String>);
+
+static LISTINGS: Mutex<Option<HashMap<(String, String), Listing>>> = Mutex::new(None);
+
+/// Host aliases from `~/.ssh/config`, skipping wildcard patterns.
+pub fn config_hosts() -> Vec<String> {
+    let Some(home) = config::home_dir() else {
+        return Vec::new();
+    };
+    let text = fs::read_to_string(home.join(".ssh").join("config")).unwrap_or_default();
+    let mut hosts = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let Some((key, value)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        if !key.eq_ignore_ascii_case("host") {
+            continue;
+        }
+        for host in value.split_whitespace() {
+            if !host.contains(['*', '?', '!']) && !hosts.iter().any(|h| h == host) {
+                hosts.push(host.to_string());
+            }
+        }
+    }
+    hosts
+}
+
+/// Host names from `~/.ssh/known_hosts`. Hashed entries can't be recovered
+/// and are skipped; `[host]:port` entries yield `host`.
+pub fn known_hosts() -> Vec<String> {
+    let Some(home) = config::home_dir() else {
+        return Vec::new();
+    };
+    let text = fs::read_to_string(home.join(".ssh").join("known_hosts")).unwrap_or_default();
+    let mut hosts: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let Some(field) = line.split_whitespace().next() else {
+            continue;
+        };
+        if field.starts_with('#') || field.starts_with('|') || field.starts_with('@') {
+            continue;
+        }
+        for host in field.split(',') {
+            let host = match host.strip_prefix('[') {
+                Some(rest) => rest.split(']').next().unwrap_or(rest),
+                None => host,
+            };
+            if !host.is_empty() && !hosts.iter().any(|h| h == host) {
+                hosts.push(host.to_string());
+            }
+        }
+    }
+    hosts
+}
+
+/// Every host octane knows about, `~/.ssh/config` aliases first.
+pub fn all_hosts() -> Vec<String> {
+    let mut hosts = config_hosts();
+    for host in known_hosts() {
+        if !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+    hosts
+}
+
+/// Runs `cmd`, killing it if it hasn't finished within `timeout`. Returns
+/// stdout only when the command exits successfully in time.
+pub fn output_with_timeout(mut cmd: Command, timeout: Duration) -> Option<String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut out = String::new();
+        let _ = stdout.read_to_string(&mut out);
+        out
+    });
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                let out = reader.join().ok()?;
+                return status.success().then_some(out);
+            }
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+/// Quotes `path` for the remote shell while keeping a leading `~/` expandable.
+fn remote_quote(path: &str) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', r"'\''"));
+    if path.is_empty() {
+        ".".to_string()
+    } else if path == "~" {
+        "~".to_string()
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        format!("~/{}", quote(rest))
+    } else {
+        quote(path)
+    }
+}
+
+/// Lists `dir` on `host` via a non-interactive `ssh host ls`. Directory names
+/// end in `/`.
+pub fn list_remote(host: &str, dir: &str) -> Vec<String> {
+    let key = (host.to_string(), dir.to_string());
+    {
+        let cache = LISTINGS.lock().unwrap();
+        if let Some((at, entries)) = cache.as_ref().and_then(|c| c.get(&key))
+            && at.elapsed() < LISTING_TTL
+        {
+            return entries.clone();
+        }
+    }
+
+    let mut cmd = Command::new("ssh");
+    cmd.args(["-o", "BatchMode=yes", "-o", "ConnectTimeout=2", host, "--"])
+        .arg(format!("ls -1pA {}", remote_quote(dir)));
+    let entries: Vec<String> = output_with_timeout(cmd, LISTING_TIMEOUT)
+        .map(|out| out.lines().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    LISTINGS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(key, (Instant::now(), entries.clone()));
+    entries
+}
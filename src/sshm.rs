@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, Config};
+use crate::picker;
+use crate::ssh;
+
+fn recent_path() -> PathBuf {
+    config::data_dir().join("sshm_recent")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Last connection time per host, as recorded by previous `sshm` sessions.
+fn load_recent() -> HashMap<String, u64> {
+    fs::read_to_string(recent_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (host, ts) = line.split_once('\t')?;
+            Some((host.to_string(), ts.parse().ok()?))
+        })
+        .collect()
+}
+
+fn record_connection(host: &str) {
+    let mut recent = load_recent();
+    recent.insert(host.to_string(), now_secs());
+    let text: String = recent
+        .iter()
+        .map(|(host, ts)| format!("{}\t{}\n", host, ts))
+        .collect();
+    let _ = fs::write(recent_path(), text);
+}
+
+fn ago(ts: u64) -> String {
+    let secs = now_secs().saturating_sub(ts);
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Hosts from `~/.ssh/config`, most recently connected first.
+fn hosts_by_recency(recent: &HashMap<String, u64>) -> Vec<String> {
+    let mut hosts = ssh::config_hosts();
+    hosts.sort_by_key(|h| std::cmp::Reverse(recent.get(h).copied().unwrap_or(0)));
+    hosts
+}
+
+fn is_safe_alias(name: &str, value: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+        && !value.contains(['\n', '\r'])
+}
+
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// A POSIX sh snippet run as the remote command. It writes a temporary rc
+/// file that sources the user's own `~/.bashrc` and then defines the
+/// propagated aliases, falls back to `TERM=xterm-256color` when the remote
+/// has no terminfo entry for the local terminal, and starts an interactive
+/// shell with that rc file. Alias values are single-quoted and the heredoc
+/// delimiter is quoted, so nothing is expanded on the way.
+fn bootstrap(aliases: &[(&String, &String)]) -> String {
+    let mut rc = String::from("[ -f ~/.bashrc ] && . ~/.bashrc\n");
+    for (name, value) in aliases {
+        rc.push_str(&format!("alias {}={}\n", name, sh_quote(value)));
+    }
+    format!(
+        "T=$(mktemp) || exit 1\n\
+         cat > \"$T\" <<'OCTANE_RC'\n{}OCTANE_RC\n\
+         infocmp \"$TERM\" >/dev/null 2>&1 || TERM=xterm-256color; export TERM\n\
+         if command -v bash >/dev/null 2>&1; then bash --rcfile \"$T\" -i; else ENV=\"$T\" sh -i; fi\n\
+         S=$?; rm -f \"$T\"; exit $S\n",
+        rc
+    )
+}
+
+fn connect(host: &str, bootstrap_session: bool, config: &Config, aliases: &HashMap<String, String>) -> i32 {
+    record_connection(host);
+    let mut cmd = Command::new("ssh");
+    if bootstrap_session {
+        let wanted = config.get_str_list("sshm.aliases");
+        let mut propagated: Vec<(&String, &String)> = aliases
+            .iter()
+            .filter(|(name, _)| wanted.is_empty() || wanted.contains(name))
+            .filter(|(name, value)| is_safe_alias(name, value))
+            .collect();
+        propagated.sort();
+        cmd.args(["-t", host, bootstrap(&propagated).as_str()]);
+    } else {
+        cmd.arg(host);
+    }
+    match cmd.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            println!("Error running ssh: {}", e);
+            1
+        }
+    }
+}
+
+/// `sshm [list] [-b|--bootstrap] [query]`
+///
+/// Picks a host from `~/.ssh/config` (fuzzy-filtered by `query`, most
+/// recently used first) and connects to it. With `--bootstrap` (or
+/// `sshm.bootstrap = true`) the remote shell gets the local aliases listed
+/// in `sshm.aliases` (all of them if unset) and a TERM fallback.
+pub fn run(args: &[&str], config: &Config, aliases: &HashMap<String, String>) -> i32 {
+    let recent = load_recent();
+    let hosts = hosts_by_recency(&recent);
+
+    if args.first() == Some(&"list") {
+        if hosts.is_empty() {
+            println!("No hosts in ~/.ssh/config");
+        }
+        for host in &hosts {
+            match recent.get(host) {
+                Some(ts) => println!("{:<30} {}", host, ago(*ts)),
+                None => println!("{:<30} never", host),
+            }
+        }
+        return 0;
+    }
+
+    let mut bootstrap_session = config.get_bool("sshm.bootstrap").unwrap_or(false);
+    let mut query = Vec::new();
+    for arg in args {
+        match *arg {
+            "-b" | "--bootstrap" => bootstrap_session = true,
+            other => query.push(other),
+        }
+    }
+    let query = query.join(" ");
+
+    if hosts.contains(&query) {
+        return connect(&query, bootstrap_session, config, aliases);
+    }
+    let candidates: Vec<String> = picker::fuzzy_filter(&query, &hosts)
+        .into_iter()
+        .map(|i| hosts[i].clone())
+        .collect();
+    let host = match candidates.len() {
+        0 => {
+            println!("sshm: no host matches '{}'", query);
+            return 1;
+        }
+        1 => candidates[0].clone(),
+        _ => match picker::pick("ssh", &candidates) {
+            Some(i) => candidates[i].clone(),
+            None => return 1,
+        },
+    };
+    connect(&host, bootstrap_session, config, aliases)
+}
@@ -0,0 +1,72 @@
+//! Output timestamping: `ts cmd` and `set -o timestamps`.
+//!
+//! A stamped command writes to pipes, and the shell copies its output to the
+//! terminal with the time in front of every line: by default the time since
+//! the command started, or with `-a` (or `ts.format = "absolute"`) the time
+//! of day. Each line is stamped when its first byte arrives, so a gap
+//! between two stamps shows where a long build stalled.
+//!
+//! `ts cmd` stamps one command (every program it runs, timed from when `ts`
+//! started); `set -o timestamps` stamps every foreground command, except the
+//! full-screen and interactive programs that are never captured either.
+
+use std::time::Instant;
+
+use chrono::Local;
+use colored::*;
+
+use crate::config::Config;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Seconds since the command started.
+    Elapsed,
+    /// The time of day.
+    Absolute,
+}
+
+impl Mode {
+    pub fn from_config(config: &Config) -> Mode {
+        match config.get_str("ts.format") {
+            Some("absolute") => Mode::Absolute,
+            _ => Mode::Elapsed,
+        }
+    }
+}
+
+/// Timestamping as switched on for the commands being run.
+#[derive(Clone, Copy)]
+pub struct Stamping {
+    pub mode: Mode,
+    /// When `ts` started; without it, each program is timed on its own.
+    pub since: Option<Instant>,
+}
+
+impl Stamping {
+    /// Whether `ts` asked for it, so even programs never captured are
+    /// stamped.
+    pub fn forced(&self) -> bool {
+        self.since.is_some()
+    }
+}
+
+/// Splits `ts [-a|-e] cmd...` into the mode asked for, if any, and the
+/// command to stamp.
+pub fn prefix(command: &str) -> Option<(Option<Mode>, &str)> {
+    let rest = command.trim_start().strip_prefix("ts ")?.trim_start();
+    let (mode, rest) = match rest.split_once(char::is_whitespace) {
+        Some(("-a", rest)) => (Some(Mode::Absolute), rest.trim_start()),
+        Some(("-e", rest)) => (Some(Mode::Elapsed), rest.trim_start()),
+        _ => (None, rest),
+    };
+    (!rest.is_empty() && !rest.starts_with('-')).then_some((mode, rest))
+}
+
+/// The stamp for a line starting now.
+pub fn stamp(mode: Mode, since: Instant) -> String {
+    let text = match mode {
+        Mode::Elapsed => format!("[{:>9.3}s]", since.elapsed().as_secs_f64()),
+        Mode::Absolute => format!("[{}]", Local::now().format("%H:%M:%S%.3f")),
+    };
+    format!("{} ", text.dimmed())
+}
@@ -0,0 +1,66 @@
+//! "Did you mean" suggestions for commands that aren't found.
+//!
+//! The names the shell could run — programs in `PATH`, builtins, aliases
+//! and functions — are compared with the one typed, and the closest few
+//! within a small number of typos are offered. `shell.correct` chooses
+//! whether they are only printed (`"suggest"`, the default), offered to run
+//! with a keypress (`"prompt"`), or not looked for (`"off"`).
+
+use std::collections::HashMap;
+
+use rustshell::builtins;
+
+use crate::complete::edit_distance;
+use crate::config::Config;
+use crate::lookup;
+
+/// How many suggestions are shown at most.
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Off,
+    Suggest,
+    /// Ask to run the best suggestion instead.
+    Prompt,
+}
+
+impl Mode {
+    pub fn from_config(config: &Config) -> Mode {
+        match config.get_str("shell.correct") {
+            Some("off") => Mode::Off,
+            Some("prompt") => Mode::Prompt,
+            _ => Mode::Suggest,
+        }
+    }
+}
+
+/// Typos tolerated: one, or two for names longer than five characters.
+fn budget(name: &str) -> usize {
+    if name.chars().count() > 5 { 2 } else { 1 }
+}
+
+/// The names closest to `name`, best first.
+pub fn similar(name: &str, aliases: &HashMap<String, String>, functions: &HashMap<String, String>) -> Vec<String> {
+    let budget = budget(name);
+    let mut names: Vec<String> = lookup::program_names();
+    names.extend(builtins::all().iter().map(|b| b.name.clone()));
+    names.extend(aliases.keys().cloned());
+    names.extend(functions.keys().cloned());
+    let mut close: Vec<(usize, String)> = names
+        .into_iter()
+        .filter(|candidate| candidate != name)
+        .filter_map(|candidate| {
+            // Cheap check first: lengths further apart than the budget can't match.
+            let gap = candidate.chars().count().abs_diff(name.chars().count());
+            if gap > budget {
+                return None;
+            }
+            let distance = edit_distance(name, &candidate);
+            (distance <= budget).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort();
+    close.dedup_by(|a, b| a.1 == b.1);
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, name)| name).collect()
+}
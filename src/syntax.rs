@@ -0,0 +1,312 @@
+//! The structure of command lines: `{ ...; }` groups and the compound
+//! commands `if`, `while`, `until` and `for`.
+//!
+//! Reserved words only count at the position of a command (`echo if` is
+//! just an argument), so the scanner walks the words of a line noting where
+//! commands start. Compound commands are parsed into slices of the original
+//! text; their conditions and bodies are run as command lists, so nesting
+//! works the same way at every level.
+
+use crate::messages::msg;
+
+/// Words that open a block, with the word that closes it.
+const OPENERS: [(&str, &str); 5] = [("if", "fi"), ("while", "done"), ("until", "done"), ("for", "done"), ("{", "}")];
+const CLOSERS: [&str; 3] = ["fi", "done", "}"];
+/// Words after which another command starts.
+const LEADERS: [&str; 9] = ["if", "elif", "then", "else", "while", "until", "do", "{", "!"];
+/// Reserved words that can't start a command on their own.
+pub const DANGLING: [&str; 7] = ["then", "elif", "else", "fi", "do", "done", "}"];
+
+/// A reserved word in command position.
+pub struct Reserved {
+    pub word: &'static str,
+    pub start: usize,
+    pub end: usize,
+    /// Blocks open before this word.
+    pub depth: i32,
+}
+
+impl Reserved {
+    fn delta(&self) -> i32 {
+        if OPENERS.iter().any(|(open, _)| *open == self.word) {
+            1
+        } else if CLOSERS.contains(&self.word) {
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+fn reserved(word: &str) -> Option<&'static str> {
+    OPENERS
+        .iter()
+        .map(|(open, _)| *open)
+        .chain(LEADERS)
+        .chain(DANGLING)
+        .chain(["function"])
+        .find(|w| *w == word)
+}
+
+/// Finds the reserved words of `text` that are in command position: at the
+/// start, after `;`, `&&`, `||`, `|`, `&` or a newline, after a word like
+/// `then` or `do`, or where a function body starts (`f() {`). Nothing in
+/// quotes counts.
+pub fn reserved_words(text: &str) -> Vec<Reserved> {
+    let mut found = Vec::new();
+    let mut depth = 0;
+    let mut command_position = true;
+    let mut after_function = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() && c != '\n' {
+            continue;
+        }
+        if "\n;&|".contains(c) {
+            command_position = true;
+            continue;
+        }
+        if c == '(' && chars.peek().is_some_and(|(_, next)| *next == ')') {
+            chars.next();
+            command_position = true;
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        // Quoted text is part of the word, separators and all.
+        let mut quote = matches!(c, '\'' | '"').then_some(c);
+        while let Some(&(i, next)) = chars.peek() {
+            if quote.is_none()
+                && (next.is_whitespace() || "\n;&|".contains(next) || (next == '(' && text[i..].starts_with("()")))
+            {
+                break;
+            }
+            match quote {
+                Some(q) if next == q => quote = None,
+                None if matches!(next, '\'' | '"') => quote = Some(next),
+                _ => {}
+            }
+            end = i + next.len_utf8();
+            chars.next();
+        }
+        let word = &text[start..end];
+        if after_function {
+            // The function's name; its body may follow.
+            after_function = false;
+            command_position = true;
+            continue;
+        }
+        match reserved(word).filter(|_| command_position) {
+            Some(word) => {
+                let reserved = Reserved { word, start, end, depth };
+                depth += reserved.delta();
+                command_position = LEADERS.contains(&word);
+                after_function = word == "function";
+                found.push(reserved);
+            }
+            None => command_position = false,
+        }
+    }
+    found
+}
+
+/// How many blocks are still open at the end of `text`; more input is
+/// needed while this is positive.
+pub fn open_blocks(text: &str) -> i32 {
+    reserved_words(text).iter().map(Reserved::delta).sum()
+}
+
+/// The block nesting at each byte offset where it changes, for splitting
+/// a line only outside of blocks.
+pub fn depth_changes(text: &str) -> Vec<(usize, i32)> {
+    reserved_words(text).iter().map(|r| (r.start, r.delta())).collect()
+}
+
+/// The body of `{ body }` when `text` is exactly one group.
+pub fn group_body(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let words = reserved_words(text);
+    let open = words.first().filter(|r| r.word == "{" && r.start == 0)?;
+    let close = words.iter().find(|r| r.word == "}" && r.depth == 1)?;
+    (close.end == text.len()).then(|| &text[open.end..close.start])
+}
+
+/// A compound command, with its parts as slices of the command text.
+pub enum Compound<'a> {
+    /// `if c; then b; [elif c; then b;]... [else b;] fi`
+    If {
+        branches: Vec<(&'a str, &'a str)>,
+        otherwise: Option<&'a str>,
+    },
+    /// `while c; do b; done`, or with `until`, looping while `c` fails.
+    Loop { until: bool, condition: &'a str, body: &'a str },
+    /// `for name [in words]; do b; done`; without `in`, loops over the
+    /// positional parameters.
+    For {
+        name: &'a str,
+        words: Option<&'a str>,
+        body: &'a str,
+    },
+}
+
+fn syntax_error(near: &str) -> String {
+    msg!("error.syntax", near)
+}
+
+/// Parses `command` if it starts with `if`, `while`, `until` or `for`.
+pub fn parse_compound(command: &str) -> Option<Result<Compound<'_>, String>> {
+    let text = command.trim();
+    let words = reserved_words(text);
+    let first = words.first().filter(|r| r.start == 0)?;
+    if !matches!(first.word, "if" | "while" | "until" | "for") {
+        return None;
+    }
+    // The block's own words, one level in.
+    let parts: Vec<&Reserved> = words
+        .iter()
+        .filter(|r| r.depth == 1 && matches!(r.word, "then" | "elif" | "else" | "fi" | "do" | "done"))
+        .collect();
+    let closer = OPENERS.iter().find(|(open, _)| *open == first.word).map(|(_, close)| *close)?;
+    let Some(end) = parts.iter().position(|r| r.word == closer) else {
+        return Some(Err(msg!("error.missing_closer", closer)));
+    };
+    if parts[end].end != text.len() {
+        return Some(Err(syntax_error(text[parts[end].end..].trim())));
+    }
+    let parts = &parts[..=end];
+    let between = |a: usize, b: &Reserved| &text[a..b.start];
+
+    let compound = match first.word {
+        "if" => {
+            let mut branches = Vec::new();
+            let mut otherwise = None;
+            let mut condition_start = first.end;
+            let mut i = 0;
+            loop {
+                let (Some(then), Some(next)) = (parts.get(i), parts.get(i + 1)) else {
+                    return Some(Err(syntax_error("fi")));
+                };
+                if then.word != "then" {
+                    return Some(Err(syntax_error(then.word)));
+                }
+                branches.push((between(condition_start, then), between(then.end, next)));
+                match next.word {
+                    "elif" => {
+                        condition_start = next.end;
+                        i += 2;
+                    }
+                    "else" => match parts.get(i + 2) {
+                        Some(fi) if fi.word == "fi" => {
+                            otherwise = Some(between(next.end, fi));
+                            break;
+                        }
+                        Some(other) => return Some(Err(syntax_error(other.word))),
+                        None => return Some(Err(syntax_error("fi"))),
+                    },
+                    "fi" => break,
+                    other => return Some(Err(syntax_error(other))),
+                }
+            }
+            Compound::If { branches, otherwise }
+        }
+        _ => {
+            let [r#do, done] = parts else {
+                return Some(Err(syntax_error(parts[0].word)));
+            };
+            if r#do.word != "do" || done.word != "done" {
+                return Some(Err(syntax_error(r#do.word)));
+            }
+            let header = between(first.end, r#do);
+            let body = between(r#do.end, done);
+            if first.word != "for" {
+                Compound::Loop { until: first.word == "until", condition: header, body }
+            } else {
+                let header = header.trim().trim_end_matches(';').trim_end();
+                let (name, rest) = header.split_once(char::is_whitespace).unwrap_or((header, ""));
+                let rest = rest.trim_start();
+                let words = match rest.strip_prefix("in") {
+                    Some(words) if words.is_empty() || words.starts_with(char::is_whitespace) => Some(words),
+                    _ if rest.is_empty() => None,
+                    _ => return Some(Err(syntax_error(rest))),
+                };
+                if name.is_empty() {
+                    return Some(Err(syntax_error("do")));
+                }
+                Compound::For { name, words, body }
+            }
+        }
+    };
+    Some(Ok(compound))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<&'static str> {
+        reserved_words(text).iter().map(|r| r.word).collect()
+    }
+
+    #[test]
+    fn reserved_words_only_in_command_position() {
+        assert_eq!(words("if true; then echo if; fi"), ["if", "then", "fi"]);
+        assert_eq!(words("for x in a b; do echo $x; done"), ["for", "do", "done"]);
+    }
+
+    #[test]
+    fn reserved_words_skip_quoted_text() {
+        assert_eq!(words("echo 'a; done'"), Vec::<&str>::new());
+        assert_eq!(words("echo \"x && fi\" ; { echo; }"), ["{", "}"]);
+        assert_eq!(open_blocks("echo 'if'"), 0);
+    }
+
+    #[test]
+    fn group_body_is_the_whole_group() {
+        assert_eq!(group_body("{ echo a; echo b; }"), Some(" echo a; echo b; "));
+        assert_eq!(group_body("{ echo a; } && echo b"), None);
+    }
+
+    #[test]
+    fn if_with_elif_and_else() {
+        let Some(Ok(Compound::If { branches, otherwise })) =
+            parse_compound("if a; then b; elif c; then d; else e; fi")
+        else {
+            panic!("not an if");
+        };
+        assert_eq!(branches, [(" a; ", " b; "), (" c; ", " d; ")]);
+        assert_eq!(otherwise, Some(" e; "));
+    }
+
+    #[test]
+    fn loops() {
+        let Some(Ok(Compound::Loop { until, condition, body })) = parse_compound("until make; do sleep 1; done")
+        else {
+            panic!("not a loop");
+        };
+        assert!(until);
+        assert_eq!((condition, body), (" make; ", " sleep 1; "));
+
+        let Some(Ok(Compound::For { name, words, body })) = parse_compound("for f in *.rs; do wc $f; done") else {
+            panic!("not a for loop");
+        };
+        assert_eq!((name, words, body), ("f", Some(" *.rs"), " wc $f; "));
+        assert!(matches!(parse_compound("for arg; do echo; done"), Some(Ok(Compound::For { words: None, .. }))));
+    }
+
+    #[test]
+    fn other_commands_are_not_compound() {
+        assert!(parse_compound("echo if").is_none());
+        assert!(parse_compound("{ if; }").is_none());
+    }
+
+    #[test]
+    fn compound_syntax_errors() {
+        let error = |command| match parse_compound(command) {
+            Some(Err(e)) => e,
+            _ => panic!("no error for {}", command),
+        };
+        assert_eq!(error("if a; then b;"), msg!("error.missing_closer", "fi"));
+        assert_eq!(error("while a; do b; done; c"), msg!("error.syntax", "; c"));
+        assert_eq!(error("if a; else b; fi"), msg!("error.syntax", "else"));
+        assert_eq!(error("for in x; do b; done"), msg!("error.syntax", "x"));
+    }
+}
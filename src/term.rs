@@ -0,0 +1,399 @@
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use colored::Color;
+
+use crate::config;
+use crate::messages::msg;
+
+/// How much color the terminal on stdout can display.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ColorLevel {
+    None = 0,
+    Basic = 1,
+    Ansi256 = 2,
+    TrueColor = 3,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(ColorLevel::Basic as u8);
+
+fn env_set(name: &str) -> bool {
+    env::var_os(name).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Detects the color level from the environment:
+///
+/// * `CLICOLOR_FORCE` (non-empty, not `0`) forces color on, even when piped.
+/// * `NO_COLOR` (non-empty), a non-TTY stdout, `TERM=dumb`, or running
+///   non-interactively (a script, `-c`, or piped commands) turns it off.
+/// * `COLORTERM=truecolor|24bit` enables 24-bit color, a `TERM` containing
+///   `256color` enables 256 colors, anything else gets the basic 16.
+fn detect(console_ok: bool, interactive: bool) -> ColorLevel {
+    let forced = env_set("CLICOLOR_FORCE");
+    if !forced
+        && (env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+            || !interactive
+            || !io::stdout().is_terminal()
+            || !console_ok
+            || env::var("TERM").is_ok_and(|t| t == "dumb"))
+    {
+        return ColorLevel::None;
+    }
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" || env::var_os("WT_SESSION").is_some() {
+        ColorLevel::TrueColor
+    } else if env::var("TERM").is_ok_and(|t| t.contains("256color")) {
+        ColorLevel::Ansi256
+    } else {
+        ColorLevel::Basic
+    }
+}
+
+/// Detects the color level once at startup and configures `colored` to
+/// match. `console_ok` is false when a Windows console refused ANSI
+/// processing.
+pub fn init(console_ok: bool, interactive: bool) {
+    let level = detect(console_ok, interactive);
+    LEVEL.store(level as u8, Ordering::Relaxed);
+    colored::control::set_override(level != ColorLevel::None);
+}
+
+pub fn color_level() -> ColorLevel {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => ColorLevel::None,
+        1 => ColorLevel::Basic,
+        2 => ColorLevel::Ansi256,
+        _ => ColorLevel::TrueColor,
+    }
+}
+
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::White, (229, 229, 229)),
+    (Color::BrightBlack, (127, 127, 127)),
+    (Color::BrightRed, (255, 0, 0)),
+    (Color::BrightGreen, (0, 255, 0)),
+    (Color::BrightYellow, (255, 255, 0)),
+    (Color::BrightBlue, (92, 92, 255)),
+    (Color::BrightMagenta, (255, 0, 255)),
+    (Color::BrightCyan, (0, 255, 255)),
+    (Color::BrightWhite, (255, 255, 255)),
+];
+
+/// Maps a 24-bit color to the nearest of the 16 basic colors when the
+/// terminal can't display it; other colors pass through unchanged.
+pub fn degrade(color: Color) -> Color {
+    match color {
+        Color::TrueColor { r, g, b } if color_level() < ColorLevel::TrueColor => {
+            let distance = |(cr, cg, cb): (u8, u8, u8)| {
+                let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                d(r, cr) + d(g, cg) + d(b, cb)
+            };
+            BASIC
+                .iter()
+                .min_by_key(|(_, rgb)| distance(*rgb))
+                .map(|(c, _)| *c)
+                .unwrap_or(color)
+        }
+        other => other,
+    }
+}
+
+/// A color vision deficiency to adjust the red, green, and yellow used for
+/// errors, success, and warnings (such as a dirty repository) for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Vision {
+    Normal = 0,
+    Deuteranopia = 1,
+    Protanopia = 2,
+    Tritanopia = 3,
+}
+
+static VISION: AtomicU8 = AtomicU8::new(Vision::Normal as u8);
+
+type Rgb = (u8, u8, u8);
+
+/// Error, success, and warning colors for each deficiency, drawn from
+/// palettes that stay distinct under it.
+const PALETTES: [(Vision, [Rgb; 3]); 3] = [
+    (Vision::Deuteranopia, [(213, 94, 0), (86, 180, 233), (240, 228, 66)]),
+    (Vision::Protanopia, [(230, 97, 0), (86, 180, 233), (240, 228, 66)]),
+    (Vision::Tritanopia, [(224, 36, 94), (0, 166, 166), (245, 245, 245)]),
+];
+
+/// Reads `color_vision` ("deuteranopia", "protanopia", "tritanopia", or
+/// "normal") from the config.
+pub fn init_vision(config: &config::Config) {
+    let vision = match config.get_str("color_vision") {
+        None | Some("normal") => Vision::Normal,
+        Some("deuteranopia") => Vision::Deuteranopia,
+        Some("protanopia") => Vision::Protanopia,
+        Some("tritanopia") => Vision::Tritanopia,
+        Some(other) => {
+            println!(
+                "Error: unknown color_vision: {} (expected \"deuteranopia\", \"protanopia\", \"tritanopia\" or \"normal\")",
+                other
+            );
+            Vision::Normal
+        }
+    };
+    VISION.store(vision as u8, Ordering::Relaxed);
+}
+
+/// Swaps red, green, and yellow for the configured color vision palette;
+/// other colors pass through unchanged.
+fn adjust(color: Color) -> Color {
+    let vision = VISION.load(Ordering::Relaxed);
+    let Some((_, [error, success, warning])) = PALETTES.iter().find(|(v, _)| *v as u8 == vision) else {
+        return color;
+    };
+    let (r, g, b) = match color {
+        Color::Red | Color::BrightRed => *error,
+        Color::Green | Color::BrightGreen => *success,
+        Color::Yellow | Color::BrightYellow => *warning,
+        other => return other,
+    };
+    Color::TrueColor { r, g, b }
+}
+
+/// The color to draw `color` with: adjusted for the user's color vision,
+/// then degraded to what the terminal can display.
+pub fn adapt(color: Color) -> Color {
+    degrade(adjust(color))
+}
+
+/// Removes ANSI CSI escape sequences, leaving only printable text.
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Whether escape sequences that only make sense on a real terminal (titles,
+/// OSC 7) should be written.
+fn is_interactive_terminal() -> bool {
+    io::stdout().is_terminal() && !env::var("TERM").is_ok_and(|t| t == "dumb")
+}
+
+pub fn hostname() -> String {
+    env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// `path` with the home directory abbreviated to `~`.
+pub fn display_path(path: &Path) -> String {
+    if let Some(home) = config::home_dir()
+        && let Ok(rest) = path.strip_prefix(&home)
+    {
+        if rest.as_os_str().is_empty() {
+            return "~".to_string();
+        }
+        return format!("~/{}", rest.display());
+    }
+    path.display().to_string()
+}
+
+/// Sets the terminal window title (OSC 0).
+pub fn set_title(title: &str) {
+    if is_interactive_terminal() {
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        print!("\x1b]0;{}\x07", title);
+        let _ = io::stdout().flush();
+    }
+}
+
+fn write_osc(sequence: &str) {
+    if is_interactive_terminal() {
+        print!("\x1b]{}\x07", sequence);
+        let _ = io::stdout().flush();
+    }
+}
+
+/// OSC 133 semantic prompt marks, which let terminals jump between prompts
+/// and select a command's output: `A` before the prompt is drawn, `C` when
+/// the command starts running and `D` with its exit status when it ends.
+pub fn mark_prompt_start() {
+    write_osc("133;A");
+}
+
+pub fn mark_command_start() {
+    write_osc("133;C");
+}
+
+pub fn mark_command_end(status: i32) {
+    write_osc(&format!("133;D;{}", status));
+}
+
+/// Sets a navigable mark at the cursor position (iTerm2 `SetMark`).
+pub fn set_mark() {
+    write_osc("1337;SetMark");
+}
+
+fn percent_encode(path: &str) -> String {
+    let mut out = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Reports the working directory with OSC 7 so terminals can open new tabs
+/// and splits in the same place.
+pub fn report_cwd(cwd: &Path) {
+    if is_interactive_terminal() {
+        let path = cwd.to_string_lossy().replace('\\', "/");
+        let path = if path.starts_with('/') { path } else { format!("/{}", path) };
+        print!("\x1b]7;file://{}{}\x07", hostname(), percent_encode(&path));
+        let _ = io::stdout().flush();
+    }
+}
+
+/// The editor to open, with any arguments it needs (`code -w`): `$VISUAL`
+/// or `$EDITOR`.
+pub fn editor() -> Vec<String> {
+    let command = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    command.split_whitespace().map(str::to_string).collect()
+}
+
+/// Creates a new temporary file, readable only by the user, whose name
+/// ends in `name` so an editor still sees its extension. An existing file
+/// is never opened, so a link planted in a shared temporary directory
+/// can't send the writes elsewhere.
+pub fn temp_file(name: &str) -> io::Result<(fs::File, PathBuf)> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let mut attempts = 0;
+    loop {
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+        let path = env::temp_dir().join(format!("octane-{}-{}-{:x}-{}", std::process::id(), n, nanos, name));
+        let mut options = fs::OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 100 => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Opens `text` in `editor` in a temporary file named after `name`, and
+/// returns the text once the editor exits.
+pub fn edit(editor: &[String], name: &str, text: &str) -> Result<String, String> {
+    let (mut created, file) = temp_file(name).map_err(|e| msg!("error.generic", e))?;
+    created.write_all(text.as_bytes()).map_err(|e| msg!("error.path", file.display(), e))?;
+    drop(created);
+    let status = Command::new(&editor[0]).args(&editor[1..]).arg(&file).status();
+    let edited = fs::read_to_string(&file);
+    let _ = fs::remove_file(&file);
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(_) => return Err(msg!("error.editor_failed", editor[0])),
+        Err(e) => return Err(format!("{}: {}", editor[0], e)),
+    }
+    edited.map_err(|e| msg!("error.path", file.display(), e))
+}
+
+/// Asks a yes/no question answered with a single key: `y` is yes, any
+/// other key no. When stdin is not a terminal the answer is no.
+pub fn confirm(prompt: &str) -> bool {
+    use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+    use crossterm::terminal;
+
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    if !io::stdin().is_terminal() || terminal::enable_raw_mode().is_err() {
+        println!();
+        return false;
+    }
+    let answer = loop {
+        match event::read() {
+            Ok(Event::Key(KeyEvent { code, kind, .. })) if kind != KeyEventKind::Release => {
+                break matches!(code, KeyCode::Char('y' | 'Y'));
+            }
+            Ok(_) => {}
+            Err(_) => break false,
+        }
+    };
+    let _ = terminal::disable_raw_mode();
+    println!("{}", if answer { "y" } else { "" });
+    answer
+}
+
+/// Reads a line without echoing it, for passwords and secrets. Returns None
+/// if the user cancels with Esc, Ctrl-C or Ctrl-D. When stdin is not a
+/// terminal the line is read as is.
+pub fn read_hidden(prompt: &str) -> Option<String> {
+    use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use crossterm::terminal;
+
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    if !io::stdin().is_terminal() {
+        let mut line = String::new();
+        io::stdin().read_line(&mut line).ok()?;
+        return Some(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    terminal::enable_raw_mode().ok()?;
+    let mut text = String::new();
+    let result = loop {
+        let Ok(Event::Key(KeyEvent { code, modifiers, kind, .. })) = event::read() else {
+            continue;
+        };
+        if kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Enter => break Some(text),
+            KeyCode::Esc => break None,
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => break None,
+            KeyCode::Char('u') if ctrl => text.clear(),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) => text.push(c),
+            _ => {}
+        }
+    };
+    let _ = terminal::disable_raw_mode();
+    println!();
+    result
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use colored::*;
+use toml::{Table, Value};
+
+use crate::config;
+use crate::dotfiles;
+use crate::term;
+
+const BUILTIN_THEMES: [(&str, &str); 6] = [
+    ("default", include_str!("../themes/default.toml")),
+    ("powerline", include_str!("../themes/powerline.toml")),
+    ("ascii", include_str!("../themes/ascii.toml")),
+    ("deuteranopia", include_str!("../themes/deuteranopia.toml")),
+    ("protanopia", include_str!("../themes/protanopia.toml")),
+    ("tritanopia", include_str!("../themes/tritanopia.toml")),
+];
+
+/// A prompt theme loaded from a TOML file.
+///
+/// `[colors]` maps a role (`label`, `host`, `cwd`, `git_clean`, `git_dirty`,
+/// `segment`, `symbol`, or a segment name such as `kube`) to a style like
+/// `"black on blue bold"`. `[format]` wraps each role's text (`"({})"`) and
+/// `[icons]` prefixes optional segments. Plain themes concatenate the
+/// formatted segments; powerline themes draw them as coloured blocks joined
+/// by `separator`.
+pub struct Theme {
+    pub name: String,
+    powerline: bool,
+    separator: String,
+    colors: HashMap<String, String>,
+    formats: HashMap<String, String>,
+    icons: HashMap<String, String>,
+}
+
+/// Where user themes are looked for: the user's own first, then those from
+/// the dotfiles repository.
+fn themes_dirs() -> [PathBuf; 2] {
+    [config::config_dir().join("themes"), dotfiles::themes_dir()]
+}
+
+fn string_map(table: &Table, key: &str) -> HashMap<String, String> {
+    table
+        .get(key)
+        .and_then(Value::as_table)
+        .map(|t| {
+            t.iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A color by name (`bright red`, `bright_red`) or as `#rrggbb`.
+pub fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#')
+        && hex.len() == 6
+    {
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::TrueColor { r: channel(0)?, g: channel(2)?, b: channel(4)? });
+    }
+    Color::from_str(&name.replace(['_', '-'], " ")).ok()
+}
+
+/// A parsed style spec: `[fg] [on bg] [bold|dimmed|italic|underline]...`.
+#[derive(Default, Clone, Copy)]
+struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    fn parse(spec: &str) -> Style {
+        let mut style = Style::default();
+        let mut words = spec.split_whitespace();
+        while let Some(word) = words.next() {
+            match word {
+                "bold" => style.bold = true,
+                "dimmed" | "dim" => style.dimmed = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                "on" => style.bg = words.next().and_then(parse_color),
+                other => style.fg = parse_color(other),
+            }
+        }
+        style
+    }
+
+    fn paint(&self, text: &str) -> String {
+        let mut s = text.normal();
+        if let Some(fg) = self.fg {
+            s = s.color(term::adapt(fg));
+        }
+        if let Some(bg) = self.bg {
+            s = s.on_color(term::adapt(bg));
+        }
+        if self.bold {
+            s = s.bold();
+        }
+        if self.dimmed {
+            s = s.dimmed();
+        }
+        if self.italic {
+            s = s.italic();
+        }
+        if self.underline {
+            s = s.underline();
+        }
+        s.to_string()
+    }
+}
+
+impl Theme {
+    pub fn parse(name: &str, text: &str) -> Result<Theme, String> {
+        let table: Table = text.parse().map_err(|e| format!("{}: {}", name, e))?;
+        let powerline = table.get("style").and_then(Value::as_str) == Some("powerline");
+        let separator = table
+            .get("separator")
+            .and_then(Value::as_str)
+            .unwrap_or(if powerline { "\u{e0b0}" } else { "" })
+            .to_string();
+        Ok(Theme {
+            name: name.to_string(),
+            powerline,
+            separator,
+            colors: string_map(&table, "colors"),
+            formats: string_map(&table, "format"),
+            icons: string_map(&table, "icons"),
+        })
+    }
+
+    /// Loads `name` from the user's themes directories, falling back to
+    /// the themes shipped with octane.
+    pub fn load(name: &str) -> Result<Theme, String> {
+        for dir in themes_dirs() {
+            if let Ok(text) = fs::read_to_string(dir.join(format!("{}.toml", name))) {
+                return Theme::parse(name, &text);
+            }
+        }
+        match BUILTIN_THEMES.iter().find(|(n, _)| *n == name) {
+            Some((_, text)) => Theme::parse(name, text),
+            None => Err(format!("unknown theme: {}", name)),
+        }
+    }
+
+    /// The theme named by `theme` in the config, or the default theme.
+    pub fn from_config(config: &config::Config) -> Theme {
+        let name = config.get_str("theme").unwrap_or("default");
+        Theme::load(name).unwrap_or_else(|e| {
+            println!("Error loading theme: {}", e);
+            Theme::load("default").expect("built-in default theme")
+        })
+    }
+
+    /// Built-in theme names followed by any user themes.
+    pub fn available() -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_THEMES.iter().map(|(n, _)| n.to_string()).collect();
+        for entries in themes_dirs().into_iter().filter_map(|dir| fs::read_dir(dir).ok()) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "toml")
+                    && let Some(stem) = path.file_stem()
+                {
+                    let stem = stem.to_string_lossy().to_string();
+                    if !names.contains(&stem) {
+                        names.push(stem);
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    pub fn icon(&self, name: &str) -> &str {
+        self.icons.get(name).map(String::as_str).unwrap_or("")
+    }
+
+    /// Looks up `role`, falling back to the generic `segment` role for
+    /// optional segments.
+    fn lookup<'a>(map: &'a HashMap<String, String>, role: &str) -> Option<&'a str> {
+        map.get(role)
+            .or_else(|| match role {
+                "label" | "host" | "cwd" | "git_clean" | "git_dirty" | "symbol" | "banner"
+                | "diff_added" | "diff_removed" => None,
+                _ => map.get("segment"),
+            })
+            .map(String::as_str)
+    }
+
+    fn style(&self, role: &str) -> Style {
+        Style::parse(Theme::lookup(&self.colors, role).unwrap_or(""))
+    }
+
+    fn format(&self, role: &str, text: &str) -> String {
+        Theme::lookup(&self.formats, role)
+            .unwrap_or("{}")
+            .replace("{}", text)
+    }
+
+    /// Paints `text` with the style configured for `role`.
+    pub fn paint(&self, role: &str, text: &str) -> String {
+        self.style(role).paint(text)
+    }
+
+    /// Renders `(role, text)` segments followed by the prompt symbol. With
+    /// `multiline` the symbol starts a line of its own below the segments.
+    pub fn render(&self, segments: &[(String, String)], multiline: bool) -> String {
+        let mut out = String::new();
+        let mut symbol = self.format("symbol", "");
+        if multiline {
+            symbol = symbol.trim_start().to_string();
+        }
+        if !self.powerline {
+            for (role, text) in segments {
+                out.push_str(&self.paint(role, &self.format(role, text)));
+            }
+            if multiline {
+                out.push('\n');
+            }
+            out.push_str(&self.paint("symbol", symbol.trim_end()));
+            out.push_str(&symbol[symbol.trim_end().len()..]);
+            return out;
+        }
+
+        for (i, (role, text)) in segments.iter().enumerate() {
+            let style = self.style(role);
+            out.push_str(&style.paint(&format!(" {} ", self.format(role, text))));
+            let next_bg = segments.get(i + 1).and_then(|(r, _)| self.style(r).bg);
+            let joint = Style { fg: style.bg, bg: next_bg, ..Style::default() };
+            out.push_str(&joint.paint(&self.separator));
+        }
+        if multiline {
+            out.push('\n');
+        }
+        out.push_str(&self.paint("symbol", symbol.trim_end()));
+        out.push(' ');
+        out
+    }
+}
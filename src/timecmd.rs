@@ -0,0 +1,129 @@
+//! The `time` prefix: how long a command took, and what it used.
+//!
+//! `time cmd` runs `cmd` (a builtin, function, group or program) and then
+//! prints the wall-clock time and the CPU time spent in user and system
+//! mode, by the shell and by every program it waited for meanwhile, as the
+//! `time` of other shells does. Where the system reports it, the peak memory
+//! of the largest of those programs follows. `time -p` prints the POSIX
+//! format instead. Programs report their usage when the shell waits for
+//! them, so octane does this itself on every platform, Windows included.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::Shell;
+
+/// CPU time and peak memory, in bytes.
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    pub user: Duration,
+    pub sys: Duration,
+    pub peak: Option<u64>,
+}
+
+/// What the programs waited for used, added up as each one exits.
+static CHILDREN: Mutex<Usage> = Mutex::new(Usage { user: Duration::ZERO, sys: Duration::ZERO, peak: None });
+
+/// Called when the shell has waited for a program that used `usage`.
+pub fn record(usage: Usage) {
+    let mut children = CHILDREN.lock().unwrap();
+    children.user += usage.user;
+    children.sys += usage.sys;
+    children.peak = children.peak.max(usage.peak);
+}
+
+/// The CPU time the shell itself has used.
+#[cfg(unix)]
+fn own() -> (Duration, Duration) {
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    // SAFETY: `getrusage` only writes to the struct it's given.
+    let usage = unsafe {
+        let mut usage: libc::rusage = std::mem::zeroed();
+        libc::getrusage(libc::RUSAGE_SELF, &mut usage);
+        usage
+    };
+    (time(usage.ru_utime), time(usage.ru_stime))
+}
+
+#[cfg(windows)]
+fn own() -> (Duration, Duration) {
+    use windows_sys::Win32::Foundation::FILETIME;
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+
+    let zero = FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 };
+    let (mut created, mut exited, mut kernel, mut user) = (zero, zero, zero, zero);
+    // SAFETY: the pseudo handle of the current process needs no closing.
+    unsafe { GetProcessTimes(GetCurrentProcess(), &mut created, &mut exited, &mut kernel, &mut user) };
+    (filetime(user), filetime(kernel))
+}
+
+/// A `FILETIME` span, in units of 100 nanoseconds.
+#[cfg(windows)]
+pub fn filetime(time: windows_sys::Win32::Foundation::FILETIME) -> Duration {
+    let ticks = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+    Duration::from_nanos(ticks * 100)
+}
+
+/// Splits `time [-p] cmd...` into whether the POSIX format was asked for and
+/// the command to time.
+pub fn prefix(command: &str) -> Option<(bool, &str)> {
+    let rest = command.trim_start().strip_prefix("time ")?.trim_start();
+    let (posix, rest) = match rest.split_once(char::is_whitespace) {
+        Some(("-p", rest)) => (true, rest.trim_start()),
+        _ => (false, rest),
+    };
+    (!rest.is_empty() && !rest.starts_with('-')).then_some((posix, rest))
+}
+
+/// `1m2.345s` as bash shows times, or `62.34` for `-p`.
+fn format(time: Duration, posix: bool) -> String {
+    let secs = time.as_secs_f64();
+    if posix {
+        format!("{:.2}", secs)
+    } else {
+        format!("{}m{:.3}s", time.as_secs() / 60, secs % 60.0)
+    }
+}
+
+/// `12.4M`, `1.2G`.
+fn format_bytes(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "K", "M", "G"] {
+        if size < 1024.0 {
+            return if unit == "B" { format!("{}{}", bytes, unit) } else { format!("{:.1}{}", size, unit) };
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1}T", size)
+}
+
+/// Runs `command` and prints what it took.
+pub fn run(shell: &mut Shell, posix: bool, command: &str, set_title: bool) -> i32 {
+    // The peak is of the programs run from here on; the outer `time` of
+    // `time f` gets it back afterwards.
+    let before = *CHILDREN.lock().unwrap();
+    CHILDREN.lock().unwrap().peak = None;
+    let (own_user, own_sys) = own();
+    let started = Instant::now();
+
+    let status = crate::run_command(shell, command, set_title);
+
+    let real = started.elapsed();
+    let (user, sys) = own();
+    let mut children = CHILDREN.lock().unwrap();
+    let peak = children.peak;
+    children.peak = children.peak.max(before.peak);
+    let user = user.saturating_sub(own_user) + children.user.saturating_sub(before.user);
+    let sys = sys.saturating_sub(own_sys) + children.sys.saturating_sub(before.sys);
+    drop(children);
+
+    if posix {
+        eprintln!("real {}\nuser {}\nsys {}", format(real, true), format(user, true), format(sys, true));
+        return status;
+    }
+    eprintln!("\nreal\t{}\nuser\t{}\nsys\t{}", format(real, false), format(user, false), format(sys, false));
+    if let Some(peak) = peak {
+        eprintln!("peak\t{}", format_bytes(peak));
+    }
+    status
+}
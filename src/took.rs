@@ -0,0 +1,46 @@
+//! How long slow commands took.
+//!
+//! Each command line at the prompt is timed. One that ran for at least
+//! `timing.after` seconds (5 by default) is followed by a dim `took 12.4s`
+//! line, unless `timing.line = false`; the `took` prompt segment shows the
+//! same until the next command.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use colored::*;
+
+use crate::capture;
+use crate::config::Config;
+use crate::messages::msg;
+use crate::queue;
+
+/// The time the last command line took, when it was slow.
+static LAST: Mutex<Option<Duration>> = Mutex::new(None);
+
+/// `0.8s`, `12.4s`, then `3m07s` and `2h05m`.
+pub fn format(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(60) {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        queue::format_elapsed(elapsed)
+    }
+}
+
+/// Records that the command line `command` took `elapsed`, and reports it
+/// when that was slow. Interactive programs (`capture.exclude`) take as long
+/// as they're used, so they don't count.
+pub fn finished(config: &Config, command: &str, elapsed: Duration) {
+    let after = Duration::from_secs(config.get_int("timing.after").unwrap_or(5).max(0) as u64);
+    let program = command.split_whitespace().next().unwrap_or("");
+    let slow = elapsed >= after && !capture::excluded(config, program);
+    *LAST.lock().unwrap() = slow.then_some(elapsed);
+    if slow && config.get_bool("timing.line").unwrap_or(true) {
+        println!("{}", msg!("took.line", format(elapsed)).dimmed());
+    }
+}
+
+/// Prompt segment with the time the last command took, if it was slow.
+pub fn segment() -> Option<String> {
+    LAST.lock().unwrap().map(format)
+}
@@ -0,0 +1,330 @@
+//! Shell and environment variables: `export`, `unset`, `set`, `read`,
+//! `NAME=value` assignments, and `NAME=value command` for a single command.
+//!
+//! Shell variables live in the shell's own table and are only seen by `$NAME`
+//! expansion; `export` moves them into the environment that commands inherit.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal};
+
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+
+use crate::alias::quote;
+use crate::ifs;
+use crate::messages::msg;
+use crate::term;
+
+/// Sets a variable for the shell and everything it runs.
+pub fn set_env(name: &str, value: &str) {
+    // SAFETY: the shell's other threads only touch the environment through
+    // std (spawning commands), which serialises access with `set_var`.
+    unsafe { env::set_var(name, value) };
+}
+
+/// Assigns `name`: an environment variable stays exported with the new
+/// value, anything else becomes a shell variable.
+pub fn assign(variables: &mut HashMap<String, String>, name: &str, value: &str) {
+    if env::var_os(name).is_some() {
+        set_env(name, value);
+    } else {
+        variables.insert(name.to_string(), value.to_string());
+    }
+}
+
+/// The value of `$name`: a shell variable, else the environment's.
+pub fn lookup(variables: &HashMap<String, String>, name: &str) -> Option<String> {
+    variables.get(name).cloned().or_else(|| env::var(name).ok())
+}
+
+/// Splits the leading `NAME=value` words off a command line, returning them
+/// with their values as written, quotes and all, and the command that
+/// follows.
+pub fn take_assignments(line: &str) -> (Vec<(String, String)>, &str) {
+    let mut assignments = Vec::new();
+    for word in split_assignments(line) {
+        let Some((name, value)) = word.split_once('=').filter(|(name, _)| valid_name(name)) else {
+            let offset = word.as_ptr() as usize - line.as_ptr() as usize;
+            return (assignments, &line[offset..]);
+        };
+        assignments.push((name.to_string(), value.to_string()));
+    }
+    (assignments, "")
+}
+
+/// Sets `assignments` in the environment for the duration of a builtin,
+/// returning what to put back with `restore`.
+pub fn apply(assignments: &[(String, String)]) -> Vec<(String, Option<OsString>)> {
+    assignments
+        .iter()
+        .map(|(name, value)| {
+            let previous = env::var_os(name);
+            set_env(name, value);
+            (name.clone(), previous)
+        })
+        .collect()
+}
+
+pub fn restore(saved: Vec<(String, Option<OsString>)>) {
+    for (name, previous) in saved.into_iter().rev() {
+        match previous {
+            // SAFETY: as in `set_env`.
+            Some(value) => unsafe { env::set_var(&name, value) },
+            None => remove_env(&name),
+        }
+    }
+}
+
+/// Removes a variable from the environment of the shell and what it runs.
+pub fn remove_env(name: &str) {
+    // SAFETY: as in `set_env`.
+    unsafe { env::remove_var(name) };
+}
+
+pub fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn sorted_env() -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = env::vars_os()
+        .map(|(k, v)| (k.to_string_lossy().into_owned(), v.to_string_lossy().into_owned()))
+        .collect();
+    vars.sort();
+    vars
+}
+
+/// `export [NAME[=value]...]`
+///
+/// Sets environment variables for the shell and every command it starts
+/// afterwards; `export NAME` exports an existing shell variable. Without
+/// arguments, lists the environment.
+pub fn export(args: &[&str], variables: &mut HashMap<String, String>) -> i32 {
+    if args.is_empty() {
+        for (name, value) in sorted_env() {
+            println!("export {}={}", name, quote(&value));
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for &assignment in args {
+        let Some((name, value)) = assignment.split_once('=') else {
+            if !valid_name(assignment) {
+                println!("{}", msg!("shell.invalid_name", "export", assignment));
+                status = 2;
+            } else if let Some(value) = variables.remove(assignment) {
+                set_env(assignment, &value);
+            }
+            continue;
+        };
+        if !valid_name(name) {
+            println!("{}", msg!("shell.invalid_name", "export", name));
+            status = 2;
+            continue;
+        }
+        variables.remove(name);
+        set_env(name, value);
+    }
+    status
+}
+
+/// Splits `A=1 B='x y' C` into assignments, keeping quoted values and
+/// `=(...)` whole.
+fn split_assignments(line: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    // Inside `=(...)`, whose spaces don't end the value.
+    let mut parens = 0usize;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => {
+                start.get_or_insert(i);
+                quote = Some(c);
+            }
+            (None, '(') => {
+                start.get_or_insert(i);
+                parens += 1;
+            }
+            (None, ')') => {
+                start.get_or_insert(i);
+                parens = parens.saturating_sub(1);
+            }
+            (None, c) if c.is_whitespace() && parens == 0 => {
+                if let Some(s) = start.take() {
+                    parts.push(&line[s..i]);
+                }
+            }
+            (None, _) => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if let Some(s) = start {
+        parts.push(&line[s..]);
+    }
+    parts
+}
+
+/// `unset NAME...`: removes shell and environment variables.
+pub fn unset(args: &[&str], variables: &mut HashMap<String, String>) -> i32 {
+    if args.is_empty() {
+        println!("{}", msg!("usage.unset"));
+        return 2;
+    }
+    let mut status = 0;
+    for name in args {
+        if !valid_name(name) {
+            println!("{}", msg!("shell.invalid_name", "unset", name));
+            status = 2;
+            continue;
+        }
+        variables.remove(*name);
+        remove_env(name);
+    }
+    status
+}
+
+/// `set`: lists every shell and environment variable as `NAME=value`.
+pub fn set(args: &[&str], variables: &HashMap<String, String>) -> i32 {
+    if !args.is_empty() {
+        println!("{}", msg!("usage.set"));
+        return 2;
+    }
+    let mut all = sorted_env();
+    all.extend(variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+    all.sort();
+    for (name, value) in all {
+        println!("{}={}", name, value);
+    }
+    0
+}
+
+/// Reads one line from stdin, with status 1 when it ended at the end of
+/// the input. On a terminal the line is edited like the command line (or
+/// typed unseen with `silent`) and Ctrl-C gives up; otherwise a trailing
+/// backslash continues the line unless `raw`.
+fn read_line(prompt: &str, silent: bool, raw: bool) -> (String, i32) {
+    if io::stdin().is_terminal() {
+        if silent {
+            return term::read_hidden(prompt).map_or((String::new(), 1), |line| (line, 0));
+        }
+        let Ok(mut editor) = DefaultEditor::new() else {
+            return (String::new(), 1);
+        };
+        return match editor.readline(prompt) {
+            Ok(line) => (line, 0),
+            Err(ReadlineError::Interrupted) => (String::new(), 130),
+            Err(_) => (String::new(), 1),
+        };
+    }
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).is_err() || !line.ends_with('\n') {
+            return (text + &line, 1);
+        }
+        let line = line.trim_end_matches(['\n', '\r']);
+        let escaped = line.len() - line.trim_end_matches('\\').len();
+        if raw || escaped.is_multiple_of(2) {
+            return (text + line, 0);
+        }
+        text.push_str(&line[..line.len() - 1]);
+    }
+}
+
+/// Drops the backslash from each `\c` pair.
+fn remove_escapes(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `read [-r] [-s] [-p prompt] [name...]`: reads a line into the named
+/// variables (`REPLY` by default). The line is split on `IFS` (spaces, tabs,
+/// and newlines when unset), and the last variable gets whatever is left.
+/// `-s` doesn't echo the input, `-r` keeps backslashes as typed.
+pub fn read(args: &[&str], variables: &mut HashMap<String, String>) -> i32 {
+    let mut prompt = "";
+    let mut silent = false;
+    let mut raw = false;
+    let mut args = args;
+    while let Some(flag) = args.first().filter(|a| a.starts_with('-') && a.len() > 1) {
+        match *flag {
+            "-p" => {
+                let Some(text) = args.get(1) else {
+                    println!("{}", msg!("usage.read"));
+                    return 2;
+                };
+                prompt = text;
+                args = &args[1..];
+            }
+            "-s" => silent = true,
+            "-r" => raw = true,
+            "-rs" | "-sr" => (silent, raw) = (true, true),
+            "--" => {
+                args = &args[1..];
+                break;
+            }
+            _ => {
+                println!("{}", msg!("usage.read"));
+                return 2;
+            }
+        }
+        args = &args[1..];
+    }
+    let names: Vec<&str> = if args.is_empty() { vec!["REPLY"] } else { args.to_vec() };
+    if let Some(name) = names.iter().find(|name| !valid_name(name)) {
+        println!("{}", msg!("shell.invalid_name", "read", name));
+        return 2;
+    }
+
+    let (line, status) = read_line(prompt, silent, raw);
+    if status == 130 {
+        return status;
+    }
+    let ifs = ifs::current(variables, true).unwrap_or_default();
+    let mut fields = ifs::split_at_most(&line, &ifs, names.len()).into_iter();
+    for name in names {
+        let field = fields.next().unwrap_or_default();
+        let value = if raw { field } else { remove_escapes(&field) };
+        assign(variables, name, &value);
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The assignments `take_assignments` finds, as `name=value`, and the rest.
+    fn assignments(line: &str) -> (Vec<String>, &str) {
+        let (found, rest) = take_assignments(line);
+        (found.iter().map(|(name, value)| format!("{}={}", name, value)).collect(), rest)
+    }
+
+    #[test]
+    fn assignments_before_a_command() {
+        assert_eq!(assignments("A=1 B='x y' make -j"), (vec!["A=1".to_string(), "B='x y'".to_string()], "make -j"));
+        assert_eq!(assignments("A=1"), (vec!["A=1".to_string()], ""));
+        assert_eq!(assignments("make A=1"), (vec![], "make A=1"));
+        assert_eq!(assignments("1A=x ls"), (vec![], "1A=x ls"));
+    }
+
+    #[test]
+    fn inline_calculations_stay_whole() {
+        assert_eq!(assignments("i==($i + 1)"), (vec!["i==($i + 1)".to_string()], ""));
+        assert_eq!(assignments("n==(2 * (3 + 4)) echo $n"), (vec!["n==(2 * (3 + 4))".to_string()], "echo $n"));
+    }
+}
@@ -0,0 +1,152 @@
+//! The `watch` builtin: runs a command over and over, full screen.
+//!
+//! `watch -n 2 cmd` runs `cmd` every two seconds and shows its latest
+//! output on the alternate screen, under a header with the interval, the
+//! command and the time. Characters that changed since the last run are
+//! shown in reverse video. Ctrl-C stops watching.
+//!
+//! The command runs inside the shell with its output sent to a temporary
+//! file, so builtins and functions can be watched as well as programs, and
+//! nothing outside the shell is needed on Windows.
+
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Seek, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::queue;
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+
+use crate::Shell;
+use crate::alias;
+use crate::exec;
+use crate::messages::msg;
+use crate::signals;
+use crate::term;
+
+/// Runs `command` with its output in `file`, returning the output and the
+/// command's status.
+fn run_captured(shell: &mut Shell, command: &str, file: &mut File) -> io::Result<(String, i32)> {
+    file.set_len(0)?;
+    file.rewind()?;
+    let _ = io::stdout().flush();
+    let saved = exec::redirect_output(file)?;
+    let status = crate::run_command(shell, &alias::expand(&shell.aliases, command), false);
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    exec::restore_output(saved);
+    let mut output = Vec::new();
+    file.rewind()?;
+    file.read_to_end(&mut output)?;
+    Ok((term::strip_ansi(&String::from_utf8_lossy(&output)), status))
+}
+
+/// Draws the header and as much of `output` as fits, with the characters
+/// that differ from `previous` in reverse video.
+fn draw(interval: f64, command: &str, output: &str, previous: Option<&str>) -> io::Result<()> {
+    let (width, height) = terminal::size().unwrap_or((80, 24));
+    let (width, height) = (width as usize, height as usize);
+    let mut out = io::stdout();
+    queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+
+    let left = msg!("watch.header", format!("{:.1}", interval), command);
+    let right = format!("{}: {}", term::hostname(), Local::now().format("%a %b %e %H:%M:%S %Y"));
+    let gap = width.saturating_sub(left.chars().count() + right.chars().count()).max(1);
+    let header: String = format!("{}{}{}", left, " ".repeat(gap), right).chars().take(width).collect();
+    queue!(out, Print(header))?;
+
+    let old: Vec<Vec<char>> = previous.map(|p| p.lines().map(|l| l.chars().collect()).collect()).unwrap_or_default();
+    for (row, line) in output.lines().take(height.saturating_sub(2)).enumerate() {
+        queue!(out, MoveTo(0, row as u16 + 2))?;
+        let old_line = old.get(row);
+        let mut reversed = false;
+        for (column, c) in line.replace('\t', "        ").chars().take(width).enumerate() {
+            let changed = previous.is_some() && old_line.and_then(|l| l.get(column)) != Some(&c);
+            if changed != reversed {
+                queue!(out, SetAttribute(if changed { Attribute::Reverse } else { Attribute::NoReverse }))?;
+                reversed = changed;
+            }
+            queue!(out, Print(c))?;
+        }
+        if reversed {
+            queue!(out, SetAttribute(Attribute::NoReverse))?;
+        }
+    }
+    out.flush()
+}
+
+/// Waits `interval` seconds, or less if Ctrl-C arrives; returns whether it
+/// did.
+fn interrupted_within(interval: f64) -> bool {
+    let until = Instant::now() + Duration::from_secs_f64(interval);
+    while Instant::now() < until {
+        if signals::take_interrupt() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    signals::take_interrupt()
+}
+
+/// `watch [-n seconds] <command...>`
+pub fn run(shell: &mut Shell, args: &[&str]) -> i32 {
+    let (interval, command) = match args {
+        ["-n", seconds, command @ ..] => match seconds.parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 && seconds.is_finite() => (seconds.max(0.1), command),
+            _ => {
+                println!("watch: {}", msg!("watch.bad_interval", seconds));
+                return 2;
+            }
+        },
+        command => (2.0, command),
+    };
+    if command.is_empty() || command[0].starts_with('-') {
+        println!("{}", msg!("usage.watch"));
+        return 2;
+    }
+    if !io::stdout().is_terminal() {
+        println!("watch: {}", msg!("watch.not_a_terminal"));
+        return 1;
+    }
+    let command = command.join(" ");
+    let (mut file, path) = match term::temp_file("watch") {
+        Ok(temp) => temp,
+        Err(e) => {
+            println!("watch: {}", e);
+            return 1;
+        }
+    };
+
+    let _ = signals::take_interrupt();
+    let _ = crossterm::execute!(io::stdout(), EnterAlternateScreen, Hide);
+    let mut previous: Option<String> = None;
+    let result = loop {
+        let (output, status) = match run_captured(shell, &command, &mut file) {
+            Ok(result) => result,
+            Err(e) => break Err(e),
+        };
+        // Ctrl-C while the command runs reaches the command, not the shell.
+        if status == 130 || signals::take_interrupt() {
+            break Ok(());
+        }
+        if let Err(e) = draw(interval, &command, &output, previous.as_deref()) {
+            break Err(e);
+        }
+        previous = Some(output);
+        if interrupted_within(interval) {
+            break Ok(());
+        }
+    };
+    let _ = crossterm::execute!(io::stdout(), Show, LeaveAlternateScreen);
+    let _ = fs::remove_file(&path);
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            println!("watch: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, Config};
+
+/// Minimum time between two fetch attempts, successful or not.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const DEFAULT_URL: &str = "https://wttr.in/{location}?format=%c%t";
+
+static FETCHING: AtomicBool = AtomicBool::new(false);
+
+/// Where and how to fetch the weather summary.
+///
+/// `weather.command` takes precedence and is run through the system shell;
+/// otherwise `weather.url` (with `{location}` substituted) is fetched via `curl`.
+pub struct Provider {
+    command: Option<String>,
+    url: String,
+}
+
+impl Provider {
+    pub fn from_config(config: &Config) -> Provider {
+        let location = config.get_str("weather.location").unwrap_or("");
+        let url = config
+            .get_str("weather.url")
+            .unwrap_or(DEFAULT_URL)
+            .replace("{location}", &location.replace(' ', "+"));
+        Provider {
+            command: config.get_str("weather.command").map(str::to_string),
+            url,
+        }
+    }
+
+    fn fetch(&self) -> Option<String> {
+        let mut cmd = match &self.command {
+            Some(command) if cfg!(windows) => {
+                let mut c = Command::new("cmd");
+                c.args(["/C", command]);
+                c
+            }
+            Some(command) => {
+                let mut c = Command::new("sh");
+                c.args(["-c", command]);
+                c
+            }
+            None => {
+                let mut c = Command::new("curl");
+                c.args(["-fsS", "--max-time", "5", &self.url]);
+                c
+            }
+        };
+        let output = cmd.stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let summary = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if summary.is_empty() || summary.contains('\n') {
+            return None;
+        }
+        Some(summary)
+    }
+}
+
+/// Cached summary as stored on disk: the last attempt time and the last
+/// successful summary, which survives failed (e.g. offline) refreshes.
+struct Cache {
+    attempted: u64,
+    summary: Option<String>,
+}
+
+fn cache_path() -> PathBuf {
+    config::cache_dir().join("weather")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_cache() -> Cache {
+    let text = fs::read_to_string(cache_path()).unwrap_or_default();
+    let mut lines = text.lines();
+    let attempted = lines.next().and_then(|l| l.parse().ok()).unwrap_or(0);
+    let summary = lines.next().filter(|l| !l.is_empty()).map(str::to_string);
+    Cache { attempted, summary }
+}
+
+fn write_cache(cache: &Cache) {
+    let text = format!(
+        "{}\n{}\n",
+        cache.attempted,
+        cache.summary.as_deref().unwrap_or("")
+    );
+    let _ = fs::write(cache_path(), text);
+}
+
+fn refresh(provider: &Provider) -> Option<String> {
+    let mut cache = read_cache();
+    cache.attempted = now_secs();
+    if let Some(summary) = provider.fetch() {
+        cache.summary = Some(summary);
+    }
+    write_cache(&cache);
+    cache.summary
+}
+
+/// Returns the cached summary for the prompt without ever blocking on the
+/// network. A stale cache triggers a single background refresh whose result
+/// shows up on a later prompt.
+pub fn segment(config: &Config) -> Option<String> {
+    let cache = read_cache();
+    if now_secs().saturating_sub(cache.attempted) >= REFRESH_INTERVAL.as_secs()
+        && !FETCHING.swap(true, Ordering::SeqCst)
+    {
+        let provider = Provider::from_config(config);
+        thread::spawn(move || {
+            refresh(&provider);
+            FETCHING.store(false, Ordering::SeqCst);
+        });
+    }
+    cache.summary
+}
+
+pub fn run(args: &[&str], config: &Config) -> i32 {
+    let summary = match args.first() {
+        Some(&"refresh") => refresh(&Provider::from_config(config)),
+        Some(_) => {
+            println!("Usage: weather [refresh]");
+            return 2;
+        }
+        None => {
+            let cache = read_cache();
+            if cache.summary.is_none() {
+                refresh(&Provider::from_config(config))
+            } else {
+                cache.summary
+            }
+        }
+    };
+    match summary {
+        Some(summary) => {
+            println!("{}", summary);
+            0
+        }
+        None => {
+            println!("Weather unavailable (offline or provider error)");
+            1
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! First-run setup: a few questions that write `config.toml`.
+//!
+//! When an interactive shell starts without a config file, it offers to
+//! pick a theme, a prompt style, emacs or vi editing keys and history
+//! settings, and to import the history of bash and zsh. Declining writes a
+//! config file with only a comment, so the offer isn't made again; `setup`
+//! goes through the questions at any time.
+
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use rustshell::history::HistoryStore;
+
+use crate::Shell;
+use crate::config::{self, Config};
+use crate::messages::msg;
+use crate::picker;
+use crate::reload;
+use crate::term;
+use crate::theme::Theme;
+
+const HEADER: &str = "# Written by octane's setup; run `setup` to answer the questions again.\n\
+                      # Every setting is described in the README.\n";
+
+/// History sizes offered, the default first.
+const SIZES: [i64; 4] = [10_000, 1_000, 50_000, 200_000];
+
+/// What was answered.
+struct Answers {
+    theme: String,
+    profile: &'static str,
+    multiline: bool,
+    vi: bool,
+    size: i64,
+    dedup: &'static str,
+    ignore_space: bool,
+    import: Vec<Vec<String>>,
+}
+
+fn config_file() -> PathBuf {
+    config::config_dir().join("config.toml")
+}
+
+/// Asks to pick one of `items` and echoes the choice.
+fn choose(prompt: &str, items: &[String]) -> Option<usize> {
+    let choice = picker::pick(prompt, items)?;
+    println!("{}: {}", prompt, items[choice]);
+    Some(choice)
+}
+
+/// The commands in a bash history file, skipping the `#<time>` lines
+/// written with `HISTTIMEFORMAT`.
+fn bash_history(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| !(line.starts_with('#') && line[1..].bytes().all(|b| b.is_ascii_digit())))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The commands in a zsh history file: `: <time>:<duration>;` prefixes
+/// (`EXTENDED_HISTORY`) dropped, lines ending in `\` joined to the next, and
+/// zsh's metafied bytes (`0x83` then the byte xor 32) decoded.
+fn zsh_history(data: &[u8]) -> Vec<String> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut iter = data.iter();
+    while let Some(&b) = iter.next() {
+        match b {
+            0x83 => bytes.extend(iter.next().map(|b| b ^ 32)),
+            _ => bytes.push(b),
+        }
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    let mut commands = Vec::new();
+    let mut pending: Option<String> = None;
+    for line in text.lines() {
+        let line = match pending.take() {
+            Some(mut command) => {
+                command.push('\n');
+                command.push_str(line);
+                command
+            }
+            None => match line.strip_prefix(": ").and_then(|rest| rest.split_once(';')) {
+                Some((_, command)) => command.to_string(),
+                None => line.to_string(),
+            },
+        };
+        match line.strip_suffix('\\') {
+            Some(head) => pending = Some(head.to_string()),
+            None if !line.trim().is_empty() => commands.push(line),
+            None => {}
+        }
+    }
+    commands.extend(pending);
+    commands
+}
+
+/// Reads the commands out of a history file.
+type Parser = fn(&[u8]) -> Vec<String>;
+
+/// The histories there are to import: the shell's name, the file and the
+/// commands in it.
+fn histories() -> Vec<(&'static str, PathBuf, Vec<String>)> {
+    let Some(home) = config::home_dir() else {
+        return Vec::new();
+    };
+    let zdotdir = env::var_os("ZDOTDIR").map_or_else(|| home.clone(), PathBuf::from);
+    let sources: [(_, _, Parser); 2] = [
+        ("bash", home.join(".bash_history"), bash_history),
+        ("zsh", zdotdir.join(".zsh_history"), zsh_history),
+    ];
+    sources
+        .into_iter()
+        .filter_map(|(name, path, parse)| {
+            let commands = parse(&fs::read(&path).ok()?);
+            (!commands.is_empty()).then_some((name, path, commands))
+        })
+        .collect()
+}
+
+fn ask() -> Option<Answers> {
+    let themes = Theme::available();
+    let theme = themes[choose(&msg!("wizard.theme"), &themes)?].clone();
+
+    let styles = [
+        msg!("wizard.prompt_default"),
+        msg!("wizard.prompt_minimal"),
+        msg!("wizard.prompt_full"),
+        msg!("wizard.prompt_two_lines"),
+    ];
+    let style = choose(&msg!("wizard.prompt"), &styles)?;
+    let profile = ["default", "minimal", "full", "default"][style];
+
+    let modes = [msg!("wizard.emacs"), msg!("wizard.vi")];
+    let vi = choose(&msg!("wizard.edit_mode"), &modes)? == 1;
+
+    let sizes: Vec<String> = SIZES.iter().map(|size| size.to_string()).collect();
+    let size = SIZES[choose(&msg!("wizard.history_size"), &sizes)?];
+    let dedups = [msg!("wizard.dedup_consecutive"), msg!("wizard.dedup_all"), msg!("wizard.dedup_none")];
+    let dedup = ["consecutive", "all", "none"][choose(&msg!("wizard.dedup"), &dedups)?];
+    let ignore_space = term::confirm(&msg!("wizard.ignore_space"));
+
+    let mut found = histories();
+    let mut import = Vec::new();
+    if !found.is_empty() {
+        let mut sources: Vec<String> = found
+            .iter()
+            .map(|(name, path, commands)| msg!("wizard.import_from", name, commands.len(), path.display()))
+            .collect();
+        if found.len() > 1 {
+            sources.push(msg!("wizard.import_all"));
+        }
+        sources.push(msg!("wizard.import_none"));
+        let choice = choose(&msg!("wizard.import"), &sources)?;
+        if choice < found.len() {
+            import.push(found.swap_remove(choice).2);
+        } else if choice + 1 < sources.len() {
+            import.extend(found.into_iter().map(|(_, _, commands)| commands));
+        }
+    }
+    Some(Answers { theme, profile, multiline: style == 3, vi, size, dedup, ignore_space, import })
+}
+
+fn render(answers: &Answers) -> String {
+    let mut text = format!("{}\ntheme = {}\n", HEADER, toml::Value::String(answers.theme.clone()));
+    text.push_str(&format!("\n[shell]\nedit_mode = \"{}\"\n", if answers.vi { "vi" } else { "emacs" }));
+    text.push_str(&format!("\n[prompt]\nprofile = \"{}\"\n", answers.profile));
+    if answers.multiline {
+        text.push_str("multiline = true\n");
+    }
+    text.push_str(&format!(
+        "\n[history]\nsize = {}\ndedup = \"{}\"\nignore_space = {}\n",
+        answers.size, answers.dedup, answers.ignore_space
+    ));
+    text
+}
+
+fn write(path: &Path, text: &str) -> bool {
+    let result = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(path, text));
+    match &result {
+        Ok(()) => println!("{}", msg!("wizard.written", path.display())),
+        Err(e) => println!("{}", msg!("error.path", path.display(), e)),
+    }
+    result.is_ok()
+}
+
+/// Appends the imported commands to `store`, oldest first.
+fn import(store: &mut dyn HistoryStore, histories: &[Vec<String>]) {
+    let mut count = 0;
+    for command in histories.iter().flatten() {
+        if let Err(e) = store.append(command) {
+            println!("{}", msg!("history.error", e));
+            break;
+        }
+        count += 1;
+    }
+    println!("{}", msg!("wizard.imported", count));
+}
+
+/// At startup: offers the questions when there is no config file and the
+/// shell is on a terminal.
+pub fn first_run() {
+    let path = config_file();
+    if path.exists() || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return;
+    }
+    println!("{}", msg!("wizard.welcome", path.display()));
+    if !term::confirm(&msg!("wizard.offer")) {
+        if write(&path, HEADER) {
+            println!("{}", msg!("wizard.declined"));
+        }
+        return;
+    }
+    let Some(answers) = ask() else {
+        println!("{}", msg!("wizard.cancelled"));
+        return;
+    };
+    if write(&path, &render(&answers))
+        && !answers.import.is_empty()
+        && let Some(mut store) = crate::open_history(&Config::load())
+    {
+        import(store.as_mut(), &answers.import);
+    }
+    println!();
+}
+
+/// `setup`
+pub fn run(shell: &mut Shell) -> i32 {
+    let path = config_file();
+    let Some(answers) = ask() else {
+        println!("{}", msg!("wizard.cancelled"));
+        return 1;
+    };
+    if path.exists() {
+        if !term::confirm(&msg!("wizard.overwrite", path.display())) {
+            println!("{}", msg!("wizard.cancelled"));
+            return 1;
+        }
+        if let Err(e) = fs::copy(&path, path.with_extension("toml.bak")) {
+            println!("{}", msg!("error.path", path.display(), e));
+            return 1;
+        }
+    }
+    if !write(&path, &render(&answers)) {
+        return 1;
+    }
+    reload::run(shell);
+    if !answers.import.is_empty()
+        && let Some(store) = &mut shell.history
+    {
+        import(store.as_mut(), &answers.import);
+        match store.load() {
+            Ok(entries) => {
+                shell.history_entries = entries;
+                shell.history_edited = true;
+            }
+            Err(e) => println!("{}", msg!("history.error", e)),
+        }
+    }
+    0
+}
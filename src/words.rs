@@ -0,0 +1,140 @@
+//! Splitting a command line into words, and quote removal.
+//!
+//! Unquoted whitespace separates words. Single quotes keep what they
+//! enclose exactly as written; double quotes keep whitespace too, and in
+//! them a backslash escapes `"`, `\` and `$`. Outside quotes a backslash
+//! escapes whitespace, a quote or `$`, and is kept before anything else, so
+//! Windows paths and patterns such as `\d` need no quoting. `""` and `''`
+//! are empty words.
+//!
+//! Expansion happens first and leaves quotes in place (see
+//! `expand_env_vars`); what it substituted is protected by `ifs`, so quotes
+//! and whitespace in a variable's value are taken literally.
+
+/// Characters a backslash escapes in double quotes.
+const ESCAPED_IN_QUOTES: [char; 3] = ['"', '\\', '$'];
+
+fn escaped_outside_quotes(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '\'' | '"' | '$')
+}
+
+/// The words of `line`, with their quotes removed.
+pub fn split(line: &str) -> Vec<String> {
+    scan(line, true).into_iter().map(|(word, _)| word).collect()
+}
+
+/// Like `split`, also telling for each word whether it has a `*` or `?`
+/// outside quotes, making it a glob pattern.
+pub fn split_globs(line: &str) -> Vec<(String, bool)> {
+    scan(line, true)
+}
+
+/// `text` as one word, with its quotes removed: for assignment values,
+/// which are never split.
+pub fn unquote(text: &str) -> String {
+    scan(text, false).into_iter().map(|(word, _)| word).collect()
+}
+
+fn scan(text: &str, split: bool) -> Vec<(String, bool)> {
+    let mut words = Vec::new();
+    // `Some` once a word has started, even if it is still empty (`""`).
+    let mut word: Option<String> = None;
+    let mut glob = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if split && c.is_whitespace() => {
+                words.extend(word.take().map(|word| (word, glob)));
+                glob = false;
+            }
+            '\'' => {
+                let word = word.get_or_insert_default();
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next_if(|next| ESCAPED_IN_QUOTES.contains(next)) {
+                            Some(next) => word.push(next),
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                let word = word.get_or_insert_default();
+                word.push(chars.next_if(|&next| escaped_outside_quotes(next)).unwrap_or('\\'));
+            }
+            c => {
+                glob |= matches!(c, '*' | '?');
+                word.get_or_insert_default().push(c);
+            }
+        }
+    }
+    words.extend(word.map(|word| (word, glob)));
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_unquoted_whitespace() {
+        assert_eq!(split("  ls   -la\tsrc "), ["ls", "-la", "src"]);
+        assert!(split("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_keep_whitespace_and_are_removed() {
+        assert_eq!(split("[ \"a b\" = 'a b' ]"), ["[", "a b", "=", "a b", "]"]);
+        assert_eq!(split("echo \"hi $1\""), ["echo", "hi $1"]);
+        assert_eq!(split("a'b c'\"d\"e"), ["ab cde"]);
+    }
+
+    #[test]
+    fn empty_quotes_are_empty_words() {
+        assert_eq!(split("test -n \"\""), ["test", "-n", ""]);
+        assert_eq!(split("printf '' x"), ["printf", "", "x"]);
+    }
+
+    #[test]
+    fn single_quotes_keep_everything() {
+        assert_eq!(split(r#"echo 'a "b" \$c'"#), ["echo", r#"a "b" \$c"#]);
+        assert_eq!(split("echo 'a;b'"), ["echo", "a;b"]);
+    }
+
+    #[test]
+    fn backslashes_in_double_quotes() {
+        assert_eq!(split(r#"printf "%-5s|%3d\n""#), ["printf", r"%-5s|%3d\n"]);
+        assert_eq!(split(r#"echo "say \"hi\" \\ \$HOME""#), ["echo", r#"say "hi" \ $HOME"#]);
+    }
+
+    #[test]
+    fn backslashes_outside_quotes() {
+        assert_eq!(split(r"touch my\ file \'x \$y"), ["touch", "my file", "'x", "$y"]);
+        assert_eq!(split(r"dir C:\Users\me ghp_\w+"), ["dir", r"C:\Users\me", r"ghp_\w+"]);
+    }
+
+    #[test]
+    fn globs_are_unquoted_stars_and_question_marks() {
+        let globs: Vec<bool> = split_globs(r#"*.svg '*.png' "?" x? a"*"b"#).into_iter().map(|(_, glob)| glob).collect();
+        assert_eq!(globs, [true, false, false, true, false]);
+    }
+
+    #[test]
+    fn unterminated_quotes_run_to_the_end() {
+        assert_eq!(split("echo 'a b"), ["echo", "a b"]);
+    }
+
+    #[test]
+    fn unquote_keeps_one_word() {
+        assert_eq!(unquote("'a b'"), "a b");
+        assert_eq!(unquote("x\"$y z\""), "x$y z");
+        assert_eq!(unquote("plain"), "plain");
+        assert_eq!(unquote("a b"), "a b");
+    }
+}
@@ -0,0 +1,154 @@
+//! Runs the shell on `-c` commands and checks what they print.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// A directory of the test's own for the shell's config, data and cache,
+/// and to run in.
+fn scratch(test: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// What `commands` print, given `input` on stdin.
+fn run_with_input(test: &str, commands: &str, input: &str) -> String {
+    let dir = scratch(test);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_octane"))
+        .args(["-c", commands])
+        .current_dir(&dir)
+        .env("OCTANE_CONFIG_DIR", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .env("XDG_CACHE_HOME", &dir)
+        .env("NO_COLOR", "1")
+        .env_remove("IFS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn run(test: &str, commands: &str) -> String {
+    run_with_input(test, commands, "")
+}
+
+#[test]
+fn test_sees_quoted_words() {
+    let output = run(
+        "test_sees_quoted_words",
+        r#"test -n ""; echo $?
+v=; [ -z "$v" ]; echo $?
+[ "a b" = "a b" ]; echo $?
+v="x y"; [ "$v" = "x y" ]; echo $?
+[ -n '' ]; echo $?"#,
+    );
+    assert_eq!(output, "1\n0\n0\n0\n1\n");
+}
+
+#[test]
+fn quotes_are_removed_before_commands_run() {
+    let output = run(
+        "quotes_are_removed_before_commands_run",
+        r#"echo 'a;b' "c  d" e\ f
+x='say "hi"'; echo "$x" '$x'
+export A="x y"; printenv A"#,
+    );
+    assert_eq!(output, "a;b c  d e f\nsay \"hi\" $x\nx y\n");
+}
+
+#[test]
+fn unquoted_expansions_are_split_on_ifs() {
+    let output = run(
+        "unquoted_expansions_are_split_on_ifs",
+        r#"IFS=:; v=a:b; for w in $v; do echo "<$w>"; done; for w in "$v"; do echo "<$w>"; done"#,
+    );
+    assert_eq!(output, "<a>\n<b>\n<a:b>\n");
+}
+
+#[test]
+fn read_takes_a_quoted_prompt() {
+    let output = run_with_input(
+        "read_takes_a_quoted_prompt",
+        r#"read -p "Branch: " branch; echo "[$branch]"; read -r -p 'Two words: ' first rest; echo "$first|$rest""#,
+        "main\nfeature x y\n",
+    );
+    assert_eq!(output, "[main]\nfeature|x y\n");
+}
+
+#[test]
+fn function_bodies_have_their_quotes_removed() {
+    let output = run(
+        "function_bodies_have_their_quotes_removed",
+        r#"greet() { echo "hi $1"; }; greet bob; greet "bob smith"
+show() { printf "%-5s|%3d\n" "$1" "$2"; }; show ab 7
+printf "%-5s|%3d\n" cd 8"#,
+    );
+    assert_eq!(output, "hi bob\nhi bob smith\nab   |  7\ncd   |  8\n");
+}
+
+#[test]
+fn aliases_may_hold_pipelines_and_lists() {
+    let output = run(
+        "aliases_may_hold_pipelines_and_lists",
+        "alias g='echo hi | tr a-z A-Z'\nalias b='echo one && echo two'\ng\nb; g | tr H J",
+    );
+    assert_eq!(output, "HI\none\ntwo\nJI\n");
+}
+
+#[test]
+fn for_expands_globs() {
+    let dir = scratch("for_expands_globs_files");
+    for name in ["b.svg", "a.svg", "c.png", ".hidden.svg"] {
+        fs::write(dir.join(name), "").unwrap();
+    }
+    let commands = format!(
+        r#"cd {}; for f in *.svg; do echo $f; done; for f in *.gif "*.svg" x; do echo $f; done"#,
+        dir.display()
+    );
+    let output = run("for_expands_globs", &commands);
+    assert_eq!(output, "a.svg\nb.svg\n*.gif\n*.svg\nx\n");
+}
+
+#[test]
+fn assignments_take_inline_calculations() {
+    let output = run("assignments_take_inline_calculations", "i=0; i==($i+1); i==($i * 10 + 1); echo $i");
+    assert_eq!(output, "11\n");
+}
+
+#[test]
+fn background_pipelines_run_until_their_last_command_ends() {
+    let output = run(
+        "background_pipelines_run_until_their_last_command_ends",
+        "true | sleep 0.5 &\nsleep 0.2; jobs; wait; echo waited; jobs",
+    );
+    let lines: Vec<&str> = output.lines().skip(1).collect();
+    assert_eq!(lines, ["[1]+  Running     true | sleep 0.5 &", "waited"]);
+}
+
+#[test]
+fn set_replaces_the_positional_parameters() {
+    let output = run(
+        "set_replaces_the_positional_parameters",
+        "set -- a 'b c'; echo $# $1 $2; shift; echo $1; set --; echo $#",
+    );
+    assert_eq!(output, "2 a b c\nb c\n0\n");
+}
+
+#[test]
+fn parallel_runs_programs_only() {
+    let output = run(
+        "parallel_runs_programs_only",
+        "parallel -j1 'sh -c \"echo $0\" {}' ::: 'a b' c; f() { :; }; parallel f ::: x; echo $?; parallel cd ::: x",
+    );
+    assert_eq!(
+        output,
+        "[a b] a b\n[c] c\nparallel: f is a function; only programs can be run\n1\n\
+         parallel: cd is a builtin; only programs can be run\n"
+    );
+}